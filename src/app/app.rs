@@ -1,47 +1,87 @@
+#[cfg(test)]
+#[path = "app_test.rs"]
+mod tests;
+
 use std::time::Duration;
-use std::{collections::HashMap, sync::Arc, time};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time,
+};
 
-use crate::config::{self};
-use crate::context::Compressor;
+use crate::app::attachment::expand_file_directives;
+use crate::app::command::{self, Command};
+use crate::app::input_history::InputHistory;
+use crate::app::pager;
+use crate::config::constants::{
+    DRAFT_SAVE_INTERVAL, MIN_LINE_WIDTH, OFFLINE_RETRY_INTERVAL, OFFLINE_RETRY_MAX_INTERVAL,
+    PARTIAL_FLUSH_INTERVAL,
+};
+use crate::config::{self, ColorTheme, TitleStrategy};
+use crate::context::window::{ContextFit, check_context_window};
+use crate::context::{Compressor, TitleGenerator, title};
 use crate::models::action::Action;
 use crate::models::conversation::FindMessage;
-use crate::models::{BackendPrompt, Conversation, Event, Message, message::Issuer};
-use crate::models::{BackendResponse, Model, UpsertConvoRequest};
-use crate::{info_notice, warn_notice};
+use crate::models::{
+    BackendPrompt, Budget, Conversation, Event, Message, ResponseFormat, message::Issuer,
+};
+use crate::models::{
+    BackendResponse, BackendUsage, Model, ToolApproval, ToolCall, TranscriptOptions,
+    UpsertConvoRequest,
+};
+use crate::{error_notice, info_notice, t, warn_notice};
 use crossterm::event::MouseButton;
 use eyre::Result;
 use ratatui::{
     Terminal,
-    layout::{Alignment, Constraint, Direction, Layout, Margin},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     prelude::{Backend, CrosstermBackend},
-    style::Stylize,
-    widgets::{Paragraph, Scrollbar, ScrollbarOrientation},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{
+        Block, BorderType, Borders, Clear, Padding, Paragraph, Scrollbar, ScrollbarOrientation,
+    },
 };
 use ratatui_macros::span;
 use syntect::highlighting::Theme;
 use tokio::sync::mpsc;
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
-use tui_textarea::Key;
+use tui_textarea::{CursorMove, Key};
 
 use crate::{
     app::app_state::AppState,
     app::ui::{
-        EditScreen, HelpScreen, HistoryScreen, Loading, ModelsScreen, Notice, TextArea, utils,
+        EditScreen, HelpScreen, HistoryScreen, Loading, ModelsScreen, Notice, TextArea, input_box,
+        loading, utils,
     },
 };
 
 use super::services::EventService;
+use super::ui::history::UNDO_DELETE_WINDOW;
+use super::ui::question::Question;
+use super::ui::search::ConversationSearch;
 use super::ui::selection::Selection;
 use super::ui::utils::is_wrapper_span;
 use super::ui::{Content, Selectable};
 use super::{destruct_terminal, init_terminal};
 
-const MIN_WIDTH: u16 = 80;
+const MIN_WIDTH: u16 = MIN_LINE_WIDTH as u16;
+/// The tool arguments shown in the approval popup are truncated to this length
+const MAX_APPROVAL_ARGS_LENGTH: usize = 300;
 
 pub struct InitProps {
     pub models: Vec<Model>,
     pub conversations: HashMap<String, Conversation>,
+    pub colors: ColorTheme,
+    /// The conversation opened at startup, a new one if not set
+    pub conversation: Option<Conversation>,
+    /// The prompts sent in the previous sessions, from the oldest
+    pub input_history: Vec<String>,
+    /// The unsent inputs by conversation ID
+    pub drafts: HashMap<String, String>,
+    /// The connection of the model used in the previous session
+    pub backend: Option<String>,
 }
 
 pub struct App<'a> {
@@ -57,8 +97,33 @@ pub struct App<'a> {
     history_screen: HistoryScreen<'a>,
     input: tui_textarea::TextArea<'a>,
     selection: Selection,
+    search: ConversationSearch<'a>,
+    pasting: bool,
+    input_history: InputHistory,
+    drafts: HashMap<String, String>, /* Conversation ID - Draft */
+    drafts_changed: bool,
+    drafts_saved_at: time::Instant,
+
+    tool_approval: Option<ToolApproval>,
+    approval_question: Question<'a>,
+    // Asks before the messages of the current conversation are removed
+    clear_question: Question<'a>,
 
     compressor: Arc<Compressor>,
+    compressing: HashMap<String, String>, /* Conversation ID - Progress */
+    title_generator: Arc<TitleGenerator>,
+    // The conversations allowed to send over their budget
+    over_budget_acknowledged: HashSet<String>,
+    // The usage of the last response in the current conversation
+    last_usage: Option<BackendUsage>,
+    // Set while the backend is unreachable, until a response comes back
+    offline: Option<Offline>,
+    // Ask for a JSON answer to the next prompt, set by `/json`
+    json_next: bool,
+
+    partial_flushed_at: time::Instant,
+    // Clear the terminal before the next render
+    redraw: bool,
 
     notice: Notice,
     loading: Loading<'a>,
@@ -72,37 +137,94 @@ impl<'a> App<'a> {
         action_tx: mpsc::UnboundedSender<Action>,
         events: &'a mut EventService,
         compressor: Arc<Compressor>,
+        title_generator: Arc<TitleGenerator>,
         cancel_token: CancellationToken,
 
         init_props: InitProps,
     ) -> App<'a> {
         let theme = Box::leak(Box::new(theme));
+        let colors = Box::leak(Box::new(init_props.colors));
         let mut conversations = init_props.conversations;
         conversations.insert(String::new(), Conversation::new_hello());
 
         let event_tx = events.event_tx();
-        App {
+        let mut app = App {
             action_tx: action_tx.clone(),
             event_tx: event_tx.clone(),
             compressor,
-            edit_screen: EditScreen::new(theme, action_tx.clone()),
+            compressing: HashMap::new(),
+            title_generator,
+            over_budget_acknowledged: HashSet::new(),
+            last_usage: None,
+            offline: None,
+            json_next: false,
+            partial_flushed_at: time::Instant::now(),
+            redraw: false,
+            edit_screen: EditScreen::new(theme, action_tx.clone(), event_tx.clone()),
             events,
-            app_state: AppState::new(theme),
+            app_state: AppState::new(theme, colors),
             input: TextArea::default().build(),
-            loading: Loading::new(vec![
-                span!("Thinking... Press ").gray(),
-                span!("Ctrl+c").green().bold(),
-                span!(" to abort!").gray(),
-            ]),
+            input_history: InputHistory::new(config::instance().general.get_input_history_size())
+                .with_entries(init_props.input_history),
+            drafts: init_props.drafts,
+            drafts_changed: false,
+            drafts_saved_at: time::Instant::now(),
+            loading: build_loading(),
             help_screen: HelpScreen::default(),
-            history_screen: HistoryScreen::new(action_tx)
+            history_screen: HistoryScreen::new(action_tx.clone())
                 .with_conversations(conversations)
                 .with_current_conversation(""),
-            models_screen: ModelsScreen::new(init_props.models, event_tx.clone()),
-            notice: Notice::default(),
-            selection: Selection::default(),
+            models_screen: ModelsScreen::new(
+                init_props.models,
+                action_tx.clone(),
+                event_tx.clone(),
+            )
+            .with_backend(init_props.backend.as_deref()),
+            notice: Notice::default()
+                .with_colors(colors.notice)
+                .with_position(config::instance().ui.notice_position)
+                .with_max_stacked(config::instance().ui.notice_max_stacked),
+            selection: Selection::default()
+                .with_highlight(utils::highlight_style(colors.highlight_style())),
+            search: ConversationSearch::default(),
+            pasting: false,
+            tool_approval: None,
+            approval_question: Question::default().with_title(" Tool Call Approval "),
+            clear_question: Question::default().with_title(t!("notice.clear_title")),
             cancel_token,
+        };
+
+        if let Some(convo) = init_props.conversation {
+            app.change_conversation(convo, false);
+        } else {
+            app.restore_draft();
+        }
+        app
+    }
+
+    /// The ID of the current conversation, empty if it is not saved yet or
+    /// a scratch one
+    pub fn current_conversation_id(&self) -> &str {
+        if self.app_state.current_convo.is_ephemeral() {
+            return "";
         }
+        self.app_state.current_convo.id()
+    }
+
+    /// The connection serving the current model
+    pub fn current_provider(&self) -> &str {
+        self.models_screen.current_provider()
+    }
+
+    /// The prompts sent in the input box, from the oldest
+    pub fn input_history(&self) -> Vec<String> {
+        self.input_history.entries()
+    }
+
+    /// The unsent inputs by conversation ID, including the current input
+    pub fn drafts(&mut self) -> &HashMap<String, String> {
+        self.stash_draft();
+        &self.drafts
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -120,11 +242,38 @@ impl<'a> App<'a> {
     }
 
     async fn handle_key_event(&mut self, event: Event) -> bool {
+        // The approval request carries the responder, so it is taken by value
+        let event = match event {
+            Event::ToolApprovalRequest(approval) => {
+                self.open_tool_approval(approval);
+                return false;
+            }
+            event => event,
+        };
+
+        // The arrow keys only recall the prompts in the input box, they
+        // scroll everywhere else
+        let event = match event {
+            Event::KeyboardUp if !self.input_focused() => Event::UiScrollUp,
+            Event::KeyboardDown if !self.input_focused() => Event::UiScrollDown,
+            event => event,
+        };
+
         // Handle critical events first
         if let Some(stop) = self.handle_global_event(&event).await {
             return stop;
         }
 
+        if self.tool_approval.is_some() {
+            self.handle_tool_approval_event(&event);
+            return false;
+        }
+
+        if self.clear_question.showing() {
+            self.handle_clear_question_event(&event);
+            return false;
+        }
+
         // Handle screen events
         if self.help_screen.showing() {
             if self.help_screen.handle_key_event(&event) {
@@ -154,10 +303,124 @@ impl<'a> App<'a> {
             return false;
         }
 
+        if self.search.showing() {
+            if self.search.handle_input_event(&event) {
+                self.handle_search_submitted();
+            }
+            return false;
+        }
+
+        if self.search.active() && self.handle_search_event(&event) {
+            return false;
+        }
+
         self.handle_input_event(event).await;
         false
     }
 
+    fn open_tool_approval(&mut self, approval: ToolApproval) {
+        // Only one tool is called at a time, deny the stale request if any
+        self.respond_tool_approval(false);
+
+        let mut question = vec![
+            span!("Allow calling tool"),
+            span!(format!("\"{}\"", approval.tool))
+                .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+                .yellow(),
+            span!(format!("(provider: {})", approval.provider)),
+        ];
+        if let Some(args) = &approval.args {
+            let mut args = args.to_string();
+            if args.chars().count() > MAX_APPROVAL_ARGS_LENGTH {
+                args = args
+                    .chars()
+                    .take(MAX_APPROVAL_ARGS_LENGTH)
+                    .collect::<String>()
+                    + "...";
+            }
+            question.push(span!("with arguments"));
+            question.push(span!(args).cyan());
+        }
+        question.push(span!("?"));
+        self.approval_question.open(question);
+        self.tool_approval = Some(approval);
+    }
+
+    fn handle_tool_approval_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyboardCharInput(input) => match input.key {
+                Key::Char('y') => self.respond_tool_approval(true),
+                Key::Char('n') | Key::Char('q') => self.respond_tool_approval(false),
+                _ => {}
+            },
+            Event::KeyboardEsc | Event::KeyboardCtrlC => self.respond_tool_approval(false),
+            _ => {}
+        }
+    }
+
+    fn respond_tool_approval(&mut self, approved: bool) {
+        if let Some(approval) = self.tool_approval.take() {
+            approval.respond(approved);
+        }
+        self.approval_question.close();
+    }
+
+    fn handle_clear_question_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyboardCharInput(input) => match input.key {
+                Key::Char('y') => {
+                    self.clear_question.close();
+                    self.handle_clear_messages();
+                }
+                Key::Char('n') | Key::Char('q') => self.clear_question.close(),
+                _ => {}
+            },
+            Event::KeyboardEsc | Event::KeyboardCtrlC => self.clear_question.close(),
+            _ => {}
+        }
+    }
+
+    /// Handle the navigation between matches while a search is active.
+    /// Returns true if the event is consumed.
+    fn handle_search_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::KeyboardEsc => self.search.clear(),
+            Event::KeyboardCtrlF => self.search.open(),
+            // The matches are cycled while the input is empty, typing goes on
+            // in the input otherwise
+            Event::KeyboardCharInput(input) if self.input.is_empty() => {
+                match input.key {
+                    Key::Char('n') => self.search.next(),
+                    Key::Char('N') => self.search.prev(),
+                    _ => return false,
+                }
+                self.scroll_to_search_match();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_search_submitted(&mut self) {
+        self.selection.clear();
+        if !self.search.active() {
+            self.search.clear();
+            return;
+        }
+        self.search
+            .set_messages(self.app_state.current_convo.messages());
+        self.scroll_to_search_match();
+    }
+
+    fn scroll_to_search_match(&mut self) {
+        let (Some(index), Some(query)) = (self.search.current_match(), self.search.query()) else {
+            return;
+        };
+        if let Some(line) = self.app_state.bubble_list.find_message_line(index, query) {
+            self.app_state.scroll.center_on(line);
+        }
+    }
+
     async fn handle_global_event(&mut self, event: &Event) -> Option<bool> {
         match &event {
             Event::Quit => {
@@ -173,6 +436,7 @@ impl<'a> App<'a> {
             }
 
             Event::BackendAbort => {
+                self.respond_tool_approval(false);
                 self.handle_abort();
                 Some(false)
             }
@@ -182,12 +446,17 @@ impl<'a> App<'a> {
                 Some(false)
             }
 
+            Event::BackendUnreachable(err) => {
+                self.handle_backend_unreachable(err);
+                Some(false)
+            }
+
             Event::BackendMessage(msg) => {
+                // The backend answered, even if with an error
+                self.offline = None;
                 self.app_state.add_message(msg.clone());
                 let convo_id = self.app_state.current_convo.id();
-                let _ = self
-                    .action_tx
-                    .send(Action::UpsertMessage(convo_id.to_string(), msg.clone()));
+                self.persist(Action::UpsertMessage(convo_id.to_string(), msg.clone()));
                 self.app_state.waiting_for_backend = false;
                 Some(false)
             }
@@ -199,6 +468,24 @@ impl<'a> App<'a> {
                     self.app_state.set_conversation(Conversation::new_hello());
                     self.change_conversation(Conversation::new_hello(), false);
                 }
+                self.notice.replace_message(info_notice!(
+                    t!("notice.conversation_deleted"),
+                    UNDO_DELETE_WINDOW
+                ));
+                Some(false)
+            }
+
+            Event::ToolCalled(call) => {
+                self.handle_tool_called(call);
+                Some(false)
+            }
+
+            Event::ConversationRestored(convo) => {
+                self.history_screen.upsert_conversation(convo);
+                self.notice.add_message(info_notice!(t!(
+                    "notice.conversation_restored",
+                    title = convo.title()
+                )));
                 Some(false)
             }
 
@@ -224,6 +511,83 @@ impl<'a> App<'a> {
                 Some(false)
             }
 
+            Event::ConversationTitleUpdated(convo_id, title) => {
+                if self.app_state.current_convo.id() == convo_id {
+                    self.app_state.current_convo.set_title(title);
+                    self.history_screen
+                        .upsert_conversation(&self.app_state.current_convo);
+                } else {
+                    self.history_screen
+                        .update_conversation_title(convo_id, title);
+                }
+                self.notice.add_message(info_notice!(
+                    t!("notice.title_updated", title = title),
+                    Duration::from_secs(5)
+                ));
+                Some(false)
+            }
+
+            Event::SemanticSearchResults(query, ids) => {
+                self.history_screen.set_semantic_results(query, ids.clone());
+                Some(false)
+            }
+
+            Event::MessageEdits(msg_id, edits) => {
+                self.edit_screen.set_message_edits(msg_id, edits.clone());
+                Some(false)
+            }
+
+            Event::CompressionStarted(convo_id) => {
+                self.compressing.insert(convo_id.clone(), String::new());
+                Some(false)
+            }
+
+            Event::CompressionProgress(convo_id, status) => {
+                if let Some(progress) = self.compressing.get_mut(convo_id) {
+                    *progress = status.clone();
+                }
+                Some(false)
+            }
+
+            Event::CompressionFinished(convo_id) => {
+                self.compressing.remove(convo_id);
+                Some(false)
+            }
+
+            Event::ModelsUpdated(models) => {
+                self.models_screen.set_models(models.clone());
+                Some(false)
+            }
+
+            Event::EditMessage(id, text) => {
+                self.handle_edit_message(id, text);
+                Some(false)
+            }
+
+            Event::SetSystemPrompt(system_prompt) => {
+                // Only the next turns are affected, past messages are kept as is
+                self.app_state
+                    .current_convo
+                    .set_system_prompt(system_prompt.as_str());
+                self.save_current_conversation(false);
+                self.notice.info(if system_prompt.is_empty() {
+                    "System prompt removed"
+                } else {
+                    "System prompt updated"
+                });
+                Some(false)
+            }
+
+            Event::KeyboardPasteStart => {
+                self.pasting = true;
+                Some(false)
+            }
+
+            Event::KeyboardPasteEnd => {
+                self.pasting = false;
+                Some(false)
+            }
+
             // Fallthrough to the next event handler
             _ => None,
         }
@@ -253,14 +617,20 @@ impl<'a> App<'a> {
             Event::KeyboardCtrlC => {
                 if self.app_state.waiting_for_backend {
                     self.app_state.waiting_for_backend = false;
+                    self.cancel_compression();
                     let _ = self.action_tx.send(Action::BackendAbort);
                     return;
                 }
 
                 // Clear text in the input area if not waiting for backend
-                if !self.input.lines().is_empty() {
+                if !self.input.is_empty() {
                     self.input = TextArea::default().build();
+                    self.input_history.reset();
+                    return;
                 }
+
+                // Then cancel the compression of the conversation
+                self.cancel_compression();
             }
 
             Event::KeyboardF1 => {
@@ -273,6 +643,11 @@ impl<'a> App<'a> {
                 self.handle_new_conversation()
             }
 
+            Event::KeyboardCtrlK => {
+                self.selection.clear();
+                self.open_clear_question();
+            }
+
             Event::KeyboardCtrlH => {
                 if !self.on_waiting_backend(true) {
                     self.selection.clear();
@@ -288,12 +663,49 @@ impl<'a> App<'a> {
             Event::KeyboardCtrlE => {
                 if !self.on_waiting_backend(true) {
                     self.selection.clear();
+                    self.edit_screen.set_messages(
+                        self.app_state.current_convo.id(),
+                        self.app_state.current_convo.messages(),
+                    );
                     self.edit_screen
-                        .set_messages(self.app_state.current_convo.messages());
+                        .set_system_prompt(self.app_state.current_convo.system_prompt());
                     self.edit_screen.toggle_showing();
                 }
             }
 
+            Event::KeyboardCtrlF => {
+                self.selection.clear();
+                self.search.open();
+            }
+
+            Event::KeyboardCtrlP => {
+                self.selection.clear();
+                self.handle_compress_now();
+            }
+
+            Event::KeyboardCtrlT => {
+                self.selection.clear();
+                self.handle_retitle(true);
+            }
+
+            Event::KeyboardCtrlO => {
+                self.selection.clear();
+                self.app_state.bubble_list.toggle_tool_args();
+                self.app_state.sync_state();
+            }
+
+            Event::KeyboardCtrlG => self.handle_open_in_pager().await,
+
+            Event::KeyboardCtrlY => self.handle_copy_conversation(false),
+
+            Event::KeyboardAltY => self.handle_copy_conversation(true),
+
+            Event::KeyboardF2 => {
+                if !self.models_screen.cycle_backend() {
+                    self.notice.info(t!("notice.no_other_backend"));
+                }
+            }
+
             Event::KeyboardCtrlR => {
                 self.selection.clear();
                 self.handle_regenerate_response().await
@@ -312,7 +724,20 @@ impl<'a> App<'a> {
                 }
             }
 
-            Event::KeyboardEnter => self.handle_send_prompt(),
+            Event::KeyboardEnter => {
+                // An Enter during an active paste is part of the pasted
+                // content, not a request to send the prompt
+                if self.pasting {
+                    if !self.on_waiting_backend(false) {
+                        self.input.insert_newline();
+                    }
+                    return;
+                }
+                self.handle_send_prompt().await
+            }
+
+            Event::KeyboardUp => self.handle_history_up(),
+            Event::KeyboardDown => self.handle_history_down(),
 
             Event::UiScrollDown => self.app_state.scroll.down(),
             Event::UiScrollUp => self.app_state.scroll.up(),
@@ -328,84 +753,138 @@ impl<'a> App<'a> {
 
     fn render<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
         terminal.draw(|f| {
-            let global_area = f.area();
-            let current_width = global_area.width;
-            if !is_line_width_sufficient(current_width) {
-                f.render_widget(
-                    Paragraph::new(utils::split_to_lines(
-                        format!(
-                            "I'm too small, make me bigger! I need at least {} cells (current: {})",
-                            MIN_WIDTH, current_width
-                        ),
-                        (current_width - 2) as usize,
-                    ))
-                    .alignment(Alignment::Left),
-                    global_area,
-                );
-                return;
+            self.render_frame(f);
+            if config::instance().theme.no_color() {
+                utils::strip_colors(f.buffer_mut());
             }
+        })?;
+        Ok(())
+    }
 
-            let textarea_len = (self.input.lines().len() + 2).try_into().unwrap();
-            let layout = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(vec![
-                    Constraint::Min(1),
-                    Constraint::Max(textarea_len),
-                    Constraint::Length(1),
-                ])
-                .split(global_area);
+    fn render_frame(&mut self, f: &mut ratatui::Frame) {
+        let global_area = f.area();
+        let current_width = global_area.width;
+        if !is_line_width_sufficient(current_width) {
+            f.render_widget(
+                Paragraph::new(utils::split_to_lines(
+                    format!(
+                        "I'm too small, make me bigger! I need at least {} cells (current: {})",
+                        MIN_WIDTH, current_width
+                    ),
+                    (current_width - 2) as usize,
+                ))
+                .alignment(Alignment::Left),
+                global_area,
+            );
+            return;
+        }
 
-            if layout[0].width as usize != self.app_state.last_known_width
-                || layout[0].height as usize != self.app_state.last_known_height
-            {
-                self.app_state.set_rect(layout[0]);
-            }
+        // The input box is capped so a long input doesn't hide the
+        // conversation, the text area scrolls to keep the cursor visible
+        let max_input_lines = config::instance().ui.get_max_input_lines();
+        let textarea_len = (self.input.lines().len().min(max_input_lines) + 2)
+            .try_into()
+            .unwrap_or(u16::MAX);
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Min(1),
+                Constraint::Max(textarea_len),
+                Constraint::Length(1),
+            ])
+            .split(global_area);
+
+        if layout[0].width as usize != self.app_state.last_known_width
+            || layout[0].height as usize != self.app_state.last_known_height
+        {
+            self.app_state.set_rect(layout[0]);
+        }
 
-            self.app_state.bubble_list.render(
-                layout[0],
-                f.buffer_mut(),
-                self.app_state.scroll.position,
-                &self.selection,
-            );
+        self.app_state.bubble_list.render(
+            layout[0],
+            f.buffer_mut(),
+            self.app_state.scroll.position,
+            &self.selection,
+            self.search.query(),
+        );
 
-            f.render_stateful_widget(
-                Scrollbar::new(ScrollbarOrientation::VerticalRight)
-                    .end_symbol(None)
-                    .begin_symbol(None),
-                layout[0].inner(Margin {
-                    vertical: 1,
-                    horizontal: 1,
-                }),
-                &mut self.app_state.scroll.scrollbar_state,
-            );
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .end_symbol(None)
+                .begin_symbol(None),
+            layout[0].inner(Margin {
+                vertical: 1,
+                horizontal: 1,
+            }),
+            &mut self.app_state.scroll.scrollbar_state,
+        );
+        self.render_new_content_indicator(f, layout[0]);
+
+        let scratch_status = self.scratch_status();
+        let offline_status = self.offline_status();
+        let stream_status = self.stream_status();
+        let usage_status = self.usage_status();
+        let status_line = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(scratch_status.width() as u16),
+                Constraint::Length(stream_status.width() as u16),
+                Constraint::Length(offline_status.width() as u16),
+                Constraint::Length(usage_status.width() as u16),
+                Constraint::Length(26),
+            ])
+            .split(layout[2]);
+        if self.search.active() {
+            f.render_widget(self.search.status_line(), status_line[0]);
+        } else {
+            self.help_screen.render_help_line(f, status_line[0]);
+        }
+        f.render_widget(scratch_status, status_line[1]);
+        f.render_widget(stream_status, status_line[2]);
+        f.render_widget(offline_status, status_line[3]);
+        f.render_widget(usage_status, status_line[4]);
+        self.render_compression_status(f, status_line[5]);
+        if self.app_state.waiting_for_backend {
+            self.loading.render(f, layout[1]);
+        } else {
+            f.render_widget(&self.input, layout[1]);
+            self.render_command_suggestions(f, layout[1]);
+        }
 
-            self.help_screen.render_help_line(f, layout[2]);
-            if self.app_state.waiting_for_backend {
-                self.loading.render(f, layout[1]);
-            } else {
-                f.render_widget(&self.input, layout[1]);
-            }
+        self.help_screen
+            .render(f, utils::popup_area(global_area, 40, 30));
 
-            self.help_screen
-                .render(f, utils::popup_area(global_area, 40, 30));
+        self.models_screen
+            .render(f, utils::popup_area(global_area, 40, 60));
 
-            self.models_screen
-                .render(f, utils::popup_area(global_area, 40, 60));
+        self.edit_screen
+            .render(f, utils::popup_area(global_area, 70, 90));
+        self.history_screen
+            .render(f, utils::popup_area(global_area, 70, 90));
 
-            self.edit_screen
-                .render(f, utils::popup_area(global_area, 70, 90));
-            self.history_screen
-                .render(f, utils::popup_area(global_area, 70, 90));
+        self.search
+            .render(f, input_box::build_area(global_area, 60));
 
-            self.notice.render(f, utils::notice_area(global_area, 30));
-        })?;
-        Ok(())
+        self.approval_question.render(f, global_area);
+        self.clear_question.render(f, global_area);
+
+        self.notice.render(
+            f,
+            utils::notice_area(global_area, 30, config::instance().ui.notice_position),
+        );
     }
 
     async fn start_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
         loop {
             let event = self.events.next().await;
             if matches!(event, Event::UiTick) {
+                // The screen is drawn by another program in the meantime
+                if std::mem::take(&mut self.redraw) {
+                    terminal.clear()?;
+                }
+                self.autosave_drafts();
+                self.retry_when_online();
                 self.render(terminal)?;
                 continue;
             }
@@ -416,9 +895,171 @@ impl<'a> App<'a> {
     }
 
     fn handle_copy_selection(&self, notice: bool) {
-        let Some((start, end)) = self.selection.get_bounds() else {
+        let Some(content) = self.selected_text() else {
+            return;
+        };
+        let _ = self.action_tx.send(Action::CopyText { content, notice });
+    }
+
+    /// Open the selected text, or the last message, in the pager. The
+    /// terminal is handed over to the pager until it exits, the pager runs
+    /// off the async task so the background tasks go on meanwhile.
+    async fn handle_open_in_pager(&mut self) {
+        let text = self.selected_text().or_else(|| {
+            self.app_state
+                .current_convo
+                .last_message()
+                .map(|msg| msg.text().to_string())
+        });
+        self.selection.clear();
+        let Some(text) = text.filter(|text| !text.trim().is_empty()) else {
+            self.notice.info(t!("notice.nothing_to_open"));
+            return;
+        };
+
+        self.events.suspend_terminal_events();
+        destruct_terminal();
+        let result = tokio::task::spawn_blocking(move || pager::open(&text))
+            .await
+            .map_err(Into::into)
+            .and_then(|result| result);
+        if let Err(err) = init_terminal() {
+            log::error!("Failed to restore the terminal: {:#}", err);
+        }
+        self.redraw = true;
+
+        if let Err(err) = result {
+            self.notice.add_message(error_notice!(format!(
+                "Failed to open the pager: {:#}",
+                err
+            )));
+        }
+    }
+
+    /// Copy the current conversation, or only the responses of the
+    /// assistant, as a Markdown transcript
+    fn handle_copy_conversation(&mut self, assistant_only: bool) {
+        let general = &config::instance().general;
+        let options = TranscriptOptions {
+            assistant_only,
+            tool_calls: general.copy_tool_calls.unwrap_or_default(),
+            context: general.copy_context.unwrap_or_default(),
+            logprobs: general.copy_logprobs.unwrap_or_default(),
+        };
+        let convo = &self.app_state.current_convo;
+        if convo.is_blank() {
+            self.notice.info(t!("notice.nothing_to_copy"));
+            return;
+        }
+        let _ = self.action_tx.send(Action::CopyText {
+            content: convo.to_markdown(options),
+            notice: true,
+        });
+    }
+
+    /// Whether the arrow keys go to the input box, no popup is open
+    fn input_focused(&self) -> bool {
+        self.tool_approval.is_none()
+            && !self.help_screen.showing()
+            && !self.models_screen.showing()
+            && !self.edit_screen.showing()
+            && !self.history_screen.showing()
+            && !self.search.showing()
+            && !self.search.active()
+    }
+
+    /// Recall the previous prompt when the input box is empty, or move up
+    /// the recalled one. The conversation is scrolled otherwise.
+    fn handle_history_up(&mut self) {
+        if self.app_state.waiting_for_backend
+            || (!self.input.is_empty() && !self.input_history.is_browsing())
+        {
+            self.app_state.scroll.up();
+            return;
+        }
+
+        if self.input.cursor().0 > 0 {
+            self.input.move_cursor(CursorMove::Up);
+            return;
+        }
+        let input = self.input.lines().join("\n");
+        if let Some(entry) = self.input_history.older(&input) {
+            let entry = entry.to_string();
+            self.set_input(&entry);
+        }
+    }
+
+    /// Recall the next prompt while browsing the sent prompts, the typed
+    /// input is restored after the newest one
+    fn handle_history_down(&mut self) {
+        if self.app_state.waiting_for_backend || !self.input_history.is_browsing() {
+            self.app_state.scroll.down();
+            return;
+        }
+
+        if self.input.cursor().0 + 1 < self.input.lines().len() {
+            self.input.move_cursor(CursorMove::Down);
+            return;
+        }
+        if let Some(entry) = self.input_history.newer() {
+            let entry = entry.to_string();
+            self.set_input(&entry);
+        }
+    }
+
+    /// Keep the input as the draft of the current conversation
+    fn stash_draft(&mut self) {
+        if self.app_state.current_convo.is_ephemeral() {
             return;
+        }
+        let id = self.app_state.current_convo.id().to_string();
+        let input = self.input.lines().join("\n");
+        let changed = if input.trim().is_empty() {
+            self.drafts.remove(&id).is_some()
+        } else {
+            self.drafts.insert(id, input.clone()).as_ref() != Some(&input)
         };
+        self.drafts_changed |= changed;
+    }
+
+    /// Put the draft of the current conversation in the input box, the input
+    /// is emptied if there is none
+    fn restore_draft(&mut self) {
+        self.input_history.reset();
+        let draft = self
+            .drafts
+            .get(self.app_state.current_convo.id())
+            .cloned()
+            .unwrap_or_default();
+        self.set_input(&draft);
+    }
+
+    fn clear_draft(&mut self) {
+        self.drafts_changed |= self
+            .drafts
+            .remove(self.app_state.current_convo.id())
+            .is_some();
+    }
+
+    /// Save the drafts if they changed since the last save
+    fn autosave_drafts(&mut self) {
+        if self.drafts_saved_at.elapsed() < DRAFT_SAVE_INTERVAL {
+            return;
+        }
+        self.drafts_saved_at = time::Instant::now();
+        self.stash_draft();
+        if std::mem::take(&mut self.drafts_changed) {
+            let _ = self.action_tx.send(Action::SaveDrafts(self.drafts.clone()));
+        }
+    }
+
+    fn set_input(&mut self, text: &str) {
+        self.input = TextArea::default().build();
+        self.input.insert_str(text);
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection.get_bounds()?;
         let start_row = start.row;
         let end_row = end.row;
 
@@ -435,17 +1076,14 @@ impl<'a> App<'a> {
                     if is_wrapper_span(s) {
                         wrapped = true;
                     }
-                    s.is_selectable() && s.is_highlighted()
+                    s.is_selectable() && self.selection.is_highlighted(s)
                 }));
                 if !wrapped {
                     spans.push(span!("\n"));
                 }
             }
         }
-        let _ = self.action_tx.send(Action::CopyText {
-            content: spans.content(),
-            notice,
-        });
+        Some(spans.content())
     }
 
     fn handle_mouse_click(&mut self, down: bool, button: MouseButton, x: u16, y: u16) {
@@ -491,7 +1129,7 @@ impl<'a> App<'a> {
         }
     }
 
-    fn handle_send_prompt(&mut self) {
+    async fn handle_send_prompt(&mut self) {
         if self.on_waiting_backend(false) {
             return;
         }
@@ -501,11 +1139,87 @@ impl<'a> App<'a> {
         if input_str.is_empty() {
             return;
         }
+        self.input_history.push(input_str);
+
+        if let Some(command) = command::parse(input_str) {
+            self.input = TextArea::default().build();
+            self.clear_draft();
+            match command {
+                Ok(command) => self.handle_command(command).await,
+                Err(err) => self.notice.add_message(error_notice!(format!("{:#}", err))),
+            }
+            return;
+        }
+        let input_str = &command::unescape(input_str).to_string();
+
+        if self.block_over_budget() {
+            return;
+        }
 
-        let first = self.app_state.current_convo.len() < 2;
+        // Inline the files attached with @file, the input is kept if a file
+        // can't be read. The files are read off the UI task
+        let input = input_str.to_string();
+        let max_size = config::instance().general.get_max_attachment_size();
+        let expanded =
+            tokio::task::spawn_blocking(move || expand_file_directives(&input, max_size)).await;
+        let expanded = match expanded.map_err(Into::into).and_then(|expanded| expanded) {
+            Ok(expanded) => expanded,
+            Err(err) => {
+                self.notice
+                    .add_message(error_notice!(format!("Failed to attach file: {:#}", err)));
+                return;
+            }
+        };
+        for warning in expanded.warnings {
+            self.notice.add_message(warn_notice!(warning));
+        }
+        let input_str = &expanded.text;
+
+        let first = self.app_state.current_convo.is_blank();
+        let awaits_title = self.app_state.current_convo.awaits_title();
+
+        let model = self.models_screen.current_model().to_string();
+        let provider = self.models_screen.current_provider().to_string();
+
+        // Check the prompt fits the context window before it is added to the
+        // conversation, so the input is kept for the user to trim it
+        let backend_config = &config::instance().backend;
+        let context = match check_context_window(
+            &self.app_state.current_convo,
+            input_str,
+            backend_config.max_context_tokens(&model),
+            backend_config
+                .context_window(&model)
+                .or(self.models_screen.current().context_window()),
+            config::instance().context.truncation.enabled,
+        ) {
+            ContextFit::Fits(context) => context,
+            ContextFit::Truncated {
+                context,
+                estimated,
+                allowed,
+            } => {
+                self.notice.add_message(warn_notice!(format!(
+                    "The prompt (~{} tokens) exceeds the context window of {} ({} tokens), the oldest messages are dropped",
+                    estimated, model, allowed
+                )));
+                context
+            }
+            ContextFit::Exceeded { estimated, allowed } => {
+                self.notice.add_message(error_notice!(format!(
+                    "The prompt (~{} tokens) exceeds the context window of {} ({} tokens), trim it by ~{} tokens",
+                    estimated,
+                    model,
+                    allowed,
+                    estimated - allowed
+                )));
+                return;
+            }
+        };
 
         let msg = Message::new_user("user", input_str);
         self.input = TextArea::default().build();
+        self.clear_draft();
         self.app_state.add_message(msg.clone());
 
         if self.app_state.current_convo.id().is_empty() {
@@ -519,74 +1233,290 @@ impl<'a> App<'a> {
         }
 
         self.app_state.waiting_for_backend = true;
+        self.app_state.stream_rate.reset();
 
         let convo_id = self.app_state.current_convo.id().to_string();
-        let model = self.models_screen.current_model();
+        let mut prompt = BackendPrompt::new(input_str)
+            .with_context(context)
+            .with_model(&model)
+            .with_provider(&provider);
+        if std::mem::take(&mut self.json_next) {
+            prompt = prompt.with_response_format(ResponseFormat::JsonObject);
+        }
 
-        let prompt = BackendPrompt::new(input_str)
-            .with_context(self.app_state.current_convo.build_context())
-            .with_model(model);
+        let generate_title = awaits_title && self.generates_titles();
+        if awaits_title && !generate_title {
+            let title = match self.title_generator.strategy() {
+                TitleStrategy::FirstMessage => title::fallback_title(input_str),
+                // Left untitled until it is renamed
+                _ => Some(String::new()),
+            };
+            if let Some(title) = title {
+                self.app_state.current_convo.set_title(title);
+                self.history_screen
+                    .upsert_conversation(&self.app_state.current_convo);
+            }
+        }
 
         if first {
             self.save_current_conversation(true);
 
-            // Save the first message to the storage
-            let _ = self.action_tx.send(Action::UpsertMessage(
-                convo_id.to_string(),
-                self.app_state.current_convo.messages()[0].clone(),
-            ));
+            // Save the welcome message to the storage, if any
+            if let Some(hello) = self
+                .app_state
+                .current_convo
+                .messages()
+                .first()
+                .filter(|msg| msg.is_app_message())
+            {
+                self.persist(Action::UpsertMessage(convo_id.to_string(), hello.clone()));
+            }
         }
 
         // Save the current message to the storage
-        let _ = self
-            .action_tx
-            .send(Action::UpsertMessage(convo_id.to_string(), msg.clone()));
+        self.persist(Action::UpsertMessage(convo_id.to_string(), msg.clone()));
 
         self.history_screen
             .update_conversation_updated_at(&convo_id, msg.created_at());
 
         let _ = self.action_tx.send(Action::BackendRequest(prompt));
+        if generate_title {
+            let _ = self.action_tx.send(Action::GenerateTitle(
+                convo_id.to_string(),
+                model,
+                input_str.to_string(),
+            ));
+        }
         self.history_screen.update_items();
     }
 
+    /// Run a slash command typed in the input box
+    async fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Model(id) => match self.models_screen.find_model(&id) {
+                Some(model) => self.models_screen.set_current_model(model),
+                None => self
+                    .notice
+                    .add_message(error_notice!(format!("Model \"{}\" is not available", id))),
+            },
+            Command::New => self.handle_new_conversation(),
+            Command::Scratch => self.handle_scratch_conversation(),
+            Command::Clear => {
+                self.notice.clear();
+                self.selection.clear();
+                self.search.clear();
+            }
+            Command::Reset => self.open_clear_question(),
+            Command::Retry => self.handle_regenerate_response().await,
+            Command::Title(title) => self.handle_set_title(&title),
+            Command::Compress => self.handle_compress_now(),
+            Command::Budget(budget) => self.handle_set_budget(budget),
+            Command::Json => {
+                self.json_next = !self.json_next;
+                let message = if self.json_next {
+                    t!("notice.json_next")
+                } else {
+                    t!("notice.json_cancelled")
+                };
+                self.notice.add_message(info_notice!(message));
+            }
+        }
+    }
+
+    fn handle_set_budget(&mut self, budget: Option<Budget>) {
+        let convo = &mut self.app_state.current_convo;
+        convo.set_budget(budget);
+        self.over_budget_acknowledged.remove(convo.id());
+        let message = match convo.effective_budget() {
+            Some(budget) => t!("notice.budget_updated", budget = format_budget(&budget)),
+            None => t!("notice.budget_removed").to_string(),
+        };
+        self.save_current_conversation(false);
+        self.notice.info(message);
+    }
+
+    /// Add the usage of a response to the spending of the conversation and
+    /// warn once the spending crosses 80% and 100% of the budget
+    fn add_spending(&mut self, tokens: usize, cost: f64) {
+        let convo = &mut self.app_state.current_convo;
+        let budget = convo.effective_budget();
+        let before = budget.and_then(|budget| budget.used(&convo.spending()));
+        convo.add_spending(tokens, cost);
+        let after = budget.and_then(|budget| budget.used(&convo.spending()));
+        self.save_current_conversation(false);
+
+        let (Some(before), Some(after)) = (before, after) else {
+            return;
+        };
+        let message = if before < 1.0 && after >= 1.0 {
+            t!("notice.over_budget")
+        } else if before < BUDGET_WARNING_THRESHOLD && after >= BUDGET_WARNING_THRESHOLD {
+            t!("notice.near_budget")
+        } else {
+            return;
+        };
+        self.notice.add_message(warn_notice!(message));
+    }
+
+    /// Whether the prompt is held back because the conversation is over its
+    /// budget. The next prompt is sent once it has been blocked.
+    fn block_over_budget(&mut self) -> bool {
+        if !config::instance()
+            .general
+            .block_over_budget
+            .unwrap_or_default()
+        {
+            return false;
+        }
+
+        let convo = &self.app_state.current_convo;
+        let over_budget = convo
+            .effective_budget()
+            .and_then(|budget| budget.used(&convo.spending()))
+            .is_some_and(|used| used >= 1.0);
+        if !over_budget || !self.over_budget_acknowledged.insert(convo.id().to_string()) {
+            return false;
+        }
+        self.notice
+            .add_message(warn_notice!(t!("notice.send_over_budget")));
+        true
+    }
+
+    /// The prompt cache hits of the last response and the spending of the
+    /// conversation against its budget, e.g.
+    /// `8k cached (80%) · 12.3k/200k tokens · $0.12/$1.00`
+    fn usage_status(&self) -> Line<'static> {
+        let mut parts = vec![];
+        if let Some(usage) = self
+            .last_usage
+            .as_ref()
+            .filter(|usage| usage.cached_prompt_tokens() > 0 && usage.prompt_tokens > 0)
+        {
+            parts.push(format!(
+                "{} cached ({:.0}%)",
+                utils::format_tokens(usage.cached_prompt_tokens()),
+                usage.cached_prompt_tokens() as f64 * 100.0 / usage.prompt_tokens as f64
+            ));
+        }
+
+        let convo = &self.app_state.current_convo;
+        let mut used = 0.0;
+        if let Some(budget) = convo.effective_budget() {
+            let spending = convo.spending();
+            if let Some(tokens) = budget.tokens {
+                parts.push(format!(
+                    "{}/{} tokens",
+                    utils::format_tokens(spending.tokens),
+                    utils::format_tokens(tokens)
+                ));
+            }
+            if let Some(cost) = budget.cost {
+                parts.push(format!("${:.2}/${:.2}", spending.cost, cost));
+            }
+            used = budget.used(&spending).unwrap_or_default();
+        }
+        if parts.is_empty() {
+            return Line::default();
+        }
+
+        let status = span!("{} ", parts.join(" · "));
+        let status = if used >= 1.0 {
+            status.red()
+        } else if used >= BUDGET_WARNING_THRESHOLD {
+            status.yellow()
+        } else {
+            status.gray()
+        };
+        Line::from(status).alignment(Alignment::Right)
+    }
+
+    fn handle_set_title(&mut self, title: &str) {
+        let convo = &mut self.app_state.current_convo;
+        if convo.id().is_empty() || convo.is_blank() {
+            self.notice.info(t!("notice.nothing_to_rename"));
+            return;
+        }
+
+        convo.set_title(title);
+        self.history_screen
+            .upsert_conversation(&self.app_state.current_convo);
+        self.save_current_conversation(false);
+        self.notice
+            .info(format!("Update conversation's title to \"{}\"", title));
+    }
+
     async fn handle_regenerate_response(&mut self) {
         if self.on_waiting_backend(true) {
             return;
         }
 
         // Rebuild the conversation by removing all the messages from the backend
-        // and resubmit the last message from user
-        {
-            let mut i = self.app_state.current_convo.len() as i32 - 1;
-            if i == 0 {
-                // Welcome message, nothing to do
-                return;
-            }
+        // and resubmit the last message from user. The tool messages belong to
+        // the response as well.
+        let Some(index) = self
+            .app_state
+            .current_convo
+            .messages()
+            .iter()
+            .rposition(|msg| !msg.is_system() && !msg.is_tool())
+        else {
+            // Welcome message, nothing to do
+            return;
+        };
+        self.remove_messages_after(index);
+        self.app_state.sync_state();
+        self.app_state.scroll.last();
 
-            while i >= 0 {
-                if !self.app_state.current_convo.messages_mut()[i as usize].is_system() {
-                    break;
-                }
-                let msg = self
-                    .app_state
-                    .current_convo
-                    .messages_mut()
-                    .remove(i as usize);
-                self.app_state
-                    .bubble_list
-                    .remove_message_by_index(i as usize);
-                // Tell the storage to remove the message
-                let _ = self
-                    .action_tx
-                    .send(Action::DeleteMessage(msg.id().to_string()));
+        self.resubmit_last_user_message();
+    }
 
-                i -= 1;
-            }
+    /// Replace the text of a user message and resend it. The messages after
+    /// it are removed, as when regenerating the response.
+    fn handle_edit_message(&mut self, id: &str, text: &str) {
+        if self.on_waiting_backend(true) {
+            return;
         }
+
+        let Some(index) = self
+            .app_state
+            .current_convo
+            .messages()
+            .iter()
+            .position(|msg| msg.id() == id && !msg.is_system() && !msg.is_tool())
+        else {
+            return;
+        };
+        self.remove_messages_after(index);
+
+        let convo_id = self.app_state.current_convo.id().to_string();
+        let msg = &mut self.app_state.current_convo.messages_mut()[index];
+        msg.set_text(text);
+        // The token count of the previous text is no longer accurate
+        msg.set_token_count(0);
+        let msg = msg.clone();
+        self.persist(Action::UpsertMessage(convo_id, msg));
+
+        self.app_state.bubble_list.remove_message_by_index(index);
         self.app_state.sync_state();
         self.app_state.scroll.last();
 
-        // Resubmit the last message from user
+        self.resubmit_last_user_message();
+    }
+
+    /// Remove the messages after the index from the current conversation and
+    /// the storage
+    fn remove_messages_after(&mut self, index: usize) {
+        let mut i = self.app_state.current_convo.len();
+        while i > index + 1 {
+            i -= 1;
+            let msg = self.app_state.current_convo.messages_mut().remove(i);
+            self.app_state.bubble_list.remove_message_by_index(i);
+            // Tell the storage to remove the message
+            self.persist(Action::DeleteMessage(msg.id().to_string()));
+        }
+    }
+
+    fn resubmit_last_user_message(&mut self) {
         let last_user_msg = self
             .app_state
             .current_convo
@@ -599,36 +1529,187 @@ impl<'a> App<'a> {
         };
 
         let model = self.models_screen.current_model();
+        let max_context_tokens = config::instance().backend.max_context_tokens(model);
         self.app_state.waiting_for_backend = true;
+        self.app_state.stream_rate.reset();
         let prompt = BackendPrompt::new(input_str)
             .with_model(model)
-            .with_context(self.app_state.current_convo.build_context());
+            .with_provider(self.models_screen.current_provider())
+            .with_context(
+                self.app_state
+                    .current_convo
+                    .build_context_with_budget(max_context_tokens),
+            );
 
         let _ = self.action_tx.send(Action::BackendRequest(prompt));
     }
 
-    fn handle_abort(&mut self) {
-        if let Some(msg) = self.app_state.current_convo.last_message() {
-            let convo_id = self.app_state.current_convo.id().to_string();
+    /// The prompt failed to reach the backend. It is kept in the
+    /// conversation and resent once the backend is back online, with Ctrl+R
+    /// to retry right away.
+    fn handle_backend_unreachable(&mut self, err: &str) {
+        self.app_state.waiting_for_backend = false;
+        let conversation_id = self.app_state.current_convo.id().to_string();
+        let attempts = match &self.offline {
+            Some(offline) if offline.conversation_id == conversation_id => offline.attempts,
+            _ => {
+                self.notice
+                    .add_message(warn_notice!(t!("notice.backend_unreachable")));
+                0
+            }
+        };
+        log::debug!("Backend unreachable after {} retries: {}", attempts, err);
+        self.offline = Some(Offline {
+            conversation_id,
+            attempts,
+            retry_at: time::Instant::now() + offline_retry_delay(attempts),
+        });
+    }
+
+    /// Resend the prompt which failed to reach the backend when the retry is
+    /// due. It waits while another conversation is opened.
+    fn retry_when_online(&mut self) {
+        let Some(offline) = self.offline.as_mut() else {
+            return;
+        };
+        match offline.poll(
+            &self.app_state.current_convo,
+            self.app_state.waiting_for_backend,
+        ) {
+            OfflineRetry::Wait => {}
+            OfflineRetry::Due => {
+                log::debug!("Resending the prompt, attempt {}", offline.attempts);
+                self.resubmit_last_user_message();
+            }
+            OfflineRetry::Answered => self.offline = None,
+        }
+    }
+
+    /// The generation speed of the response being streamed, e.g. `~45 tok/s`
+    fn stream_status(&self) -> Line<'static> {
+        let rate = self
+            .app_state
+            .stream_rate
+            .tokens_per_sec()
+            .filter(|_| self.app_state.waiting_for_backend);
+        match rate {
+            Some(rate) => Line::from(span!("~{:.0} tok/s ", rate).gray()),
+            None => Line::default(),
+        }
+    }
+
+    fn scratch_status(&self) -> Line<'static> {
+        if !self.app_state.current_convo.is_ephemeral() {
+            return Line::default();
+        }
+        Line::from(span!("● {} ", t!("status.scratch")).yellow().bold())
+    }
+
+    fn offline_status(&self) -> Line<'static> {
+        if self.offline.is_none() {
+            return Line::default();
+        }
+        Line::from(span!("● {} ", t!("status.offline")).red().bold())
+    }
+
+    /// Cancel the compression of the current conversation, the other
+    /// conversations are left compressing
+    fn cancel_compression(&self) {
+        let convo_id = self.app_state.current_convo.id();
+        if self.compressing.contains_key(convo_id) {
             let _ = self
                 .action_tx
-                .send(Action::UpsertMessage(convo_id, msg.clone()));
+                .send(Action::CancelCompression(convo_id.to_string()));
+        }
+    }
+
+    fn handle_abort(&mut self) {
+        let convo_id = self.app_state.current_convo.id().to_string();
+        if let Some(msg) = self.app_state.current_convo.last_mut_message() {
+            // The response was interrupted while streaming, render it again
+            // to show it
+            if msg.is_system() {
+                msg.set_incomplete(true);
+                let id = msg.id().to_string();
+                self.app_state.bubble_list.remove_message(id);
+            }
+            let msg = msg.clone();
+            self.persist(Action::UpsertMessage(convo_id, msg));
         }
 
         let message = Message::new_system("system", "Aborted!");
         self.app_state.add_message(message.clone());
     }
 
+    fn handle_tool_called(&mut self, call: &ToolCall) {
+        let convo_id = self.app_state.current_convo.id().to_string();
+        if let Some(previous) = self.app_state.add_tool_call(call) {
+            self.persist(Action::UpsertMessage(convo_id.clone(), previous));
+        }
+        if let Some(msg) = self.app_state.current_convo.last_message() {
+            self.persist(Action::UpsertMessage(convo_id, msg.clone()));
+        }
+    }
+
+    /// Save the message ended with a boundary or by the end of the response,
+    /// it is complete although the response may go on. The completion is only
+    /// announced once, when the response is done. A blank response is not
+    /// saved.
+    fn save_ended_message(&mut self, done: bool) {
+        let convo_id = self.app_state.current_convo.id().to_string();
+        let Some(msg) = self.app_state.current_convo.last_message() else {
+            return;
+        };
+        if convo_id.is_empty() || !msg.is_system() || msg.text().trim().is_empty() {
+            return;
+        }
+        let msg = msg.clone();
+        if done {
+            self.persist(Action::MessageCompleted(convo_id, msg));
+        } else {
+            self.persist(Action::UpsertMessage(convo_id, msg));
+        }
+    }
+
+    /// Save the response being streamed from time to time, marked as
+    /// incomplete, so what streamed so far survives a crash. The response is
+    /// saved again once it completes.
+    fn flush_partial_response(&mut self) {
+        if self.partial_flushed_at.elapsed() < PARTIAL_FLUSH_INTERVAL {
+            return;
+        }
+        self.partial_flushed_at = time::Instant::now();
+
+        let convo_id = self.app_state.current_convo.id().to_string();
+        let Some(msg) = self.app_state.current_convo.last_message() else {
+            return;
+        };
+        if convo_id.is_empty() || !msg.is_system() {
+            return;
+        }
+        self.persist(Action::UpsertMessage(
+            convo_id,
+            msg.clone().with_incomplete(true),
+        ));
+    }
+
     fn handle_response(&mut self, resp: &BackendResponse) {
-        let notify = resp.done && resp.init_conversation;
+        self.offline = None;
         let done = resp.done;
         self.app_state.handle_backend_response(resp);
 
+        if resp.boundary && !done {
+            self.save_ended_message(false);
+            return;
+        }
+
         if !done {
+            self.flush_partial_response();
             return;
         }
 
         if let Some(ref usage) = resp.usage {
+            self.last_usage = Some(usage.clone());
             let convo_id = self.app_state.current_convo.id().to_string();
             if let Some(msg) = self
                 .app_state
@@ -636,9 +1717,8 @@ impl<'a> App<'a> {
                 .last_message_of_mut(Some(Issuer::user()))
             {
                 msg.set_token_count(usage.prompt_tokens);
-                let _ = self
-                    .action_tx
-                    .send(Action::UpsertMessage(convo_id.to_string(), msg.clone()));
+                let msg = msg.clone();
+                self.persist(Action::UpsertMessage(convo_id.to_string(), msg));
             }
 
             if let Some(msg) = self
@@ -649,74 +1729,307 @@ impl<'a> App<'a> {
                 msg.set_token_count(usage.completion_tokens);
             }
 
+            let pricing = self
+                .models_screen
+                .find_model(&resp.model)
+                .and_then(|model| model.pricing());
+            self.add_spending(
+                usage.total_tokens,
+                pricing
+                    .map(|pricing| pricing.cost(usage))
+                    .unwrap_or_default(),
+            );
+
             if config::instance().general.show_usage.unwrap_or_default() {
-                self.notice.add_message(info_notice!(
-                    format!("Usage: {}", usage.to_string()),
-                    Duration::from_secs(7)
-                ));
+                let mut message = format!("Usage: {}", usage);
+                if let Some(pricing) = pricing {
+                    message.push_str(&format!(", Cost: ~${:.4}", pricing.cost(usage)));
+                }
+                self.notice
+                    .add_message(info_notice!(message, Duration::from_secs(7)));
             }
         }
 
-        if notify {
-            let title = self.app_state.current_convo.title();
-            self.notice.add_message(info_notice!(
-                format!("Update conversation's title to \"{}\"", title),
-                Duration::from_secs(5)
-            ));
-            // This will update the conversation title in the history
-            self.history_screen
-                .upsert_conversation(&self.app_state.current_convo);
-        }
-
         // Update the conversation updated_at in the history
         self.history_screen.update_conversation_updated_at(
             self.app_state.current_convo.id(),
             self.app_state.current_convo.updated_at(),
         );
 
-        let convo_id = self.app_state.current_convo.id().to_string();
-        let last_message = self.app_state.current_convo.last_message().unwrap().clone();
         // Upsert message to the storage
-        let _ = self
-            .action_tx
-            .send(Action::UpsertMessage(convo_id, last_message));
+        self.save_ended_message(true);
+
+        // Regenerate the title every N messages to keep it relevant. A
+        // response completes an exchange of 2 messages, so the title is
+        // regenerated when the exchange crosses a multiple of N. The first
+        // exchange is titled when it is submitted.
+        let retitle_after = config::instance()
+            .general
+            .auto_retitle_after
+            .unwrap_or_default();
+        let len = self.app_state.current_convo.len();
+        if len > 2
+            && self.generates_titles()
+            && retitle_after > 0
+            && len / retitle_after > len.saturating_sub(2) / retitle_after
+        {
+            self.handle_retitle(false);
+        }
 
         // If the conversation should be compressed, we will process it
         // in the background and notify the app when it's done to fetch
         // the context and update the conversation. This will mitigate
         // impact to the current conversation.
-        if self
-            .compressor
-            .should_compress(&self.app_state.current_convo)
-        {
+        // A scratch conversation is never compressed, the compression works
+        // on the saved messages
+        let convo = &self.app_state.current_convo;
+        if !convo.is_ephemeral() && self.compressor.should_compress(convo) {
             let convo_id = self.app_state.current_convo.id().to_string();
-            let model = config::instance()
-                .context
-                .compression
-                .compress_model
-                .as_deref()
-                .unwrap_or(self.models_screen.current_model())
-                .to_string();
-
-            self.models_screen.current_model().to_string();
+            let model = self.compress_model();
             let _ = self
                 .action_tx
                 .send(Action::CompressConversation(convo_id, model));
         }
     }
 
+    /// Regenerate the title of the current conversation from its latest
+    /// messages. The title is updated by the `ConversationTitleUpdated` event.
+    fn handle_retitle(&mut self, notice: bool) {
+        if self.on_waiting_backend(notice) {
+            return;
+        }
+
+        let convo = &self.app_state.current_convo;
+        if convo.is_ephemeral() {
+            if notice {
+                self.notice.info(t!("notice.not_in_scratch"));
+            }
+            return;
+        }
+        if convo.id().is_empty() || convo.is_blank() {
+            if notice {
+                self.notice.info(t!("notice.nothing_to_retitle"));
+            }
+            return;
+        }
+
+        if notice {
+            self.notice.info(t!("notice.regenerating_title"));
+        }
+        let _ = self.action_tx.send(Action::RegenerateTitle(
+            convo.id().to_string(),
+            self.models_screen.current_model().to_string(),
+            convo.messages().to_vec(),
+        ));
+    }
+
+    fn handle_compress_now(&mut self) {
+        if self.on_waiting_backend(true) {
+            return;
+        }
+
+        if self.app_state.current_convo.is_ephemeral() {
+            self.notice.info(t!("notice.not_in_scratch"));
+            return;
+        }
+        let convo_id = self.app_state.current_convo.id().to_string();
+        if convo_id.is_empty() || self.app_state.current_convo.is_blank() {
+            self.notice.info(t!("notice.nothing_to_compress"));
+            return;
+        }
+
+        if self.compressing.contains_key(&convo_id) {
+            self.notice
+                .add_message(warn_notice!(t!("notice.compressing")));
+            return;
+        }
+
+        let model = self.compress_model();
+        let _ = self
+            .action_tx
+            .send(Action::CompressConversation(convo_id, model));
+    }
+
+    fn compress_model(&self) -> String {
+        self.compressor
+            .model(self.models_screen.current_model())
+            .to_string()
+    }
+
+    /// Show the slash commands matching the input above the input box
+    fn render_command_suggestions(&self, f: &mut ratatui::Frame, input_area: Rect) {
+        let Some(input) = self
+            .input
+            .lines()
+            .first()
+            .filter(|_| self.input.lines().len() == 1)
+        else {
+            return;
+        };
+        let commands = command::suggestions(input);
+        if commands.is_empty() {
+            return;
+        }
+
+        let usage_width = commands
+            .iter()
+            .map(|command| command.usage.len())
+            .max()
+            .unwrap_or_default();
+        let lines = commands
+            .iter()
+            .map(|command| {
+                Line::from(vec![
+                    span!(format!("{:usage_width$}", command.usage))
+                        .green()
+                        .bold(),
+                    span!("  "),
+                    span!(command.description).gray(),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let width = lines
+            .iter()
+            .map(|line| line.width() as u16 + 4)
+            .max()
+            .unwrap_or_default()
+            .min(input_area.width);
+        let height = (lines.len() as u16 + 2).min(input_area.y);
+        let area = Rect::new(input_area.x, input_area.y - height, width, height);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .padding(Padding::horizontal(1))
+            .title(" Commands ");
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// Point at the content added below the viewport while scrolled up
+    fn render_new_content_indicator(&self, f: &mut ratatui::Frame, area: ratatui::layout::Rect) {
+        if !self.app_state.scroll.has_new_content() || area.height < 2 {
+            return;
+        }
+        let area = ratatui::layout::Rect {
+            y: area.bottom() - 1,
+            height: 1,
+            width: area.width.saturating_sub(2),
+            ..area
+        };
+        let line = Line::from(
+            span!(" ↓ new content ")
+                .style(utils::highlight_style(Style::default().black().on_yellow())),
+        );
+        f.render_widget(line.alignment(Alignment::Right), area);
+    }
+
+    fn render_compression_status(&self, f: &mut ratatui::Frame, area: ratatui::layout::Rect) {
+        let convo = &self.app_state.current_convo;
+        let line = if let Some(progress) = self.compressing.get(convo.id()) {
+            let status = if progress.is_empty() {
+                "compressing"
+            } else {
+                progress
+            };
+            Line::from(vec![
+                span!(self.loading.frame()).yellow(),
+                span!(" {}", status).gray(),
+            ])
+        } else if !convo.contexts().is_empty() {
+            Line::from(span!("compressed ✓").green())
+        } else {
+            return;
+        };
+        f.render_widget(line.alignment(Alignment::Right), area);
+    }
+
     fn handle_new_conversation(&mut self) {
         if self.on_waiting_backend(true) {
             return;
         }
 
-        if self.app_state.current_convo.len() < 2 {
+        let convo = &self.app_state.current_convo;
+        if convo.is_blank() && !convo.is_ephemeral() {
             return;
         }
         self.upsert_default_conversation();
         self.change_conversation(Conversation::new_hello(), false);
     }
 
+    /// Ask before removing the messages of the current conversation
+    fn open_clear_question(&mut self) {
+        if self.on_waiting_backend(true) {
+            return;
+        }
+
+        let convo = &self.app_state.current_convo;
+        if convo.is_blank() {
+            self.notice.info(t!("notice.nothing_to_clear"));
+            return;
+        }
+        let title = match convo.title() {
+            "" => t!("history.untitled"),
+            title => title,
+        };
+        self.clear_question.open(vec![
+            span!(t!("notice.clear_question")),
+            span!(format!("\"{}\"", title))
+                .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+                .yellow(),
+            span!("?"),
+        ]);
+    }
+
+    /// Remove the messages of the current conversation. Unlike a new
+    /// conversation, the ID, the title and the settings are kept.
+    fn handle_clear_messages(&mut self) {
+        if self.on_waiting_backend(true) {
+            return;
+        }
+
+        let convo_id = self.app_state.current_convo.id().to_string();
+        self.cancel_compression();
+        self.persist(Action::ClearMessages(convo_id.clone()));
+
+        let mut convo = self.app_state.current_convo.clone();
+        convo.clear_messages();
+        let title = convo.title().to_string();
+        // The conversation is blank now, it is saved here for the update time
+        self.persist(Action::UpsertConversation(UpsertConvoRequest {
+            convo: convo.clone(),
+            include_context: false,
+            include_messages: false,
+        }));
+
+        self.history_screen.upsert_conversation(&convo);
+        self.app_state.set_conversation(convo);
+        self.last_usage = None;
+        self.search.clear();
+        self.notice
+            .info(t!("notice.messages_cleared", title = title));
+    }
+
+    /// Switch to a scratch conversation, kept in memory only. It is left out
+    /// of the history and discarded once switched away.
+    fn handle_scratch_conversation(&mut self) {
+        if self.on_waiting_backend(true) {
+            return;
+        }
+
+        if self.app_state.current_convo.is_ephemeral() {
+            self.notice.info(t!("notice.already_scratch"));
+            return;
+        }
+        let convo = Conversation::new_hello()
+            .with_id(uuid::Uuid::new_v4().to_string())
+            .with_title(t!("history.scratch"))
+            .with_ephemeral(true);
+        self.change_conversation(convo, false);
+        self.notice.info(t!("notice.scratch_started"));
+    }
+
     fn upsert_default_conversation(&mut self) {
         let convo = Conversation::new_hello();
         self.history_screen.add_conversation_and_set(&convo);
@@ -732,44 +2045,66 @@ impl<'a> App<'a> {
         // Otherwise, let save the last message in the conversation
         let convo_id = self.app_state.current_convo.id();
         if let Some(last) = self.app_state.current_convo.last_message() {
-            let _ = self
-                .action_tx
-                .send(Action::UpsertMessage(convo_id.to_string(), last.clone()));
+            self.persist(Action::UpsertMessage(convo_id.to_string(), last.clone()));
         }
     }
 
+    /// The titles are generated and saved by the title generator, not for a
+    /// scratch conversation
+    fn generates_titles(&self) -> bool {
+        self.title_generator.is_enabled() && !self.app_state.current_convo.is_ephemeral()
+    }
+
+    /// Send the action saving the current conversation to the storage, a
+    /// scratch conversation is never saved
+    fn persist(&self, action: Action) {
+        if self.app_state.current_convo.is_ephemeral() {
+            return;
+        }
+        let _ = self.action_tx.send(action);
+    }
+
     fn save_current_conversation(&mut self, save_messages: bool) {
-        if self.app_state.current_convo.len() < 2 {
+        if self.app_state.current_convo.is_blank() {
             return;
         }
 
-        let _ = self
-            .action_tx
-            .send(Action::UpsertConversation(UpsertConvoRequest {
-                convo: self.app_state.current_convo.clone(),
-                include_context: false,
-                include_messages: save_messages,
-            }));
+        self.persist(Action::UpsertConversation(UpsertConvoRequest {
+            convo: self.app_state.current_convo.clone(),
+            include_context: false,
+            include_messages: save_messages,
+        }));
     }
 
     fn change_conversation(&mut self, convo: Conversation, save_messages: bool) {
         // Save the current conversation
         self.save_current_conversation(save_messages);
+        self.stash_draft();
 
         // Change the conversation
         self.history_screen.set_current_conversation(convo.id());
         let title = convo.title().to_string();
         self.app_state.set_conversation(convo);
-        self.notice.info(format!("Switching to \"{}\"", title));
-        self.input = TextArea::default().build();
+        self.last_usage = None;
+        self.notice.info(t!("notice.switching", title = title));
+        if self
+            .app_state
+            .current_convo
+            .last_message()
+            .is_some_and(|msg| msg.is_incomplete())
+        {
+            self.notice
+                .add_message(warn_notice!(t!("notice.incomplete_response")));
+        }
+        self.restore_draft();
+        self.search.clear();
         self.app_state.sync_state();
     }
 
     fn on_waiting_backend(&mut self, notice: bool) -> bool {
         if self.app_state.waiting_for_backend && notice {
-            self.notice.add_message(warn_notice!(
-                "Waiting for backend to respond, please wait..."
-            ));
+            self.notice
+                .add_message(warn_notice!(t!("notice.waiting_backend")));
         }
         self.app_state.waiting_for_backend
     }
@@ -778,3 +2113,77 @@ impl<'a> App<'a> {
 fn is_line_width_sufficient(line_width: u16) -> bool {
     line_width >= MIN_WIDTH
 }
+
+/// Build the loading message and spinner from the configuration
+fn build_loading<'a>() -> Loading<'a> {
+    let general = &config::instance().general;
+    let message = match general.loading_message.as_deref() {
+        Some(message) => Line::from(span!(message.to_string()).gray()),
+        None => Line::from(vec![
+            span!(t!("loading.press")).gray(),
+            span!("Ctrl+c").green().bold(),
+            span!(t!("loading.to_abort")).gray(),
+        ]),
+    };
+    Loading::new(message).with_frames(loading::spinner_frames(general.spinner))
+}
+
+/// The fraction of the budget at which the first warning is shown
+const BUDGET_WARNING_THRESHOLD: f64 = 0.8;
+
+/// The prompt of the conversation which failed to reach the backend
+struct Offline {
+    conversation_id: String,
+    attempts: u32,
+    retry_at: time::Instant,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum OfflineRetry {
+    Wait,
+    /// The prompt is resent, the attempt is counted
+    Due,
+    /// The prompt has been answered or removed in the meantime
+    Answered,
+}
+
+impl Offline {
+    /// Whether the prompt is due to be resent in the opened conversation
+    fn poll(&mut self, convo: &Conversation, waiting_for_backend: bool) -> OfflineRetry {
+        if waiting_for_backend
+            || self.retry_at > time::Instant::now()
+            || self.conversation_id != convo.id()
+        {
+            return OfflineRetry::Wait;
+        }
+        if !convo
+            .messages()
+            .last()
+            .is_some_and(|msg| !msg.is_system() && !msg.is_tool())
+        {
+            return OfflineRetry::Answered;
+        }
+
+        self.attempts += 1;
+        self.retry_at = time::Instant::now() + offline_retry_delay(self.attempts);
+        OfflineRetry::Due
+    }
+}
+
+/// The delay before resending the prompt after the given number of retries
+fn offline_retry_delay(attempts: u32) -> Duration {
+    OFFLINE_RETRY_INTERVAL
+        .saturating_mul(2u32.saturating_pow(attempts))
+        .min(OFFLINE_RETRY_MAX_INTERVAL)
+}
+
+fn format_budget(budget: &Budget) -> String {
+    let mut parts = vec![];
+    if let Some(tokens) = budget.tokens {
+        parts.push(format!("{} tokens", utils::format_tokens(tokens)));
+    }
+    if let Some(cost) = budget.cost {
+        parts.push(format!("${:.2}", cost));
+    }
+    parts.join(" and ")
+}
@@ -1,11 +1,17 @@
-use crate::models::{BackendResponse, Conversation, Message};
+#[cfg(test)]
+#[path = "app_state_test.rs"]
+mod tests;
+
+use crate::config::{self, ColorTheme};
+use crate::models::{BackendResponse, Conversation, Message, ToolCall};
 use ratatui::layout::Rect;
 use syntect::highlighting::Theme;
 
-use crate::{app::ui::BubbleList, app::ui::Scroll};
+use crate::{app::stream_rate::StreamRate, app::ui::BubbleList, app::ui::Scroll};
 
 pub(crate) struct AppState<'a> {
     theme: &'a Theme,
+    colors: &'a ColorTheme,
     pub bubble_list: BubbleList<'a>,
     pub last_known_height: usize,
     pub last_known_width: usize,
@@ -13,24 +19,33 @@ pub(crate) struct AppState<'a> {
 
     pub current_convo: Conversation,
     pub waiting_for_backend: bool,
+    pub stream_rate: StreamRate,
+    // The last message ended with a boundary, the response resumes in a
+    // new message
+    message_ended: bool,
 }
 
 impl<'a> AppState<'a> {
-    pub fn new(theme: &'a Theme) -> AppState<'a> {
+    pub fn new(theme: &'a Theme, colors: &'a ColorTheme) -> AppState<'a> {
         AppState {
             theme,
-            bubble_list: BubbleList::new(theme),
+            colors,
+            bubble_list: BubbleList::new(theme, colors),
             last_known_height: 0,
             last_known_width: 0,
             current_convo: Conversation::new_hello(),
             scroll: Scroll::default(),
             waiting_for_backend: false,
+            stream_rate: StreamRate::default(),
+            message_ended: false,
         }
     }
 
     pub fn set_conversation(&mut self, convo: Conversation) {
         self.current_convo = convo;
-        self.bubble_list = BubbleList::new(self.theme);
+        self.message_ended = false;
+        self.bubble_list = BubbleList::new(self.theme, self.colors)
+            .with_tool_args_expanded(self.bubble_list.tool_args_expanded());
         self.sync_state();
         // Move the scroll to the last message
         self.scroll.last();
@@ -44,63 +59,100 @@ impl<'a> AppState<'a> {
 
     pub fn add_message(&mut self, message: Message) {
         self.current_convo.append_message(message);
+        self.message_ended = false;
         self.sync_state();
         self.scroll.last();
     }
 
-    pub fn handle_backend_response(&mut self, resp: &BackendResponse) {
-        if self.current_convo.is_empty()
-            || matches!(self.current_convo.messages().last(), Some(last) if !last.is_system())
+    /// Append the tool call to the current conversation. The response
+    /// streamed before the call is returned to be saved, it is dropped
+    /// instead if it's blank. The view follows the call like the response.
+    pub fn add_tool_call(&mut self, call: &ToolCall) -> Option<Message> {
+        let mut previous = None;
+        if let Some(last) = self
+            .current_convo
+            .messages()
+            .last()
+            .filter(|m| m.is_system())
         {
-            self.current_convo
-                .append_message(Message::new_system(&resp.model, "").with_id(&resp.id));
+            if last.text().trim().is_empty() {
+                let id = last.id().to_string();
+                self.current_convo.messages_mut().pop();
+                self.bubble_list.remove_message(id);
+            } else {
+                previous = Some(last.clone());
+            }
         }
+        self.current_convo.append_message(Message::new_tool(call));
+        self.message_ended = false;
+        self.sync_state();
+        previous
+    }
 
-        {
+    /// Append the response to the current conversation. A blank chunk
+    /// doesn't start a message, e.g. the turn only calling tools leaves no
+    /// empty message behind, but its `done` and usage are still handled.
+    pub fn handle_backend_response(&mut self, resp: &BackendResponse) {
+        if resp.boundary && resp.text.is_empty() && !resp.done {
+            self.message_ended = true;
+            return;
+        }
+
+        let last = self.current_convo.messages().last();
+        let resumed = self.message_ended || last.is_some_and(|last| last.is_tool());
+        // A blank message is continued rather than left behind
+        let ended = self.message_ended && last.is_some_and(|last| !last.text().trim().is_empty());
+        let mut streaming = last.is_some_and(|last| last.is_system()) && !ended;
+        if !streaming && !resp.text.trim().is_empty() {
+            let mut message = Message::new_system(&resp.model, "");
+            // The response resumed after a tool call or a boundary is a new
+            // message, the response id belongs to its first message
+            if !resumed {
+                message = message.with_id(&resp.id);
+            }
+            self.current_convo.append_message(message);
+            streaming = true;
+        }
+
+        if streaming {
+            self.message_ended = resp.boundary;
             let last_message = self.current_convo.last_mut_message().unwrap();
             last_message.append(&resp.text);
+            self.stream_rate.add(&resp.text);
+            if resp.refusal {
+                last_message.set_refusal(true);
+            }
+            last_message.append_logprobs(&resp.logprobs);
         }
 
         if resp.done {
-            if resp.init_conversation {
-                // The init convesrsation message will contain the title of
-                // the conversation at the beginning of the text and starts with #
-
-                // Get the first line of the last message
-                let first_line = self
-                    .current_convo
-                    .messages()
-                    .last()
-                    .unwrap()
-                    .text()
-                    .lines()
-                    .next()
-                    .unwrap_or("");
-                // Check if the first line starts with #
-                if first_line.starts_with('#') {
-                    // Remove the # and any leading spaces
-                    let title = first_line.trim_start_matches('#').trim();
-                    if !title.is_empty() {
-                        // Set the title of the conversation
-                        self.current_convo.set_title(title.to_string());
-                    }
-                }
+            if let Some(last_message) = self.current_convo.last_mut_message() {
+                let updated_at = last_message.created_at();
+                self.current_convo.set_updated_at(updated_at);
             }
-            let updated_at = self.current_convo.last_mut_message().unwrap().created_at();
-            self.current_convo.set_updated_at(updated_at);
             self.waiting_for_backend = false;
+            self.stream_rate.reset();
         }
         self.sync_state();
     }
 
+    /// Rebuild the lines of the messages. The view follows the streamed
+    /// response if it was at the bottom or `ui.follow_output` is set,
+    /// otherwise the position is kept and the new content is flagged.
     pub fn sync_state(&mut self) {
+        let previous_len = self.bubble_list.len();
         self.bubble_list
             .set_messages(self.current_convo.messages(), self.last_known_width);
         let scrollbar_at_bottom = self.scroll.is_position_at_last();
         self.scroll
             .set_state(self.bubble_list.len(), self.last_known_height);
-        if self.waiting_for_backend && scrollbar_at_bottom {
+        if !self.waiting_for_backend {
+            return;
+        }
+        if scrollbar_at_bottom || config::instance().ui.follow_output.unwrap_or_default() {
             self.scroll.last();
+        } else if self.bubble_list.len() > previous_len {
+            self.scroll.set_new_content();
         }
     }
 }
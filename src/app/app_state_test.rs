@@ -0,0 +1,91 @@
+use super::*;
+use crate::models::BackendUsage;
+
+#[test]
+fn test_tool_call_only_turn() {
+    let theme = Theme::default();
+    let colors = ColorTheme::default();
+    let mut state = AppState::new(&theme, &colors);
+    state.set_rect(Rect::new(0, 0, 80, 24));
+    state.set_conversation(Conversation::default().with_id("1"));
+    state.add_message(Message::new_user("user", "What's the weather?"));
+    state.waiting_for_backend = true;
+
+    // The turn starts with blank deltas before calling a tool
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o"));
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_text("\n"));
+    assert_eq!(state.current_convo.len(), 1);
+
+    let call = ToolCall {
+        tool: "weather".to_string(),
+        provider: "mcp".to_string(),
+        args: None,
+        result: "Sunny".to_string(),
+        is_error: false,
+    };
+    assert!(state.add_tool_call(&call).is_none());
+
+    // The response completes without any text
+    state.handle_backend_response(
+        &BackendResponse::new("r1", "gpt-4o")
+            .with_done()
+            .with_usage(BackendUsage::default()),
+    );
+    assert!(!state.waiting_for_backend);
+    let messages = state.current_convo.messages();
+    assert_eq!(messages.len(), 2);
+    assert!(messages[1].is_tool());
+    assert!(!messages.iter().any(|msg| msg.is_system()));
+}
+
+#[test]
+fn test_blank_delta_after_text() {
+    let theme = Theme::default();
+    let colors = ColorTheme::default();
+    let mut state = AppState::new(&theme, &colors);
+    state.set_rect(Rect::new(0, 0, 80, 24));
+    state.set_conversation(Conversation::default().with_id("1"));
+    state.add_message(Message::new_user("user", "Hi"));
+
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_text("Hello"));
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_text(" "));
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_done());
+
+    let messages = state.current_convo.messages();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].id(), "r1");
+    assert_eq!(messages[1].text(), "Hello ");
+}
+
+#[test]
+fn test_boundary_splits_response() {
+    let theme = Theme::default();
+    let colors = ColorTheme::default();
+    let mut state = AppState::new(&theme, &colors);
+    state.set_rect(Rect::new(0, 0, 80, 24));
+    state.set_conversation(Conversation::default().with_id("1"));
+    state.add_message(Message::new_user("user", "Hi"));
+    state.waiting_for_backend = true;
+
+    // The first message ends with the boundary of its last chunk
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_text("Let me "));
+    state.handle_backend_response(
+        &BackendResponse::new("r1", "gpt-4o")
+            .with_text("check.")
+            .with_boundary(),
+    );
+    // A bare boundary doesn't start an empty message, the text resumes in a
+    // new one
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_boundary());
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_text("It's sunny."));
+    state.handle_backend_response(&BackendResponse::new("r1", "gpt-4o").with_done());
+
+    assert!(!state.waiting_for_backend);
+    let messages = state.current_convo.messages();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1].id(), "r1");
+    assert_eq!(messages[1].text(), "Let me check.");
+    assert!(messages[2].is_system());
+    assert_ne!(messages[2].id(), "r1");
+    assert_eq!(messages[2].text(), "It's sunny.");
+}
@@ -0,0 +1,39 @@
+use super::*;
+
+#[test]
+fn test_offline_poll() {
+    let mut convo = Conversation::default().with_id("1");
+    convo.append_message(Message::new_user("user", "Hello"));
+    let mut offline = Offline {
+        conversation_id: "1".to_string(),
+        attempts: 0,
+        retry_at: time::Instant::now() + offline_retry_delay(0),
+    };
+
+    // The retry is not due yet
+    assert_eq!(offline.poll(&convo, false), OfflineRetry::Wait);
+
+    offline.retry_at = time::Instant::now();
+    assert_eq!(offline.poll(&convo, false), OfflineRetry::Due);
+    assert_eq!(offline.attempts, 1);
+    assert!(offline.retry_at > time::Instant::now());
+
+    // Nothing is resent while waiting for the backend or in another
+    // conversation
+    offline.retry_at = time::Instant::now();
+    assert_eq!(offline.poll(&convo, true), OfflineRetry::Wait);
+    let other = Conversation::default().with_id("2");
+    assert_eq!(offline.poll(&other, false), OfflineRetry::Wait);
+    assert_eq!(offline.attempts, 1);
+
+    // The backend is back online
+    convo.append_message(Message::new_system("gpt-4o", "Hi"));
+    assert_eq!(offline.poll(&convo, false), OfflineRetry::Answered);
+}
+
+#[test]
+fn test_offline_retry_delay() {
+    assert_eq!(offline_retry_delay(0), OFFLINE_RETRY_INTERVAL);
+    assert_eq!(offline_retry_delay(1), OFFLINE_RETRY_INTERVAL * 2);
+    assert_eq!(offline_retry_delay(100), OFFLINE_RETRY_MAX_INTERVAL);
+}
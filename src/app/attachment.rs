@@ -0,0 +1,250 @@
+#[cfg(test)]
+#[path = "attachment_test.rs"]
+mod tests;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use eyre::{Context, Result, bail};
+use regex::Regex;
+
+const FILE_DIRECTIVE: &str = "@file";
+/// The maximum number of files attached to a prompt
+const MAX_FILES: usize = 32;
+/// The maximum size in bytes of all the files attached to a prompt, unless a
+/// single file may be larger
+const MAX_TOTAL_SIZE: usize = 1024 * 1024;
+
+/// The prompt with the `@file` directives replaced by the file contents
+#[derive(Debug, Default)]
+pub struct ExpandedPrompt {
+    pub text: String,
+    /// The files attached, in order
+    pub files: Vec<String>,
+    /// The problems which don't prevent the prompt from being sent, e.g. a
+    /// file truncated to the size limit
+    pub warnings: Vec<String>,
+}
+
+/// Replace the lines starting with `@file <path>...` with the content of the
+/// files in fenced blocks annotated with the file name. The paths can be glob
+/// patterns (`*`, `?` and `**`). The files larger than `max_size` bytes are
+/// truncated, as are the files past the total size limit. The directives
+/// inside code blocks are left untouched. This reads the file system, so it
+/// must not run on the async runtime.
+pub fn expand_file_directives(input: &str, max_size: usize) -> Result<ExpandedPrompt> {
+    let mut expanded = ExpandedPrompt::default();
+    let total_size = MAX_TOTAL_SIZE.max(max_size);
+    let mut remaining = total_size;
+    let mut lines = vec![];
+    let mut in_codeblock = false;
+
+    for line in input.lines() {
+        if line.trim_start().starts_with("```") {
+            in_codeblock = !in_codeblock;
+        }
+
+        let patterns = match parse_directive(line) {
+            Some(patterns) if !in_codeblock => patterns,
+            _ => {
+                lines.push(line.to_string());
+                continue;
+            }
+        };
+        if patterns.is_empty() {
+            bail!("{} requires a path", FILE_DIRECTIVE);
+        }
+
+        for pattern in patterns {
+            let paths = expand_pattern(pattern)?;
+            if paths.is_empty() {
+                bail!("no file matches {}", pattern);
+            }
+            if expanded.files.len() + paths.len() > MAX_FILES {
+                bail!("more than {} files are attached", MAX_FILES);
+            }
+            for path in paths {
+                let name = display_path(&path);
+                if remaining == 0 {
+                    bail!("the attached files exceed {} bytes", total_size);
+                }
+                let limit = max_size.min(remaining);
+                let (content, truncated) =
+                    read_file(&path, limit).wrap_err(format!("reading {}", name))?;
+                remaining -= content.len();
+                if truncated {
+                    expanded.warnings.push(format!(
+                        "{} is larger than {} bytes, only the beginning is attached",
+                        name, limit
+                    ));
+                }
+                let fence = code_fence(&content);
+                lines.push(format!("{}{}", fence, name));
+                lines.push(content.trim_end_matches('\n').to_string());
+                lines.push(fence);
+                expanded.files.push(name);
+            }
+        }
+    }
+
+    expanded.text = lines.join("\n");
+    Ok(expanded)
+}
+
+/// Return a fence one backtick longer than the longest run of backticks in
+/// the content, so a fence inside the file doesn't close the block
+fn code_fence(content: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Return the paths of the directive, `None` if the line is not a directive
+fn parse_directive(line: &str) -> Option<Vec<&str>> {
+    let rest = line.trim().strip_prefix(FILE_DIRECTIVE)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(rest.split_whitespace().collect())
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Expand the glob pattern into the matching files, sorted. A path without
+/// glob characters is returned as is, so a missing file is reported when it
+/// is read.
+fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>> {
+    if !is_glob(pattern) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+
+    let (base, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (PathBuf::new(), pattern),
+    };
+    let parts = rest
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            if part == "**" || !is_glob(part) {
+                return Ok(Component::Literal(part.to_string()));
+            }
+            Ok(Component::Pattern(component_regex(part)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut paths = vec![];
+    walk(&base, &parts, &mut paths);
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+enum Component {
+    Literal(String),
+    Pattern(Regex),
+}
+
+fn component_regex(part: &str) -> Result<Regex> {
+    let mut re = String::from("^");
+    for c in part.chars() {
+        match c {
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).wrap_err(format!("compiling pattern {}", part))
+}
+
+/// Collect the files matching the components, the walk stops once more
+/// files than can be attached are found
+fn walk(dir: &Path, parts: &[Component], paths: &mut Vec<PathBuf>) {
+    if paths.len() > MAX_FILES {
+        return;
+    }
+    let Some((part, rest)) = parts.split_first() else {
+        if dir.is_file() {
+            paths.push(dir.to_path_buf());
+        }
+        return;
+    };
+
+    match part {
+        Component::Literal(name) if name == "**" => {
+            // Match zero or more directories, the symlinks are not followed
+            walk(dir, rest, paths);
+            for (name, path) in read_dir(dir) {
+                if !name.starts_with('.') && path.is_dir() && !path.is_symlink() {
+                    walk(&path, parts, paths);
+                }
+            }
+        }
+        Component::Literal(name) => walk(&dir.join(name), rest, paths),
+        Component::Pattern(re) => {
+            for (name, path) in read_dir(dir) {
+                // The hidden files are only matched explicitly
+                if name.starts_with('.') && !re.as_str().starts_with(r"^\.") {
+                    continue;
+                }
+                if re.is_match(&name) {
+                    walk(&path, rest, paths);
+                }
+            }
+        }
+    }
+}
+
+fn read_dir(dir: &Path) -> Vec<(String, PathBuf)> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = dir.join(&name);
+            (name, path)
+        })
+        .collect()
+}
+
+fn display_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().to_string()
+}
+
+/// Read at most `max_size` bytes of the file, returns whether it was
+/// truncated
+fn read_file(path: &Path, max_size: usize) -> Result<(String, bool)> {
+    let file = File::open(path)?;
+    let mut bytes = vec![];
+    file.take(max_size as u64 + 1).read_to_end(&mut bytes)?;
+
+    let truncated = bytes.len() > max_size;
+    bytes.truncate(max_size);
+    match String::from_utf8(bytes) {
+        Ok(content) => Ok((content, truncated)),
+        // A character cut by the size limit
+        Err(err) if truncated && err.utf8_error().error_len().is_none() => {
+            let valid = err.utf8_error().valid_up_to();
+            Ok((
+                String::from_utf8_lossy(&err.as_bytes()[..valid]).into(),
+                true,
+            ))
+        }
+        Err(_) => bail!("not a text file"),
+    }
+}
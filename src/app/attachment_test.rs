@@ -0,0 +1,162 @@
+use super::*;
+
+fn temp_dir(files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chatty-test-{}", uuid::Uuid::new_v4()));
+    for (name, content) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_parse_directive() {
+    assert_eq!(parse_directive("@file a.rs"), Some(vec!["a.rs"]));
+    assert_eq!(
+        parse_directive("  @file a.rs  b.rs "),
+        Some(vec!["a.rs", "b.rs"])
+    );
+    assert_eq!(parse_directive("@file"), Some(vec![]));
+    assert_eq!(parse_directive("@files a.rs"), None);
+    assert_eq!(parse_directive("see @file a.rs"), None);
+}
+
+#[test]
+fn test_expand_file_directives() {
+    let dir = temp_dir(&[("a.rs", "fn a() {}\n"), ("b.txt", "hello")]);
+    let a = dir.join("a.rs").to_string_lossy().to_string();
+    let b = dir.join("b.txt").to_string_lossy().to_string();
+
+    let input = format!("Explain\n@file {}\n@file {}\nThanks", a, b);
+    let expanded = expand_file_directives(&input, 1024).unwrap();
+    assert_eq!(
+        expanded.text,
+        format!(
+            "Explain\n```{}\nfn a() {{}}\n```\n```{}\nhello\n```\nThanks",
+            a, b
+        )
+    );
+    assert_eq!(expanded.files, vec![a, b]);
+    assert!(expanded.warnings.is_empty());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_expand_file_directives_embedded_fence() {
+    let dir = temp_dir(&[("README.md", "Run\n```sh\ncargo run\n```\n")]);
+    let readme = dir.join("README.md").to_string_lossy().to_string();
+
+    let expanded = expand_file_directives(&format!("@file {}", readme), 1024).unwrap();
+    assert_eq!(
+        expanded.text,
+        format!("````{}\nRun\n```sh\ncargo run\n```\n````", readme)
+    );
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_expand_file_directives_glob() {
+    let dir = temp_dir(&[
+        ("src/a.rs", "a"),
+        ("src/b.rs", "b"),
+        ("src/c.txt", "c"),
+        ("src/nested/d.rs", "d"),
+        ("src/.hidden.rs", "hidden"),
+    ]);
+    let base = dir.to_string_lossy().to_string();
+
+    let expanded = expand_file_directives(&format!("@file {}/src/*.rs", base), 1024).unwrap();
+    assert_eq!(
+        expanded.files,
+        vec![format!("{}/src/a.rs", base), format!("{}/src/b.rs", base)]
+    );
+
+    let expanded = expand_file_directives(&format!("@file {}/**/?.rs", base), 1024).unwrap();
+    assert_eq!(
+        expanded.files,
+        vec![
+            format!("{}/src/a.rs", base),
+            format!("{}/src/b.rs", base),
+            format!("{}/src/nested/d.rs", base),
+        ]
+    );
+
+    let err = expand_file_directives(&format!("@file {}/*.md", base), 1024).unwrap_err();
+    assert!(err.to_string().starts_with("no file matches"));
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_expand_file_directives_size_limit() {
+    let dir = temp_dir(&[("big.txt", "0123456789"), ("utf8.txt", "ééé")]);
+    let big = dir.join("big.txt").to_string_lossy().to_string();
+    let utf8 = dir.join("utf8.txt").to_string_lossy().to_string();
+
+    let expanded = expand_file_directives(&format!("@file {}", big), 4).unwrap();
+    assert_eq!(expanded.text, format!("```{}\n0123\n```", big));
+    assert_eq!(expanded.warnings.len(), 1);
+
+    // A character cut by the limit is dropped
+    let expanded = expand_file_directives(&format!("@file {}", utf8), 3).unwrap();
+    assert_eq!(expanded.text, format!("```{}\né\n```", utf8));
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_expand_file_directives_errors() {
+    let err = expand_file_directives("@file /chatty/not/found.rs", 1024).unwrap_err();
+    assert_eq!(err.to_string(), "reading /chatty/not/found.rs");
+
+    let err = expand_file_directives("@file", 1024).unwrap_err();
+    assert_eq!(err.to_string(), "@file requires a path");
+
+    // The directives in code blocks are kept
+    let input = "```\n@file /chatty/not/found.rs\n```";
+    let expanded = expand_file_directives(input, 1024).unwrap();
+    assert_eq!(expanded.text, input);
+    assert!(expanded.files.is_empty());
+}
+
+#[test]
+fn test_expand_file_directives_total_limits() {
+    let half = "0".repeat(MAX_TOTAL_SIZE / 2 + 1);
+    let dir = temp_dir(&[("a.txt", &half), ("b.txt", &half), ("c.txt", "c")]);
+    let base = dir.to_string_lossy().to_string();
+
+    // The second file is cut to the remaining size, no room for the third
+    let err = expand_file_directives(&format!("@file {}/*.txt", base), MAX_TOTAL_SIZE).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("the attached files exceed {} bytes", MAX_TOTAL_SIZE)
+    );
+
+    let expanded = expand_file_directives(
+        &format!("@file {}/a.txt {}/b.txt", base, base),
+        MAX_TOTAL_SIZE,
+    )
+    .unwrap();
+    assert_eq!(expanded.files.len(), 2);
+    assert_eq!(expanded.warnings.len(), 1);
+    let _ = std::fs::remove_dir_all(dir);
+
+    let names = (0..=MAX_FILES)
+        .map(|i| (format!("{}.txt", i), "x"))
+        .collect::<Vec<_>>();
+    let files = names
+        .iter()
+        .map(|(name, content)| (name.as_str(), *content))
+        .collect::<Vec<_>>();
+    let dir = temp_dir(&files);
+    let err = expand_file_directives(&format!("@file {}/*.txt", dir.to_string_lossy()), 1024)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("more than {} files are attached", MAX_FILES)
+    );
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,199 @@
+#[cfg(test)]
+#[path = "command_test.rs"]
+mod tests;
+
+use eyre::{Result, bail};
+
+use crate::models::Budget;
+
+/// A slash command typed in the input box instead of a prompt
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Model(String),
+    New,
+    Clear,
+    /// Remove the messages of the conversation, its ID and title are kept
+    Reset,
+    Retry,
+    Title(String),
+    Compress,
+    /// Override the budget of the conversation, `None` restores the default
+    Budget(Option<Budget>),
+    /// Ask for a JSON answer to the next prompt, toggled off if already set
+    Json,
+    /// Start a conversation which is never saved
+    Scratch,
+}
+
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: "model",
+        usage: "/model <name>",
+        description: "Switch to another model",
+    },
+    CommandInfo {
+        name: "new",
+        usage: "/new",
+        description: "Start a new conversation",
+    },
+    CommandInfo {
+        name: "scratch",
+        usage: "/scratch",
+        description: "Start a conversation which is never saved",
+    },
+    CommandInfo {
+        name: "clear",
+        usage: "/clear",
+        description: "Clear the notices and the search",
+    },
+    CommandInfo {
+        name: "reset",
+        usage: "/reset",
+        description: "Remove the messages of the conversation",
+    },
+    CommandInfo {
+        name: "retry",
+        usage: "/retry",
+        description: "Regenerate the last response",
+    },
+    CommandInfo {
+        name: "title",
+        usage: "/title <text>",
+        description: "Rename the conversation",
+    },
+    CommandInfo {
+        name: "compress",
+        usage: "/compress",
+        description: "Compress the conversation now",
+    },
+    CommandInfo {
+        name: "budget",
+        usage: "/budget <$cost|tokens|off|default>",
+        description: "Set the budget of the conversation",
+    },
+    CommandInfo {
+        name: "json",
+        usage: "/json",
+        description: "Answer the next prompt in JSON",
+    },
+];
+
+/// Parse the input as a slash command. `None` is returned if the input is a
+/// prompt, a prompt starting with a slash is written with two slashes.
+pub fn parse(input: &str) -> Option<Result<Command>> {
+    let input = input.trim();
+    let rest = input.strip_prefix('/')?;
+    if rest.starts_with('/') {
+        return None;
+    }
+
+    let (name, arg) = match rest.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (rest, ""),
+    };
+    Some(build(name, arg))
+}
+
+fn build(name: &str, arg: &str) -> Result<Command> {
+    let command = match name {
+        "model" | "title" | "budget" if arg.is_empty() => bail!("usage: {}", usage(name)),
+        "model" => Command::Model(arg.to_string()),
+        "title" => Command::Title(arg.to_string()),
+        "new" | "scratch" | "clear" | "reset" | "retry" | "compress" | "json"
+            if !arg.is_empty() =>
+        {
+            bail!("/{} takes no arguments", name)
+        }
+        "new" => Command::New,
+        "scratch" => Command::Scratch,
+        "clear" => Command::Clear,
+        "reset" => Command::Reset,
+        "retry" => Command::Retry,
+        "compress" => Command::Compress,
+        "json" => Command::Json,
+        "budget" => Command::Budget(parse_budget(arg)?),
+        _ => bail!("unknown command /{}, press / to list the commands", name),
+    };
+    Ok(command)
+}
+
+/// Parse the budget of `/budget`, e.g. `$0.5`, `200k` tokens or both of
+/// them. `off` removes the limits and `default` restores the default budget.
+fn parse_budget(arg: &str) -> Result<Option<Budget>> {
+    match arg {
+        "off" => return Ok(Some(Budget::default())),
+        "default" => return Ok(None),
+        _ => {}
+    }
+
+    let mut budget = Budget::default();
+    for limit in arg.split_whitespace() {
+        if let Some(cost) = limit.strip_prefix('$') {
+            match cost.parse::<f64>() {
+                Ok(cost) if cost > 0.0 => budget.cost = Some(cost),
+                _ => bail!("invalid cost \"{}\", e.g. $0.5", limit),
+            }
+            continue;
+        }
+
+        let lower = limit.to_lowercase();
+        let (number, multiplier) = match lower.strip_suffix('k') {
+            Some(number) => (number, 1_000.0),
+            None => match lower.strip_suffix('m') {
+                Some(number) => (number, 1_000_000.0),
+                None => (lower.as_str(), 1.0),
+            },
+        };
+        match number.parse::<f64>() {
+            Ok(tokens) if tokens >= 1.0 / multiplier => {
+                budget.tokens = Some((tokens * multiplier).round() as usize)
+            }
+            _ => bail!("invalid tokens \"{}\", e.g. 200k", limit),
+        }
+    }
+    Ok(Some(budget))
+}
+
+fn usage(name: &str) -> &'static str {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name)
+        .map(|command| command.usage)
+        .unwrap_or_default()
+}
+
+/// Remove the escaping slash of a prompt starting with two slashes
+pub fn unescape(input: &str) -> &str {
+    match input.strip_prefix("//") {
+        Some(_) => &input[1..],
+        None => input,
+    }
+}
+
+/// The commands matching the input while it is typed. The command name is
+/// completed by prefix, the usage of the command is shown once it is typed.
+pub fn suggestions(input: &str) -> Vec<&'static CommandInfo> {
+    let Some(rest) = input.strip_prefix('/') else {
+        return vec![];
+    };
+    if rest.starts_with('/') || rest.contains('\n') {
+        return vec![];
+    }
+
+    match rest.split_once(char::is_whitespace) {
+        Some((name, _)) => COMMANDS
+            .iter()
+            .filter(|command| command.name == name)
+            .collect(),
+        None => COMMANDS
+            .iter()
+            .filter(|command| command.name.starts_with(rest))
+            .collect(),
+    }
+}
@@ -0,0 +1,97 @@
+use super::*;
+
+#[test]
+fn test_parse() {
+    assert!(parse("hello").is_none());
+    assert!(parse("//usr/bin").is_none());
+    assert!(parse("a /new").is_none());
+
+    let cases = [
+        ("/new", Command::New),
+        ("/scratch", Command::Scratch),
+        (" /clear ", Command::Clear),
+        ("/reset", Command::Reset),
+        ("/retry", Command::Retry),
+        ("/compress", Command::Compress),
+        ("/json", Command::Json),
+        ("/model gpt-4o", Command::Model("gpt-4o".to_string())),
+        (
+            "/title  My  title ",
+            Command::Title("My  title".to_string()),
+        ),
+        ("/budget default", Command::Budget(None)),
+        ("/budget off", Command::Budget(Some(Budget::default()))),
+        (
+            "/budget $0.5",
+            Command::Budget(Some(Budget {
+                tokens: None,
+                cost: Some(0.5),
+            })),
+        ),
+        (
+            "/budget 1.5k $2",
+            Command::Budget(Some(Budget {
+                tokens: Some(1500),
+                cost: Some(2.0),
+            })),
+        ),
+        (
+            "/budget 2M",
+            Command::Budget(Some(Budget {
+                tokens: Some(2_000_000),
+                cost: None,
+            })),
+        ),
+    ];
+    for (input, want) in cases {
+        assert_eq!(parse(input).unwrap().unwrap(), want, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_parse_errors() {
+    let cases = [
+        (
+            "/unknown",
+            "unknown command /unknown, press / to list the commands",
+        ),
+        ("/model", "usage: /model <name>"),
+        ("/title ", "usage: /title <text>"),
+        ("/new now", "/new takes no arguments"),
+        ("/scratch now", "/scratch takes no arguments"),
+        ("/reset all", "/reset takes no arguments"),
+        ("/budget", "usage: /budget <$cost|tokens|off|default>"),
+        ("/budget $abc", "invalid cost \"$abc\", e.g. $0.5"),
+        ("/budget $0", "invalid cost \"$0\", e.g. $0.5"),
+        ("/budget lots", "invalid tokens \"lots\", e.g. 200k"),
+    ];
+    for (input, want) in cases {
+        let err = parse(input).unwrap().unwrap_err();
+        assert_eq!(err.to_string(), want, "input: {}", input);
+    }
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape("//usr/bin"), "/usr/bin");
+    assert_eq!(unescape("/new"), "/new");
+    assert_eq!(unescape("hello"), "hello");
+}
+
+#[test]
+fn test_suggestions() {
+    let names = |input: &str| {
+        suggestions(input)
+            .iter()
+            .map(|command| command.name)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names("/").len(), COMMANDS.len());
+    assert_eq!(names("/c"), vec!["clear", "compress"]);
+    assert_eq!(names("/model gpt"), vec!["model"]);
+    assert!(names("/x").is_empty());
+    assert!(names("//").is_empty());
+    assert!(names("hello").is_empty());
+    assert!(names("/new\nmore").is_empty());
+}
@@ -276,6 +276,9 @@ impl Initializer {
 
             let paragraph = Paragraph::new(tasks).block(Block::default());
             paragraph.render(popup_area, f.buffer_mut());
+            if config::instance().theme.no_color() {
+                utils::strip_colors(f.buffer_mut());
+            }
         })?;
         Ok(())
     }
@@ -0,0 +1,104 @@
+#[cfg(test)]
+#[path = "input_history_test.rs"]
+mod tests;
+
+use std::collections::VecDeque;
+
+/// The prompts sent in the input box, recalled with the arrow keys like the
+/// history of a shell. The oldest prompts are dropped beyond the capacity.
+#[derive(Debug, Default)]
+pub struct InputHistory {
+    entries: VecDeque<String>,
+    capacity: usize,
+    // The entry shown in the input box while browsing, counted from the
+    // newest one
+    index: Option<usize>,
+    // The input typed before browsing, restored after the newest entry
+    draft: String,
+}
+
+impl InputHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Load the entries, from the oldest to the newest
+    pub fn with_entries(mut self, entries: Vec<String>) -> Self {
+        for entry in entries {
+            self.push(entry);
+        }
+        self
+    }
+
+    /// The entries from the oldest to the newest
+    pub fn entries(&self) -> Vec<String> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Add a sent prompt and stop browsing. A prompt repeating the newest
+    /// entry is only kept once.
+    pub fn push(&mut self, entry: impl Into<String>) {
+        self.reset();
+        let entry = entry.into();
+        if self.capacity == 0
+            || entry.trim().is_empty()
+            || self.entries.back().is_some_and(|last| *last == entry)
+        {
+            return;
+        }
+        self.entries.push_back(entry);
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn is_browsing(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Stop browsing, the input box is left as is
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.draft.clear();
+    }
+
+    /// Move to the previous (older) entry. The current input is kept as the
+    /// draft when browsing starts. `None` if there is no older entry.
+    pub fn older(&mut self, input: &str) -> Option<&str> {
+        let index = match self.index {
+            Some(index) if index + 1 < self.entries.len() => index + 1,
+            Some(_) => return None,
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = input.to_string();
+                0
+            }
+        };
+        self.index = Some(index);
+        self.get(index)
+    }
+
+    /// Move to the next (newer) entry, the draft is returned after the
+    /// newest one. `None` if not browsing.
+    pub fn newer(&mut self) -> Option<&str> {
+        match self.index? {
+            0 => {
+                self.index = None;
+                Some(&self.draft)
+            }
+            index => {
+                self.index = Some(index - 1);
+                self.get(index - 1)
+            }
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&str> {
+        self.entries
+            .get(self.entries.len().checked_sub(index + 1)?)
+            .map(String::as_str)
+    }
+}
@@ -0,0 +1,43 @@
+use super::*;
+
+#[test]
+fn test_browse() {
+    let mut history = InputHistory::new(10).with_entries(vec!["first".into(), "second".into()]);
+    assert!(!history.is_browsing());
+    assert_eq!(history.newer(), None);
+
+    assert_eq!(history.older("draft"), Some("second"));
+    assert!(history.is_browsing());
+    assert_eq!(history.older(""), Some("first"));
+    assert_eq!(history.older(""), None);
+    assert_eq!(history.newer(), Some("second"));
+    // The draft is restored after the newest entry
+    assert_eq!(history.newer(), Some("draft"));
+    assert!(!history.is_browsing());
+    assert_eq!(history.newer(), None);
+}
+
+#[test]
+fn test_push() {
+    let mut history = InputHistory::new(2);
+    assert_eq!(history.older(""), None);
+
+    history.push("first");
+    history.push("first");
+    history.push("  ");
+    assert_eq!(history.entries(), vec!["first"]);
+
+    history.push("second");
+    history.push("third");
+    assert_eq!(history.entries(), vec!["second", "third"]);
+
+    // Sending stops browsing
+    assert_eq!(history.older(""), Some("third"));
+    history.push("fourth");
+    assert!(!history.is_browsing());
+    assert_eq!(history.older(""), Some("fourth"));
+
+    let mut history = InputHistory::new(0);
+    history.push("first");
+    assert!(history.entries().is_empty());
+}
@@ -1,8 +1,13 @@
 #[allow(clippy::module_inception)]
 pub mod app;
 pub mod app_state;
+pub mod attachment;
+pub mod command;
 pub mod initializer;
+pub mod input_history;
+pub mod pager;
 pub mod services;
+pub mod stream_rate;
 pub mod ui;
 
 use std::io;
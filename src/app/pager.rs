@@ -0,0 +1,61 @@
+#[cfg(test)]
+#[path = "pager_test.rs"]
+mod tests;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use eyre::{Context, Result, bail};
+
+const DEFAULT_PAGER: &str = "less";
+
+/// Write the text to a temp file and open it with `$PAGER`, or `$EDITOR` if
+/// it is not set. The call blocks until the program exits, so it must not run
+/// on the async runtime, and the terminal must be handed over to it by the
+/// caller.
+pub fn open(text: &str) -> Result<()> {
+    let command = pager_command(
+        std::env::var("PAGER").ok().as_deref(),
+        std::env::var("EDITOR").ok().as_deref(),
+    );
+
+    let path = std::env::temp_dir().join(format!("chatty-{}.md", uuid::Uuid::new_v4()));
+    fs::write(&path, text).wrap_err(format!("writing {}", path.display()))?;
+    let result = run(&command, &path);
+    if let Err(err) = fs::remove_file(&path) {
+        log::warn!("Failed to remove {}: {}", path.display(), err);
+    }
+    result
+}
+
+/// Run the command with the path appended through the shell, so the quoting
+/// and the variables of the command are handled the way the shell does
+fn run(command: &str, path: &Path) -> Result<()> {
+    if command.trim().is_empty() {
+        bail!("no pager or editor is set");
+    }
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", command))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .wrap_err(format!("launching {}", command))?;
+    if !status.success() {
+        bail!("{} exited with {}", command, status);
+    }
+    Ok(())
+}
+
+/// The command to open the file with, e.g. `code --wait`. The pager is
+/// preferred over the editor, `less` is used if neither is set.
+fn pager_command(pager: Option<&str>, editor: Option<&str>) -> String {
+    [pager, editor]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|command| !command.is_empty())
+        .unwrap_or(DEFAULT_PAGER)
+        .to_string()
+}
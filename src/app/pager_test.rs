@@ -0,0 +1,46 @@
+use super::*;
+
+#[test]
+fn test_pager_command() {
+    let cases = [
+        (Some("bat -p"), Some("vim"), "bat -p"),
+        (None, Some("code --wait"), "code --wait"),
+        (Some("  "), Some("nano"), "nano"),
+        (None, None, "less"),
+    ];
+    for (pager, editor, want) in cases {
+        assert_eq!(
+            pager_command(pager, editor),
+            want,
+            "{:?} {:?}",
+            pager,
+            editor
+        );
+    }
+}
+
+#[test]
+fn test_run_failed() {
+    let path = Path::new("/dev/null");
+    let err = run("false", path).unwrap_err();
+    assert!(err.to_string().starts_with("false exited with"));
+
+    assert!(run(" ", path).is_err());
+    assert!(run("true", path).is_ok());
+}
+
+#[test]
+fn test_run_quoted_command() {
+    let dir = std::env::temp_dir().join(format!("chatty test {}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("in put.md");
+    fs::write(&path, "hello").unwrap();
+
+    // The quoted arguments are kept whole, as is the path with spaces
+    let out = dir.join("out put.md");
+    let command = format!("sh -c 'cp \"$1\" \"$0\"' '{}'", out.display());
+    run(&command, &path).unwrap();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "hello");
+
+    let _ = fs::remove_dir_all(dir);
+}
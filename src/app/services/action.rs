@@ -1,26 +1,34 @@
 use eyre::Result;
+use std::collections::HashMap;
 use std::sync::{Arc, atomic};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
-    backend::ArcBackend,
-    context::Compressor,
+    backend::{ArcBackend, error::ProviderError, utils::is_connection_error},
+    config::{self, model_info::ModelCatalog},
+    context::{Compressor, TitleGenerator, title::TitleSource},
     error_event, info_event,
     models::{
-        Action, ArcEventTx, BackendPrompt, Context, Conversation, Event, Message,
+        Action, ArcEventTx, BackendPrompt, Context, Conversation, Event, Message, Severity,
         UpsertConvoRequest,
     },
-    storage::ArcStorage,
+    storage::{ArcStorage, DRAFTS_STATE, semantic::SemanticIndex},
     warn_event,
 };
 
-use super::clipboard::ClipboardService;
+use super::{
+    clipboard::{self, ClipboardService},
+    hooks::Hooks,
+};
 
 pub struct ActionService {
     backend: ArcBackend,
     storage: ArcStorage,
     compressor: Arc<Compressor>,
+    title_generator: Arc<TitleGenerator>,
+    hooks: Hooks,
 
     action_rx: mpsc::UnboundedReceiver<Action>,
     event_tx: mpsc::UnboundedSender<Event>,
@@ -28,6 +36,7 @@ pub struct ActionService {
     pending_tasks: Arc<atomic::AtomicUsize>,
 
     worker: Option<tokio::task::JoinHandle<Result<()>>>,
+    compressions: HashMap<String, CancellationToken>, /* Conversation ID - Token */
 }
 
 impl ActionService {
@@ -42,6 +51,8 @@ impl ActionService {
         pending_tasks: Arc<atomic::AtomicUsize>,
     ) -> Self {
         Self {
+            title_generator: Arc::new(TitleGenerator::new(backend.clone())),
+            hooks: Hooks::default(),
             backend,
             storage,
             compressor,
@@ -51,9 +62,20 @@ impl ActionService {
             cancel_token,
             pending_tasks,
             worker: None,
+            compressions: HashMap::new(),
         }
     }
 
+    pub fn with_title_generator(mut self, title_generator: Arc<TitleGenerator>) -> Self {
+        self.title_generator = title_generator;
+        self
+    }
+
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         log::debug!("Action service started");
         loop {
@@ -83,7 +105,14 @@ impl ActionService {
                 self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
                 self.worker = Some(tokio::spawn(async move {
                     if let Err(err) = completions(&backend, prompt, Arc::clone(&event_tx)).await {
-                        worker_error(err, Arc::clone(&event_tx)).await?;
+                        if is_connection_error(&err) {
+                            log::warn!("Backend unreachable: {:#}", err);
+                            event_tx
+                                .send(Event::BackendUnreachable(format!("{:#}", err)))
+                                .await?;
+                        } else {
+                            worker_error(err, Arc::clone(&event_tx)).await?;
+                        }
                     }
                     Ok(())
                 }))
@@ -108,17 +137,43 @@ impl ActionService {
             }
 
             Action::DeleteConversation(id) => self.process_delete_convo(&id).await,
+            Action::RestoreConversation(id) => self.process_restore_convo(&id).await,
             Action::UpsertConversation(req) => self.process_upsert_convo(req).await,
             Action::UpsertMessage(convo_id, message) => {
                 self.process_upsert_message(&convo_id, message).await
             }
+            Action::MessageCompleted(convo_id, message) => {
+                self.process_upsert_message(&convo_id, message.clone())
+                    .await;
+                self.hooks.message_completed(&convo_id, &message);
+            }
             Action::UpsertConvoContext(convo_id, ctx) => {
                 self.process_upsert_context(&convo_id, ctx).await
             }
             Action::DeleteMessage(msg_id) => self.process_delete_message(&msg_id).await,
+            Action::ClearMessages(convo_id) => self.process_clear_messages(&convo_id).await,
+            Action::GetMessageEdits(convo_id, msg_id) => {
+                self.process_get_message_edits(&convo_id, msg_id).await
+            }
+            Action::SaveDrafts(drafts) => self.process_save_drafts(drafts).await,
             Action::CompressConversation(convo_id, model_id) => {
                 self.process_copress_convo(&convo_id, &model_id)
             }
+            Action::CancelCompression(convo_id) => {
+                if let Some(token) = self.compressions.remove(&convo_id) {
+                    token.cancel();
+                }
+            }
+            Action::GenerateTitle(convo_id, model_id, message) => {
+                self.process_generate_title(&convo_id, &model_id, TitleSource::Message(message))
+            }
+            Action::RefreshModels => self.process_refresh_models(),
+            Action::SemanticSearch(query) => self.process_semantic_search(query),
+            Action::RegenerateTitle(convo_id, model_id, messages) => self.process_generate_title(
+                &convo_id,
+                &model_id,
+                TitleSource::Conversation(messages),
+            ),
             Action::SetConversation(convo_id) => {
                 self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
                 let result = self.get_convo(&convo_id).await;
@@ -144,6 +199,31 @@ impl ActionService {
             .send(Event::ConversationDeleted(convo_id.to_string()));
     }
 
+    async fn process_restore_convo(&mut self, convo_id: &str) {
+        self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
+        let result = match self.storage.restore_conversation(convo_id).await {
+            Ok(_) => self.storage.get_conversation(convo_id).await,
+            Err(err) => Err(err),
+        };
+        self.pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
+        match result {
+            Ok(Some(convo)) => {
+                let _ = self.event_tx.send(Event::ConversationRestored(convo));
+            }
+            Ok(None) => {
+                let _ = self
+                    .event_tx
+                    .send(warn_event!("The conversation no longer exists"));
+            }
+            Err(err) => {
+                let _ = self.event_tx.send(error_event!(format!(
+                    "Failed to restore conversation: {}",
+                    err
+                )));
+            }
+        }
+    }
+
     async fn process_delete_message(&mut self, msg_id: &str) {
         self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
         let result = self.storage.delete_messsage(msg_id).await;
@@ -155,6 +235,43 @@ impl ActionService {
         }
     }
 
+    async fn process_clear_messages(&mut self, convo_id: &str) {
+        self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
+        let result = self.storage.clear_messages(convo_id).await;
+        self.pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
+        if let Err(err) = result {
+            let _ = self
+                .event_tx
+                .send(error_event!(format!("Failed to clear messages: {}", err)));
+        }
+    }
+
+    async fn process_get_message_edits(&mut self, convo_id: &str, msg_id: String) {
+        match self.storage.get_message_edits(convo_id, &msg_id).await {
+            Ok(edits) => {
+                let _ = self.event_tx.send(Event::MessageEdits(msg_id, edits));
+            }
+            Err(err) => {
+                let _ = self.event_tx.send(error_event!(format!(
+                    "Failed to get message edits: {}",
+                    err
+                )));
+            }
+        }
+    }
+
+    async fn process_save_drafts(&mut self, drafts: HashMap<String, String>) {
+        self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
+        let result = match serde_json::to_string(&drafts) {
+            Ok(drafts) => self.storage.set_app_state(DRAFTS_STATE, &drafts).await,
+            Err(err) => Err(err.into()),
+        };
+        self.pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
+        if let Err(err) = result {
+            log::error!("Failed to save the drafts: {:#}", err);
+        }
+    }
+
     async fn process_upsert_context(&mut self, convo_id: &str, ctx: Context) {
         self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
         let result = self.storage.upsert_context(convo_id, ctx).await;
@@ -182,12 +299,18 @@ impl ActionService {
         let convo_id = req.convo.id().to_string();
         let result = self.storage.upsert_conversation(req.convo.clone()).await;
         self.pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
-        if let Err(err) = result {
-            let _ = self.event_tx.send(error_event!(format!(
-                "Failed to upsert conversation: {}",
-                err
-            )));
-            return;
+        let created = match result {
+            Ok(inserted) => inserted,
+            Err(err) => {
+                let _ = self.event_tx.send(error_event!(format!(
+                    "Failed to upsert conversation: {}",
+                    err
+                )));
+                return;
+            }
+        };
+        if created {
+            self.hooks.conversation_created(&req.convo);
         }
 
         if req.include_messages {
@@ -230,86 +353,265 @@ impl ActionService {
                 .join("\n\n")
         }
 
-        ClipboardService::set(payload)?;
-        let _ = self.event_tx.send(info_event!("Copied to clipboard!"));
-        Ok(())
+        self.set_clipboard(payload, true)
     }
 
     async fn copy_text(&self, content: String, notice: bool) -> Result<()> {
-        ClipboardService::set(content)?;
+        self.set_clipboard(content, notice)
+    }
+
+    /// Copy the content to the clipboard. If it is not available, the
+    /// content of an explicit copy (with the notice) is saved to a temp file
+    /// and the user is told its path.
+    fn set_clipboard(&self, content: String, notice: bool) -> Result<()> {
+        let copied = if ClipboardService::is_available() {
+            ClipboardService::set(content.as_str())
+        } else {
+            Err(eyre::eyre!("clipboard is not available"))
+        };
+
+        if let Err(err) = copied {
+            log::warn!("Failed to copy to clipboard: {:#}", err);
+            if !notice {
+                return Ok(());
+            }
+            let path = clipboard::save_to_temp_file(&content)?;
+            let _ = self.event_tx.send(warn_event!(format!(
+                "Clipboard is not available, saved to {}",
+                path.display()
+            )));
+            return Ok(());
+        }
+
         if notice {
             let _ = self.event_tx.send(info_event!("Copied to clipboard!"));
         }
         Ok(())
     }
 
-    fn process_copress_convo(&mut self, conversation_id: &str, model_id: &str) {
-        let storage = self.storage.clone();
-        let compressor = self.compressor.clone();
-        let conversation_id = conversation_id.to_string();
-        let model_id = model_id.to_string();
+    fn process_refresh_models(&mut self) {
+        let backend = self.backend.clone();
         let event_tx = self.event_tx.clone();
         self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
 
         let pending_tasks = self.pending_tasks.clone();
         tokio::spawn(async move {
-            let _ = event_tx.send(warn_event!(
-                format!("Compressing conversation using model \"{}\"... Please do NOT close the app until this process is finished!", model_id)
-            ));
-
-            let convo = match storage.get_conversation(&conversation_id).await {
-                Ok(conversation) => conversation,
+            match backend.refresh_models().await {
+                Ok(models) => {
+                    let config = config::instance();
+                    let catalog = ModelCatalog::load(config.backend.model_info_path.as_deref())
+                        .unwrap_or_default();
+                    let models = models
+                        .into_iter()
+                        .map(|model| catalog.annotate(model, &config.backend))
+                        .collect::<Vec<_>>();
+                    let _ =
+                        event_tx.send(info_event!(format!("Available {} model(s)", models.len())));
+                    let _ = event_tx.send(Event::ModelsUpdated(models));
+                }
                 Err(err) => {
-                    log::error!("Failed to get conversation: {}", err);
+                    log::error!("Failed to refresh models: {}", err);
                     let _ =
-                        event_tx.send(warn_event!(format!("Failed to get conversation: {}", err)));
-                    None
+                        event_tx.send(error_event!(format!("Failed to refresh models: {}", err)));
                 }
-            };
-
-            if convo.is_none() {
-                pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
-                return;
             }
+            pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
+        });
+    }
 
-            let convo = convo.unwrap();
+    fn process_semantic_search(&mut self, query: String) {
+        let config = &config::instance().search;
+        let index =
+            SemanticIndex::new(self.backend.clone(), self.storage.clone()).from_config(config);
+        let limit = config.max_results();
+        let event_tx = self.event_tx.clone();
+        self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
 
-            let context = match compressor.compress(&model_id, &convo).await {
-                Ok(context) => context,
+        let pending_tasks = self.pending_tasks.clone();
+        tokio::spawn(async move {
+            match index.search(&query, limit).await {
+                Ok(results) => {
+                    let ids = results.into_iter().map(|(id, _)| id).collect();
+                    let _ = event_tx.send(Event::SemanticSearchResults(query, ids));
+                }
                 Err(err) => {
-                    log::error!("Failed to compress conversation: {}", err);
-                    let _ = event_tx.send(warn_event!(format!(
-                        "Failed to compress conversation: {}",
+                    log::error!("Failed to search conversations: {:#}", err);
+                    let _ = event_tx.send(error_event!(format!(
+                        "Failed to search conversations: {:#}",
                         err
                     )));
-                    None
                 }
-            };
-
-            if context.is_none() {
-                pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
-                return;
             }
+            pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
+        });
+    }
+
+    fn process_generate_title(
+        &mut self,
+        conversation_id: &str,
+        model_id: &str,
+        source: TitleSource,
+    ) {
+        let storage = self.storage.clone();
+        let title_generator = self.title_generator.clone();
+        let conversation_id = conversation_id.to_string();
+        let model_id = model_id.to_string();
+        let event_tx = self.event_tx.clone();
+        self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
 
-            // Push the context to the conversation
-            if let Err(err) = storage
-                .upsert_context(&conversation_id, context.unwrap())
-                .await
+        let pending_tasks = self.pending_tasks.clone();
+        tokio::spawn(async move {
+            if let Err(err) = generate_title(
+                storage,
+                title_generator,
+                &conversation_id,
+                &model_id,
+                &source,
+                &event_tx,
+            )
+            .await
             {
-                let _ = event_tx.send(warn_event!(format!("Failed to save context: {}", err)));
-                pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
-                return;
+                log::error!("Failed to generate title: {}", err);
+                let _ = event_tx.send(warn_event!(format!("Failed to generate title: {}", err)));
             }
+            pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
+        });
+    }
+
+    fn process_copress_convo(&mut self, conversation_id: &str, model_id: &str) {
+        let storage = self.storage.clone();
+        let compressor = self.compressor.clone();
+        let conversation_id = conversation_id.to_string();
+        let model_id = model_id.to_string();
+        let event_tx = self.event_tx.clone();
+        self.pending_tasks.fetch_add(1, atomic::Ordering::SeqCst);
+
+        // The compression is cancelled with the abort of its conversation and
+        // on quit, the token of a finished compression is cancelled so it is
+        // dropped here
+        self.compressions.retain(|_, token| !token.is_cancelled());
+        let token = self.cancel_token.child_token();
+        self.compressions
+            .insert(conversation_id.clone(), token.clone());
 
-            if let Some(convo) = storage.get_conversation(&conversation_id).await.unwrap() {
-                let _ = event_tx.send(Event::ConversationUpdated(convo));
-                let _ = event_tx.send(info_event!("Context compressed!"));
+        let pending_tasks = self.pending_tasks.clone();
+        tokio::spawn(async move {
+            let _ = event_tx.send(Event::CompressionStarted(conversation_id.clone()));
+            let timeout = compressor.timeout();
+            let compression =
+                compress_convo(storage, compressor, &conversation_id, &model_id, &event_tx);
+            tokio::select! {
+                _ = token.cancelled() => {
+                    let _ = event_tx.send(warn_event!("Compression cancelled"));
+                }
+                finished = with_timeout(timeout, compression) => {
+                    if !finished {
+                        let _ = event_tx.send(warn_event!(format!(
+                            "Compression cancelled after {}s",
+                            timeout.unwrap_or_default().as_secs()
+                        )));
+                    }
+                }
             }
+            token.cancel();
+            let _ = event_tx.send(Event::CompressionFinished(conversation_id));
             pending_tasks.fetch_sub(1, atomic::Ordering::SeqCst);
         });
     }
 }
 
+/// Run the future within the timeout if set, returns false if it timed out
+async fn with_timeout(timeout: Option<Duration>, future: impl Future<Output = ()>) -> bool {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.is_ok(),
+        None => {
+            future.await;
+            true
+        }
+    }
+}
+
+async fn generate_title(
+    storage: ArcStorage,
+    title_generator: Arc<TitleGenerator>,
+    conversation_id: &str,
+    model_id: &str,
+    source: &TitleSource,
+    event_tx: &mpsc::UnboundedSender<Event>,
+) -> Result<()> {
+    let Some(title) = title_generator.generate_from(model_id, source).await? else {
+        return Ok(());
+    };
+
+    if let Some(convo) = storage.get_conversation(conversation_id).await? {
+        storage
+            .upsert_conversation(convo.with_title(&title))
+            .await?;
+    }
+
+    let _ = event_tx.send(Event::ConversationTitleUpdated(
+        conversation_id.to_string(),
+        title,
+    ));
+    Ok(())
+}
+
+async fn compress_convo(
+    storage: ArcStorage,
+    compressor: Arc<Compressor>,
+    conversation_id: &str,
+    model_id: &str,
+    event_tx: &mpsc::UnboundedSender<Event>,
+) {
+    let _ = event_tx.send(warn_event!(
+        format!("Compressing conversation using model \"{}\"... Please do NOT close the app until this process is finished!", model_id)
+    ));
+
+    let convo = match storage.get_conversation(conversation_id).await {
+        Ok(conversation) => conversation,
+        Err(err) => {
+            log::error!("Failed to get conversation: {}", err);
+            let _ = event_tx.send(warn_event!(format!("Failed to get conversation: {}", err)));
+            None
+        }
+    };
+
+    let Some(convo) = convo else {
+        return;
+    };
+
+    let context = match compressor
+        .force_compress_with_progress(model_id, &convo, Some(event_tx))
+        .await
+    {
+        Ok(context) => context,
+        Err(err) => {
+            log::error!("Failed to compress conversation: {}", err);
+            let _ = event_tx.send(warn_event!(format!(
+                "Failed to compress conversation: {}",
+                err
+            )));
+            return;
+        }
+    };
+
+    let Some(context) = context else {
+        let _ = event_tx.send(info_event!("Nothing to compress"));
+        return;
+    };
+
+    // Push the context to the conversation
+    if let Err(err) = storage.upsert_context(conversation_id, context).await {
+        let _ = event_tx.send(warn_event!(format!("Failed to save context: {}", err)));
+        return;
+    }
+
+    if let Ok(Some(convo)) = storage.get_conversation(conversation_id).await {
+        let _ = event_tx.send(Event::ConversationUpdated(convo));
+        let _ = event_tx.send(info_event!("Context compressed!"));
+    }
+}
+
 async fn completions(
     backend: &ArcBackend,
     prompt: BackendPrompt,
@@ -320,12 +622,24 @@ async fn completions(
 }
 
 async fn worker_error(err: eyre::Error, event_tx: ArcEventTx) -> Result<()> {
+    log::error!("Backend failed: {:?}", err);
     event_tx
-        .send(Event::BackendMessage(Message::new_system(
-            "system",
-            format!("Error: Backend failed with the following error: \n\n {err:?}"),
-        )))
+        .send(Event::BackendMessage(
+            Message::new_system("system", error_message(&err)).with_severity(Severity::Error),
+        ))
         .await?;
 
     Ok(())
 }
+
+/// The message shown for a failed request, the status and the code of the
+/// provider error with a hint if it is answered by the provider
+fn error_message(err: &eyre::Error) -> String {
+    let Some(provider_err) = ProviderError::find(err) else {
+        return format!("Error: Backend failed with the following error: \n\n {err:#}");
+    };
+    match provider_err.hint() {
+        Some(hint) => format!("Error: {}\n\n{}", provider_err, hint),
+        None => format!("Error: {}", provider_err),
+    }
+}
@@ -1,8 +1,19 @@
-use eyre::{Result, bail};
+#[cfg(test)]
+#[path = "clipboard_test.rs"]
+mod tests;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use base64::Engine;
+use eyre::{Context, Result, bail};
 use once_cell::sync::OnceCell;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
+use crate::config::{self, ClipboardMode};
+
 static SENDER: OnceCell<mpsc::UnboundedSender<String>> = OnceCell::new();
 
 pub struct ClipboardService;
@@ -37,7 +48,24 @@ impl ClipboardService {
         Ok(())
     }
 
+    /// Whether the text is copied to the system clipboard, the service only
+    /// needs to be started then
+    pub fn uses_system() -> bool {
+        !use_osc52(config::instance().general.clipboard, is_remote_session())
+    }
+
+    /// Whether the clipboard can be written. The system clipboard service
+    /// stops on the first error.
+    pub fn is_available() -> bool {
+        !Self::uses_system() || SENDER.get().is_some_and(|tx| !tx.is_closed())
+    }
+
+    /// Copy the text to the clipboard selected by `general.clipboard`
     pub fn set(text: impl Into<String>) -> Result<()> {
+        if !Self::uses_system() {
+            return set_osc52(&text.into());
+        }
+
         if let Some(tx) = SENDER.get() {
             tx.send(text.into())?;
             return Ok(());
@@ -46,3 +74,81 @@ impl ClipboardService {
         bail!("clipboard service is not initialized")
     }
 }
+
+/// Whether OSC 52 is used instead of the system clipboard
+fn use_osc52(mode: ClipboardMode, remote: bool) -> bool {
+    match mode {
+        ClipboardMode::Auto => remote,
+        ClipboardMode::System => false,
+        ClipboardMode::Osc52 => true,
+    }
+}
+
+/// Whether the app runs over SSH, the system clipboard is the one of the
+/// remote host then
+fn is_remote_session() -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()))
+}
+
+/// Copy the text to the clipboard of the terminal with the OSC 52 escape
+/// sequence, which reaches the local clipboard over SSH. The terminal may
+/// ignore it silently if it doesn't support OSC 52.
+fn set_osc52(text: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    crossterm::execute!(stdout, SetClipboard::new(text)).wrap_err("writing OSC 52")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Write the text to a temp file when it can't be copied, the path is
+/// returned for the user to pick it up. The file is readable by the user
+/// only and never replaces an existing one, e.g. a link planted in the
+/// shared temp directory.
+pub fn save_to_temp_file(text: &str) -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("chatty-copy-{}.txt", uuid::Uuid::new_v4()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .wrap_err(format!("writing {}", path.display()))?;
+    Ok(path)
+}
+
+/// The OSC 52 sequence setting the clipboard. It is wrapped in the tmux
+/// passthrough sequence inside tmux.
+struct SetClipboard {
+    content: String,
+    tmux: bool,
+}
+
+impl SetClipboard {
+    fn new(text: &str) -> Self {
+        Self {
+            content: base64::engine::general_purpose::STANDARD.encode(text),
+            tmux: std::env::var_os("TMUX").is_some(),
+        }
+    }
+}
+
+impl crossterm::Command for SetClipboard {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        if self.tmux {
+            write!(f, "\x1bPtmux;\x1b\x1b]52;c;{}\x07\x1b\\", self.content)
+        } else {
+            write!(f, "\x1b]52;c;{}\x07", self.content)
+        }
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "OSC 52 is not supported by the legacy Windows console",
+        ))
+    }
+}
@@ -0,0 +1,38 @@
+use super::*;
+
+#[test]
+fn test_set_clipboard_sequence() {
+    let mut command = SetClipboard {
+        content: base64::engine::general_purpose::STANDARD.encode("hello"),
+        tmux: false,
+    };
+    let mut sequence = String::new();
+    crossterm::Command::write_ansi(&command, &mut sequence).unwrap();
+    assert_eq!(sequence, "\x1b]52;c;aGVsbG8=\x07");
+
+    command.tmux = true;
+    let mut sequence = String::new();
+    crossterm::Command::write_ansi(&command, &mut sequence).unwrap();
+    assert_eq!(sequence, "\x1bPtmux;\x1b\x1b]52;c;aGVsbG8=\x07\x1b\\");
+}
+
+#[test]
+fn test_save_to_temp_file() {
+    let path = save_to_temp_file("hello").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_use_osc52() {
+    assert!(use_osc52(ClipboardMode::Auto, true));
+    assert!(!use_osc52(ClipboardMode::Auto, false));
+    assert!(use_osc52(ClipboardMode::Osc52, false));
+    assert!(!use_osc52(ClipboardMode::System, true));
+}
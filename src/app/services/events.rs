@@ -1,4 +1,13 @@
-use crate::{config::constants::FRAME_DURATION, models::Event};
+#[cfg(test)]
+#[path = "events_test.rs"]
+mod tests;
+
+use std::collections::VecDeque;
+
+use crate::{
+    config::constants::{FRAME_DURATION, PASTE_BURST_INTERVAL, STREAM_FLUSH_INTERVAL},
+    models::{BackendResponse, Event},
+};
 use crossterm::event::{Event as CrosstermEvent, EventStream, MouseEventKind};
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc;
@@ -9,12 +18,93 @@ pub struct EventService {
     crossterm_events: EventStream,
     event_rx: mpsc::UnboundedReceiver<Event>,
     event_tx: mpsc::UnboundedSender<Event>,
+
+    pending: VecDeque<Event>,
+    pasting: bool,
+    last_key_at: Option<time::Instant>,
+    responses: ResponseBuffer,
+}
+
+/// Coalesce the chunks of a streamed response so the app handles them at most
+/// once per `STREAM_FLUSH_INTERVAL` instead of once per chunk
+#[derive(Default)]
+struct ResponseBuffer {
+    response: Option<BackendResponse>,
+    flush_at: Option<time::Instant>,
+}
+
+impl ResponseBuffer {
+    /// Buffer the chunk and return the responses to handle right away: the
+    /// buffered one if the chunk belongs to another response, and the merged
+    /// response once the last chunk of it or of one of its messages arrives.
+    fn push(&mut self, resp: BackendResponse) -> Vec<BackendResponse> {
+        let mut ready = vec![];
+        match self.response.as_mut() {
+            Some(buffered) if buffered.id == resp.id && buffered.model == resp.model => {
+                buffered.merge(resp)
+            }
+            _ => {
+                ready.extend(self.take());
+                self.flush_at = Some(time::Instant::now() + STREAM_FLUSH_INTERVAL);
+                self.response = Some(resp);
+            }
+        }
+
+        if self
+            .response
+            .as_ref()
+            .is_some_and(|resp| resp.done || resp.boundary)
+        {
+            ready.extend(self.take());
+        }
+        ready
+    }
+
+    fn take(&mut self) -> Option<BackendResponse> {
+        self.flush_at = None;
+        self.response.take()
+    }
+
+    fn flush_at(&self) -> Option<time::Instant> {
+        self.flush_at
+    }
 }
 
 impl EventService {
-    fn handle_crossterm(&self, event: CrosstermEvent) -> Option<Event> {
+    fn handle_event(&mut self, event: Event) -> Option<Event> {
+        let mut events = match event {
+            Event::ChatCompletionResponse(resp) => self
+                .responses
+                .push(resp)
+                .into_iter()
+                .map(Event::ChatCompletionResponse)
+                .collect::<Vec<_>>(),
+            // Keep the order of the events, the buffered response goes first
+            event => self
+                .responses
+                .take()
+                .map(Event::ChatCompletionResponse)
+                .into_iter()
+                .chain(Some(event))
+                .collect(),
+        }
+        .into_iter();
+
+        let event = events.next();
+        self.pending.extend(events);
+        event
+    }
+
+    fn handle_crossterm(&mut self, event: CrosstermEvent) -> Option<Event> {
         match event {
-            CrosstermEvent::Paste(text) => Some(Event::KeyboardPaste(text)),
+            CrosstermEvent::Paste(text) => {
+                // Bracketed paste delivers the whole content at once, wrap it
+                // so the app sees the same start/end sequence as a key burst
+                self.pasting = false;
+                self.pending.push_back(Event::KeyboardPaste(text));
+                self.pending.push_back(Event::KeyboardPasteEnd);
+                Some(Event::KeyboardPasteStart)
+            }
             CrosstermEvent::Mouse(mouse_event) => match mouse_event.kind {
                 MouseEventKind::ScrollUp => Some(Event::UiScrollUp),
                 MouseEventKind::ScrollDown => Some(Event::UiScrollDown),
@@ -36,60 +126,120 @@ impl EventService {
                 _ => None,
             },
             CrosstermEvent::Key(key_event) => {
-                let input: Input = key_event.into();
-                if input.key == Key::Enter && (input.shift || input.alt) {
-                    return Some(Event::KeyboardNewLine);
-                }
-
-                // Map ctrl events
-                if input.ctrl {
-                    match input.key {
-                        Key::Char('u') => return Some(Event::UiScrollPageUp),
-                        Key::Char('d') => return Some(Event::UiScrollPageDown),
-                        Key::Char('q') => return Some(Event::Quit),
-                        Key::Char('c') => return Some(Event::KeyboardCtrlC),
-                        Key::Char('r') => return Some(Event::KeyboardCtrlR),
-                        Key::Char('l') => return Some(Event::KeyboardCtrlL),
-                        Key::Char('h') => return Some(Event::KeyboardCtrlH),
-                        Key::Char('n') => return Some(Event::KeyboardCtrlN),
-                        Key::Char('e') => return Some(Event::KeyboardCtrlE),
-                        _ => return None,
-                    }
-                }
-
-                match input.key {
-                    Key::Esc => Some(Event::KeyboardEsc),
-                    Key::F(1) => Some(Event::KeyboardF1),
-                    Key::Enter => Some(Event::KeyboardEnter),
-                    Key::Up => Some(Event::UiScrollUp),
-                    Key::Down => Some(Event::UiScrollDown),
-                    Key::MouseScrollUp => Some(Event::UiScrollPageUp),
-                    Key::MouseScrollDown => Some(Event::UiScrollPageDown),
-                    Key::PageUp => Some(Event::UiScrollPageUp),
-                    Key::PageDown => Some(Event::UiScrollPageDown),
-                    _ => Some(Event::KeyboardCharInput(input)),
-                }
+                let event = self.handle_key(key_event.into())?;
+                Some(self.track_paste_burst(event))
             }
             _ => None,
         }
     }
 
+    /// Some terminals don't support bracketed paste and send the pasted
+    /// content as a burst of key events instead. Detect those bursts so an
+    /// embedded Enter is not interpreted as "send".
+    fn track_paste_burst(&mut self, event: Event) -> Event {
+        let now = time::Instant::now();
+        let burst = self
+            .last_key_at
+            .is_some_and(|last| now.duration_since(last) <= PASTE_BURST_INTERVAL);
+        self.last_key_at = Some(now);
+
+        if burst && !self.pasting {
+            self.pasting = true;
+            self.pending.push_back(event);
+            return Event::KeyboardPasteStart;
+        }
+
+        if !burst && self.pasting {
+            self.pasting = false;
+            self.pending.push_back(event);
+            return Event::KeyboardPasteEnd;
+        }
+        event
+    }
+
+    fn handle_key(&self, input: Input) -> Option<Event> {
+        if input.key == Key::Enter && (input.shift || input.alt) {
+            return Some(Event::KeyboardNewLine);
+        }
+
+        // Map ctrl events
+        if input.ctrl {
+            match input.key {
+                Key::Char('u') => return Some(Event::UiScrollPageUp),
+                Key::Char('d') => return Some(Event::UiScrollPageDown),
+                Key::Char('q') => return Some(Event::Quit),
+                Key::Char('c') => return Some(Event::KeyboardCtrlC),
+                Key::Char('r') => return Some(Event::KeyboardCtrlR),
+                Key::Char('l') => return Some(Event::KeyboardCtrlL),
+                Key::Char('h') => return Some(Event::KeyboardCtrlH),
+                Key::Char('n') => return Some(Event::KeyboardCtrlN),
+                Key::Char('e') => return Some(Event::KeyboardCtrlE),
+                Key::Char('p') => return Some(Event::KeyboardCtrlP),
+                Key::Char('f') => return Some(Event::KeyboardCtrlF),
+                Key::Char('t') => return Some(Event::KeyboardCtrlT),
+                Key::Char('o') => return Some(Event::KeyboardCtrlO),
+                Key::Char('g') => return Some(Event::KeyboardCtrlG),
+                Key::Char('y') => return Some(Event::KeyboardCtrlY),
+                Key::Char('k') => return Some(Event::KeyboardCtrlK),
+                _ => return None,
+            }
+        }
+
+        if input.alt && input.key == Key::Char('y') {
+            return Some(Event::KeyboardAltY);
+        }
+
+        match input.key {
+            Key::Esc => Some(Event::KeyboardEsc),
+            Key::F(1) => Some(Event::KeyboardF1),
+            Key::F(2) => Some(Event::KeyboardF2),
+            Key::Enter => Some(Event::KeyboardEnter),
+            Key::Up => Some(Event::KeyboardUp),
+            Key::Down => Some(Event::KeyboardDown),
+            Key::MouseScrollUp => Some(Event::UiScrollPageUp),
+            Key::MouseScrollDown => Some(Event::UiScrollPageDown),
+            Key::PageUp => Some(Event::UiScrollPageUp),
+            Key::PageDown => Some(Event::UiScrollPageDown),
+            _ => Some(Event::KeyboardCharInput(input)),
+        }
+    }
+
+    /// Stop reading the terminal events until the next call of [`EventService::next`],
+    /// so the keys go to the program the terminal is handed over to
+    pub fn suspend_terminal_events(&mut self) {
+        self.crossterm_events = EventStream::new();
+    }
+
     pub fn event_tx(&self) -> mpsc::UnboundedSender<Event> {
         self.event_tx.clone()
     }
 
     pub async fn next(&mut self) -> Event {
+        if let Some(event) = self.pending.pop_front() {
+            return event;
+        }
+
         loop {
+            let flush_at = self.responses.flush_at();
             let e = tokio::select! {
-                event = self.event_rx.recv() => event,
+                event = self.event_rx.recv() => event.and_then(|event| self.handle_event(event)),
                 event = self.crossterm_events.next().fuse() => match event {
                     Some(Ok(input)) => self.handle_crossterm(input),
                     Some(Err(_)) => None,
                     None => None
                 },
+                _ = time::sleep_until(flush_at.unwrap_or_else(time::Instant::now)), if flush_at.is_some() => {
+                    self.responses.take().map(Event::ChatCompletionResponse)
+                },
                 _ = time::sleep(FRAME_DURATION) => Some(Event::UiTick)
             };
 
+            if self.pasting && matches!(e, Some(Event::UiTick)) {
+                self.pasting = false;
+                self.pending.push_back(Event::UiTick);
+                return Event::KeyboardPasteEnd;
+            }
+
             if let Some(event) = e {
                 return event;
             }
@@ -104,6 +254,10 @@ impl Default for EventService {
             crossterm_events: EventStream::new(),
             event_rx,
             event_tx,
+            pending: VecDeque::new(),
+            pasting: false,
+            last_key_at: None,
+            responses: ResponseBuffer::default(),
         }
     }
 }
@@ -0,0 +1,107 @@
+use crate::models::{BackendUsage, TokenLogprob};
+
+use super::*;
+
+#[test]
+fn test_response_buffer() {
+    let mut buffer = ResponseBuffer::default();
+
+    assert!(
+        buffer
+            .push(BackendResponse::new("1", "gpt").with_text("Hel"))
+            .is_empty()
+    );
+    assert!(buffer.flush_at().is_some());
+    assert!(
+        buffer
+            .push(BackendResponse::new("1", "gpt").with_text("lo"))
+            .is_empty()
+    );
+
+    // The chunk of another response flushes the buffered one
+    let ready = buffer.push(BackendResponse::new("2", "gpt").with_text("Hi"));
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].id, "1");
+    assert_eq!(ready[0].text, "Hello");
+    assert!(!ready[0].done);
+
+    // The last chunk is merged and flushed right away
+    let ready = buffer.push(
+        BackendResponse::new("2", "gpt")
+            .with_text(" there")
+            .with_done()
+            .with_usage(BackendUsage {
+                prompt_tokens: 1,
+                completion_tokens: 2,
+                total_tokens: 3,
+                ..Default::default()
+            }),
+    );
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].text, "Hi there");
+    assert!(ready[0].done);
+    assert_eq!(ready[0].usage.as_ref().map(|u| u.total_tokens), Some(3));
+    assert!(buffer.flush_at().is_none());
+    assert!(buffer.take().is_none());
+}
+
+#[test]
+fn test_response_buffer_boundary() {
+    let mut buffer = ResponseBuffer::default();
+
+    assert!(
+        buffer
+            .push(BackendResponse::new("1", "gpt").with_text("Let me check"))
+            .is_empty()
+    );
+    // The message is flushed as soon as it ends
+    let ready = buffer.push(BackendResponse::new("1", "gpt").with_boundary());
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].text, "Let me check");
+    assert!(ready[0].boundary);
+    assert!(!ready[0].done);
+
+    // The response resumes without the boundary
+    assert!(
+        buffer
+            .push(BackendResponse::new("1", "gpt").with_text("Done"))
+            .is_empty()
+    );
+    let ready = buffer.push(BackendResponse::new("1", "gpt").with_done());
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].text, "Done");
+    assert!(!ready[0].boundary);
+}
+
+#[test]
+fn test_response_buffer_logprobs() {
+    let logprob = |token: &str| TokenLogprob {
+        token: token.to_string(),
+        logprob: -0.1,
+        top_logprobs: vec![],
+    };
+    let mut buffer = ResponseBuffer::default();
+
+    assert!(
+        buffer
+            .push(
+                BackendResponse::new("1", "gpt")
+                    .with_text("Hel")
+                    .with_logprobs(vec![logprob("Hel")])
+            )
+            .is_empty()
+    );
+    let ready = buffer.push(
+        BackendResponse::new("1", "gpt")
+            .with_text("lo")
+            .with_logprobs(vec![logprob("lo")])
+            .with_done(),
+    );
+    assert_eq!(ready.len(), 1);
+    let tokens = ready[0]
+        .logprobs
+        .iter()
+        .map(|logprob| logprob.token.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(tokens, vec!["Hel", "lo"]);
+}
@@ -0,0 +1,108 @@
+#[cfg(test)]
+#[path = "hooks_test.rs"]
+mod tests;
+
+use eyre::{Context, Result};
+use serde_json::json;
+
+use crate::{
+    config::{HookEvent, HooksConfig, WebhookConfig},
+    models::{Conversation, Message},
+};
+
+/// Post the events of the conversations to the configured webhooks. The
+/// requests are sent in the background and a failure is only logged, the
+/// chat never waits for them.
+#[derive(Default, Clone)]
+pub struct Hooks {
+    client: reqwest::Client,
+    webhooks: Vec<WebhookConfig>,
+}
+
+impl Hooks {
+    pub fn new(config: &HooksConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout())
+            .build()
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "Failed to build the webhook client, without timeout: {}",
+                    err
+                );
+                reqwest::Client::default()
+            });
+        Self {
+            client,
+            webhooks: config.webhooks.clone(),
+        }
+    }
+
+    /// Whether any webhook is notified of the event
+    pub fn subscribes(&self, event: HookEvent) -> bool {
+        self.webhooks.iter().any(|hook| hook.subscribes(event))
+    }
+
+    pub fn conversation_created(&self, convo: &Conversation) {
+        self.fire(
+            HookEvent::ConversationCreated,
+            json!({
+                "conversation_id": convo.id(),
+                "title": convo.title(),
+                "created_at": convo.created_at().to_rfc3339(),
+            }),
+        );
+    }
+
+    pub fn message_completed(&self, convo_id: &str, message: &Message) {
+        self.fire(
+            HookEvent::MessageCompleted,
+            json!({
+                "conversation_id": convo_id,
+                "message": {
+                    "id": message.id(),
+                    "issuer": message.issuer_str(),
+                    "text": message.text(),
+                    "token_count": message.token_count(),
+                    "created_at": message.created_at().to_rfc3339(),
+                },
+            }),
+        );
+    }
+
+    fn fire(&self, event: HookEvent, data: serde_json::Value) {
+        for hook in self.webhooks.iter().filter(|hook| hook.subscribes(event)) {
+            let client = self.client.clone();
+            let url = hook.url.clone();
+            let body = payload(event, data.clone());
+            tokio::spawn(async move {
+                if let Err(err) = post(&client, &url, &body).await {
+                    log::warn!("Failed to post {} to {}: {:#}", event.as_str(), url, err);
+                }
+            });
+        }
+    }
+}
+
+/// The body posted to the webhooks, the data of the event with its name
+fn payload(event: HookEvent, mut data: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = data.as_object_mut() {
+        object.insert("event".to_string(), json!(event.as_str()));
+        object.insert(
+            "timestamp".to_string(),
+            json!(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+    data
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: &serde_json::Value) -> Result<()> {
+    client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .wrap_err("sending request")?
+        .error_for_status()
+        .wrap_err("unexpected response")?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use mockito::Matcher;
+
+use super::*;
+
+fn webhook(url: String, events: Vec<HookEvent>) -> WebhookConfig {
+    WebhookConfig { url, events }
+}
+
+#[test]
+fn test_payload() {
+    let body = payload(
+        HookEvent::MessageCompleted,
+        json!({"conversation_id": "convo1"}),
+    );
+    assert_eq!(body["event"], "message_completed");
+    assert_eq!(body["conversation_id"], "convo1");
+    assert!(body["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn test_fire() {
+    let mut server = mockito::Server::new_async().await;
+    let all = server
+        .mock("POST", "/all")
+        .with_status(200)
+        .expect(2)
+        .create_async()
+        .await;
+    let messages = server
+        .mock("POST", "/messages")
+        .match_body(Matcher::PartialJson(json!({
+            "event": "message_completed",
+            "conversation_id": "convo1",
+            "message": {"id": "msg1", "issuer": "gpt-4o", "text": "Hello"},
+        })))
+        .with_status(500)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let hooks = Hooks::new(&HooksConfig {
+        webhooks: vec![
+            webhook(format!("{}/all", server.url()), vec![]),
+            webhook(
+                format!("{}/messages", server.url()),
+                vec![HookEvent::MessageCompleted],
+            ),
+        ],
+        timeout_secs: Some(1),
+    });
+    assert!(hooks.subscribes(HookEvent::ConversationCreated));
+
+    let convo = Conversation::default().with_id("convo1");
+    hooks.conversation_created(&convo);
+    hooks.message_completed(
+        "convo1",
+        &Message::new_system("gpt-4o", "Hello").with_id("msg1"),
+    );
+
+    // The requests are sent in the background
+    for _ in 0..50 {
+        if all.matched_async().await && messages.matched_async().await {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    all.assert_async().await;
+    messages.assert_async().await;
+
+    let hooks = Hooks::new(&HooksConfig {
+        webhooks: vec![webhook(server.url(), vec![HookEvent::MessageCompleted])],
+        timeout_secs: None,
+    });
+    assert!(!hooks.subscribes(HookEvent::ConversationCreated));
+    assert!(!Hooks::default().subscribes(HookEvent::MessageCompleted));
+}
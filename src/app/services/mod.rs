@@ -2,10 +2,14 @@ pub mod action;
 pub mod clipboard;
 pub mod crossterm_stream;
 pub mod events;
+pub mod hooks;
+pub mod signal;
 
 pub use clipboard::ClipboardService;
 pub use crossterm_stream::CrosstermStream;
 pub use events::EventService;
+pub use hooks::Hooks;
+pub use signal::SignalService;
 
 use std::sync::{Arc, atomic};
 
@@ -0,0 +1,77 @@
+#[cfg(test)]
+#[path = "signal_test.rs"]
+mod tests;
+
+use eyre::Result;
+use tokio::sync::mpsc;
+
+use crate::{app::destruct_terminal, models::Event};
+
+/// The exit code of a process interrupted by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Handle the termination signals of the OS, e.g. `kill` or a Ctrl+C which
+/// doesn't go through the app
+pub struct SignalService;
+
+impl SignalService {
+    /// Quit on the first SIGINT or SIGTERM the same way as Ctrl+Q does, the
+    /// current conversation is saved and the pending tasks are drained. The
+    /// signals are listened to until the process exits, a second one exits
+    /// right away without waiting for the shutdown.
+    pub async fn start(event_tx: mpsc::UnboundedSender<Event>) -> Result<()> {
+        let signals = Signals::new()?;
+        Self::run(signals, event_tx).await
+    }
+
+    async fn run(mut signals: Signals, event_tx: mpsc::UnboundedSender<Event>) -> Result<()> {
+        let signal = signals.recv().await;
+        log::info!("Received {}, shutting down", signal);
+        // The app may be quitting already, the extra event is never handled
+        let _ = event_tx.send(Event::Quit);
+
+        let signal = signals.recv().await;
+        log::warn!("Received {} again, exiting without waiting", signal);
+        destruct_terminal();
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+}
+
+#[cfg(unix)]
+struct Signals {
+    interrupt: tokio::signal::unix::Signal,
+    terminate: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Result<Self> {
+        use tokio::signal::unix::{SignalKind, signal};
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+        })
+    }
+
+    async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.interrupt.recv() => "SIGINT",
+            _ = self.terminate.recv() => "SIGTERM",
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self) -> &'static str {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_quit_on_signal() {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    // The handlers are installed before the signal is raised
+    let signals = Signals::new().expect("failed to listen to the signals");
+    let handle = tokio::spawn(SignalService::run(signals, tx));
+
+    unsafe { libc::raise(libc::SIGTERM) };
+    let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("no event is sent on the signal");
+    assert!(matches!(event, Some(Event::Quit)));
+
+    // Only one quit is sent for the signal
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rx.try_recv().is_err());
+    handle.abort();
+}
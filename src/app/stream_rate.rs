@@ -0,0 +1,57 @@
+#[cfg(test)]
+#[path = "stream_rate_test.rs"]
+mod tests;
+
+use std::time::{Duration, Instant};
+
+use crate::models::message::estimate_tokens;
+
+/// The rate below this duration is too noisy to be shown
+const MIN_ELAPSED: Duration = Duration::from_millis(500);
+
+/// The generation speed of the response being streamed. The tokens are
+/// estimated from the text, the providers only report the usage once the
+/// response completes.
+#[derive(Debug, Default)]
+pub struct StreamRate {
+    started_at: Option<Instant>,
+    tokens: usize,
+}
+
+impl StreamRate {
+    /// Count a chunk of the response, the clock starts with the first one
+    pub fn add(&mut self, text: &str) {
+        self.add_at(text, Instant::now());
+    }
+
+    fn add_at(&mut self, text: &str, now: Instant) {
+        if text.is_empty() {
+            return;
+        }
+        if self.started_at.is_none() {
+            // The first chunk only starts the clock, it arrives after the
+            // time to the first token
+            self.started_at = Some(now);
+            return;
+        }
+        self.tokens += estimate_tokens(text);
+    }
+
+    /// The tokens generated per second, none until the response streamed
+    /// long enough
+    pub fn tokens_per_sec(&self) -> Option<f64> {
+        self.tokens_per_sec_at(Instant::now())
+    }
+
+    fn tokens_per_sec_at(&self, now: Instant) -> Option<f64> {
+        let elapsed = now.duration_since(self.started_at?);
+        if elapsed < MIN_ELAPSED || self.tokens == 0 {
+            return None;
+        }
+        Some(self.tokens as f64 / elapsed.as_secs_f64())
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,28 @@
+use super::*;
+
+#[test]
+fn test_tokens_per_sec() {
+    let start = Instant::now();
+    let mut rate = StreamRate::default();
+    assert_eq!(rate.tokens_per_sec_at(start), None);
+
+    rate.add_at("", start);
+    assert!(rate.started_at.is_none());
+
+    rate.add_at("Hello", start);
+    assert_eq!(rate.tokens_per_sec_at(start + Duration::from_secs(1)), None);
+
+    // 40 characters are about 10 tokens
+    rate.add_at(&"a".repeat(40), start + Duration::from_millis(100));
+    assert_eq!(
+        rate.tokens_per_sec_at(start + Duration::from_millis(200)),
+        None
+    );
+    assert_eq!(
+        rate.tokens_per_sec_at(start + Duration::from_secs(2)),
+        Some(5.0)
+    );
+
+    rate.reset();
+    assert_eq!(rate.tokens_per_sec_at(start + Duration::from_secs(2)), None);
+}
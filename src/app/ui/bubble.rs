@@ -1,13 +1,23 @@
-use crate::{config, models::Message};
+#[cfg(test)]
+#[path = "bubble_test.rs"]
+mod tests;
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::{
+    config::{self, ColorTheme, LineAlign, color_theme::RoleColors},
+    models::Message,
+};
 use ratatui::{
-    style::{Color, Style, Stylize},
+    style::{Style, Stylize},
     text::{Line, Span},
 };
 use ratatui_macros::span;
 use syntect::highlighting::Theme;
 use unicode_width::UnicodeWidthStr;
 
-use super::{Selectable, utils};
+use super::{Selectable, math, utils};
 
 pub const DEFAULT_PADDING: usize = 8;
 pub const DEFAULT_BORDER_ELEMENTS_LEN: usize = 5;
@@ -16,6 +26,8 @@ pub const DEFAULT_OUTER_PADDING_PERCENTAGE: f32 = 0.04;
 pub struct Bubble<'a> {
     message: &'a Message,
     max_width: usize,
+    colors: &'a ColorTheme,
+    tool_args_expanded: bool,
 
     // Settings
     padding: usize,
@@ -24,10 +36,12 @@ pub struct Bubble<'a> {
 }
 
 impl<'a> Bubble<'_> {
-    pub fn new(message: &'a Message, max_width: usize) -> Bubble<'a> {
+    pub fn new(message: &'a Message, max_width: usize, colors: &'a ColorTheme) -> Bubble<'a> {
         Bubble {
             message,
             max_width,
+            colors,
+            tool_args_expanded: false,
 
             // Settings
             // Unicode character border + padding
@@ -40,6 +54,11 @@ impl<'a> Bubble<'_> {
         }
     }
 
+    pub fn with_tool_args_expanded(mut self, expanded: bool) -> Self {
+        self.tool_args_expanded = expanded;
+        self
+    }
+
     pub fn with_padding(mut self, padding: usize) -> Self {
         self.padding = padding;
         self
@@ -68,31 +87,135 @@ impl<'a> Bubble<'_> {
     }
 
     pub fn as_lines(&mut self, theme: &'a Theme) -> Vec<Line<'a>> {
+        let margin = self.cap_max_width();
         let max_line_len = self.get_max_line_length();
 
-        let lines = utils::build_message_lines(self.message.text(), max_line_len, theme, |line| {
+        let text = self.text();
+        let lines = utils::build_message_lines(&text, max_line_len, theme, |line| {
             self.format_spans(line.spans, max_line_len)
         });
 
-        if !config::instance().general.bubble.unwrap_or_default() {
-            return self.format_inline_message(lines);
+        let mut lines = if !config::instance().general.bubble.unwrap_or_default() {
+            self.format_inline_message(lines)
+        } else {
+            self.wrap_lines_in_bubble(lines, max_line_len)
+        };
+
+        if margin > 0 {
+            let margin = " ".repeat(margin);
+            for line in lines.iter_mut() {
+                line.spans
+                    .insert(0, Span::from(margin.clone()).unselectable());
+            }
+        }
+        lines
+    }
+
+    /// Narrow the width to `ui.max_line_width` of the side of the message.
+    /// Return the left margin placing the narrowed message in the window.
+    fn cap_max_width(&mut self) -> usize {
+        let ui = &config::instance().ui;
+        let Some(width) = ui
+            .get_max_line_width(!self.left_aligned())
+            .filter(|width| *width < self.max_width)
+        else {
+            return 0;
+        };
+
+        let margin = match ui.align {
+            LineAlign::Center => (self.max_width - width) / 2,
+            LineAlign::Left => 0,
+        };
+        self.max_width = width;
+        margin
+    }
+
+    /// The text to render. A tool message is rendered from its tool call,
+    /// the arguments are only shown when expanded. A JSON answer is rendered
+    /// as a code block and the math of a response with `ui.render_math`.
+    fn text(&self) -> Cow<'_, str> {
+        let Some(call) = self.message.tool_call() else {
+            if self.message.is_system() {
+                if let Some(json) = utils::fence_json(self.message.text()) {
+                    return Cow::Owned(json);
+                }
+                if config::instance().ui.render_math.unwrap_or_default() {
+                    return math::render_math(self.message.text());
+                }
+            }
+            return Cow::Borrowed(self.message.text());
+        };
+
+        let mut text = String::new();
+        if let Some(args) = &call.args {
+            if self.tool_args_expanded {
+                let args = serde_json::to_string_pretty(args).unwrap_or_default();
+                text.push_str(&format!("Arguments:\n```json\n{}\n```\n", args));
+            } else {
+                text.push_str("Arguments: ... (Ctrl+O to expand)\n");
+            }
         }
+        let label = if call.is_error { "Error" } else { "Result" };
+        text.push_str(&format!("{}: {}", label, call.result));
+        Cow::Owned(text)
+    }
 
-        self.wrap_lines_in_bubble(lines, max_line_len)
+    fn issuer(&self) -> String {
+        match self.message.tool_call() {
+            Some(call) => format!("{} ({})", call.tool, call.provider),
+            None if self.message.is_incomplete() => {
+                format!("{} (incomplete)", self.message.issuer_str())
+            }
+            None if self.message.is_refusal() => {
+                format!("{} (refused)", self.message.issuer_str())
+            }
+            None => self.message.issuer_str().to_string(),
+        }
+    }
+
+    /// The timestamp shown in the header if enabled. An invalid format
+    /// shows nothing instead of failing the rendering.
+    fn timestamp(&self) -> Option<String> {
+        let general = &config::instance().general;
+        if !general.show_timestamps.unwrap_or_default() {
+            return None;
+        }
+
+        let mut timestamp = String::new();
+        let created_at = self.message.created_at().with_timezone(&chrono::Local);
+        write!(
+            timestamp,
+            "{}",
+            created_at.format(general.get_timestamp_format())
+        )
+        .ok()?;
+        Some(timestamp)
+    }
+
+    /// System and tool messages are aligned to the left
+    fn left_aligned(&self) -> bool {
+        self.message.is_system() || self.message.is_tool()
     }
 
     fn format_inline_message(&self, mut lines: Vec<Line<'a>>) -> Vec<Line<'a>> {
+        let issuer = self.issuer();
+        // The timestamp replaces the time of the header if it fits
         let time = self
-            .message
-            .created_at()
-            .with_timezone(&chrono::Local)
-            .format("%H:%M %m/%d")
-            .to_string();
-        let padding = self.max_width - time.width() - self.message.issuer_str().width() - 5;
+            .timestamp()
+            .filter(|timestamp| issuer.width() + timestamp.width() + 5 <= self.max_width)
+            .unwrap_or_else(|| {
+                self.message
+                    .created_at()
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M %m/%d")
+                    .to_string()
+            });
+        let padding = self
+            .max_width
+            .saturating_sub(time.width() + issuer.width() + 5);
         let header = vec![
             self.highlighted_span("┃ ".to_string()).unselectable(),
-            self.highlighted_span(self.message.issuer_str().to_string())
-                .unselectable(),
+            self.highlighted_span(issuer).unselectable(),
             span!(" ".repeat(padding).to_string()).unselectable(),
             self.highlighted_span(time).unselectable(),
         ];
@@ -102,55 +225,62 @@ impl<'a> Bubble<'_> {
     }
 
     fn wrap_lines_in_bubble(&self, lines: Vec<Line<'a>>, max_line_len: usize) -> Vec<Line<'a>> {
-        // Replace top bar ─ with the issuer string
-        let issuer = self.message.issuer_str();
+        // Replace top bar ─ with the issuer string. The timestamp is only
+        // shown if it fits, so it never widens the bubble.
+        let mut issuer = self.issuer();
+        let mut timestamp_shown = false;
+        if let Some(timestamp) = self.timestamp() {
+            let header = format!("{} · {}", issuer, timestamp);
+            if header.width() < max_line_len {
+                issuer = header;
+                timestamp_shown = true;
+            }
+        }
         let top_bar = format!(
             "╭─ {} {}╮",
             issuer,
             ["─"].repeat(max_line_len - issuer.width() - 1).join("")
         );
 
-        // Replace bottom bar ─ with the date
-        let date = self
-            .message
-            .created_at()
-            .with_timezone(&chrono::Local)
-            .format("%H:%M %m/%d");
-        let bottom_bar = format!(
-            "╰─ {} {}╯",
-            date,
-            ["─"]
-                .repeat(max_line_len - date.to_string().width() - 1)
-                .join("")
-        );
+        // Replace bottom bar ─ with the date, unless the timestamp is shown
+        let bottom_bar = if timestamp_shown {
+            format!("╰{}╯", ["─"].repeat(max_line_len + 2).join(""))
+        } else {
+            let date = self
+                .message
+                .created_at()
+                .with_timezone(&chrono::Local)
+                .format("%H:%M %m/%d");
+            format!(
+                "╰─ {} {}╯",
+                date,
+                ["─"]
+                    .repeat(max_line_len - date.to_string().width() - 1)
+                    .join("")
+            )
+        };
         let bar_padding =
             utils::repeat_from_substactions(" ", vec![self.max_width, max_line_len, self.padding]);
 
-        if self.message.is_system() {
-            let mut res = vec![
-                self.highlighted_line(format!("{top_bar}{bar_padding}"))
-                    .unselectable(),
-            ];
-            res.extend(lines);
-            res.push(
-                self.highlighted_line(format!("{bottom_bar}{bar_padding}"))
-                    .unselectable(),
-            );
-            return res;
-        }
-
-        let mut res = vec![
-            self.highlighted_line(format!("{bar_padding}{top_bar}"))
-                .unselectable(),
-        ];
+        let mut res = vec![self.bar_line(top_bar, bar_padding.clone())];
         res.extend(lines);
-        res.push(
-            self.highlighted_line(format!("{bar_padding}{bottom_bar}"))
-                .unselectable(),
-        );
+        res.push(self.bar_line(bottom_bar, bar_padding));
         res
     }
 
+    /// Build the top or bottom bar of the bubble, the padding is placed on
+    /// the opposite side of the alignment
+    fn bar_line(&self, bar: String, padding: String) -> Line<'a> {
+        let bar = self.highlighted_span(bar);
+        let padding = Span::from(padding);
+        let spans = if self.left_aligned() {
+            vec![bar, padding]
+        } else {
+            vec![padding, bar]
+        };
+        Line::from(spans).unselectable()
+    }
+
     fn get_max_line_length(&self) -> usize {
         let wrapper_char = if config::instance()
             .general
@@ -172,7 +302,6 @@ impl<'a> Bubble<'_> {
 
         let line_boder_width = self.boder_elements_length + min_bubble_padding_length;
         let mut max_line_len = self
-            .message
             .text()
             .lines()
             .map(|line| line.width())
@@ -183,7 +312,7 @@ impl<'a> Bubble<'_> {
             max_line_len = self.max_width - line_boder_width;
         }
 
-        let issuer = &self.message.issuer_str();
+        let issuer = self.issuer();
         // 2 Padding space
         if issuer.width() + 2 > max_line_len {
             max_line_len = issuer.width() + 2;
@@ -211,12 +340,32 @@ impl<'a> Bubble<'_> {
     }
 
     fn format_spans(&self, mut spans: Vec<Span<'a>>, max_line_len: usize) -> Line<'a> {
+        // The tool calls are left out of the copied text unless opted in
+        if self.message.is_tool()
+            && !config::instance()
+                .general
+                .copy_tool_calls
+                .unwrap_or_default()
+        {
+            spans = spans.into_iter().map(|span| span.unselectable()).collect();
+        }
+
         let bubble = config::instance().general.bubble.unwrap_or_default();
         if !bubble {
             spans.insert(0, self.highlighted_span("┃ ".to_string()).unselectable());
             return Line::from(spans);
         }
 
+        if let Some(bg) = self.role_colors().bg {
+            spans = spans
+                .into_iter()
+                .map(|span| {
+                    let style = Style::default().bg(bg).patch(span.style);
+                    span.style(style)
+                })
+                .collect();
+        }
+
         let line_str_len: usize = spans.iter().map(|e| e.content.width()).sum();
         let fill = utils::repeat_from_substactions(" ", vec![max_line_len, line_str_len]);
         let formatted_line_len = line_str_len + fill.len() + self.padding;
@@ -228,7 +377,7 @@ impl<'a> Bubble<'_> {
         let outer_padding =
             utils::repeat_from_substactions(" ", vec![self.max_width, formatted_line_len]);
 
-        if self.message.is_system() {
+        if self.left_aligned() {
             // Left alignment
             wrapped_spans.push(Span::from(outer_padding).unselectable());
             return Line::from(wrapped_spans);
@@ -240,22 +389,21 @@ impl<'a> Bubble<'_> {
         Line::from(line_spans)
     }
 
-    fn highlighted_span(&self, text: String) -> Span<'a> {
-        let color = if self.message.is_system() {
-            Color::Rgb(255, 140, 105)
+    fn role_colors(&self) -> RoleColors {
+        if self.message.is_tool() {
+            self.colors.tool
+        } else if self.message.is_app_message() && self.message.is_error() {
+            self.colors.error
+        } else if self.message.is_app_message() {
+            self.colors.system
+        } else if self.message.is_system() {
+            self.colors.assistant
         } else {
-            Color::Rgb(64, 224, 208)
-        };
-        Span::styled(
-            text,
-            Style {
-                fg: Some(color),
-                ..Style::default()
-            },
-        )
+            self.colors.user
+        }
     }
 
-    fn highlighted_line(&self, text: String) -> Line<'a> {
-        Line::from(self.highlighted_span(text))
+    fn highlighted_span(&self, text: String) -> Span<'a> {
+        Span::styled(text, self.role_colors().style())
     }
 }
@@ -1,11 +1,11 @@
 use std::{collections::BTreeMap, sync::Arc};
 
-use crate::models::Message;
+use crate::{config::ColorTheme, models::Message};
 use ratatui::{buffer::Buffer, layout::Rect, text::Line};
 use syntect::highlighting::Theme;
 use unicode_width::UnicodeWidthStr;
 
-use super::{Content, Selectable, Selection, bubble::Bubble};
+use super::{Content, Selectable, Selection, bubble::Bubble, search};
 
 struct CacheEntry<'a> {
     message_id: String,
@@ -15,23 +15,43 @@ struct CacheEntry<'a> {
 
 pub struct BubbleList<'a> {
     theme: &'a Theme,
+    colors: &'a ColorTheme,
     cache: BTreeMap<usize, CacheEntry<'a>>,
     lines: Vec<Arc<Line<'a>>>,
     line_width: usize,
     line_len: usize,
+    tool_args_expanded: bool,
 }
 
 impl<'a> BubbleList<'a> {
-    pub fn new(theme: &'a Theme) -> Self {
+    pub fn new(theme: &'a Theme, colors: &'a ColorTheme) -> Self {
         Self {
             theme,
+            colors,
             cache: BTreeMap::new(),
             lines: Vec::new(),
             line_len: 0,
             line_width: 0,
+            tool_args_expanded: false,
         }
     }
 
+    pub fn with_tool_args_expanded(mut self, expanded: bool) -> Self {
+        self.tool_args_expanded = expanded;
+        self
+    }
+
+    pub fn tool_args_expanded(&self) -> bool {
+        self.tool_args_expanded
+    }
+
+    /// Show or hide the arguments of the tool calls. The messages must be set
+    /// again to be rendered with the new state.
+    pub fn toggle_tool_args(&mut self) {
+        self.tool_args_expanded = !self.tool_args_expanded;
+        self.cache.clear();
+    }
+
     pub fn remove_message(&mut self, id: impl Into<String>) {
         let id = id.into();
         self.cache.retain(|_, entry| entry.message_id != id);
@@ -63,7 +83,9 @@ impl<'a> BubbleList<'a> {
                     }
                 }
 
-                let bubble_lines = Bubble::new(message, line_width).as_lines(self.theme);
+                let bubble_lines = Bubble::new(message, line_width, self.colors)
+                    .with_tool_args_expanded(self.tool_args_expanded)
+                    .as_lines(self.theme);
                 let bubble_lines_len = bubble_lines.len();
 
                 self.cache.insert(
@@ -93,6 +115,24 @@ impl<'a> BubbleList<'a> {
         &self.lines
     }
 
+    /// Return the line index of the first line of the message at the
+    /// given index which contains the query, or the first line of the
+    /// message if none matches.
+    pub fn find_message_line(&self, index: usize, query: &str) -> Option<usize> {
+        let entry = self.cache.get(&index)?;
+        let start: usize = self
+            .cache
+            .range(..index)
+            .map(|(_, entry)| entry.lines.len())
+            .sum();
+        let offset = entry
+            .lines
+            .iter()
+            .position(|line| search::line_contains(line, query))
+            .unwrap_or_default();
+        Some(start + offset)
+    }
+
     pub fn screen_pos_to_line_pos(
         &self,
         x: u16,
@@ -137,7 +177,14 @@ impl<'a> BubbleList<'a> {
         visible_lines
     }
 
-    pub fn render(&self, rect: Rect, buf: &mut Buffer, scroll_index: usize, sel: &Selection) {
+    pub fn render(
+        &self,
+        rect: Rect,
+        buf: &mut Buffer,
+        scroll_index: usize,
+        sel: &Selection,
+        query: Option<&str>,
+    ) {
         for (i, line) in self
             .get_visible_lines(rect.height as usize, scroll_index)
             .iter()
@@ -147,6 +194,9 @@ impl<'a> BubbleList<'a> {
             if line.is_selectable() && sel.contains_row(i + scroll_index) {
                 line = sel.format_line(line, i + scroll_index);
             }
+            if let Some(query) = query {
+                line = search::highlight_matches(line, query);
+            }
             buf.set_line(0, i as u16, &line, rect.width);
         }
     }
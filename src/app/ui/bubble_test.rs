@@ -0,0 +1,50 @@
+use super::*;
+
+fn render(bubble_mode: bool) -> Vec<String> {
+    let mut cfg = config::Configuration::default();
+    cfg.general.bubble = Some(bubble_mode);
+    cfg.general.show_timestamps = Some(true);
+    cfg.general.timestamp_format = Some("%Y-%m-%d".to_string());
+    config::init(cfg).expect("Failed to initialize config");
+
+    let created_at = chrono::DateTime::parse_from_rfc3339("2024-05-06T12:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let message =
+        Message::new_user("user", "Hello, how is the weather today?").with_created_at(created_at);
+    let colors = ColorTheme::default();
+    let theme = Theme::default();
+    Bubble::new(&message, 60, &colors)
+        .as_lines(&theme)
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[test]
+fn test_timestamp_shown_once() {
+    let date = chrono::DateTime::parse_from_rfc3339("2024-05-06T12:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d")
+        .to_string();
+    for bubble_mode in [false, true] {
+        let lines = render(bubble_mode);
+        let text = lines.join("\n");
+        assert_eq!(
+            text.matches(&date).count(),
+            1,
+            "bubble mode {}: {}",
+            bubble_mode,
+            text
+        );
+        // The time of the header is replaced
+        assert!(
+            !text.contains("05/06"),
+            "bubble mode {}: {}",
+            bubble_mode,
+            text
+        );
+        assert!(text.contains("Hello"));
+    }
+}
@@ -1,11 +1,13 @@
-use crate::models::{Action, Event, Message};
+use crate::models::{Action, Event, Message, storage::MessageEdit};
+use crate::{config, info_event};
 use ratatui::{
     Frame,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Flex, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Text},
     widgets::{
         Block, BorderType, Borders, Clear, List, ListItem, ListState, Padding, Paragraph, Widget,
+        Wrap,
     },
 };
 use ratatui_macros::span;
@@ -14,33 +16,158 @@ use tokio::sync::mpsc;
 use tui_textarea::Key;
 use unicode_width::UnicodeWidthStr;
 
-use super::{Dim, utils};
+use super::{Dim, TextArea, utils};
 
 pub struct EditScreen<'a> {
     action_tx: mpsc::UnboundedSender<Action>,
+    event_tx: mpsc::UnboundedSender<Event>,
 
     theme: &'a Theme,
 
     showing: bool,
+    conversation_id: String,
     messages: Vec<SelectedMessage>,
     list_state: ListState,
+
+    editing_system_prompt: bool,
+    system_prompt: String,
+    system_prompt_input: tui_textarea::TextArea<'a>,
+
+    // The ID of the user message being edited
+    editing_message: Option<String>,
+    message_input: tui_textarea::TextArea<'a>,
+
+    showing_info: bool,
+
+    // The previous texts of the selected message, from the oldest
+    message_edits: Option<Vec<MessageEdit>>,
+    edits_scroll: u16,
 }
 
-impl<'a> EditScreen<'_> {
-    pub fn new(theme: &'a Theme, action_tx: mpsc::UnboundedSender<Action>) -> EditScreen<'a> {
+impl<'a> EditScreen<'a> {
+    pub fn new(
+        theme: &'a Theme,
+        action_tx: mpsc::UnboundedSender<Action>,
+        event_tx: mpsc::UnboundedSender<Event>,
+    ) -> EditScreen<'a> {
         EditScreen {
             action_tx,
+            event_tx,
 
             showing: false,
+            conversation_id: String::new(),
             messages: vec![],
             list_state: ListState::default(),
             theme,
+
+            editing_system_prompt: false,
+            system_prompt: String::new(),
+            system_prompt_input: build_system_prompt_input(""),
+
+            editing_message: None,
+            message_input: TextArea::default().build(),
+
+            showing_info: false,
+
+            message_edits: None,
+            edits_scroll: 0,
+        }
+    }
+
+    pub fn set_system_prompt(&mut self, system_prompt: Option<&str>) {
+        self.system_prompt = system_prompt.unwrap_or_default().to_string();
+        self.editing_system_prompt = false;
+    }
+
+    fn start_editing_system_prompt(&mut self) {
+        self.system_prompt_input = build_system_prompt_input(&self.system_prompt);
+        self.editing_system_prompt = true;
+    }
+
+    fn save_system_prompt(&mut self) {
+        self.system_prompt = self
+            .system_prompt_input
+            .lines()
+            .join("\n")
+            .trim()
+            .to_string();
+        self.editing_system_prompt = false;
+        let _ = self
+            .event_tx
+            .send(Event::SetSystemPrompt(self.system_prompt.clone()));
+    }
+
+    fn start_editing_message(&mut self) {
+        let Some(message) = self.selected_message() else {
+            return;
+        };
+        if message.is_system() || message.is_tool() {
+            let _ = self
+                .event_tx
+                .send(info_event!("Only the user messages can be edited"));
+            return;
         }
+
+        let mut input = TextArea::new(" Message ").build();
+        input.insert_str(message.text());
+        self.editing_message = Some(message.id().to_string());
+        self.message_input = input;
+    }
+
+    /// Send the edited message to be resent, the messages after it are
+    /// removed from the conversation
+    fn save_message(&mut self) {
+        let text = self.message_input.lines().join("\n").trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        let Some(id) = self.editing_message.take() else {
+            return;
+        };
+        self.showing = false;
+        let _ = self.event_tx.send(Event::EditMessage(id, text));
+    }
+
+    fn request_message_edits(&mut self) {
+        let Some(message) = self.selected_message() else {
+            return;
+        };
+        if !config::instance().storage.track_edits() {
+            let _ = self.event_tx.send(info_event!(
+                "The edits are not tracked, enable storage.sqlite.track_edits to keep them"
+            ));
+            return;
+        }
+        let _ = self.action_tx.send(Action::GetMessageEdits(
+            self.conversation_id.clone(),
+            message.id().to_string(),
+        ));
+    }
+
+    /// Show the versions of the message if it is still selected
+    pub fn set_message_edits(&mut self, msg_id: &str, edits: Vec<MessageEdit>) {
+        if !self.showing || self.selected_message().is_none_or(|msg| msg.id() != msg_id) {
+            return;
+        }
+        if edits.is_empty() {
+            let _ = self
+                .event_tx
+                .send(info_event!("The message has not been edited"));
+            return;
+        }
+        self.message_edits = Some(edits);
+        self.edits_scroll = 0;
     }
 
-    pub fn set_messages(&mut self, messages: &[Message]) {
+    pub fn set_messages(&mut self, convo_id: &str, messages: &[Message]) {
+        self.conversation_id = convo_id.to_string();
+        let copy_tool_calls = config::instance()
+            .general
+            .copy_tool_calls
+            .unwrap_or_default();
         self.messages = messages
             .iter()
+            .filter(|message| copy_tool_calls || !message.is_tool())
             .map(|message| SelectedMessage {
                 msg: message.clone(),
                 selected: false,
@@ -60,6 +187,14 @@ impl<'a> EditScreen<'_> {
 
     pub fn toggle_showing(&mut self) {
         self.showing = !self.showing;
+        self.showing_info = false;
+        self.editing_message = None;
+        self.message_edits = None;
+    }
+
+    fn selected_message(&self) -> Option<&Message> {
+        let i = self.list_state.selected()?;
+        self.messages.get(i).map(|item| &item.msg)
     }
 
     fn toggle_selected(&mut self) {
@@ -93,6 +228,16 @@ impl<'a> EditScreen<'_> {
 
         f.dim_bg();
 
+        if self.editing_system_prompt {
+            render_editor(f, area, " System Prompt ", &self.system_prompt_input);
+            return;
+        }
+
+        if self.editing_message.is_some() {
+            render_editor(f, area, " Edit Message ", &self.message_input);
+            return;
+        }
+
         let instructions = vec![
             span!(" "),
             span!("q").green().bold(),
@@ -102,7 +247,15 @@ impl<'a> EditScreen<'_> {
             span!("y").green().bold(),
             span!(" to copy selected, ").white(),
             span!("c").green().bold(),
-            span!(" to quick copy ").white(),
+            span!(" to quick copy, ").white(),
+            span!("e").green().bold(),
+            span!(" to edit and resend, ").white(),
+            span!("p").green().bold(),
+            span!(" to edit system prompt, ").white(),
+            span!("i").green().bold(),
+            span!(" to show info, ").white(),
+            span!("v").green().bold(),
+            span!(" to show versions ").white(),
         ];
 
         let block = Block::default()
@@ -125,6 +278,85 @@ impl<'a> EditScreen<'_> {
             .split(inner);
         self.render_messages_panel(f, layout[0]);
         self.render_preview_panel(f, layout[1]);
+
+        if self.showing_info {
+            self.render_info_popup(f, area);
+        }
+
+        if self.message_edits.is_some() {
+            self.render_edits_popup(f, area);
+        }
+    }
+
+    fn render_edits_popup(&self, f: &mut Frame, area: Rect) {
+        let (Some(message), Some(edits)) = (self.selected_message(), &self.message_edits) else {
+            return;
+        };
+
+        let instructions = vec![
+            span!(" "),
+            span!("j/k").green().bold(),
+            span!(" to scroll, ").white(),
+            span!("Esc").green().bold(),
+            span!(" to close ").white(),
+        ];
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::LightBlue))
+            .padding(Padding::symmetric(1, 0))
+            .title(Line::from(" Message Versions ").bold())
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(instructions));
+
+        let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+        let vertical = Layout::vertical([Constraint::Percentage(80)]).flex(Flex::Center);
+        let [popup] = vertical.areas(area);
+        let [popup] = horizontal.areas(popup);
+
+        f.render_widget(Clear, popup);
+        f.render_widget(
+            Paragraph::new(build_edit_lines(message, edits))
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((self.edits_scroll, 0)),
+            popup,
+        );
+    }
+
+    fn render_info_popup(&self, f: &mut Frame, area: Rect) {
+        let Some(message) = self.selected_message() else {
+            return;
+        };
+
+        let instructions = vec![
+            span!(" "),
+            span!("y").green().bold(),
+            span!(" to copy id, ").white(),
+            span!("Esc").green().bold(),
+            span!(" to close ").white(),
+        ];
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::LightBlue))
+            .padding(Padding::symmetric(1, 0))
+            .title(Line::from(" Message Info ").bold())
+            .title_alignment(Alignment::Center)
+            .title_bottom(Line::from(instructions));
+
+        let lines = build_info_lines(message);
+        let width = lines.iter().map(|l| l.width()).max().unwrap_or_default() as u16 + 4;
+        let height = lines.len() as u16 + 2;
+        let horizontal = Layout::horizontal([Constraint::Length(width.max(36))]).flex(Flex::Center);
+        let vertical = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center);
+        let [popup] = vertical.areas(area);
+        let [popup] = horizontal.areas(popup);
+
+        f.render_widget(Clear, popup);
+        f.render_widget(Paragraph::new(lines).block(block), popup);
     }
 
     fn render_messages_panel(&mut self, frame: &mut Frame, area: Rect) {
@@ -180,6 +412,22 @@ impl<'a> EditScreen<'_> {
     }
 
     pub async fn handle_key_event(&mut self, event: &Event) -> bool {
+        if self.editing_system_prompt {
+            return self.handle_system_prompt_event(event);
+        }
+
+        if self.editing_message.is_some() {
+            return self.handle_message_event(event);
+        }
+
+        if self.showing_info {
+            return self.handle_info_event(event);
+        }
+
+        if self.message_edits.is_some() {
+            return self.handle_edits_event(event);
+        }
+
         match event {
             Event::KeyboardCtrlE => {
                 self.showing = !self.showing;
@@ -220,6 +468,10 @@ impl<'a> EditScreen<'_> {
                     }
                 }
                 Key::Char(' ') => self.toggle_selected(),
+                Key::Char('e') => self.start_editing_message(),
+                Key::Char('p') => self.start_editing_system_prompt(),
+                Key::Char('i') => self.showing_info = self.selected_message().is_some(),
+                Key::Char('v') => self.request_message_edits(),
                 Key::Char('q') => {
                     self.showing = false;
                     return false;
@@ -238,6 +490,202 @@ impl<'a> EditScreen<'_> {
     }
 }
 
+impl EditScreen<'_> {
+    fn handle_system_prompt_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Quit => {
+                self.showing = false;
+                return true;
+            }
+            Event::KeyboardEsc => self.editing_system_prompt = false,
+            Event::KeyboardEnter => self.save_system_prompt(),
+            Event::KeyboardNewLine => self.system_prompt_input.insert_newline(),
+            Event::KeyboardPaste(text) => {
+                self.system_prompt_input
+                    .set_yank_text(text.replace('\r', "\n"));
+                self.system_prompt_input.paste();
+            }
+            Event::KeyboardCharInput(input) => {
+                self.system_prompt_input.input(input.clone());
+            }
+            _ => {}
+        }
+        false
+    }
+}
+
+impl EditScreen<'_> {
+    fn handle_message_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Quit => {
+                self.showing = false;
+                self.editing_message = None;
+                return true;
+            }
+            Event::KeyboardEsc => self.editing_message = None,
+            Event::KeyboardEnter => self.save_message(),
+            Event::KeyboardNewLine => self.message_input.insert_newline(),
+            Event::KeyboardPaste(text) => {
+                self.message_input.set_yank_text(text.replace('\r', "\n"));
+                self.message_input.paste();
+            }
+            Event::KeyboardCharInput(input) => {
+                self.message_input.input(input.clone());
+            }
+            _ => {}
+        }
+        false
+    }
+}
+
+impl EditScreen<'_> {
+    fn handle_info_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Quit => {
+                self.showing = false;
+                self.showing_info = false;
+                return true;
+            }
+            Event::KeyboardEsc => self.showing_info = false,
+            Event::KeyboardCharInput(input) => match input.key {
+                Key::Char('y') => {
+                    if let Some(message) = self.selected_message() {
+                        let _ = self.action_tx.send(Action::CopyText {
+                            content: message.id().to_string(),
+                            notice: true,
+                        });
+                    }
+                }
+                Key::Char('i') | Key::Char('q') => self.showing_info = false,
+                _ => {}
+            },
+            _ => {}
+        }
+        false
+    }
+}
+
+impl EditScreen<'_> {
+    fn handle_edits_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Quit => {
+                self.showing = false;
+                self.message_edits = None;
+                return true;
+            }
+            Event::KeyboardEsc => self.message_edits = None,
+            Event::UiScrollUp => self.edits_scroll = self.edits_scroll.saturating_sub(1),
+            Event::UiScrollDown => self.edits_scroll = self.edits_scroll.saturating_add(1),
+            Event::KeyboardCharInput(input) => match input.key {
+                Key::Char('k') => self.edits_scroll = self.edits_scroll.saturating_sub(1),
+                Key::Char('j') => self.edits_scroll = self.edits_scroll.saturating_add(1),
+                Key::Char('v') | Key::Char('q') => self.message_edits = None,
+                _ => {}
+            },
+            _ => {}
+        }
+        false
+    }
+}
+
+/// The current text of the message then its previous ones, from the newest
+fn build_edit_lines<'a>(message: &Message, edits: &[MessageEdit]) -> Vec<Line<'a>> {
+    let mut lines = vec![Line::from(span!("Current").light_cyan().bold())];
+    lines.extend(
+        message
+            .text()
+            .lines()
+            .map(|line| Line::from(line.to_string())),
+    );
+    for edit in edits.iter().rev() {
+        let edited_at = edit
+            .edited_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S");
+        lines.push(Line::default());
+        lines.push(Line::from(
+            span!(format!("Replaced at {}", edited_at))
+                .light_cyan()
+                .bold(),
+        ));
+        lines.extend(edit.text.lines().map(|line| Line::from(line.to_string())));
+    }
+    lines
+}
+
+fn build_info_lines<'a>(message: &Message) -> Vec<Line<'a>> {
+    let created_at = message
+        .created_at()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M:%S %:z")
+        .to_string();
+    let tokens = if message.token_count() > 0 {
+        message.token_count().to_string()
+    } else {
+        format!("~{} (estimated)", message.estimated_token_count())
+    };
+    let issuer = if message.is_tool() {
+        format!("{} (tool)", message.issuer_str())
+    } else if message.is_system() {
+        format!("{} (system)", message.issuer_str())
+    } else {
+        format!("{} (user)", message.issuer_str())
+    };
+
+    [
+        ("ID", message.id().to_string()),
+        ("Issuer", issuer),
+        ("Created At", created_at),
+        ("Tokens", tokens),
+    ]
+    .into_iter()
+    .map(|(label, value)| {
+        Line::from(vec![
+            span!(format!("{:<12}", label)).light_cyan().bold(),
+            span!(value),
+        ])
+    })
+    .collect()
+}
+
+/// Render the text editor of the system prompt or a message
+fn render_editor(f: &mut Frame, area: Rect, title: &str, input: &tui_textarea::TextArea) {
+    let instructions = vec![
+        span!(" "),
+        span!("Enter").green().bold(),
+        span!(" to save, ").white(),
+        span!("Shift+Enter").green().bold(),
+        span!(" for new line, ").white(),
+        span!("Esc").green().bold(),
+        span!(" to cancel ").white(),
+    ];
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::LightBlue))
+        .padding(Padding::symmetric(1, 0))
+        .title(Line::from(title.to_string()).bold())
+        .title_alignment(Alignment::Center)
+        .title_bottom(Line::from(instructions))
+        .style(Style::default());
+
+    f.render_widget(Clear, area);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    f.render_widget(input, inner);
+}
+
+fn build_system_prompt_input<'a>(system_prompt: &str) -> tui_textarea::TextArea<'a> {
+    let mut input = TextArea::new(" Prompt ")
+        .with_placeholder("Type the system prompt for this conversation...")
+        .build();
+    if !system_prompt.is_empty() {
+        input.insert_str(system_prompt);
+    }
+    input
+}
+
 fn build_list_items<'a>(messages: &[SelectedMessage], max_width: usize) -> Vec<ListItem<'a>> {
     messages
         .iter()
@@ -250,17 +698,15 @@ fn build_list_items<'a>(messages: &[SelectedMessage], max_width: usize) -> Vec<L
             }
             spans.push(span!(Style::default(); " "));
 
-            let fg_color = if item.msg.is_system() {
-                Color::LightCyan
+            let (fg_color, role) = if item.msg.is_tool() {
+                (Color::Gray, "T")
+            } else if item.msg.is_system() {
+                (Color::LightCyan, "S")
             } else {
-                Color::LightMagenta
+                (Color::LightMagenta, "U")
             };
 
-            let mut content = format!(
-                "{}: {}",
-                if item.msg.is_system() { "S" } else { "U" },
-                item.msg.text()
-            );
+            let mut content = format!("{}: {}", role, item.msg.text());
             // If the content is too long, we will truncate it
             // and add ellipsis
             if content.width() > max_width {
@@ -1,4 +1,5 @@
 use crate::models::Event;
+use crate::t;
 use ratatui_macros::span;
 use std::{
     cmp::{max, min},
@@ -100,14 +101,14 @@ impl HelpScreen<'_> {
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::LightBlue))
             .padding(Padding::symmetric(1, 0))
-            .title(Line::from(" Help ").bold())
+            .title(Line::from(t!("help.title")).bold())
             .title_alignment(Alignment::Center)
             .title_bottom(Line::from(vec![
                 " ".into(),
                 span!("q").green().bold(),
-                span!(" to close, ").white(),
+                span!(t!("help.close")).white(),
                 span!("↑/k/↓/j").green().bold(),
-                span!(" to move up/down ").white(),
+                span!(t!("help.move")).white(),
             ]))
             .style(Style::default());
         f.render_widget(Clear, area);
@@ -171,23 +172,34 @@ impl HelpScreen<'_> {
 
 fn build_key_bindings() -> Vec<KeyBinding> {
     vec![
-        KeyBinding::new(Input::new(Key::Char('q')), "Close Popup"),
-        KeyBinding::new(Input::new(Key::F(1)), "Show Help").with_short_desc("Help"),
-        KeyBinding::new(Input::new(Key::Char('h')).ctrl(), "Show Chat [H]istory")
-            .with_short_desc("History"),
-        KeyBinding::new(Input::new(Key::Char('q')).ctrl(), "[Q]uit").with_short_desc("Quit"),
+        KeyBinding::new(Input::new(Key::Char('q')), t!("help.close_popup")),
+        KeyBinding::new(Input::new(Key::F(1)), t!("help.show_help"))
+            .with_short_desc(t!("help.help")),
+        KeyBinding::new(Input::new(Key::Char('h')).ctrl(), t!("help.show_history"))
+            .with_short_desc(t!("help.history")),
+        KeyBinding::new(Input::new(Key::Char('q')).ctrl(), t!("help.quit_long"))
+            .with_short_desc(t!("help.quit")),
+        KeyBinding::new(Input::new(Key::Char('c')).ctrl(), t!("help.abort")),
+        KeyBinding::new(Input::new(Key::Char('r')).ctrl(), t!("help.regenerate")),
+        KeyBinding::new(Input::new(Key::Char('l')).ctrl(), t!("help.select_model")),
+        KeyBinding::new(Input::new(Key::Char('e')).ctrl(), t!("help.edit_mode")),
+        KeyBinding::new(Input::new(Key::Char('n')).ctrl(), t!("help.new_chat")),
+        KeyBinding::new(Input::new(Key::Char('k')).ctrl(), t!("help.clear_messages")),
+        KeyBinding::new(Input::new(Key::Char('p')).ctrl(), t!("help.compress")),
+        KeyBinding::new(Input::new(Key::Char('f')).ctrl(), t!("help.find")),
+        KeyBinding::new(Input::new(Key::Char('t')).ctrl(), t!("help.retitle")),
+        KeyBinding::new(Input::new(Key::Char('o')).ctrl(), t!("help.tool_args")),
+        KeyBinding::new(Input::new(Key::Char('g')).ctrl(), t!("help.open_pager")),
         KeyBinding::new(
-            Input::new(Key::Char('c')).ctrl(),
-            "Abort Request/[C]lear Chat",
+            Input::new(Key::Char('y')).ctrl(),
+            t!("help.copy_conversation"),
         ),
-        KeyBinding::new(Input::new(Key::Char('r')).ctrl(), "[R]egenerate Response"),
-        KeyBinding::new(Input::new(Key::Char('l')).ctrl(), "[L]ist/Select Model"),
-        KeyBinding::new(Input::new(Key::Char('e')).ctrl(), "[E]dit Mode"),
-        KeyBinding::new(Input::new(Key::Char('n')).ctrl(), "[N]ew Chat"),
-        KeyBinding::new(Input::new(Key::Up), "Scroll Up"),
-        KeyBinding::new(Input::new(Key::Down), "Scroll Down"),
-        KeyBinding::new(Input::new(Key::Up).ctrl(), "Scroll Page Up"),
-        KeyBinding::new(Input::new(Key::Down).ctrl(), "Scroll Page Down"),
+        KeyBinding::new(Input::new(Key::Char('y')).alt(), t!("help.copy_responses")),
+        KeyBinding::new(Input::new(Key::F(2)), t!("help.cycle_backend")),
+        KeyBinding::new(Input::new(Key::Up), t!("help.scroll_up")),
+        KeyBinding::new(Input::new(Key::Down), t!("help.scroll_down")),
+        KeyBinding::new(Input::new(Key::Up).ctrl(), t!("help.page_up")),
+        KeyBinding::new(Input::new(Key::Down).ctrl(), t!("help.page_down")),
     ]
 }
 
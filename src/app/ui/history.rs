@@ -1,8 +1,15 @@
+#[cfg(test)]
+#[path = "history_test.rs"]
+mod tests;
+
+use crate::config::{self, HistoryGroup, HistorySort, UiConfig};
 use crate::models::{Action, Conversation, Event, UpsertConvoRequest};
+use crate::t;
 use chrono::{Local, Utc};
+use crossterm::event::MouseButton;
 use ratatui::{
     Frame,
-    layout::{Alignment, Rect},
+    layout::{Alignment, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span, Text},
     widgets::{
@@ -11,8 +18,10 @@ use ratatui::{
 };
 use ratatui_macros::span;
 use std::{
-    collections::{BTreeMap, HashMap},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Display,
+    time::{Duration, Instant},
 };
 use tokio::sync::mpsc;
 use tui_textarea::Key;
@@ -23,15 +32,15 @@ use super::{
 };
 use super::{question::Question, utils};
 
-const NO_CONVERSATIONS: &str = "No conversations found";
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a deleted conversation can be restored with `u`
+pub const UNDO_DELETE_WINDOW: Duration = Duration::from_secs(5);
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
-enum ConversationGroup {
-    Today,
-    Yesterday,
-    Last7Days,
-    Last30Days,
-    Older,
+/// A date group of the history, ordered from the most recent one
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct ConversationGroup {
+    order: usize,
+    title: String,
 }
 
 pub struct HistoryScreen<'a> {
@@ -46,12 +55,25 @@ pub struct HistoryScreen<'a> {
     rename: InputBox<'a>,
     search: InputBox<'a>,
     current_search: String,
+    /// The conversations matching the current search by meaning, from the
+    /// most similar one. The title filter applies until they arrive.
+    semantic_results: Option<Vec<String>>,
 
     question: Question<'a>,
 
     current_conversation: Option<String>,
     state: ListState,
 
+    /// The screen area of each visible row, keyed by the item index. Updated
+    /// on every render to map the mouse clicks to the items.
+    row_areas: Vec<(usize, Rect)>,
+    last_click: Option<(usize, Instant)>,
+
+    /// The conversations marked with `Space` to be deleted at once
+    marked: HashSet<String>,
+
+    last_deleted: Option<(Vec<String>, Instant)>,
+
     last_known_width: usize,
 }
 
@@ -64,17 +86,28 @@ impl<'a> HistoryScreen<'a> {
             conversations: HashMap::new(),
 
             idx_map: HashMap::new(),
-            rename: InputBox::default().with_title(" Rename "),
-            search: InputBox::default().with_title(" Search "),
-            question: Question::default().with_title(" Delete Conversation "),
+            rename: InputBox::default().with_title(t!("history.rename_title")),
+            search: InputBox::default().with_title(if config::instance().search.semantic {
+                t!("history.semantic_search_title")
+            } else {
+                t!("history.search_title")
+            }),
+            question: Question::default().with_title(t!("history.delete_title")),
 
             current_search: String::new(),
+            semantic_results: None,
             current_conversation: None,
 
             last_known_width: 0,
 
             items: vec![],
             state: ListState::default(),
+
+            row_areas: vec![],
+            last_click: None,
+
+            marked: HashSet::new(),
+            last_deleted: None,
         }
     }
 
@@ -106,6 +139,7 @@ impl<'a> HistoryScreen<'a> {
     }
 
     pub fn remove_conversation(&mut self, conversation: &str) {
+        self.marked.remove(conversation);
         if let Some(convo) = self.conversations.remove(conversation) {
             if convo.id() == self.current_conversation.as_deref().unwrap_or_default() {
                 self.current_conversation = None;
@@ -126,6 +160,10 @@ impl<'a> HistoryScreen<'a> {
     }
 
     pub fn upsert_conversation(&mut self, conversation: &Conversation) {
+        // The scratch conversations are never listed
+        if conversation.is_ephemeral() {
+            return;
+        }
         self.conversations.insert(
             conversation.id().to_string(),
             Conversation::default()
@@ -193,9 +231,8 @@ impl<'a> HistoryScreen<'a> {
             self.state.select(None);
             return;
         }
-        self.state.select(Some(0));
-        // if the first item is a group header, we need to select the next item
-        self.next_row();
+        // Skip the group header, if any
+        self.state.select(self.idx_map.keys().min().copied());
     }
 
     fn last(&mut self) {
@@ -217,52 +254,91 @@ impl<'a> HistoryScreen<'a> {
         }
     }
 
+    pub fn update_conversation_title(&mut self, conversation_id: &str, title: &str) {
+        if let Some(conversation) = self.conversations.get_mut(conversation_id) {
+            conversation.set_title(title);
+            self.update_items();
+        }
+    }
+
+    /// Show the conversations found by the semantic search, the results of
+    /// a previous search are dropped
+    pub fn set_semantic_results(&mut self, query: &str, ids: Vec<String>) {
+        if query != self.current_search {
+            return;
+        }
+        self.semantic_results = Some(ids);
+        self.update_items();
+        if !self.items.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
     pub fn update_items(&mut self) {
         self.items.clear();
         self.idx_map.clear();
 
         if self.conversations.is_empty() {
             self.items.push(ListItem::new(
-                Text::from(NO_CONVERSATIONS).alignment(Alignment::Center),
+                Text::from(t!("history.no_conversations")).alignment(Alignment::Center),
             ));
             self.state.select(None);
             return;
         }
 
-        let mut conversations: BTreeMap<ConversationGroup, Vec<&Conversation>> = BTreeMap::new();
+        let ui = &config::instance().ui;
+        let search = self.current_search.to_lowercase();
+        let conversations = match self.semantic_results.as_ref() {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| self.conversations.get(id))
+                .collect::<Vec<_>>(),
+            None => {
+                let mut conversations = self
+                    .conversations
+                    .values()
+                    .filter(|c| search.is_empty() || c.title().to_lowercase().contains(&search))
+                    .collect::<Vec<_>>();
+                conversations.sort_by(|a, b| compare_conversations(a, b, ui.history_sort));
+                conversations
+            }
+        };
 
+        let mut groups: BTreeMap<Option<ConversationGroup>, Vec<&Conversation>> = BTreeMap::new();
+        let date_groups = date_groups(ui);
         let now = Utc::now();
-        self.conversations
-            .iter()
-            .filter(|(_, c)| {
-                if self.current_search.is_empty() {
-                    return true;
-                }
-                c.title()
-                    .to_lowercase()
-                    .contains(&self.current_search.to_lowercase())
-            })
-            .for_each(|(_, c)| {
-                let group = categorize_conversation(now, c.updated_at());
-
-                conversations.entry(group).or_default().push(c);
+        for c in conversations {
+            // The semantic results are kept in the order of similarity
+            let grouped = ui.history_group_by_date() && self.semantic_results.is_none();
+            let group = grouped.then(|| {
+                let date = match ui.history_sort {
+                    HistorySort::Created => c.created_at(),
+                    _ => c.updated_at(),
+                };
+                categorize_conversation(now, date, &date_groups)
             });
+            groups.entry(group).or_default().push(c);
+        }
 
-        for (group, mut conversations) in conversations {
-            self.items.push(group.to_list_item());
-            conversations.sort_by(|a, b| {
-                // If the id is empty, always put it at the top of the list
-                if a.id().is_empty() {
-                    return std::cmp::Ordering::Less;
-                }
-
-                b.updated_at()
-                    .with_timezone(&Local)
-                    .cmp(&a.updated_at().with_timezone(&Local))
-            });
+        for (group, conversations) in groups {
+            if let Some(group) = group {
+                self.items.push(group.to_list_item());
+            }
 
             for c in conversations {
-                let mut spans = vec![span!(c.title())];
+                let mut spans = vec![];
+                if !self.marked.is_empty() {
+                    spans.push(if self.marked.contains(c.id()) {
+                        span!("✓ ").green().bold()
+                    } else {
+                        span!("  ")
+                    });
+                }
+                if c.title().is_empty() {
+                    spans.push(span!(t!("history.untitled")).italic().dark_gray());
+                } else {
+                    spans.push(span!(c.title()));
+                }
                 if self.current_conversation.as_deref() == Some(c.id()) {
                     spans.push(Span::styled(" ", Style::default()));
                     spans.push(Span::styled("[*]", Style::default().fg(Color::LightRed)))
@@ -301,26 +377,7 @@ impl<'a> HistoryScreen<'a> {
                 return true;
             }
 
-            Event::KeyboardEnter => {
-                if self.state.selected().is_none() || self.conversations.is_empty() {
-                    return false;
-                }
-
-                let id = match self.get_selected_conversation_id() {
-                    Some(id) => id.to_string(),
-                    None => return false,
-                };
-
-                if self.current_conversation.as_deref() == Some(&id) {
-                    return false;
-                }
-
-                self.showing = false;
-                self.action_tx
-                    .send(Action::SetConversation(id.clone()))
-                    .ok();
-                self.current_conversation = Some(id);
-            }
+            Event::KeyboardEnter => self.open_selected(),
 
             Event::KeyboardCharInput(input) => match input.key {
                 Key::Char('j') => self.next_row(),
@@ -334,6 +391,23 @@ impl<'a> HistoryScreen<'a> {
 
                 Key::Char('/') => self.search.open(self.current_search.clone()),
 
+                Key::Char('u') => self.undo_delete(),
+
+                Key::Char(' ') => self.toggle_mark(),
+
+                Key::Char('d') if !self.marked.is_empty() => {
+                    let quest = vec![
+                        span!(t!("history.delete_question")),
+                        span!(t!(
+                            "history.marked_conversations",
+                            count = self.marked.len()
+                        ))
+                        .add_modifier(Modifier::BOLD | Modifier::ITALIC)
+                        .yellow(),
+                        span!("?"),
+                    ];
+                    self.question.open(quest);
+                }
                 Key::Char('d') => {
                     let conversation = match self.get_selected_conversation() {
                         Some(c) => c,
@@ -345,7 +419,7 @@ impl<'a> HistoryScreen<'a> {
                     }
 
                     let quest = vec![
-                        span!("Do you want to delete"),
+                        span!(t!("history.delete_question")),
                         span!(format!("\"{}\"", conversation.title()))
                             .add_modifier(Modifier::BOLD | Modifier::ITALIC)
                             .yellow(),
@@ -371,11 +445,81 @@ impl<'a> HistoryScreen<'a> {
             Event::UiScrollPageUp => self.pageup(),
             Event::UiScrollPageDown => self.pagedown(),
 
+            Event::UiMouseDown {
+                button: MouseButton::Left,
+                x,
+                y,
+            } => self.handle_mouse_click(*x, *y),
+
             _ => {}
         }
         false
     }
 
+    fn open_selected(&mut self) {
+        if self.state.selected().is_none() || self.conversations.is_empty() {
+            return;
+        }
+
+        let id = match self.get_selected_conversation_id() {
+            Some(id) => id.to_string(),
+            None => return,
+        };
+
+        if self.current_conversation.as_deref() == Some(&id) {
+            return;
+        }
+
+        self.showing = false;
+        self.action_tx
+            .send(Action::SetConversation(id.clone()))
+            .ok();
+        self.current_conversation = Some(id);
+    }
+
+    /// A single click selects the clicked conversation, a double click opens
+    /// it. Clicks on the group headers are ignored.
+    fn handle_mouse_click(&mut self, x: u16, y: u16) {
+        let Some(idx) = self
+            .row_areas
+            .iter()
+            .find(|(_, area)| area.contains(Position::new(x, y)))
+            .map(|(idx, _)| *idx)
+        else {
+            return;
+        };
+
+        if !self.idx_map.contains_key(&idx) {
+            return;
+        }
+
+        let double_click = matches!(
+            self.last_click,
+            Some((last_idx, at)) if last_idx == idx && at.elapsed() <= DOUBLE_CLICK_INTERVAL
+        );
+        self.state.select(Some(idx));
+        if double_click {
+            self.last_click = None;
+            self.open_selected();
+        } else {
+            self.last_click = Some((idx, Instant::now()));
+        }
+    }
+
+    fn update_row_areas(&mut self, inner: Rect) {
+        self.row_areas.clear();
+        let mut y = inner.y;
+        for (idx, item) in self.items.iter().enumerate().skip(self.state.offset()) {
+            if y >= inner.bottom() {
+                break;
+            }
+            let height = (item.height() as u16).min(inner.bottom() - y);
+            self.row_areas
+                .push((idx, Rect::new(inner.x, y, inner.width, height)));
+            y += height;
+        }
+    }
+
     async fn handle_search_popup(&mut self, event: &Event) {
         match event {
             Event::KeyboardEsc | Event::KeyboardCtrlC => {
@@ -383,6 +527,12 @@ impl<'a> HistoryScreen<'a> {
             }
             Event::KeyboardEnter => {
                 self.current_search = self.search.close().unwrap_or_default();
+                self.semantic_results = None;
+                if config::instance().search.semantic && !self.current_search.trim().is_empty() {
+                    let _ = self
+                        .action_tx
+                        .send(Action::SemanticSearch(self.current_search.clone()));
+                }
                 self.update_items();
                 if !self.items.is_empty() {
                     self.state.select(Some(0));
@@ -420,17 +570,54 @@ impl<'a> HistoryScreen<'a> {
         }
     }
 
+    /// Mark or unmark the selected conversation to be deleted and move to
+    /// the next one. The new and the current conversations can't be marked.
+    fn toggle_mark(&mut self) {
+        let Some(id) = self.get_selected_conversation_id().map(String::from) else {
+            return;
+        };
+        if id.is_empty() || self.current_conversation.as_deref() == Some(&id) {
+            return;
+        }
+
+        if !self.marked.remove(&id) {
+            self.marked.insert(id);
+        }
+        self.update_items();
+        self.next_row();
+    }
+
     async fn on_delete(&mut self) {
-        let convo_id = match self.get_selected_conversation_id() {
-            Some(id) => id.to_string(),
-            None => return,
+        let convo_ids = if self.marked.is_empty() {
+            match self.get_selected_conversation_id() {
+                Some(id) => vec![id.to_string()],
+                None => return,
+            }
+        } else {
+            self.marked.drain().collect()
         };
 
-        log::debug!("Deleting conversation: {}", convo_id);
+        for convo_id in &convo_ids {
+            log::debug!("Deleting conversation: {}", convo_id);
+            let _ = self
+                .action_tx
+                .send(Action::DeleteConversation(convo_id.to_string()));
+        }
+        self.last_deleted = Some((convo_ids, Instant::now()));
+    }
 
-        let _ = self
-            .action_tx
-            .send(Action::DeleteConversation(convo_id.to_string()));
+    fn undo_delete(&mut self) {
+        let Some((convo_ids, deleted_at)) = self.last_deleted.take() else {
+            return;
+        };
+        if deleted_at.elapsed() > UNDO_DELETE_WINDOW {
+            return;
+        }
+
+        for convo_id in convo_ids {
+            log::debug!("Restoring conversation: {}", convo_id);
+            let _ = self.action_tx.send(Action::RestoreConversation(convo_id));
+        }
     }
 
     pub async fn rename_conversation(&mut self, new_title: &str) {
@@ -494,15 +681,17 @@ impl<'a> HistoryScreen<'a> {
         let instructions: Vec<Span> = vec![
             " ".into(),
             span!("q").green().bold(),
-            span!(" to close, ").white(),
+            span!(t!("history.close")).white(),
             span!("Enter").green().bold(),
-            span!(" to select, ").white(),
+            span!(t!("history.select")).white(),
+            span!("Space").green().bold(),
+            span!(t!("history.mark")).white(),
             span!("d").green().bold(),
-            span!(" to delete, ").white(),
+            span!(t!("history.delete")).white(),
             span!("r").green().bold(),
-            span!(" to rename ").white(),
+            span!(t!("history.rename")).white(),
             span!("/").green().bold(),
-            span!(" to search ").white(),
+            span!(t!("history.search")).white(),
         ];
 
         let block = Block::default()
@@ -510,7 +699,7 @@ impl<'a> HistoryScreen<'a> {
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::LightBlue))
             .padding(Padding::symmetric(2, 0))
-            .title(Line::from(" Chat History ").bold())
+            .title(Line::from(t!("history.title")).bold())
             .title_alignment(Alignment::Center)
             .title_bottom(Line::from(instructions));
 
@@ -526,6 +715,7 @@ impl<'a> HistoryScreen<'a> {
             .block(block)
             .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
         list.render(area, f.buffer_mut(), &mut self.state);
+        self.update_row_areas(inner);
 
         let rename_area = input_box::build_area(inner, ((inner.width as f32 * 0.8).ceil()) as u16);
         self.rename.render(f, rename_area);
@@ -538,13 +728,7 @@ impl<'a> HistoryScreen<'a> {
 
 impl Display for ConversationGroup {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConversationGroup::Today => write!(f, "Today"),
-            ConversationGroup::Yesterday => write!(f, "Yesterday"),
-            ConversationGroup::Last7Days => write!(f, "Last 7 Days"),
-            ConversationGroup::Last30Days => write!(f, "Last 30 Days"),
-            ConversationGroup::Older => write!(f, "Older"),
-        }
+        write!(f, "{}", self.title)
     }
 }
 
@@ -565,18 +749,67 @@ impl ConversationGroup {
     }
 }
 
+/// The order of the conversations in the history. The new conversation,
+/// which has no id yet, is always at the top.
+fn compare_conversations(a: &Conversation, b: &Conversation, sort: HistorySort) -> Ordering {
+    b.id()
+        .is_empty()
+        .cmp(&a.id().is_empty())
+        .then_with(|| match sort {
+            HistorySort::Recent => b.updated_at().cmp(&a.updated_at()),
+            HistorySort::Created => b.created_at().cmp(&a.created_at()),
+            // The untitled conversations go after the titled ones
+            HistorySort::Title => a
+                .title()
+                .is_empty()
+                .cmp(&b.title().is_empty())
+                .then_with(|| a.title().to_lowercase().cmp(&b.title().to_lowercase()))
+                .then_with(|| b.updated_at().cmp(&a.updated_at())),
+        })
+}
+
+/// The date groups of `ui.history_groups` from the most recent one, or the
+/// default ones if not set
+fn date_groups(ui: &UiConfig) -> Vec<HistoryGroup> {
+    if ui.history_groups.is_empty() {
+        return [
+            ("history.today", 0),
+            ("history.yesterday", 1),
+            ("history.last_7_days", 7),
+            ("history.last_30_days", 30),
+        ]
+        .into_iter()
+        .map(|(key, max_days)| HistoryGroup {
+            title: t!(key).to_string(),
+            max_days,
+        })
+        .collect();
+    }
+    let mut groups = ui.history_groups.clone();
+    groups.sort_by_key(|group| group.max_days);
+    groups
+}
+
+/// Put the conversation into the first group it is recent enough for, the
+/// ones older than every group are in the "Older" group
 fn categorize_conversation(
     now: chrono::DateTime<Utc>,
     updated_at: chrono::DateTime<Utc>,
+    groups: &[HistoryGroup],
 ) -> ConversationGroup {
     let age =
         now.with_timezone(&Local).date_naive() - updated_at.with_timezone(&Local).date_naive();
     let days = age.num_days();
-    match days {
-        0 => ConversationGroup::Today,
-        1 => ConversationGroup::Yesterday,
-        2..=7 => ConversationGroup::Last7Days,
-        8..=30 => ConversationGroup::Last30Days,
-        _ => ConversationGroup::Older,
-    }
+    groups
+        .iter()
+        .enumerate()
+        .find(|(_, group)| (0..=group.max_days as i64).contains(&days))
+        .map(|(order, group)| ConversationGroup {
+            order,
+            title: group.title.clone(),
+        })
+        .unwrap_or_else(|| ConversationGroup {
+            order: groups.len(),
+            title: t!("history.older").to_string(),
+        })
 }
@@ -0,0 +1,161 @@
+use super::*;
+
+#[test]
+fn test_compare_conversations() {
+    let now = Utc::now();
+    let conversations = [
+        Conversation::default()
+            .with_id("b")
+            .with_title("beta")
+            .with_created_at(now - chrono::Duration::days(2))
+            .with_updated_at(now),
+        Conversation::default()
+            .with_id("a")
+            .with_title("Alpha")
+            .with_created_at(now - chrono::Duration::days(1))
+            .with_updated_at(now - chrono::Duration::hours(1)),
+        Conversation::default()
+            .with_id("c")
+            .with_created_at(now)
+            .with_updated_at(now - chrono::Duration::hours(2)),
+        Conversation::default(),
+    ];
+
+    let cases = [
+        (HistorySort::Recent, vec!["", "b", "a", "c"]),
+        (HistorySort::Created, vec!["", "c", "a", "b"]),
+        (HistorySort::Title, vec!["", "a", "b", "c"]),
+    ];
+    for (sort, want) in cases {
+        let mut sorted = conversations.iter().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| compare_conversations(a, b, sort));
+        let ids = sorted.iter().map(|c| c.id()).collect::<Vec<_>>();
+        assert_eq!(ids, want, "{:?}", sort);
+    }
+}
+
+#[tokio::test]
+async fn test_bulk_delete() {
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+    let conversations = ["", "a", "b", "c"]
+        .into_iter()
+        .map(|id| (id.to_string(), Conversation::default().with_id(id)))
+        .collect();
+    let mut history = HistoryScreen::new(action_tx)
+        .with_conversations(conversations)
+        .with_current_conversation("c");
+    history.update_items();
+
+    // The new and the current conversations can't be marked
+    for id in ["", "a", "b", "c"] {
+        let idx = history
+            .idx_map
+            .iter()
+            .find(|(_, i)| i.as_str() == id)
+            .map(|(idx, _)| *idx);
+        history.state.select(idx);
+        history.toggle_mark();
+    }
+    assert_eq!(history.marked, HashSet::from(["a".into(), "b".into()]));
+
+    history.on_delete().await;
+    assert!(history.marked.is_empty());
+    let mut deleted = vec![];
+    while let Ok(Action::DeleteConversation(id)) = action_rx.try_recv() {
+        deleted.push(id);
+    }
+    deleted.sort();
+    assert_eq!(deleted, vec!["a", "b"]);
+
+    history.remove_conversation("a");
+    history.remove_conversation("b");
+    assert_eq!(history.idx_map.len(), 2);
+
+    history.undo_delete();
+    let mut restored = vec![];
+    while let Ok(Action::RestoreConversation(id)) = action_rx.try_recv() {
+        restored.push(id);
+    }
+    assert_eq!(restored.len(), 2);
+}
+
+#[test]
+fn test_semantic_results() {
+    let (action_tx, _action_rx) = mpsc::unbounded_channel();
+    let conversations = ["a", "b", "c"]
+        .into_iter()
+        .map(|id| {
+            let convo = Conversation::default().with_id(id).with_title(id);
+            (id.to_string(), convo)
+        })
+        .collect();
+    let mut history = HistoryScreen::new(action_tx).with_conversations(conversations);
+    history.current_search = "query".to_string();
+
+    // The results of a previous search are dropped
+    history.set_semantic_results("previous", vec!["a".to_string()]);
+    assert!(history.semantic_results.is_none());
+
+    // Kept in the order of similarity without the group headers, the
+    // conversations deleted since are skipped
+    history.set_semantic_results(
+        "query",
+        vec!["c".to_string(), "deleted".to_string(), "a".to_string()],
+    );
+    let mut ids = history.idx_map.iter().collect::<Vec<_>>();
+    ids.sort();
+    let ids = ids
+        .into_iter()
+        .map(|(_, id)| id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec!["c", "a"]);
+    assert_eq!(history.items.len(), 2);
+}
+
+#[test]
+fn test_categorize_conversation() {
+    let now = Utc::now();
+    let days_ago = |days: i64| now - chrono::Duration::days(days);
+
+    let groups = date_groups(&UiConfig::default());
+    let titles = [0, 1, 5, 30, 31]
+        .into_iter()
+        .map(|days| categorize_conversation(now, days_ago(days), &groups).title)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        titles,
+        vec!["Today", "Yesterday", "Last 7 Days", "Last 30 Days", "Older"]
+    );
+
+    let ui = UiConfig {
+        history_groups: vec![
+            HistoryGroup {
+                title: "This Month".to_string(),
+                max_days: 30,
+            },
+            HistoryGroup {
+                title: "This Week".to_string(),
+                max_days: 6,
+            },
+        ],
+        ..Default::default()
+    };
+    let groups = date_groups(&ui);
+    let week = categorize_conversation(now, days_ago(0), &groups);
+    let month = categorize_conversation(now, days_ago(7), &groups);
+    let older = categorize_conversation(now, days_ago(90), &groups);
+    assert_eq!(week.title, "This Week");
+    assert_eq!(month.title, "This Month");
+    assert_eq!(older.title, "Older");
+    assert!(week < month && month < older);
+}
+
+#[test]
+fn test_upsert_ephemeral_conversation() {
+    let (action_tx, _action_rx) = mpsc::unbounded_channel();
+    let mut history = HistoryScreen::new(action_tx);
+    history.upsert_conversation(&Conversation::default().with_id("a"));
+    history.upsert_conversation(&Conversation::default().with_id("b").with_ephemeral(true));
+    assert!(history.conversations.contains_key("a"));
+    assert!(!history.conversations.contains_key("b"));
+}
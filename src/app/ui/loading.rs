@@ -1,26 +1,88 @@
+#[cfg(test)]
+#[path = "loading_test.rs"]
+mod tests;
+
 use ratatui::{
     Frame,
     layout::Rect,
     style::{Modifier, Style},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{Block, BorderType, Borders, Padding, Paragraph},
 };
 
-#[derive(Default)]
-pub struct Loading<'a>(Line<'a>);
+use crate::config::SpinnerStyle;
+
+const BRAILLE_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const DOTS_FRAMES: &[&str] = &[".  ", ".. ", "...", " ..", "  .", "   "];
+const LINE_FRAMES: &[&str] = &["-", "\\", "|", "/"];
+const SPINNER_INTERVAL_MS: i64 = 100;
+
+/// Return the frames of the spinner style
+pub fn spinner_frames(style: SpinnerStyle) -> &'static [&'static str] {
+    match style {
+        SpinnerStyle::Braille => BRAILLE_FRAMES,
+        SpinnerStyle::Dots => DOTS_FRAMES,
+        SpinnerStyle::Line => LINE_FRAMES,
+    }
+}
+
+/// Return the current frame of the spinner based on the wall clock, so
+/// every caller renders the same frame at the same time.
+pub fn spinner_frame(frames: &[&'static str]) -> &'static str {
+    let index = chrono::Utc::now().timestamp_millis() / SPINNER_INTERVAL_MS;
+    frames[index as usize % frames.len()]
+}
+
+pub struct Loading<'a> {
+    message: Line<'a>,
+    frames: &'static [&'static str],
+}
+
+impl Default for Loading<'_> {
+    fn default() -> Self {
+        Self {
+            message: Line::default(),
+            frames: BRAILLE_FRAMES,
+        }
+    }
+}
 
 impl<'a> Loading<'a> {
     pub fn new(text: impl Into<Line<'a>>) -> Loading<'a> {
-        Loading(text.into())
+        Loading::default().with_message(text)
+    }
+
+    pub fn with_message(mut self, text: impl Into<Line<'a>>) -> Self {
+        self.message = text.into();
+        self
+    }
+
+    /// Set the frames of the spinner, the spinner is hidden if empty
+    pub fn with_frames(mut self, frames: &'static [&'static str]) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// The current frame of the spinner
+    pub fn frame(&self) -> &'static str {
+        if self.frames.is_empty() {
+            return "";
+        }
+        spinner_frame(self.frames)
     }
 
-    fn value(&self) -> &Line {
-        &self.0
+    fn value(&self) -> Line<'a> {
+        let mut line = self.message.clone();
+        if !self.frames.is_empty() {
+            line.spans
+                .insert(0, Span::raw(format!("{} ", self.frame())));
+        }
+        line
     }
 
     pub fn render(&self, frame: &mut Frame, rect: Rect) {
         frame.render_widget(
-            Paragraph::new(Text::from(vec![self.value().clone()]))
+            Paragraph::new(Text::from(vec![self.value()]))
                 .style(Style {
                     add_modifier: Modifier::ITALIC,
                     ..Default::default()
@@ -0,0 +1,24 @@
+use super::*;
+
+#[test]
+fn test_spinner_frames() {
+    for style in [
+        SpinnerStyle::Braille,
+        SpinnerStyle::Dots,
+        SpinnerStyle::Line,
+    ] {
+        let frames = spinner_frames(style);
+        assert!(!frames.is_empty());
+        assert!(frames.contains(&spinner_frame(frames)));
+    }
+}
+
+#[test]
+fn test_loading_value() {
+    let loading = Loading::new("Thinking...").with_frames(&["*"]);
+    assert_eq!(loading.value().to_string(), "* Thinking...");
+
+    let loading = Loading::new("Thinking...").with_frames(&[]);
+    assert_eq!(loading.frame(), "");
+    assert_eq!(loading.value().to_string(), "Thinking...");
+}
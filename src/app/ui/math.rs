@@ -0,0 +1,494 @@
+#[cfg(test)]
+#[path = "math_test.rs"]
+mod tests;
+
+use std::borrow::Cow;
+
+/// Render the math of a message, `$...$`, `$$...$$`, `\(...\)` and
+/// `\[...\]`, with Unicode symbols, superscripts and subscripts. The
+/// expressions which can't be rendered are left as is, the display ones are
+/// set off in a LaTeX code block. Code is never rendered.
+pub fn render_math(text: &str) -> Cow<'_, str> {
+    if !text.contains(['$', '\\']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut prose = String::new();
+    let mut in_codeblock = false;
+    for line in text.split_inclusive('\n') {
+        if line.trim().starts_with("```") {
+            if !in_codeblock {
+                render_prose(&std::mem::take(&mut prose), &mut out);
+            }
+            in_codeblock = !in_codeblock;
+            out.push_str(line);
+        } else if in_codeblock {
+            out.push_str(line);
+        } else {
+            prose.push_str(line);
+        }
+    }
+    render_prose(&prose, &mut out);
+    Cow::Owned(out)
+}
+
+/// Render the math of a text without code block
+fn render_prose(text: &str, out: &mut String) {
+    let mut rest = text;
+    while let Some(start) = rest.find(['$', '\\', '`']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // The inline code is kept as is, it ends on the same line and a lone
+        // backtick is literal
+        if rest.starts_with('`') {
+            let line = &rest[1..rest.find('\n').unwrap_or(rest.len())];
+            let end = line.find('`').map_or(1, |end| end + 2);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let Some((expr, display, len)) = find_math(rest) else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            out.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        };
+        match convert(expr.trim()) {
+            Some(rendered) => out.push_str(&rendered),
+            None if display => {
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&format!("```latex\n{}\n```", expr.trim()));
+                if !rest[len..].starts_with('\n') {
+                    out.push('\n');
+                }
+            }
+            None => out.push_str(&rest[..len]),
+        }
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+}
+
+/// Find the math starting the text. Returns the expression, whether it is
+/// displayed and the length of the math with its delimiters.
+fn find_math(text: &str) -> Option<(&str, bool, usize)> {
+    for (open, close, display) in [
+        ("$$", "$$", true),
+        ("\\[", "\\]", true),
+        ("\\(", "\\)", false),
+    ] {
+        if let Some(rest) = text.strip_prefix(open) {
+            let end = rest.find(close)?;
+            return Some((&rest[..end], display, open.len() + end + close.len()));
+        }
+    }
+
+    // An inline `$` math is on a single line, doesn't start or end with a
+    // space and isn't followed by a digit, the amounts like `$5 or $10` are
+    // not math
+    let rest = text.strip_prefix('$')?;
+    if rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let end = line.find('$')?;
+    let expr = &line[..end];
+    let after = line[end + 1..].chars().next();
+    if expr.ends_with(char::is_whitespace) || after.is_some_and(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((expr, false, end + 2))
+}
+
+/// Convert the expression to Unicode, `None` if it is too complex
+fn convert(expr: &str) -> Option<String> {
+    let mut parser = Parser {
+        chars: expr.chars().collect(),
+        pos: 0,
+    };
+    let rendered = parser.parse_until(None)?;
+    (!rendered.trim().is_empty()).then_some(rendered)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        self.pos += 1;
+        c
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Parse until the closing brace of a group, or the end of the
+    /// expression if `end` is `None`
+    fn parse_until(&mut self, end: Option<char>) -> Option<String> {
+        let mut out = String::new();
+        loop {
+            match self.next() {
+                None if end.is_some() => return None,
+                None => return Some(out),
+                Some(c) if Some(c) == end => return Some(out),
+                Some('{') => out.push_str(&self.parse_until(Some('}'))?),
+                Some('}') | Some('&') => return None,
+                Some('\\') => out.push_str(&self.command()?),
+                Some('^') => out.push_str(&map_chars(&self.argument()?, superscript)?),
+                Some('_') => out.push_str(&map_chars(&self.argument()?, subscript)?),
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    /// The argument of a command, a group or a single token
+    fn argument(&mut self) -> Option<String> {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        match self.next()? {
+            '{' => self.parse_until(Some('}')),
+            '\\' => self.command(),
+            c => Some(c.to_string()),
+        }
+    }
+
+    /// Convert the command following a backslash
+    fn command(&mut self) -> Option<String> {
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            name.push(c);
+            self.pos += 1;
+        }
+        if name.is_empty() {
+            return match self.next()? {
+                ',' | ';' | ':' | ' ' => Some(" ".to_string()),
+                '!' => Some(String::new()),
+                c @ ('{' | '}' | '%' | '$' | '_' | '&' | '#') => Some(c.to_string()),
+                _ => None,
+            };
+        }
+
+        let rendered = match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.argument()?;
+                let denominator = self.argument()?;
+                match vulgar_fraction(&numerator, &denominator) {
+                    Some(fraction) => fraction.to_string(),
+                    None => format!("{}/{}", atom(&numerator), atom(&denominator)),
+                }
+            }
+            "sqrt" => {
+                let root = if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let index = self.parse_until(Some(']'))?;
+                    match index.trim() {
+                        "3" => '∛',
+                        "4" => '∜',
+                        _ => return None,
+                    }
+                } else {
+                    '√'
+                };
+                format!("{}{}", root, atom(&self.argument()?))
+            }
+            "text" | "textrm" | "mathrm" | "mathit" | "mathbf" | "mathsf" | "operatorname" => {
+                self.argument()?
+            }
+            "mathbb" => map_chars(&self.argument()?, double_struck)?,
+            "hat" | "bar" | "vec" | "dot" | "tilde" => {
+                let arg = self.argument()?;
+                let mut chars = arg.chars();
+                let (Some(c), None) = (chars.next(), chars.next()) else {
+                    return None;
+                };
+                let mark = match name.as_str() {
+                    "hat" => '\u{302}',
+                    "bar" => '\u{304}',
+                    "vec" => '\u{20d7}',
+                    "dot" => '\u{307}',
+                    _ => '\u{303}',
+                };
+                format!("{}{}", c, mark)
+            }
+            "left" | "right" => {
+                // `\left.` has no delimiter
+                if self.peek() == Some('.') {
+                    self.pos += 1;
+                }
+                String::new()
+            }
+            "displaystyle" | "big" | "Big" | "bigl" | "bigr" | "Bigl" | "Bigr" => String::new(),
+            "quad" => "  ".to_string(),
+            "qquad" => "    ".to_string(),
+            name => symbol(name)?.to_string(),
+        };
+        Some(rendered)
+    }
+}
+
+/// Wrap the operand of a fraction or a root in parentheses unless it is a
+/// single term
+fn atom(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().all(char::is_alphanumeric) || text.chars().count() == 1 {
+        text.to_string()
+    } else {
+        format!("({})", text)
+    }
+}
+
+fn map_chars(text: &str, map: fn(char) -> Option<char>) -> Option<String> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(map)
+        .collect()
+}
+
+fn vulgar_fraction(numerator: &str, denominator: &str) -> Option<char> {
+    let fraction = match (numerator.trim(), denominator.trim()) {
+        ("1", "2") => '½',
+        ("1", "3") => '⅓',
+        ("2", "3") => '⅔',
+        ("1", "4") => '¼',
+        ("3", "4") => '¾',
+        ("1", "5") => '⅕',
+        ("1", "8") => '⅛',
+        _ => return None,
+    };
+    Some(fraction)
+}
+
+fn superscript(c: char) -> Option<char> {
+    let sup = match c {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '-' | '−' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'a' => 'ᵃ',
+        'b' => 'ᵇ',
+        'c' => 'ᶜ',
+        'd' => 'ᵈ',
+        'e' => 'ᵉ',
+        'f' => 'ᶠ',
+        'g' => 'ᵍ',
+        'h' => 'ʰ',
+        'i' => 'ⁱ',
+        'j' => 'ʲ',
+        'k' => 'ᵏ',
+        'l' => 'ˡ',
+        'm' => 'ᵐ',
+        'n' => 'ⁿ',
+        'o' => 'ᵒ',
+        'p' => 'ᵖ',
+        'r' => 'ʳ',
+        's' => 'ˢ',
+        't' => 'ᵗ',
+        'u' => 'ᵘ',
+        'v' => 'ᵛ',
+        'w' => 'ʷ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'z' => 'ᶻ',
+        'T' => 'ᵀ',
+        // Already raised
+        '°' | '′' | '″' | '*' => c,
+        '∘' => '°',
+        _ => return None,
+    };
+    Some(sup)
+}
+
+fn subscript(c: char) -> Option<char> {
+    let sub = match c {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '-' | '−' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        _ => return None,
+    };
+    Some(sub)
+}
+
+fn double_struck(c: char) -> Option<char> {
+    let letter = match c {
+        'C' => 'ℂ',
+        'N' => 'ℕ',
+        'P' => 'ℙ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'Z' => 'ℤ',
+        _ => return None,
+    };
+    Some(letter)
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    let symbol = match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" | "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" | "vartheta" => "θ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "rho" | "varrho" => "ρ",
+        "sigma" => "σ",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" | "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "times" => "×",
+        "cdot" => "·",
+        "pm" => "±",
+        "mp" => "∓",
+        "div" => "÷",
+        "ast" => "∗",
+        "star" => "⋆",
+        "circ" => "∘",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "sum" => "∑",
+        "prod" => "∏",
+        "int" => "∫",
+        "iint" => "∬",
+        "oint" => "∮",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "emptyset" | "varnothing" => "∅",
+        "forall" => "∀",
+        "exists" => "∃",
+        "neg" | "lnot" => "¬",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "degree" => "°",
+        "angle" => "∠",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "mid" => "∣",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "prime" => "′",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "aleph" => "ℵ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "sin" => "sin",
+        "cos" => "cos",
+        "tan" => "tan",
+        "log" => "log",
+        "ln" => "ln",
+        "exp" => "exp",
+        "lim" => "lim",
+        "max" => "max",
+        "min" => "min",
+        "det" => "det",
+        "gcd" => "gcd",
+        _ => return None,
+    };
+    Some(symbol)
+}
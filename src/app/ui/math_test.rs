@@ -0,0 +1,59 @@
+use super::*;
+
+#[test]
+fn test_render_math() {
+    let cases = [
+        ("The area is $\\pi r^2$.", "The area is π r².".to_string()),
+        ("$$E = mc^2$$", "E = mc²".to_string()),
+        (
+            "\\(x_{n+1} \\leq \\frac{1}{2} x_n\\)",
+            "xₙ₊₁ ≤ ½ xₙ".to_string(),
+        ),
+        ("\\[\\sqrt{a^2 + b^2}\\]", "√(a² + b²)".to_string()),
+        (
+            "$\\frac{a+b}{c}$ and $\\mathbb{R}^n$",
+            "(a+b)/c and ℝⁿ".to_string(),
+        ),
+        ("$90^\\circ$, $\\vec{v}$", "90°, v\u{20d7}".to_string()),
+        ("$\\left( \\alpha \\right)$", "( α )".to_string()),
+        ("$\\sum_{i=1}^{n} x_i$", "∑ᵢ₌₁ⁿ xᵢ".to_string()),
+        // Not math
+        ("It costs $5 or $10", "It costs $5 or $10".to_string()),
+        ("From $5-$10", "From $5-$10".to_string()),
+        ("A $ sign and $x $", "A $ sign and $x $".to_string()),
+        ("C:\\path\\to", "C:\\path\\to".to_string()),
+        ("Hello", "Hello".to_string()),
+        // Too complex, left as is
+        ("$x_{\\alpha}$", "$x_{\\alpha}$".to_string()),
+        (
+            "Matrix:\n$$\\begin{pmatrix} a & b \\end{pmatrix}$$\nDone",
+            "Matrix:\n```latex\n\\begin{pmatrix} a & b \\end{pmatrix}\n```\nDone".to_string(),
+        ),
+        (
+            "Sum $$\\sum_{k} \\binom{n}{k}$$ here",
+            "Sum \n```latex\n\\sum_{k} \\binom{n}{k}\n```\n here".to_string(),
+        ),
+    ];
+    for (text, want) in cases {
+        assert_eq!(render_math(text), want, "text: {}", text);
+    }
+}
+
+#[test]
+fn test_render_math_skips_code() {
+    let text = "`$x^2$` is $x^2$\n```python\nprint(\"$a^2$\")\n```\n$a^2$";
+    assert_eq!(
+        render_math(text),
+        "`$x^2$` is x²\n```python\nprint(\"$a^2$\")\n```\na²"
+    );
+}
+
+#[test]
+fn test_render_math_lone_backtick() {
+    // A lone backtick doesn't turn the rest into code
+    assert_eq!(render_math("Press ` then $x^2$"), "Press ` then x²");
+    assert_eq!(
+        render_math("Run `ls\nthen $x^2$ and `$y^2$`"),
+        "Run `ls\nthen x² and `$y^2$`"
+    );
+}
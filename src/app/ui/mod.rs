@@ -5,10 +5,12 @@ pub mod help;
 pub mod history;
 pub mod input_box;
 pub mod loading;
+pub mod math;
 pub mod models;
 pub mod notice;
 pub mod question;
 pub mod scroll;
+pub mod search;
 pub mod selection;
 pub mod syntaxes;
 pub mod textarea;
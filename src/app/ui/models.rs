@@ -1,9 +1,14 @@
+#[cfg(test)]
+#[path = "models_test.rs"]
+mod tests;
+
 use std::collections::{BTreeMap, HashMap};
 
 use crate::{
-    config::{self},
+    config::{self, BackendConfig},
     info_event,
-    models::{Event, Model},
+    models::{Action, Event, Model},
+    warn_event,
 };
 use ratatui::{
     Frame,
@@ -22,14 +27,112 @@ use super::{
     utils,
 };
 
+/// The model selected at startup: `backend.default_model`, then the default
+/// model of each enabled connection in order. `None` if none of them is
+/// served, the default models not served are returned as warnings.
+pub fn resolve_default_model<'m>(
+    models: &'m [Model],
+    backend: &BackendConfig,
+) -> (Option<&'m Model>, Vec<String>) {
+    let mut warnings = vec![];
+    let connections = backend
+        .connections
+        .iter()
+        .filter(|conn| conn.enabled())
+        .collect::<Vec<_>>();
+
+    if let Some(want) = backend.default_model.as_deref().filter(|m| !m.is_empty()) {
+        let mut served = models.iter().filter(|model| model.id() == want);
+        if let Some(first) = served.next() {
+            // Prefer the connection which has the model as its default
+            let preferred = std::iter::once(first).chain(served).find(|model| {
+                connections.iter().any(|conn| {
+                    conn.name() == model.provider() && conn.default_model() == Some(want)
+                })
+            });
+            return (Some(preferred.unwrap_or(first)), warnings);
+        }
+        warnings.push(format!(
+            "Model \"{}\" not found, using the default model",
+            want
+        ));
+    }
+
+    for conn in connections {
+        let Some(want) = conn.default_model() else {
+            continue;
+        };
+        match models
+            .iter()
+            .find(|model| model.id() == want && model.provider() == conn.name())
+        {
+            Some(model) => return (Some(model), warnings),
+            None => warnings.push(format!(
+                "Model \"{}\" of connection \"{}\" not found",
+                want,
+                conn.name()
+            )),
+        }
+    }
+    (None, warnings)
+}
+
+/// The enabled connections serving any of the models, in the config order
+pub fn backend_order<'c>(models: &[Model], backend: &'c BackendConfig) -> Vec<&'c str> {
+    backend
+        .connections
+        .iter()
+        .filter(|conn| conn.enabled())
+        .map(|conn| conn.name())
+        .filter(|name| models.iter().any(|model| model.provider() == *name))
+        .collect()
+}
+
+/// The model to use on the connection: the model of the same id if served,
+/// then the default model of the connection, then its first model
+pub fn backend_model<'m>(
+    models: &'m [Model],
+    backend: &BackendConfig,
+    provider: &str,
+    current: &str,
+) -> Option<&'m Model> {
+    let served = || models.iter().filter(|model| model.provider() == provider);
+    let default_model = backend
+        .connections
+        .iter()
+        .find(|conn| conn.name() == provider)
+        .and_then(|conn| conn.default_model());
+
+    served()
+        .find(|model| model.id() == current)
+        .or_else(|| served().find(|model| Some(model.id()) == default_model))
+        .or_else(|| served().next())
+}
+
+/// The model on the connection next to the one of the current model, back to
+/// the first connection after the last one
+pub fn next_backend_model<'m>(
+    models: &'m [Model],
+    backend: &BackendConfig,
+    current: &Model,
+) -> Option<&'m Model> {
+    let order = backend_order(models, backend);
+    let next = match order.iter().position(|name| *name == current.provider()) {
+        Some(idx) => order[(idx + 1) % order.len()],
+        None => *order.first()?,
+    };
+    backend_model(models, backend, next, current.id())
+}
+
 pub struct ModelsScreen<'a> {
+    action_tx: mpsc::UnboundedSender<Action>,
     event_tx: mpsc::UnboundedSender<Event>,
 
     showing: bool,
     models: Vec<Model>,
-    idx_map: HashMap<usize, String>,
+    idx_map: HashMap<usize, Model>,
 
-    current_model: String,
+    current_model: Model,
     state: ListState,
     items: Vec<ListItem<'a>>,
 
@@ -40,21 +143,19 @@ pub struct ModelsScreen<'a> {
 }
 
 impl<'a> ModelsScreen<'a> {
-    pub fn new(models: Vec<Model>, event_tx: mpsc::UnboundedSender<Event>) -> ModelsScreen<'a> {
-        let want_model = config::instance()
-            .backend
-            .default_model
-            .as_deref()
-            .unwrap_or_default();
-
-        let default_model = models
-            .iter()
-            .find(|model| want_model == model.id())
-            .unwrap_or_else(|| &models[0])
-            .id()
-            .to_string();
+    pub fn new(
+        models: Vec<Model>,
+        action_tx: mpsc::UnboundedSender<Action>,
+        event_tx: mpsc::UnboundedSender<Event>,
+    ) -> ModelsScreen<'a> {
+        let (default_model, warnings) = resolve_default_model(&models, &config::instance().backend);
+        let default_model = default_model.unwrap_or_else(|| &models[0]).clone();
+        for warning in warnings {
+            let _ = event_tx.send(warn_event!(warning));
+        }
 
         ModelsScreen {
+            action_tx,
             event_tx,
             showing: false,
             models,
@@ -69,20 +170,82 @@ impl<'a> ModelsScreen<'a> {
         }
     }
 
-    pub fn current_model(&self) -> &str {
+    /// The current model with its metadata
+    pub fn current(&self) -> &Model {
         &self.current_model
     }
 
-    pub fn set_current_model(&mut self, model: &str) {
+    pub fn current_model(&self) -> &str {
+        self.current_model.id()
+    }
+
+    /// The connection serving the current model, the same model id may be
+    /// served by several connections
+    pub fn current_provider(&self) -> &str {
+        self.current_model.provider()
+    }
+
+    /// Find the model by its id, the connection of the current model is
+    /// preferred if several connections serve it
+    pub fn find_model(&self, id: &str) -> Option<Model> {
+        let mut models = self.models.iter().filter(|model| model.id() == id);
+        let first = models.next()?;
+        let preferred = std::iter::once(first)
+            .chain(models)
+            .find(|model| model.provider() == self.current_model.provider());
+        Some(preferred.unwrap_or(first).clone())
+    }
+
+    pub fn set_current_model(&mut self, model: Model) {
         if self.current_model == model {
             return;
         }
-        self.current_model = model.to_string();
 
         let _ = self
             .event_tx
             .send(info_event!(format!("Model changed to \"{}\"", model)));
+        self.current_model = model;
+
+        self.build_items();
+        self.set_cursor_to_selected();
+    }
+
+    /// Use the model on the connection of the previous session, the default
+    /// model is kept if the connection no longer serves any model
+    pub fn with_backend(mut self, provider: Option<&str>) -> Self {
+        let Some(provider) = provider.filter(|p| *p != self.current_model.provider()) else {
+            return self;
+        };
+        let backend = &config::instance().backend;
+        if let Some(model) = backend_model(&self.models, backend, provider, self.current_model.id())
+        {
+            self.current_model = model.clone();
+        }
+        self
+    }
 
+    /// Switch to the model on the next connection, return false if no other
+    /// connection serves any model
+    pub fn cycle_backend(&mut self) -> bool {
+        let backend = &config::instance().backend;
+        let next = next_backend_model(&self.models, backend, &self.current_model).cloned();
+        match next {
+            Some(model) if model.provider() != self.current_model.provider() => {
+                self.set_current_model(model);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the models with the refreshed ones. The current model is
+    /// kept even if it is no longer listed.
+    pub fn set_models(&mut self, models: Vec<Model>) {
+        if let Some(model) = models.iter().find(|model| **model == self.current_model) {
+            // The metadata may have changed
+            self.current_model = model.clone();
+        }
+        self.models = models;
         self.build_items();
         self.set_cursor_to_selected();
     }
@@ -154,8 +317,8 @@ impl<'a> ModelsScreen<'a> {
             return false;
         }
 
-        let model = model.to_string();
-        self.set_current_model(&model);
+        let model = model.clone();
+        self.set_current_model(model);
 
         true
     }
@@ -183,6 +346,8 @@ impl<'a> ModelsScreen<'a> {
             span!(" to close, ").white(),
             span!("Enter").green().bold(),
             span!(" to select, ").white(),
+            span!("r").green().bold(),
+            span!(" to refresh, ").white(),
             span!("/").green().bold(),
             span!(" to search ").white(),
         ];
@@ -246,6 +411,10 @@ impl<'a> ModelsScreen<'a> {
                 Key::Char('g') => self.first(),
                 Key::Char('G') => self.last(),
                 Key::Char('/') => self.search.open(&self.current_search),
+                Key::Char('r') => {
+                    let _ = self.event_tx.send(info_event!("Refreshing the models..."));
+                    let _ = self.action_tx.send(Action::RefreshModels);
+                }
                 Key::Char('q') => {
                     self.showing = false;
                 }
@@ -280,7 +449,7 @@ impl<'a> ModelsScreen<'a> {
         self.idx_map.clear();
         self.items.clear();
 
-        let mut models: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut models: BTreeMap<String, Vec<Model>> = BTreeMap::new();
 
         self.models
             .iter()
@@ -288,26 +457,30 @@ impl<'a> ModelsScreen<'a> {
                 if self.current_search.is_empty() {
                     return true;
                 }
-                model
-                    .id()
-                    .to_lowercase()
-                    .contains(&self.current_search.to_lowercase())
+                let search = self.current_search.to_lowercase();
+                model.id().to_lowercase().contains(&search)
+                    || model.provider().to_lowercase().contains(&search)
             })
             .for_each(|m| {
-                let model = m.id().to_string();
                 let alias = m.provider().to_string();
-                models.entry(alias).or_default().push(model);
+                models.entry(alias).or_default().push(m.clone());
             });
 
         for (provider, models) in models {
             self.items.push(header_item(provider));
 
             for model in models {
-                let mut spans = vec![span!(model)];
+                let mut spans = vec![span!(model.id())];
                 if self.current_model == model {
                     spans.push(Span::styled(" ", Style::default()));
                     spans.push(Span::styled("[*]", Style::default().fg(Color::LightRed)))
                 }
+                if let Some(metadata) = format_metadata(&model) {
+                    spans.push(Span::styled(
+                        format!("  {}", metadata),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
 
                 let lines = utils::split_to_lines(spans, self.last_known_width - 2);
                 self.items.push(ListItem::new(Text::from(lines)));
@@ -317,6 +490,25 @@ impl<'a> ModelsScreen<'a> {
     }
 }
 
+/// Format the context window and the pricing of the model, e.g.
+/// `128k ctx · $2.50/$10.00 per 1M`
+fn format_metadata(model: &Model) -> Option<String> {
+    let mut parts = vec![];
+    if let Some(context_window) = model.context_window() {
+        parts.push(format!("{} ctx", utils::format_tokens(context_window)));
+    }
+    if let Some(pricing) = model.pricing() {
+        parts.push(format!(
+            "${:.2}/${:.2} per 1M",
+            pricing.input, pricing.output
+        ));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join(" · "))
+}
+
 fn header_item<'a>(value: String) -> ListItem<'a> {
     ListItem::new(Text::from(value).alignment(Alignment::Center).bold())
         .style(
@@ -0,0 +1,133 @@
+use super::*;
+
+fn backend_config(toml: &str) -> BackendConfig {
+    toml::from_str(toml).expect("Failed to parse backend config")
+}
+
+#[test]
+fn test_resolve_default_model() {
+    let models = vec![
+        Model::new("gpt-4o").with_provider("openai"),
+        Model::new("gpt-4o").with_provider("azure"),
+        Model::new("gpt-4o-mini").with_provider("azure"),
+        Model::new("gemini-2.0-flash").with_provider("gemini"),
+    ];
+    let connections = r#"
+        [[connections]]
+        enabled = true
+        kind = "openai"
+        alias = "openai"
+        default_model = "o3"
+
+        [[connections]]
+        enabled = true
+        kind = "openai"
+        alias = "azure"
+        default_model = "gpt-4o"
+
+        [[connections]]
+        enabled = false
+        kind = "gemini"
+        alias = "gemini"
+        default_model = "gemini-2.0-flash"
+    "#;
+
+    // The global default is served by the connection having it as default
+    let config = backend_config(&format!("default_model = \"gpt-4o\"\n{}", connections));
+    let (model, warnings) = resolve_default_model(&models, &config);
+    assert_eq!(model, Some(&models[1]));
+    assert!(warnings.is_empty());
+
+    // The default of the first connection serving its default model
+    let config = backend_config(&format!("default_model = \"unknown\"\n{}", connections));
+    let (model, warnings) = resolve_default_model(&models, &config);
+    assert_eq!(model, Some(&models[1]));
+    assert_eq!(
+        warnings,
+        vec![
+            "Model \"unknown\" not found, using the default model",
+            "Model \"o3\" of connection \"openai\" not found",
+        ]
+    );
+
+    // The disabled connections are skipped
+    let config = backend_config(
+        r#"
+        [[connections]]
+        enabled = false
+        kind = "gemini"
+        alias = "gemini"
+        default_model = "gemini-2.0-flash"
+        "#,
+    );
+    let (model, warnings) = resolve_default_model(&models, &config);
+    assert_eq!(model, None);
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn test_next_backend_model() {
+    let models = vec![
+        Model::new("gpt-4o").with_provider("openai"),
+        Model::new("o3").with_provider("openai"),
+        Model::new("gpt-4o").with_provider("azure"),
+        Model::new("gpt-4o-mini").with_provider("azure"),
+        Model::new("gemini-2.0-flash").with_provider("gemini"),
+        Model::new("gemini-2.5-pro").with_provider("gemini"),
+        Model::new("claude").with_provider("bedrock"),
+    ];
+    let config = backend_config(
+        r#"
+        [[connections]]
+        enabled = true
+        kind = "openai"
+        alias = "openai"
+
+        [[connections]]
+        enabled = true
+        kind = "openai"
+        alias = "azure"
+        default_model = "gpt-4o-mini"
+
+        [[connections]]
+        enabled = false
+        kind = "bedrock"
+        alias = "bedrock"
+
+        [[connections]]
+        enabled = true
+        kind = "gemini"
+        alias = "gemini"
+        default_model = "gemini-2.5-pro"
+
+        [[connections]]
+        enabled = true
+        kind = "openai"
+        alias = "offline"
+        "#,
+    );
+    assert_eq!(
+        backend_order(&models, &config),
+        vec!["openai", "azure", "gemini"]
+    );
+
+    // The same model is kept if served by the next connection
+    let next = next_backend_model(&models, &config, &models[0]);
+    assert_eq!(next, Some(&models[2]));
+
+    // Then the default model of the connection
+    let next = next_backend_model(&models, &config, &models[1]);
+    assert_eq!(next, Some(&models[3]));
+    let next = next_backend_model(&models, &config, &models[3]);
+    assert_eq!(next, Some(&models[5]));
+
+    // Then its first model, back to the first connection after the last one
+    let next = next_backend_model(&models, &config, &models[5]);
+    assert_eq!(next, Some(&models[0]));
+
+    // The first connection if the current one is not listed
+    let next = next_backend_model(&models, &config, &models[6]);
+    assert_eq!(next, Some(&models[0]));
+
+    assert_eq!(next_backend_model(&[], &config, &models[0]), None);
+}
@@ -1,11 +1,18 @@
+#[cfg(test)]
+#[path = "notice_test.rs"]
+mod tests;
+
 use std::time::{self, Duration};
 
 use super::utils;
+use crate::config::color_theme::NoticeTheme;
+use crate::config::{self, NoticePosition};
 use crate::models::{NoticeKind, NoticeMessage};
+use crate::t;
 use ratatui::{
     Frame,
     layout::Rect,
-    style::{Color, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
     widgets::{List, ListItem},
 };
@@ -20,6 +27,9 @@ struct MessageWrapper {
 pub struct Notice {
     notices: Vec<MessageWrapper>,
     display_duration: time::Duration,
+    colors: NoticeTheme,
+    position: NoticePosition,
+    max_stacked: Option<usize>,
 }
 
 impl Notice {
@@ -30,6 +40,21 @@ impl Notice {
         }
     }
 
+    pub fn with_colors(mut self, colors: NoticeTheme) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn with_position(mut self, position: NoticePosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_max_stacked(mut self, max_stacked: Option<usize>) -> Self {
+        self.max_stacked = max_stacked;
+        self
+    }
+
     pub fn add_message(&mut self, msg: NoticeMessage) {
         let now = chrono::Utc::now();
         self.notices.push(MessageWrapper {
@@ -38,6 +63,13 @@ impl Notice {
         });
     }
 
+    /// Show the notice, replacing the same one if it is shown, e.g. for the
+    /// conversations deleted at once
+    pub fn replace_message(&mut self, msg: NoticeMessage) {
+        self.notices.retain(|n| n.value.message() != msg.message());
+        self.add_message(msg);
+    }
+
     pub fn info(&mut self, msg: impl Into<String>) {
         self.add_message(NoticeMessage::info(msg))
     }
@@ -54,6 +86,8 @@ impl Notice {
         self.notices.clear();
     }
 
+    /// Drop the expired notices, each one expires on its own duration
+    /// whether it is shown or collapsed
     fn sync(&mut self) {
         let now = chrono::Utc::now();
         self.notices.retain(|msg| {
@@ -76,9 +110,25 @@ impl Notice {
         let max_width = area.width as usize - 2;
         let max_height = area.height as usize - 2;
 
-        let items = build_list_items(&self.notices, max_width, max_height);
-        let list = List::new(items);
-        f.render_widget(list, area);
+        let items = build_list_items(
+            &self.notices,
+            &self.colors,
+            self.position,
+            self.max_stacked,
+            max_width,
+            max_height,
+        );
+        let height = items.iter().map(|item| item.height() as u16).sum();
+        let area = if self.position.is_bottom() {
+            Rect {
+                y: area.bottom().saturating_sub(height),
+                height,
+                ..area
+            }
+        } else {
+            Rect { height, ..area }
+        };
+        f.render_widget(List::new(items), area);
     }
 }
 
@@ -87,33 +137,86 @@ impl Default for Notice {
         Self {
             notices: vec![],
             display_duration: Duration::from_secs(3),
+            colors: NoticeTheme::default(),
+            position: NoticePosition::default(),
+            max_stacked: None,
         }
     }
 }
 
+/// Build the notices from the oldest to the newest. The latest ones fitting
+/// in the height, at most `max_stacked`, are shown and the others collapsed
+/// into a "+N more" line at the far side from the window edge.
 fn build_list_items<'a>(
     notices: &[MessageWrapper],
+    colors: &NoticeTheme,
+    position: NoticePosition,
+    max_stacked: Option<usize>,
     max_width: usize,
     max_height: usize,
 ) -> Vec<ListItem<'a>> {
+    let max_stacked = max_stacked.unwrap_or(usize::MAX);
     let mut items = vec![];
     let mut current_height = 0;
 
-    for item in notices {
-        let lines = build_bubble(item.value.message(), max_width, item.value.kind());
+    for (i, item) in notices.iter().rev().enumerate() {
+        let (border_color, text_color) = notice_colors(colors, item.value.kind());
+        let lines = build_bubble(item.value.message(), max_width, border_color, text_color);
 
-        current_height += lines.len();
-        if current_height > max_height {
+        // Keep a line for the collapsed notices if any left
+        let more_height = usize::from(i + 1 < notices.len());
+        if items.len() >= max_stacked || current_height + lines.len() + more_height > max_height {
             break;
         }
+        current_height += lines.len();
+        // The kind is told apart by the color, or by bold text without colors
+        let style = match item.value.kind() {
+            NoticeKind::Warning | NoticeKind::Error if config::instance().theme.no_color() => {
+                Style::default().add_modifier(Modifier::BOLD)
+            }
+            _ => Style::default(),
+        };
+        items.push(ListItem::new(lines).style(style));
+    }
+    items.reverse();
 
-        let item = ListItem::new(lines).style(Style::default());
-        items.push(item);
+    let hidden = notices.len() - items.len();
+    if hidden > 0 {
+        let more = Line::from(t!("notice.more", count = hidden)).dim();
+        let more = if position.is_left() {
+            more.left_aligned()
+        } else {
+            more.right_aligned()
+        };
+        if position.is_bottom() {
+            items.insert(0, ListItem::new(more));
+        } else {
+            items.push(ListItem::new(more));
+        }
     }
     items
 }
 
-fn build_bubble<'a>(message: &str, max_width: usize, notice_type: &NoticeKind) -> Vec<Line<'a>> {
+/// Return the border and text colors of the notice kind, the colors which
+/// are not set in the theme fall back to the default ones
+fn notice_colors(colors: &NoticeTheme, kind: &NoticeKind) -> (Color, Color) {
+    let colors = match kind {
+        NoticeKind::Info => colors.info,
+        NoticeKind::Warning => colors.warning,
+        NoticeKind::Error => colors.error,
+    };
+    (
+        colors.border.unwrap_or_else(|| kind.border_color()),
+        colors.text.unwrap_or_else(|| kind.text_color()),
+    )
+}
+
+fn build_bubble<'a>(
+    message: &str,
+    max_width: usize,
+    border_color: Color,
+    text_color: Color,
+) -> Vec<Line<'a>> {
     // build lines from message based on max_width
     let mut lines = vec![];
 
@@ -131,16 +234,15 @@ fn build_bubble<'a>(message: &str, max_width: usize, notice_type: &NoticeKind) -
         lines.push(line.trim().to_string());
     }
 
-    wrap_bubble(lines, max_width, notice_type)
+    wrap_bubble(lines, max_width, border_color, text_color)
 }
 
 fn wrap_bubble<'a>(
     lines: Vec<String>,
     max_width: usize,
-    notice_type: &NoticeKind,
+    border_color: Color,
+    text_color: Color,
 ) -> Vec<Line<'a>> {
-    let border_color = notice_type.border_color();
-    let text_color = notice_type.text_color();
     let top_bar = highlight_line(
         format!("╭{}╮", ["─"].repeat(max_width).join("")),
         border_color,
@@ -0,0 +1,93 @@
+use super::*;
+
+fn wrap(msg: NoticeMessage) -> MessageWrapper {
+    MessageWrapper {
+        value: msg,
+        created_at: chrono::Utc::now(),
+    }
+}
+
+#[test]
+fn test_build_list_items() {
+    let notices = vec![
+        wrap(NoticeMessage::info("first")),
+        wrap(NoticeMessage::info("second")),
+        wrap(NoticeMessage::info("third")),
+    ];
+    let colors = NoticeTheme::default();
+
+    // Every notice fits
+    let items = build_list_items(&notices, &colors, NoticePosition::TopRight, None, 20, 20);
+    assert_eq!(items.len(), 3);
+
+    // The latest ones are shown, the others collapsed below
+    let items = build_list_items(&notices, &colors, NoticePosition::TopRight, Some(2), 20, 20);
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0].height(), 3);
+    assert_eq!(items[2].height(), 1);
+    assert_eq!(
+        items[2],
+        ListItem::new(Line::from("+1 more").dim().right_aligned())
+    );
+
+    // The collapsed line is above at the bottom
+    let items = build_list_items(
+        &notices,
+        &colors,
+        NoticePosition::BottomLeft,
+        Some(1),
+        20,
+        20,
+    );
+    assert_eq!(items.len(), 2);
+    assert_eq!(
+        items[0],
+        ListItem::new(Line::from("+2 more").dim().left_aligned())
+    );
+
+    // Limited by the height, keeping a line for the collapsed ones
+    let items = build_list_items(&notices, &colors, NoticePosition::TopRight, None, 20, 6);
+    assert_eq!(items.len(), 2);
+    assert_eq!(
+        items[1],
+        ListItem::new(Line::from("+2 more").dim().right_aligned())
+    );
+}
+
+#[test]
+fn test_sync_expires_independently() {
+    let mut notice = Notice::new(Duration::from_secs(3));
+    notice.add_message(NoticeMessage::info("default"));
+    notice.add_message(NoticeMessage::info("long").with_duration(Duration::from_secs(10)));
+    notice.add_message(NoticeMessage::info("short").with_duration(Duration::from_secs(1)));
+
+    let elapsed = chrono::Duration::seconds(2);
+    notice
+        .notices
+        .iter_mut()
+        .for_each(|n| n.created_at -= elapsed);
+    notice.sync();
+    let messages: Vec<_> = notice.notices.iter().map(|n| n.value.message()).collect();
+    assert_eq!(messages, vec!["default", "long"]);
+
+    notice
+        .notices
+        .iter_mut()
+        .for_each(|n| n.created_at -= elapsed);
+    notice.sync();
+    let messages: Vec<_> = notice.notices.iter().map(|n| n.value.message()).collect();
+    assert_eq!(messages, vec!["long"]);
+}
+
+#[test]
+fn test_replace_message() {
+    let mut notice = Notice::new(Duration::from_secs(3));
+    notice.add_message(NoticeMessage::info("saved"));
+    notice.add_message(NoticeMessage::info("saved"));
+    notice.add_message(NoticeMessage::info("deleted"));
+    notice.replace_message(NoticeMessage::info("deleted"));
+
+    // Only the replaced notice is shown once
+    let messages: Vec<_> = notice.notices.iter().map(|n| n.value.message()).collect();
+    assert_eq!(messages, vec!["saved", "saved", "deleted"]);
+}
@@ -1,3 +1,7 @@
+#[cfg(test)]
+#[path = "scroll_test.rs"]
+mod tests;
+
 use ratatui::widgets::ScrollbarState;
 
 #[derive(Debug, Default)]
@@ -6,6 +10,8 @@ pub struct Scroll {
     viewport_len: usize,
     pub position: usize,
     pub scrollbar_state: ScrollbarState,
+    // Content was added below the viewport while the position was kept
+    new_content: bool,
 }
 
 impl Scroll {
@@ -29,6 +35,9 @@ impl Scroll {
             .saturating_add(1)
             .clamp(0, clamp.saturating_sub(1));
         self.scrollbar_state.next();
+        if self.is_position_at_last() {
+            self.new_content = false;
+        }
     }
 
     pub fn page_down(&mut self) {
@@ -50,6 +59,29 @@ impl Scroll {
     pub fn last(&mut self) {
         self.position = self.get_position_as_if_last();
         self.scrollbar_state.last();
+        self.new_content = false;
+    }
+
+    /// Whether there is new content below the viewport, which was not
+    /// followed because the position was not at the bottom
+    pub fn has_new_content(&self) -> bool {
+        self.new_content
+    }
+
+    pub fn set_new_content(&mut self) {
+        self.new_content = true;
+    }
+
+    /// Move the position so the given line is at the center of the viewport
+    pub fn center_on(&mut self, line: usize) {
+        let position = line
+            .saturating_sub(self.viewport_len / 2)
+            .min(self.get_position_as_if_last());
+        self.position = position;
+        self.scrollbar_state = self.scrollbar_state.position(position);
+        if self.is_position_at_last() {
+            self.new_content = false;
+        }
     }
 
     pub fn set_state(&mut self, list_len: usize, viewport_len: usize) {
@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn test_new_content() {
+    let mut scroll = Scroll::default();
+    scroll.set_state(30, 10);
+    scroll.last();
+    assert_eq!(scroll.position, 20);
+
+    scroll.up();
+    scroll.set_state(35, 10);
+    scroll.set_new_content();
+    assert!(scroll.has_new_content());
+
+    // Still above the bottom
+    scroll.down();
+    assert!(scroll.has_new_content());
+
+    // Reaching the bottom clears the indicator
+    (0..5).for_each(|_| scroll.down());
+    assert_eq!(scroll.position, 25);
+    assert!(!scroll.has_new_content());
+
+    scroll.set_new_content();
+    scroll.last();
+    assert!(!scroll.has_new_content());
+}
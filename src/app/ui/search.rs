@@ -0,0 +1,194 @@
+#[cfg(test)]
+#[path = "search_test.rs"]
+mod tests;
+
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Stylize},
+    text::{Line, Span},
+};
+use ratatui_macros::span;
+
+use crate::models::{Event, Message};
+
+use super::{Selectable, input_box::InputBox, utils};
+
+/// Search for a term within the current conversation. The query is entered
+/// in an [`InputBox`] and matches are cycled message by message.
+pub struct ConversationSearch<'a> {
+    input: InputBox<'a>,
+    query: String,
+    matches: Vec<usize>,
+    current: usize,
+}
+
+impl ConversationSearch<'_> {
+    pub fn open(&mut self) {
+        self.input.open(self.query.clone());
+    }
+
+    pub fn showing(&self) -> bool {
+        self.input.showing()
+    }
+
+    /// Whether a query is submitted and the matches are navigable
+    pub fn active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        if self.query.is_empty() {
+            return None;
+        }
+        Some(&self.query)
+    }
+
+    pub fn clear(&mut self) {
+        self.input.close();
+        self.query.clear();
+        self.matches.clear();
+        self.current = 0;
+    }
+
+    /// Handle events while the query input is showing. Returns true when the
+    /// query is submitted.
+    pub fn handle_input_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::KeyboardEsc | Event::KeyboardCtrlC => self.clear(),
+            Event::KeyboardEnter => {
+                self.query = self.input.close().unwrap_or_default().trim().to_string();
+                return true;
+            }
+            _ => self.input.handle_key_event(event),
+        }
+        false
+    }
+
+    /// Find the messages matching the current query and reset the cursor to
+    /// the last (most recent) match.
+    pub fn set_messages(&mut self, messages: &[Message]) {
+        self.matches = find_matches(messages, &self.query);
+        self.current = self.matches.len().saturating_sub(1);
+    }
+
+    /// Return the index of the message of the current match
+    pub fn current_match(&self) -> Option<usize> {
+        self.matches.get(self.current).copied()
+    }
+
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+        }
+    }
+
+    pub fn prev(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect) {
+        self.input.render(f, area);
+    }
+
+    pub fn status_line(&self) -> Line<'_> {
+        let position = if self.matches.is_empty() {
+            span!("no matches").red()
+        } else {
+            span!(format!("{}/{}", self.current + 1, self.matches.len())).white()
+        };
+        Line::from(vec![
+            span!("Search ").gray(),
+            span!(format!("\"{}\"", self.query)).yellow().bold(),
+            span!(" ("),
+            position,
+            span!(") | "),
+            span!("n/N").light_green().bold(),
+            span!(" next/prev (empty input) | ").white(),
+            span!("Esc").light_green().bold(),
+            span!(" to exit search").white(),
+        ])
+    }
+}
+
+impl Default for ConversationSearch<'_> {
+    fn default() -> Self {
+        Self {
+            input: InputBox::default()
+                .with_title(" Search in Conversation ")
+                .with_placeholder("Type the search term..."),
+            query: String::new(),
+            matches: vec![],
+            current: 0,
+        }
+    }
+}
+
+/// Return the indices of the messages containing the query, case-insensitive.
+pub fn find_matches(messages: &[Message], query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return vec![];
+    }
+    let query = query.to_lowercase();
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, msg)| msg.text().to_lowercase().contains(&query))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Highlight the occurrences of the query in the selectable spans of the
+/// line, case-insensitive. Occurrences spanning across spans are ignored.
+pub fn highlight_matches<'a>(line: Line<'a>, query: &str) -> Line<'a> {
+    if query.is_empty() || !line.is_selectable() {
+        return line;
+    }
+
+    let query = query.to_lowercase();
+    let style = line.style;
+    let alignment = line.alignment;
+    let mut spans = vec![];
+    for span in line.spans {
+        let content = span.content.to_string();
+        let lower = content.to_lowercase();
+        // Lowercasing may change the byte length of some characters, in that
+        // case the indices can't be mapped back to the original content
+        if !span.is_selectable() || lower.len() != content.len() || !lower.contains(&query) {
+            spans.push(span);
+            continue;
+        }
+
+        let mut start = 0;
+        for (idx, matched) in lower.match_indices(&query) {
+            if idx > start {
+                spans.push(Span::styled(content[start..idx].to_string(), span.style));
+            }
+            let end = idx + matched.len();
+            spans.push(Span::styled(
+                content[idx..end].to_string(),
+                utils::highlight_style(
+                    span.style
+                        .fg(Color::Black)
+                        .bg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ));
+            start = end;
+        }
+        if start < content.len() {
+            spans.push(Span::styled(content[start..].to_string(), span.style));
+        }
+    }
+
+    let mut line = Line::from(spans).style(style);
+    line.alignment = alignment;
+    line
+}
+
+pub fn line_contains(line: &Line, query: &str) -> bool {
+    let content: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+    content.to_lowercase().contains(&query.to_lowercase())
+}
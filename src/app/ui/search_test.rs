@@ -0,0 +1,71 @@
+use ratatui::{style::Style, text::Line};
+use ratatui_macros::span;
+
+use crate::models::Message;
+
+use super::*;
+
+#[test]
+fn test_find_matches() {
+    let messages = vec![
+        Message::new_system("system", "Hello, how can I help you?"),
+        Message::new_user("user", "Tell me about RUST"),
+        Message::new_system("system", "Rust is a systems programming language"),
+        Message::new_user("user", "Thanks"),
+    ];
+
+    assert_eq!(find_matches(&messages, "rust"), vec![1, 2]);
+    assert_eq!(find_matches(&messages, "HELLO"), vec![0]);
+    assert!(find_matches(&messages, "python").is_empty());
+    assert!(find_matches(&messages, "").is_empty());
+}
+
+#[test]
+fn test_highlight_matches() {
+    let matched = Style::default()
+        .fg(Color::Black)
+        .bg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+
+    let line = Line::from(vec![
+        span!("  ").unselectable(),
+        span!("Rust is fun, rust is fast"),
+    ]);
+    let expected = Line::from(vec![
+        span!("  ").unselectable(),
+        span!(matched; "Rust"),
+        span!(" is fun, "),
+        span!(matched; "rust"),
+        span!(" is fast"),
+    ]);
+    assert_eq!(highlight_matches(line, "rust"), expected);
+
+    let line = Line::from(vec![span!("nothing here")]);
+    assert_eq!(highlight_matches(line.clone(), "rust"), line);
+}
+
+#[test]
+fn test_search_navigation() {
+    let messages = vec![
+        Message::new_user("user", "foo"),
+        Message::new_system("system", "bar"),
+        Message::new_user("user", "foo bar"),
+    ];
+
+    let mut search = ConversationSearch {
+        query: "foo".to_string(),
+        ..Default::default()
+    };
+    search.set_messages(&messages);
+    assert_eq!(search.current_match(), Some(2));
+
+    search.next();
+    assert_eq!(search.current_match(), Some(0));
+
+    search.prev();
+    assert_eq!(search.current_match(), Some(2));
+
+    search.clear();
+    assert!(!search.active());
+    assert_eq!(search.current_match(), None);
+}
@@ -4,7 +4,10 @@ mod tests;
 
 use std::cmp::Ordering;
 
-use ratatui::text::{Line, Span};
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
 use ratatui_macros::span;
 use unicode_width::UnicodeWidthStr;
 
@@ -16,13 +19,35 @@ pub struct Index {
     pub col: usize,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Selection {
     start: Option<Index>,
     end: Option<Index>,
+    highlight: Style,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self {
+            start: None,
+            end: None,
+            highlight: span!("").highlighted().style,
+        }
+    }
 }
 
 impl Selection {
+    pub fn with_highlight(mut self, highlight: Style) -> Self {
+        self.highlight = highlight;
+        self
+    }
+
+    /// Check if the span is styled as selected text. The whole style is
+    /// compared, a text may have the colors of the highlight.
+    pub fn is_highlighted(&self, span: &Span) -> bool {
+        span.style == self.highlight
+    }
+
     pub fn set_start(&mut self, row: usize, col: usize) {
         self.start = Some(Index { row, col });
     }
@@ -152,7 +177,7 @@ impl Selection {
 
             // If span is completely within selection
             if ptr >= start && ptr + span_width <= end {
-                ret_line.spans.push(span.highlighted());
+                ret_line.spans.push(span.style(self.highlight));
                 ptr += span_width;
                 continue;
             }
@@ -174,9 +199,7 @@ impl Selection {
             // Add selected portion
             if sel_end > sel_start {
                 let selected: String = content[sel_start..sel_end].iter().collect();
-                ret_line
-                    .spans
-                    .push(Span::styled(selected, span!("").highlighted().style));
+                ret_line.spans.push(Span::styled(selected, self.highlight));
             }
 
             // Add suffix if needed (unselected text after selection)
@@ -61,3 +61,25 @@ fn test_format_line() {
     ]);
     assert_eq!(formatted_line, expected_line);
 }
+
+#[test]
+fn test_is_highlighted() {
+    let line = Line::from(vec![
+        span!("Hello ").bold(),
+        span!("world, "),
+        span!("Sir").highlighted().bold(),
+    ]);
+    let mut sel = Selection::default();
+    sel.set_start(1, 0);
+    sel.set_end(1, 4);
+
+    let formatted_line = sel.format_line(line, 1);
+    let highlighted = formatted_line
+        .spans
+        .iter()
+        .filter(|span| sel.is_highlighted(span))
+        .map(|span| span.content.as_ref())
+        .collect::<Vec<_>>();
+    // The text with the colors of the highlight is not selected
+    assert_eq!(highlighted, vec!["Hello"]);
+}
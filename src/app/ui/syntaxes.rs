@@ -24,6 +24,14 @@ impl Syntaxes {
             return syntax;
         }
 
+        // The code blocks of the attached files are annotated with the path
+        let extension = std::path::Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str());
+        if let Some(syntax) = extension.and_then(|ext| SYNTAX_SET.find_syntax_by_extension(ext)) {
+            return syntax;
+        }
+
         SYNTAX_SET.find_syntax_plain_text()
     }
 
@@ -3,17 +3,18 @@
 mod tests;
 
 use ratatui::{
+    buffer::Buffer,
     layout::{Constraint, Flex, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
 };
 use ratatui_macros::span;
 use syntect::{easy::HighlightLines, highlighting::Theme};
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
     app::ui::syntaxes::{SYNTAX_SET, Syntaxes},
-    config,
+    config::{self, CodeOverflow, NoticePosition},
 };
 
 use super::Selectable;
@@ -26,8 +27,13 @@ pub fn popup_area(area: Rect, percent_width: u16, percent_height: u16) -> Rect {
     area
 }
 
-pub fn notice_area(area: Rect, percent_width: u16) -> Rect {
-    let horizontal = Layout::horizontal([Constraint::Percentage(percent_width)]).flex(Flex::End);
+pub fn notice_area(area: Rect, percent_width: u16, position: NoticePosition) -> Rect {
+    let flex = if position.is_left() {
+        Flex::Start
+    } else {
+        Flex::End
+    };
+    let horizontal = Layout::horizontal([Constraint::Percentage(percent_width)]).flex(flex);
     let [area] = horizontal.areas(area);
     area
 }
@@ -109,11 +115,16 @@ pub fn build_message_lines<'a, 'b, F>(
 where
     F: Fn(Line<'a>) -> Line<'a>,
 {
+    let ui = &config::instance().ui;
     let mut highlight = HighlightLines::new(Syntaxes::get("text"), theme);
     let mut in_codeblock = false;
+    // The number of the code line and the width of the numbers of the block
+    let mut line_number = 0;
+    let mut number_width = 0;
     let mut lines: Vec<Line> = vec![];
 
-    for line in content.lines() {
+    let content_lines = content.lines().collect::<Vec<_>>();
+    for (i, line) in content_lines.iter().enumerate() {
         let mut spans = vec![];
         if line.trim().starts_with("```") {
             let lang = line.trim().replace("```", "");
@@ -121,7 +132,14 @@ where
             if !in_codeblock {
                 highlight = HighlightLines::new(syntax, theme);
                 in_codeblock = true;
-                spans = vec![Span::from(line.to_owned())];
+                spans = vec![Span::from(line.to_string())];
+
+                line_number = 0;
+                let block_len = content_lines[i + 1..]
+                    .iter()
+                    .take_while(|line| !line.trim().starts_with("```"))
+                    .count();
+                number_width = block_len.to_string().len();
             } else {
                 in_codeblock = false
             }
@@ -147,10 +165,22 @@ where
                     )
                 })
                 .collect();
+
+            line_number += 1;
+            let number = ui
+                .code_line_numbers
+                .unwrap_or_default()
+                .then_some((line_number, number_width));
+            lines.extend(
+                build_code_lines(spans, max_width, number, ui.code_overflow)
+                    .into_iter()
+                    .map(&format_spans),
+            );
+            continue;
         }
 
         if spans.is_empty() {
-            spans = vec![Span::styled(line.to_owned(), Style::default())];
+            spans = vec![Span::styled(line.to_string(), Style::default())];
         }
 
         lines.extend(
@@ -163,6 +193,135 @@ where
     lines
 }
 
+/// Fit a highlighted code line in the width, the long lines are wrapped or
+/// truncated per `ui.code_overflow`. The line number, given with the width of
+/// the numbers, is dimmed and left out of the copied text.
+fn build_code_lines(
+    spans: Vec<Span<'_>>,
+    max_width: usize,
+    number: Option<(usize, usize)>,
+    overflow: CodeOverflow,
+) -> Vec<Line<'_>> {
+    let gutter = number.map(|(_, width)| width + 1).unwrap_or_default();
+    let width = max_width.saturating_sub(gutter).max(1);
+    let mut lines = match overflow {
+        CodeOverflow::Wrap => split_to_lines(spans, width)
+            .into_iter()
+            .flat_map(|line| break_long_line(line, width))
+            .collect(),
+        CodeOverflow::Truncate => vec![truncate_line(spans, width)],
+    };
+    // A blank line of code is kept, unlike the blank lines of the text
+    if lines.is_empty() {
+        lines.push(Line::default());
+    }
+
+    if let Some((number, number_width)) = number {
+        for (i, line) in lines.iter_mut().enumerate() {
+            let text = if i == 0 {
+                format!("{:>number_width$} ", number)
+            } else {
+                " ".repeat(gutter)
+            };
+            line.spans.insert(0, Span::from(text).dim().unselectable());
+        }
+    }
+    lines
+}
+
+/// Break a line still wider than the width, e.g. a long word without
+/// spaces, at the characters
+fn break_long_line(line: Line<'_>, max_width: usize) -> Vec<Line<'_>> {
+    if line.width() <= max_width {
+        return vec![line];
+    }
+
+    let wrapper_width = wrapper_span().width();
+    let mut lines = vec![];
+    let mut current: Vec<Span> = vec![];
+    let mut current_width = 0;
+    for span in line.spans {
+        if is_wrapper_span(&span) {
+            current.push(span);
+            continue;
+        }
+        let mut text = String::new();
+        for ch in span.content.chars() {
+            let ch_width = ch.width().unwrap_or_default();
+            if current_width + ch_width + wrapper_width > max_width && current_width > 0 {
+                if !text.is_empty() {
+                    current.push(Span::styled(std::mem::take(&mut text), span.style));
+                }
+                current.push(wrapper_span());
+                lines.push(Line::from(std::mem::take(&mut current)));
+                current_width = 0;
+            }
+            text.push(ch);
+            current_width += ch_width;
+        }
+        if !text.is_empty() {
+            current.push(Span::styled(text, span.style));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(Line::from(current));
+    }
+    lines
+}
+
+/// Cut the spans at the width, the cut is marked with a dimmed ellipsis
+fn truncate_line(spans: Vec<Span<'_>>, max_width: usize) -> Line<'_> {
+    let line = Line::from(spans);
+    if line.width() <= max_width {
+        return line;
+    }
+
+    let mut truncated = vec![];
+    let mut width = 0;
+    for span in line.spans {
+        let mut text = String::new();
+        for ch in span.content.chars() {
+            let ch_width = ch.width().unwrap_or_default();
+            if width + ch_width >= max_width {
+                break;
+            }
+            text.push(ch);
+            width += ch_width;
+        }
+        let full = text.len() == span.content.len();
+        if !text.is_empty() {
+            truncated.push(Span::styled(text, span.style));
+        }
+        if !full {
+            break;
+        }
+    }
+    truncated.push(Span::from("…").dim().unselectable());
+    Line::from(truncated)
+}
+
+/// Wrap a raw JSON answer, e.g. of the JSON mode, in a code block so it is
+/// highlighted as is instead of being rendered as markdown
+pub fn fence_json(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    if !trimmed.starts_with(['{', '[']) {
+        return None;
+    }
+    serde_json::from_str::<serde_json::Value>(trimmed).ok()?;
+    Some(format!("```json\n{}\n```", trimmed))
+}
+
+/// Format the token count in a short form, e.g. `12.3k` or `2M`
+pub fn format_tokens(tokens: usize) -> String {
+    if tokens >= 1_000_000 {
+        format!("{}M", ((tokens as f64 / 100_000.0).round() / 10.0))
+    } else if tokens >= 1_000 {
+        format!("{}k", ((tokens as f64 / 100.0).round() / 10.0))
+    } else {
+        tokens.to_string()
+    }
+}
+
 pub fn repeat_from_substactions(text: &str, subs: Vec<usize>) -> String {
     let count = subs
         .into_iter()
@@ -218,3 +377,20 @@ pub(super) fn spans_to_text(spans: &[Span<'_>]) -> String {
         .collect::<Vec<String>>()
         .join("")
 }
+
+/// Remove the colors of the rendered cells. The modifiers are kept, so the
+/// highlighted text is still shown in reverse video.
+pub fn strip_colors(buf: &mut Buffer) {
+    for cell in buf.content.iter_mut() {
+        cell.set_fg(Color::Reset).set_bg(Color::Reset);
+    }
+}
+
+/// The style of a highlighted text. It is reversed when the colors are
+/// disabled since the background no longer tells it apart.
+pub fn highlight_style(style: Style) -> Style {
+    if config::instance().theme.no_color() {
+        return style.add_modifier(Modifier::REVERSED);
+    }
+    style
+}
@@ -66,9 +66,102 @@ fn test_is_wrapper_span() {
     assert!(is_wrapper_span(&span));
 }
 
+#[test]
+fn test_fence_json() {
+    assert_eq!(
+        fence_json(" {\"a\": [1, \"*b*\"]}\n").as_deref(),
+        Some("```json\n{\"a\": [1, \"*b*\"]}\n```")
+    );
+    assert_eq!(
+        fence_json("[1, 2]").as_deref(),
+        Some("```json\n[1, 2]\n```")
+    );
+    assert_eq!(fence_json("{\"a\": 1"), None);
+    assert_eq!(fence_json("[link](https://example.com)"), None);
+    assert_eq!(fence_json("hello"), None);
+}
+
+#[test]
+fn test_build_code_lines() {
+    let code = || vec![span!("let x = "), span!("abcdefghijklmno").bold()];
+
+    let lines = build_code_lines(code(), 14, Some((3, 2)), CodeOverflow::Wrap);
+    let contents = lines.iter().map(|line| line.content()).collect::<Vec<_>>();
+    // The numbers are left out of the content
+    assert_eq!(contents, vec!["let x = ", "abcdefghij", "klmno"]);
+    let gutters = lines
+        .iter()
+        .map(|line| line.spans[0].content.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(gutters, vec![" 3 ", "   ", "   "]);
+    for line in &lines {
+        assert!(!line.spans[0].is_selectable());
+        assert!(line.spans[0].style.add_modifier.contains(Modifier::DIM));
+    }
+    assert!(
+        lines[2].spans[1]
+            .style
+            .add_modifier
+            .contains(Modifier::BOLD)
+    );
+
+    let lines = build_code_lines(code(), 14, None, CodeOverflow::Truncate);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].content(), "let x = abcde");
+    assert_eq!(lines[0].spans.last().unwrap().content, "…");
+    assert!(!lines[0].spans.last().unwrap().is_selectable());
+
+    // A blank line is kept to keep the numbers in sequence
+    let lines = build_code_lines(vec![span!("")], 14, Some((1, 1)), CodeOverflow::Wrap);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].spans[0].content, "1 ");
+}
+
+#[test]
+fn test_strip_colors() {
+    let mut buf = Buffer::empty(Rect::new(0, 0, 4, 1));
+    buf.set_string(
+        0,
+        0,
+        "ab",
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::Cyan)
+            .add_modifier(Modifier::REVERSED),
+    );
+    strip_colors(&mut buf);
+    for cell in buf.content.iter() {
+        assert_eq!(cell.fg, Color::Reset);
+        assert_eq!(cell.bg, Color::Reset);
+    }
+    assert!(buf[(0, 0)].modifier.contains(Modifier::REVERSED));
+    assert!(!buf[(2, 0)].modifier.contains(Modifier::REVERSED));
+
+    let style = Style::default().bg(Color::Yellow);
+    assert_eq!(highlight_style(style), style);
+
+    let mut cfg = config::Configuration::default();
+    cfg.theme.no_color = Some(true);
+    config::init(cfg).expect("Failed to initialize config");
+    assert!(
+        highlight_style(style)
+            .add_modifier
+            .contains(Modifier::REVERSED)
+    );
+}
+
 fn check_span_style(line: &Line, styles: &[Style]) {
     assert_eq!(line.spans.len(), styles.len());
     for (i, span) in line.spans.iter().enumerate() {
         assert_eq!(span.style, styles[i]);
     }
 }
+
+#[test]
+fn test_format_tokens() {
+    assert_eq!(format_tokens(512), "512");
+    assert_eq!(format_tokens(12_345), "12.3k");
+    assert_eq!(format_tokens(128_000), "128k");
+    assert_eq!(format_tokens(1_048_576), "1M");
+    assert_eq!(format_tokens(2_500_000), "2.5M");
+}
@@ -0,0 +1,79 @@
+use aws_smithy_eventstream::frame::{DecodedFrame, MessageFrameDecoder};
+use aws_smithy_types::event_stream::Message;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use eyre::{Context, Result, bail};
+
+use super::types::{ExceptionPayload, PayloadPart};
+
+/// Decode the `application/vnd.amazon.eventstream` framing of the
+/// `InvokeModelWithResponseStream` response into the model chunks.
+#[derive(Default)]
+pub struct EventStreamDecoder {
+    decoder: MessageFrameDecoder,
+    buf: Vec<u8>,
+}
+
+impl EventStreamDecoder {
+    /// Feed the received bytes to the decoder and return the payload of the
+    /// chunks which are complete. An exception sent by the service is
+    /// returned as an error.
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<Vec<u8>>> {
+        self.buf.extend_from_slice(data);
+
+        let mut chunks = vec![];
+        loop {
+            let mut remaining = self.buf.as_slice();
+            let frame = self
+                .decoder
+                .decode_frame(&mut remaining)
+                .wrap_err("decoding event stream frame")?;
+            let consumed = self.buf.len() - remaining.len();
+            self.buf.drain(..consumed);
+
+            match frame {
+                DecodedFrame::Complete(message) => {
+                    if let Some(chunk) = decode_message(&message)? {
+                        chunks.push(chunk);
+                    }
+                }
+                DecodedFrame::Incomplete => break,
+            }
+        }
+        Ok(chunks)
+    }
+}
+
+fn decode_message(message: &Message) -> Result<Option<Vec<u8>>> {
+    let message_type = header(message, ":message-type").unwrap_or("event");
+    match message_type {
+        "event" => {
+            if header(message, ":event-type") != Some("chunk") {
+                return Ok(None);
+            }
+            let part = serde_json::from_slice::<PayloadPart>(message.payload())
+                .wrap_err("parsing payload part")?;
+            let bytes = BASE64_STANDARD
+                .decode(part.bytes)
+                .wrap_err("decoding payload part")?;
+            Ok(Some(bytes))
+        }
+        _ => {
+            let exception = header(message, ":exception-type")
+                .or(header(message, ":error-code"))
+                .unwrap_or("UnknownException")
+                .to_string();
+            let payload =
+                serde_json::from_slice::<ExceptionPayload>(message.payload()).unwrap_or_default();
+            bail!("{}: {}", exception, payload.message)
+        }
+    }
+}
+
+fn header<'a>(message: &'a Message, name: &str) -> Option<&'a str> {
+    message
+        .headers()
+        .iter()
+        .find(|header| header.name().as_str() == name)
+        .and_then(|header| header.value().as_string().ok())
+        .map(|value| value.as_str())
+}
@@ -0,0 +1,362 @@
+#[cfg(test)]
+#[path = "mod_test.rs"]
+mod tests;
+
+mod event_stream;
+mod signer;
+mod types;
+
+use std::{fmt::Display, sync::Arc, time};
+
+use async_trait::async_trait;
+use eyre::{Context, Result, bail, eyre};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    backend::{ArcBackend, Backend, redact::redact},
+    config::user_agent,
+    models::{
+        ArcEventTx, BackendConnection, BackendPrompt, BackendResponse, BackendUsage, Event, Model,
+        ResponseFormat,
+    },
+    warn_event,
+};
+
+use event_stream::EventStreamDecoder;
+pub use signer::Signer;
+use types::{FoundationModelsResponse, ModelFamily};
+
+/// The default maximum number of output tokens, Anthropic models require
+/// `max_tokens` to be set
+const DEFAULT_MAX_OUTPUT_TOKENS: usize = 4096;
+
+pub struct Bedrock {
+    alias: String,
+    endpoint: Option<String>,
+    region: Option<String>,
+    profile: Option<String>,
+    timeout: Option<time::Duration>,
+
+    want_models: Vec<String>,
+    max_output_tokens: Option<usize>,
+
+    signer: Option<Signer>,
+}
+
+#[async_trait]
+impl Backend for Bedrock {
+    fn name(&self) -> &str {
+        &self.alias
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        // The credentials are loaded by `init`, there is no cheaper request
+        // than a completion to verify them
+        if self.signer.is_none() {
+            bail!("the backend is not initialized");
+        }
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<Vec<Model>> {
+        let mut models = if !self.want_models.is_empty() {
+            self.want_models.clone()
+        } else {
+            self.list_foundation_models()
+                .await
+                .wrap_err("listing foundation models")?
+        };
+        models.sort();
+
+        Ok(models
+            .into_iter()
+            .map(|id| Model::new(id).with_provider(&self.alias))
+            .collect())
+    }
+
+    async fn get_completion(&self, prompt: BackendPrompt, event_tx: ArcEventTx) -> Result<()> {
+        if prompt.model().is_empty() {
+            bail!("no model is set");
+        }
+
+        let model = prompt.model();
+        let family = ModelFamily::from_model(model)
+            .ok_or_else(|| eyre!("model {} is not supported", model))?;
+
+        if prompt
+            .response_format()
+            .is_some_and(|format| format != ResponseFormat::Text)
+        {
+            event_tx
+                .send(warn_event!(format!(
+                    "JSON mode is not supported by model \"{}\", answering in text",
+                    model
+                )))
+                .await?;
+        }
+
+        let body = family.build_body(
+            prompt.context(),
+            prompt.text(),
+            self.max_output_tokens.unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS),
+        )?;
+
+        let url = format!(
+            "{}/model/{}/invoke-with-response-stream",
+            self.runtime_endpoint()?,
+            encode_model_id(model)
+        );
+        let res = self
+            .send_request("POST", &url, body)
+            .await
+            .wrap_err("sending completion request")?;
+
+        let message_id = uuid::Uuid::new_v4().to_string();
+        let mut usage: Option<BackendUsage> = None;
+        let mut decoder = EventStreamDecoder::default();
+        let mut stream = res.bytes_stream();
+
+        while let Some(data) = stream.try_next().await.wrap_err("reading response")? {
+            for chunk in decoder.push(&data)? {
+                log::trace!(
+                    "streaming response: {}",
+                    redact(&String::from_utf8_lossy(&chunk))
+                );
+                let chunk = family.parse_chunk(&chunk)?;
+                if chunk.usage.is_some() {
+                    usage = chunk.usage;
+                }
+
+                let text = match chunk.text {
+                    Some(text) if !text.is_empty() => text,
+                    _ => continue,
+                };
+
+                event_tx
+                    .send(Event::ChatCompletionResponse(
+                        BackendResponse::new(&message_id, model).with_text(text),
+                    ))
+                    .await?;
+            }
+        }
+
+        let mut msg = BackendResponse::new(&message_id, model).with_done();
+        if let Some(usage) = usage {
+            msg = msg.with_usage(usage);
+        }
+        event_tx.send(Event::ChatCompletionResponse(msg)).await?;
+        Ok(())
+    }
+}
+
+impl From<Bedrock> for ArcBackend {
+    fn from(value: Bedrock) -> Self {
+        Arc::new(value)
+    }
+}
+
+impl From<&BackendConnection> for Bedrock {
+    fn from(value: &BackendConnection) -> Self {
+        let mut bedrock = Bedrock::default();
+
+        if let Some(alias) = value.alias() {
+            bedrock.alias = alias.to_string();
+        }
+
+        if !value.endpoint().is_empty() {
+            bedrock.endpoint = Some(value.endpoint().to_string());
+        }
+
+        bedrock.region = value.region().map(|r| r.to_string());
+        bedrock.profile = value.profile().map(|p| p.to_string());
+        bedrock.timeout = value.timeout();
+        bedrock.max_output_tokens = value.max_output_tokens();
+        bedrock.want_models = value.models().to_vec();
+        bedrock
+    }
+}
+
+impl Bedrock {
+    /// Resolve the region and the credentials from the AWS config
+    pub async fn init(&mut self) -> Result<()> {
+        let signer = Signer::load(self.region.as_deref(), self.profile.as_deref())
+            .await
+            .wrap_err("loading AWS config")?;
+        self.signer = Some(signer);
+        Ok(())
+    }
+
+    pub fn with_alias(mut self, alias: &str) -> Self {
+        self.alias = alias.to_string();
+        self
+    }
+
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_want_models(mut self, models: Vec<String>) -> Self {
+        self.want_models = models;
+        self
+    }
+
+    pub fn with_max_output_tokens(mut self, max_output_tokens: usize) -> Self {
+        self.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+
+    pub fn with_signer(mut self, signer: Signer) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    fn signer(&self) -> Result<&Signer> {
+        self.signer
+            .as_ref()
+            .ok_or_else(|| eyre!("Bedrock backend is not initialized"))
+    }
+
+    fn runtime_endpoint(&self) -> Result<String> {
+        if let Some(endpoint) = &self.endpoint {
+            return Ok(endpoint.trim_end_matches('/').to_string());
+        }
+        Ok(format!(
+            "https://bedrock-runtime.{}.amazonaws.com",
+            self.signer()?.region()
+        ))
+    }
+
+    async fn list_foundation_models(&self) -> Result<Vec<String>> {
+        let url = format!(
+            "https://bedrock.{}.amazonaws.com/foundation-models?byOutputModality=TEXT",
+            self.signer()?.region()
+        );
+        let res = self.send_request("GET", &url, vec![]).await?;
+        let res = res
+            .json::<FoundationModelsResponse>()
+            .await
+            .wrap_err("parsing foundation models response")?;
+
+        Ok(res
+            .model_summaries
+            .into_iter()
+            .filter(|m| m.response_streaming_supported.unwrap_or_default())
+            .filter(|m| ModelFamily::from_model(&m.model_id).is_some())
+            .map(|m| m.model_id)
+            .collect())
+    }
+
+    async fn send_request(
+        &self,
+        method: &str,
+        url: &str,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response> {
+        let reqwest_url = reqwest::Url::parse(url).wrap_err("parsing url")?;
+        let service = if reqwest_url
+            .host_str()
+            .is_some_and(|host| host.starts_with("bedrock."))
+        {
+            "bedrock"
+        } else {
+            "bedrock-runtime"
+        };
+        let host = match (reqwest_url.host_str(), reqwest_url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            _ => bail!("invalid url {}", url),
+        };
+
+        let headers = [
+            ("host", host.as_str()),
+            ("content-type", "application/json"),
+            ("accept", "application/json"),
+        ];
+        let signed_headers = self
+            .signer()?
+            .sign(service, method, url, &headers, &body)
+            .await?;
+
+        let method = reqwest::Method::from_bytes(method.as_bytes()).wrap_err("parsing method")?;
+        let mut req = reqwest::Client::new()
+            .request(method, reqwest_url)
+            .header("User-Agent", user_agent());
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            req = req.header(*name, *value);
+        }
+        for (name, value) in signed_headers {
+            req = req.header(name, value);
+        }
+        if !body.is_empty() {
+            req = req.body(body);
+        }
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+
+        let res = req.send().await.wrap_err("sending request")?;
+        if !res.status().is_success() {
+            let http_code = res.status().as_u16();
+            let resp = res.text().await.wrap_err("reading error response")?;
+            log::error!("Error response: {}", resp);
+            let mut err = serde_json::from_str::<BedrockError>(&resp).unwrap_or(BedrockError {
+                message: resp,
+                ..Default::default()
+            });
+            err.http_code = http_code;
+            return Err(err.into());
+        }
+        Ok(res)
+    }
+}
+
+impl Default for Bedrock {
+    fn default() -> Self {
+        Self {
+            alias: "Bedrock".to_string(),
+            endpoint: None,
+            region: None,
+            profile: None,
+            timeout: None,
+            want_models: vec![],
+            max_output_tokens: None,
+            signer: None,
+        }
+    }
+}
+
+#[derive(Default, Error, Debug, Deserialize)]
+pub struct BedrockError {
+    #[serde(skip)]
+    pub http_code: u16,
+    #[serde(alias = "Message")]
+    pub message: String,
+}
+
+impl Display for BedrockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bedrock error ({}): {}", self.http_code, self.message)
+    }
+}
+
+/// Percent-encode the model id to be used in the url path. Model ids
+/// usually contain `:` (e.g. `anthropic.claude-v2:1`) and the ARNs contain `/`.
+fn encode_model_id(model: &str) -> String {
+    model
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
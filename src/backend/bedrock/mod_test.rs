@@ -0,0 +1,167 @@
+use aws_credential_types::Credentials;
+use aws_smithy_types::event_stream::{Header, HeaderValue, Message as EventMessage};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use tokio::sync::mpsc;
+
+use super::types::{AnthropicRequest, PayloadPart};
+use super::*;
+use crate::models::{ArcEventTx, Message};
+
+#[test]
+fn test_encode_model_id() {
+    assert_eq!(
+        encode_model_id("anthropic.claude-3-haiku-20240307-v1:0"),
+        "anthropic.claude-3-haiku-20240307-v1%3A0"
+    );
+    assert_eq!(
+        encode_model_id("amazon.titan-text-lite-v1"),
+        "amazon.titan-text-lite-v1"
+    );
+}
+
+#[test]
+fn test_model_family() {
+    assert_eq!(
+        ModelFamily::from_model("us.anthropic.claude-3-5-sonnet-20240620-v1:0"),
+        Some(ModelFamily::Anthropic)
+    );
+    assert_eq!(
+        ModelFamily::from_model("amazon.titan-text-express-v1"),
+        Some(ModelFamily::Titan)
+    );
+    assert_eq!(
+        ModelFamily::from_model("meta.llama3-8b-instruct-v1:0"),
+        None
+    );
+}
+
+#[test]
+fn test_anthropic_request() {
+    let context = vec![
+        Message::new_system("system", "You are a pirate").with_context(true),
+        Message::new_system("system", "Hello, how can I help you?"),
+        Message::new_user("user", "Hi"),
+        Message::new_user("user", "Who are you?"),
+        Message::new_system("system", "Ahoy!"),
+    ];
+    let req = AnthropicRequest::new(&context, "Where is the treasure?", 100);
+    let body = serde_json::to_value(&req).expect("Failed to serialize");
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "anthropic_version": "bedrock-2023-05-31",
+            "max_tokens": 100,
+            "system": "You are a pirate",
+            "messages": [
+                {"role": "user", "content": "Hi\n\nWho are you?"},
+                {"role": "assistant", "content": "Ahoy!"},
+                {"role": "user", "content": "Where is the treasure?"},
+            ],
+        })
+    );
+}
+
+#[test]
+fn test_event_stream_decoder() {
+    let body = encode_chunks(&[r#"{"outputText":"Hello"}"#, r#"{"outputText":" there"}"#]);
+
+    let mut decoder = EventStreamDecoder::default();
+    // Feed the frames in small parts to make sure incomplete frames are kept
+    let chunks = body
+        .chunks(7)
+        .flat_map(|part| decoder.push(part).expect("Failed to decode"))
+        .collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0], br#"{"outputText":"Hello"}"#);
+    assert_eq!(chunks[1], br#"{"outputText":" there"}"#);
+
+    let exception = EventMessage::new(r#"{"message":"Too many requests"}"#)
+        .add_header(string_header(":message-type", "exception"))
+        .add_header(string_header(":exception-type", "throttlingException"));
+    let mut body = vec![];
+    aws_smithy_eventstream::frame::write_message_to(&exception, &mut body)
+        .expect("Failed to encode message");
+    let err = EventStreamDecoder::default()
+        .push(&body)
+        .expect_err("Expected an exception");
+    assert_eq!(err.to_string(), "throttlingException: Too many requests");
+}
+
+#[tokio::test]
+async fn test_get_completion() {
+    let body = encode_chunks(&[
+        r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":10}}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello "}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"there!"}}"#,
+        r#"{"type":"message_stop","amazon-bedrock-invocationMetrics":{"inputTokenCount":10,"outputTokenCount":2}}"#,
+    ]);
+
+    let mut server = mockito::Server::new_async().await;
+    let completion_handler = server
+        .mock(
+            "POST",
+            "/model/anthropic.claude-3-haiku-20240307-v1%3A0/invoke-with-response-stream",
+        )
+        .match_header(
+            "authorization",
+            mockito::Matcher::Regex("^AWS4-HMAC-SHA256 Credential=AKID/".to_string()),
+        )
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "anthropic_version": "bedrock-2023-05-31",
+            "max_tokens": 4096,
+        })))
+        .with_status(200)
+        .with_header("content-type", "application/vnd.amazon.eventstream")
+        .with_body(body)
+        .create();
+
+    let backend =
+        Bedrock::default()
+            .with_endpoint(&server.url())
+            .with_signer(Signer::with_credentials(
+                "us-east-1",
+                Credentials::new("AKID", "SECRET", None, None, "test"),
+            ));
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let sender: ArcEventTx = Arc::new(tx);
+    let prompt = BackendPrompt::new("Hello").with_model("anthropic.claude-3-haiku-20240307-v1:0");
+    backend
+        .get_completion(prompt, sender)
+        .await
+        .expect("Failed to get completion");
+    completion_handler.assert();
+
+    let events = std::iter::from_fn(|| rx.try_recv().ok())
+        .filter_map(|event| match event {
+            Event::ChatCompletionResponse(msg) => Some(msg),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].text, "Hello ");
+    assert_eq!(events[1].text, "there!");
+    assert!(events[2].done);
+    assert_eq!(events[2].usage.as_ref().map(|u| u.total_tokens), Some(12));
+}
+
+fn encode_chunks(chunks: &[&str]) -> Vec<u8> {
+    let mut body = vec![];
+    for chunk in chunks {
+        let payload = serde_json::to_vec(&PayloadPart {
+            bytes: BASE64_STANDARD.encode(chunk),
+        })
+        .expect("Failed to serialize payload");
+        let message = EventMessage::new(payload)
+            .add_header(string_header(":message-type", "event"))
+            .add_header(string_header(":event-type", "chunk"))
+            .add_header(string_header(":content-type", "application/json"));
+        aws_smithy_eventstream::frame::write_message_to(&message, &mut body)
+            .expect("Failed to encode message");
+    }
+    body
+}
+
+fn string_header(name: &'static str, value: &'static str) -> Header {
+    Header::new(name, HeaderValue::String(value.into()))
+}
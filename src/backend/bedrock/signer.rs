@@ -0,0 +1,100 @@
+use std::time::SystemTime;
+
+use aws_config::{BehaviorVersion, Region};
+use aws_credential_types::{
+    Credentials,
+    provider::{ProvideCredentials, SharedCredentialsProvider},
+};
+use aws_sigv4::{
+    http_request::{SignableBody, SignableRequest, SigningSettings, sign},
+    sign::v4,
+};
+use eyre::{Context, Result, eyre};
+
+/// Sign the requests to AWS with Signature Version 4. The credentials are
+/// resolved with the default AWS credential chain (environment variables,
+/// shared config and credentials files).
+pub struct Signer {
+    region: String,
+    credentials: SharedCredentialsProvider,
+}
+
+impl Signer {
+    pub async fn load(region: Option<&str>, profile: Option<&str>) -> Result<Self> {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = region {
+            loader = loader.region(Region::new(region.to_string()));
+        }
+        if let Some(profile) = profile {
+            loader = loader.profile_name(profile);
+        }
+        let sdk_config = loader.load().await;
+
+        let region = sdk_config
+            .region()
+            .map(|region| region.to_string())
+            .ok_or_else(|| eyre!("no AWS region is configured"))?;
+        let credentials = sdk_config
+            .credentials_provider()
+            .ok_or_else(|| eyre!("no AWS credentials provider is available"))?;
+        Ok(Self {
+            region,
+            credentials,
+        })
+    }
+
+    pub fn with_credentials(region: impl Into<String>, credentials: Credentials) -> Self {
+        Self {
+            region: region.into(),
+            credentials: SharedCredentialsProvider::new(credentials),
+        }
+    }
+
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Sign the request and return the headers which must be added to it.
+    /// The `url` is expected to be already percent-encoded.
+    pub async fn sign(
+        &self,
+        service: &str,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Vec<(String, String)>> {
+        let credentials = self
+            .credentials
+            .provide_credentials()
+            .await
+            .wrap_err("resolving AWS credentials")?;
+        let identity = credentials.into();
+
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region)
+            .name(service)
+            .time(SystemTime::now())
+            .settings(SigningSettings::default())
+            .build()
+            .wrap_err("building signing params")?
+            .into();
+
+        let request = SignableRequest::new(
+            method,
+            url,
+            headers.iter().copied(),
+            SignableBody::Bytes(body),
+        )
+        .wrap_err("building signable request")?;
+
+        let (instructions, _) = sign(request, &params)
+            .wrap_err("signing request")?
+            .into_parts();
+        Ok(instructions
+            .headers()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect())
+    }
+}
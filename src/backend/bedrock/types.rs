@@ -0,0 +1,267 @@
+use eyre::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{BackendUsage, Message};
+
+const ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+/// The model families supported by the Bedrock backend. Each family has its
+/// own request and response schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFamily {
+    Anthropic,
+    Titan,
+}
+
+impl ModelFamily {
+    /// Detect the family from the model id. Cross-region inference profiles
+    /// (e.g. `us.anthropic.claude-3-5-sonnet-20240620-v1:0`) are supported.
+    pub fn from_model(model: &str) -> Option<Self> {
+        if model.contains("anthropic.") {
+            return Some(Self::Anthropic);
+        }
+        if model.contains("amazon.titan-text") {
+            return Some(Self::Titan);
+        }
+        None
+    }
+
+    /// Build the request body from the context and the prompt text
+    pub fn build_body(
+        &self,
+        context: &[Message],
+        text: &str,
+        max_tokens: usize,
+    ) -> Result<Vec<u8>> {
+        let body = match self {
+            Self::Anthropic => {
+                serde_json::to_vec(&AnthropicRequest::new(context, text, max_tokens))
+            }
+            Self::Titan => serde_json::to_vec(&TitanRequest::new(context, text, max_tokens)),
+        };
+        body.wrap_err("serializing request body")
+    }
+
+    /// Parse a chunk of the response stream
+    pub fn parse_chunk(&self, data: &[u8]) -> Result<ChunkResponse> {
+        let mut chunk = ChunkResponse::default();
+        match self {
+            Self::Anthropic => {
+                match serde_json::from_slice::<AnthropicChunk>(data)
+                    .wrap_err("parsing anthropic chunk")?
+                {
+                    AnthropicChunk::ContentBlockDelta { delta } => chunk.text = delta.text,
+                    AnthropicChunk::Error { error } => {
+                        bail!("{}: {}", error.error_type, error.message)
+                    }
+                    _ => {}
+                }
+            }
+            Self::Titan => {
+                let titan =
+                    serde_json::from_slice::<TitanChunk>(data).wrap_err("parsing titan chunk")?;
+                chunk.text = titan.output_text;
+            }
+        }
+
+        let metrics = serde_json::from_slice::<InvocationMetricsChunk>(data)
+            .wrap_err("parsing invocation metrics")?;
+        chunk.usage = metrics.metrics.map(BackendUsage::from);
+        Ok(chunk)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChunkResponse {
+    pub text: Option<String>,
+    pub usage: Option<BackendUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnthropicRequest {
+    anthropic_version: String,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AnthropicMessage {
+    role: String,
+    content: String,
+}
+
+impl AnthropicRequest {
+    pub fn new(context: &[Message], text: &str, max_tokens: usize) -> Self {
+        let system = context
+            .iter()
+            .filter(|msg| msg.is_context())
+            .map(|msg| msg.text())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut messages: Vec<AnthropicMessage> = vec![];
+        let history = context
+            .iter()
+            .filter(|msg| !msg.is_context())
+            .map(|msg| (msg.is_system(), msg.text()))
+            .chain([(false, text)]);
+        for (is_system, text) in history {
+            let role = if is_system { "assistant" } else { "user" };
+            // The conversation must start with a user message and the roles
+            // must alternate, so the consecutive messages are merged
+            match messages.last_mut() {
+                Some(last) if last.role == role => {
+                    last.content.push_str("\n\n");
+                    last.content.push_str(text);
+                }
+                None if is_system => continue,
+                _ => messages.push(AnthropicMessage {
+                    role: role.to_string(),
+                    content: text.to_string(),
+                }),
+            }
+        }
+
+        Self {
+            anthropic_version: ANTHROPIC_VERSION.to_string(),
+            max_tokens,
+            system: if system.is_empty() {
+                None
+            } else {
+                Some(system)
+            },
+            messages,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicChunk {
+    ContentBlockDelta {
+        delta: AnthropicDelta,
+    },
+    Error {
+        error: AnthropicError,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicDelta {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicError {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TitanRequest {
+    input_text: String,
+    text_generation_config: TitanGenerationConfig,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TitanGenerationConfig {
+    max_token_count: usize,
+}
+
+impl TitanRequest {
+    /// Titan models only accept a plain text input, so the conversation is
+    /// flattened into a transcript
+    pub fn new(context: &[Message], text: &str, max_tokens: usize) -> Self {
+        let mut input = String::new();
+        for msg in context {
+            if msg.is_context() {
+                input.push_str(msg.text());
+            } else if msg.is_system() {
+                input.push_str(&format!("Bot: {}", msg.text()));
+            } else {
+                input.push_str(&format!("User: {}", msg.text()));
+            }
+            input.push('\n');
+        }
+        input.push_str(&format!("User: {}\nBot:", text));
+
+        Self {
+            input_text: input,
+            text_generation_config: TitanGenerationConfig {
+                max_token_count: max_tokens,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TitanChunk {
+    #[serde(default)]
+    output_text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvocationMetricsChunk {
+    #[serde(rename = "amazon-bedrock-invocationMetrics", default)]
+    metrics: Option<InvocationMetrics>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InvocationMetrics {
+    input_token_count: usize,
+    output_token_count: usize,
+    #[serde(default)]
+    cache_read_input_token_count: Option<usize>,
+}
+
+impl From<InvocationMetrics> for BackendUsage {
+    fn from(value: InvocationMetrics) -> Self {
+        // The input tokens read from the cache are not counted in the input
+        let prompt_tokens =
+            value.input_token_count + value.cache_read_input_token_count.unwrap_or_default();
+        BackendUsage {
+            prompt_tokens,
+            completion_tokens: value.output_token_count,
+            total_tokens: prompt_tokens + value.output_token_count,
+            cached_prompt_tokens: value.cache_read_input_token_count,
+            reasoning_tokens: None,
+        }
+    }
+}
+
+/// The payload of an event stream chunk, the model response is base64
+/// encoded in `bytes`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayloadPart {
+    pub bytes: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExceptionPayload {
+    #[serde(default, alias = "Message")]
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundationModelsResponse {
+    pub model_summaries: Vec<FoundationModelSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoundationModelSummary {
+    pub model_id: String,
+    #[serde(default)]
+    pub response_streaming_supported: Option<bool>,
+}
@@ -0,0 +1,506 @@
+#[cfg(test)]
+#[path = "cohere_test.rs"]
+mod tests;
+
+use std::{collections::HashMap, fmt::Display, sync::RwLock, time};
+
+use crate::{
+    backend::{
+        redact::redact,
+        utils::{LineReader, context_truncation, with_extra_body},
+    },
+    config::{self, ModelSetting, user_agent},
+    models::{
+        ArcEventTx, BackendConnection, BackendPrompt, BackendResponse, BackendUsage, Event,
+        Message, Model, ResponseFormat,
+    },
+    warn_event,
+};
+use async_trait::async_trait;
+use eyre::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::backend::Backend;
+
+/// The Cohere chat API. The roles of Cohere differ from the other backends,
+/// the prompt is sent as the `message` and the previous turns as the
+/// `chat_history`.
+pub struct Cohere {
+    alias: String,
+    endpoint: String,
+    api_key: Option<String>,
+    timeout: Option<time::Duration>,
+
+    want_models: Vec<String>,
+    max_output_tokens: Option<usize>,
+    extra_body: Option<serde_json::Map<String, serde_json::Value>>,
+
+    model_settings: HashMap<String, ModelSetting>,
+    // Models fetched by the health check or the first listing, reused to
+    // avoid listing them again until they are refreshed
+    models: RwLock<Option<Vec<Model>>>,
+}
+
+impl Cohere {
+    async fn fetch_models(&self) -> Result<Vec<Model>> {
+        let url = reqwest::Url::parse_with_params(
+            format!("{}/v1/models", &self.endpoint).as_str(),
+            &[("endpoint", "chat"), ("page_size", "1000")],
+        )
+        .wrap_err("parsing url")?;
+
+        let mut builder = reqwest::Client::new()
+            .get(url)
+            .header("User-Agent", user_agent());
+
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        if let Some(timeout) = &self.timeout {
+            builder = builder.timeout(*timeout);
+        }
+
+        let resp = builder.send().await?;
+        if !resp.status().is_success() {
+            return Err(error_from(resp).await.into());
+        }
+
+        let all = self.want_models.is_empty();
+        let mut models = resp
+            .json::<ModelListResponse>()
+            .await
+            .wrap_err("parsing model list response")?
+            .models
+            .into_iter()
+            .filter(|m| all || self.want_models.contains(&m.name))
+            .map(|m| Model::new(m.name).with_provider(&self.alias))
+            .collect::<Vec<_>>();
+
+        models.sort_by(|a, b| a.id().cmp(b.id()));
+        Ok(models)
+    }
+
+    pub async fn init(&mut self) -> Result<()> {
+        let models = self.list_models().await.wrap_err("listing models")?;
+        for settings in &config::instance().backend.model_settings {
+            let re = settings.model.build().wrap_err("building model filter")?;
+            if let Some(model) = models.iter().find(|m| re.is_match(m.id())) {
+                self.model_settings
+                    .insert(model.id().to_string(), settings.clone());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_want_models(mut self, models: Vec<String>) -> Self {
+        self.want_models = models;
+        self
+    }
+
+    pub fn with_alias(mut self, alias: &str) -> Self {
+        self.alias = alias.to_string();
+        self
+    }
+
+    pub fn with_max_output_tokens(mut self, max_output_tokens: usize) -> Self {
+        self.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+
+    /// Drop the cached models, they are fetched again on the next listing
+    fn invalidate_models(&self) {
+        if let Ok(mut cache) = self.models.write() {
+            *cache = None;
+        }
+    }
+
+    async fn chat_completion(&self, request: ChatRequest, event_tx: ArcEventTx) -> Result<()> {
+        let model = request.model.clone();
+        let settings = self.model_settings.get(&model);
+        let request = with_extra_body(
+            &request,
+            &[
+                self.extra_body.as_ref(),
+                settings.and_then(|s| s.extra_body.as_ref()),
+            ],
+        )?;
+
+        let url = format!("{}/v1/chat", self.endpoint);
+        let mut builder = reqwest::Client::new()
+            .post(&url)
+            .header("User-Agent", user_agent());
+
+        if let Some(key) = &self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        log::trace!(
+            "Sending completion request to {}: {}",
+            url,
+            redact(&serde_json::to_string(&request).unwrap_or_default())
+        );
+
+        let resp = builder
+            .json(&request)
+            .send()
+            .await
+            .inspect_err(|err| {
+                // The endpoint may serve other models once it is reachable again
+                if err.is_connect() {
+                    self.invalidate_models();
+                }
+            })
+            .wrap_err("sending completion request")?;
+
+        if !resp.status().is_success() {
+            return Err(error_from(resp).await.into());
+        }
+
+        let mut lines_reader = LineReader::new(resp.bytes_stream());
+        let message_id = uuid::Uuid::new_v4().to_string();
+        while let Some(line) = lines_reader.next_line().await? {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            log::trace!("Received line: {}", redact(line));
+
+            let event: StreamEvent =
+                serde_json::from_str(line).wrap_err(format!("parsing stream event: {}", line))?;
+            match event {
+                StreamEvent::TextGeneration { text } => {
+                    event_tx
+                        .send(Event::ChatCompletionResponse(
+                            BackendResponse::new(&message_id, &model).with_text(text),
+                        ))
+                        .await?;
+                }
+                StreamEvent::StreamEnd {
+                    finish_reason,
+                    response,
+                } => {
+                    if finish_reason.starts_with("ERROR") {
+                        log::warn!("Cohere response stopped: {}", finish_reason);
+                        event_tx
+                            .send(warn_event!(format!(
+                                "Cohere response stopped: {}",
+                                finish_reason
+                            )))
+                            .await?;
+                    }
+
+                    let usage = response
+                        .and_then(|r| r.meta)
+                        .and_then(|meta| meta.tokens)
+                        .map(|tokens| BackendUsage::from(&tokens))
+                        .unwrap_or_default();
+                    event_tx
+                        .send(Event::ChatCompletionResponse(
+                            BackendResponse::new(&message_id, &model)
+                                .with_done()
+                                .with_usage(usage),
+                        ))
+                        .await?;
+                    return Ok(());
+                }
+                StreamEvent::Other => {}
+            }
+        }
+
+        bail!("the stream ended without the stream-end event")
+    }
+}
+
+#[async_trait]
+impl Backend for Cohere {
+    fn name(&self) -> &str {
+        &self.alias
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        let models = self.fetch_models().await?;
+        if let Ok(mut cache) = self.models.write() {
+            *cache = Some(models);
+        }
+        Ok(())
+    }
+
+    async fn list_models(&self) -> Result<Vec<Model>> {
+        if let Some(models) = self.models.read().ok().and_then(|cache| cache.clone()) {
+            return Ok(models);
+        }
+        let models = self.fetch_models().await?;
+        if let Ok(mut cache) = self.models.write() {
+            *cache = Some(models.clone());
+        }
+        Ok(models)
+    }
+
+    async fn refresh_models(&self) -> Result<Vec<Model>> {
+        self.invalidate_models();
+        self.list_models().await
+    }
+
+    async fn get_completion(&self, prompt: BackendPrompt, event_tx: ArcEventTx) -> Result<()> {
+        if prompt.model().is_empty() {
+            bail!("no model is set");
+        }
+
+        let mut messages = prompt.context().to_vec();
+        messages.push(Message::new_user("user", prompt.text()));
+
+        if let Some(max_output_tokens) = self.max_output_tokens {
+            context_truncation(&mut messages, max_output_tokens);
+        }
+
+        let settings = self.model_settings.get(prompt.model());
+        let response_format = prompt
+            .response_format()
+            .or_else(|| settings.and_then(|s| s.response_format))
+            .unwrap_or_default();
+
+        let mut request = ChatRequest::from_messages(prompt.model(), &messages);
+        request.max_tokens = settings
+            .and_then(|s| s.max_tokens)
+            .or(self.max_output_tokens);
+        request.temperature = settings.and_then(|s| s.temperature);
+        request.p = settings.and_then(|s| s.top_p);
+        request.stop_sequences = settings.map(|s| s.stop.clone()).unwrap_or_default();
+        request.response_format = (response_format == ResponseFormat::JsonObject)
+            .then(|| serde_json::json!({ "type": "json_object" }));
+
+        self.chat_completion(request, event_tx).await
+    }
+}
+
+impl Default for Cohere {
+    fn default() -> Self {
+        Cohere {
+            alias: "Cohere".to_string(),
+            endpoint: "https://api.cohere.com".to_string(),
+            api_key: None,
+            timeout: None,
+
+            want_models: Vec::new(),
+            max_output_tokens: None,
+            extra_body: None,
+
+            model_settings: HashMap::new(),
+            models: RwLock::new(None),
+        }
+    }
+}
+
+impl From<&BackendConnection> for Cohere {
+    fn from(value: &BackendConnection) -> Self {
+        let mut backend = Cohere::default();
+
+        if let Some(alias) = value.alias() {
+            backend.alias = alias.to_string();
+        }
+
+        backend.endpoint = value.endpoint().trim_end_matches('/').to_string();
+
+        if let Some(key) = value.api_key() {
+            backend.api_key = Some(key.to_string());
+        }
+
+        if let Some(timeout) = value.timeout() {
+            backend.timeout = Some(timeout);
+        }
+
+        backend.max_output_tokens = value.max_output_tokens();
+        backend.extra_body = value.extra_body().cloned();
+
+        backend.with_want_models(value.models().to_vec())
+    }
+}
+
+/// Read the error of a failed request, the body is kept as the message if it
+/// is not the JSON error of Cohere
+async fn error_from(resp: reqwest::Response) -> CohereError {
+    let http_code = resp.status().as_u16();
+    let text = resp.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorResponse>(&text)
+        .map(|err| err.message)
+        .unwrap_or(text);
+    CohereError { http_code, message }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+struct ModelResponse {
+    name: String,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+struct ModelListResponse {
+    #[serde(default)]
+    models: Vec<ModelResponse>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum ChatRole {
+    User,
+    Chatbot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChatMessage {
+    role: ChatRole,
+    message: String,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+struct ChatRequest {
+    model: String,
+    /// The prompt of the user
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chat_history: Vec<ChatMessage>,
+    /// The system prompt and the context summaries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preamble: Option<String>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+}
+
+impl ChatRequest {
+    /// Split the messages into the preamble, the history and the prompt which
+    /// is the last message. The tool messages are left out, a tool turn of
+    /// Cohere carries the results of its own tool calls.
+    fn from_messages(model: &str, messages: &[Message]) -> Self {
+        let (context, mut turns): (Vec<_>, Vec<_>) = messages
+            .iter()
+            .filter(|m| !m.is_tool())
+            .partition(|m| m.is_context());
+        let message = turns
+            .pop()
+            .map(|m| m.text().to_string())
+            .unwrap_or_default();
+
+        let preamble = context
+            .iter()
+            .map(|m| m.text())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        ChatRequest {
+            model: model.to_string(),
+            message,
+            chat_history: turns.into_iter().map(ChatMessage::from).collect(),
+            preamble: (!preamble.is_empty()).then_some(preamble),
+            stream: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&Message> for ChatMessage {
+    fn from(value: &Message) -> Self {
+        let role = if value.is_system() {
+            ChatRole::Chatbot
+        } else {
+            ChatRole::User
+        };
+        ChatMessage {
+            role,
+            message: value.text().to_string(),
+        }
+    }
+}
+
+/// The events of the streamed response, one JSON object per line
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "event_type", rename_all = "kebab-case")]
+enum StreamEvent {
+    TextGeneration {
+        text: String,
+    },
+    StreamEnd {
+        #[serde(default)]
+        finish_reason: String,
+        response: Option<ChatResponse>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+struct ChatResponse {
+    meta: Option<ChatMeta>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+struct ChatMeta {
+    tokens: Option<ChatTokens>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+struct ChatTokens {
+    #[serde(default)]
+    input_tokens: f64,
+    #[serde(default)]
+    output_tokens: f64,
+}
+
+impl From<&ChatTokens> for BackendUsage {
+    fn from(value: &ChatTokens) -> Self {
+        // Cohere counts the tokens as floats
+        let prompt_tokens = value.input_tokens as usize;
+        let completion_tokens = value.output_tokens as usize;
+        BackendUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+#[derive(Default, Error, Debug)]
+pub struct CohereError {
+    pub http_code: u16,
+    pub message: String,
+}
+
+impl Display for CohereError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cohere error ({}): {}", self.http_code, self.message)
+    }
+}
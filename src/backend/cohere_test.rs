@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use mockito::Matcher;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use super::*;
+
+#[tokio::test]
+async fn test_list_models() {
+    let mut server = mockito::Server::new_async().await;
+    let models_handler = server
+        .mock("GET", "/v1/models")
+        .match_query(Matcher::UrlEncoded("endpoint".into(), "chat".into()))
+        .match_header("authorization", "Bearer test_token")
+        .with_status(200)
+        .with_body(
+            r#"{"models":[{"name":"command-r-plus"},{"name":"command-r"},{"name":"command-light"}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let backend = setup_backend(server.url())
+        .with_want_models(vec!["command-r-plus".to_string(), "command-r".to_string()]);
+
+    let models = backend.list_models().await.expect("Failed to list models");
+    let ids = models.iter().map(|m| m.id()).collect::<Vec<_>>();
+    assert_eq!(ids, vec!["command-r", "command-r-plus"]);
+    assert_eq!(models[0].provider(), "Cohere");
+
+    // The models are cached
+    backend.list_models().await.expect("Failed to list models");
+    models_handler.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_get_completion() {
+    let body = [
+        r#"{"is_finished":false,"event_type":"stream-start","generation_id":"gen1"}"#,
+        r#"{"is_finished":false,"event_type":"text-generation","text":"This is"}"#,
+        r#"{"is_finished":false,"event_type":"text-generation","text":" a test"}"#,
+        r#"{"is_finished":true,"event_type":"stream-end","finish_reason":"COMPLETE","response":{"text":"This is a test","meta":{"billed_units":{"input_tokens":10,"output_tokens":4},"tokens":{"input_tokens":12,"output_tokens":4}}}}"#,
+    ]
+    .join("\n");
+
+    let mut server = mockito::Server::new_async().await;
+    let completion_handler = server
+        .mock("POST", "/v1/chat")
+        .match_header("authorization", "Bearer test_token")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "model": "command-r",
+            "message": "How are you?",
+            "preamble": "You are a helpful assistant",
+            "chat_history": [
+                {"role": "USER", "message": "Hello"},
+                {"role": "CHATBOT", "message": "Hi there!"},
+            ],
+            "stream": true,
+        })))
+        .with_status(200)
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let prompt = BackendPrompt::new("How are you?")
+        .with_model("command-r")
+        .with_context(vec![
+            Message::new_system("system", "You are a helpful assistant").with_context(true),
+            Message::new_user("user", "Hello"),
+            Message::new_system("command-r", "Hi there!"),
+        ]);
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let sender: ArcEventTx = Arc::new(tx);
+    setup_backend(server.url())
+        .get_completion(prompt, sender)
+        .await
+        .expect("Failed to get completion");
+    completion_handler.assert_async().await;
+
+    let events = collect_responses(&mut rx, 3).await;
+    let text = events.iter().map(|e| e.text.as_str()).collect::<String>();
+    assert_eq!(text, "This is a test");
+
+    let last = events.last().unwrap();
+    assert!(last.done);
+    assert_eq!(last.model, "command-r");
+    let usage = last.usage.as_ref().expect("usage is set");
+    assert_eq!(usage.prompt_tokens, 12);
+    assert_eq!(usage.completion_tokens, 4);
+    assert_eq!(usage.total_tokens, 16);
+}
+
+#[tokio::test]
+async fn test_get_completion_error() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/chat")
+        .with_status(401)
+        .with_body(r#"{"message":"invalid api token"}"#)
+        .create_async()
+        .await;
+
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+    let err = setup_backend(server.url())
+        .get_completion(
+            BackendPrompt::new("Hello").with_model("command-r"),
+            Arc::new(tx),
+        )
+        .await
+        .expect_err("the request is rejected");
+    assert_eq!(
+        err.to_string(),
+        "Cohere error (401): invalid api token".to_string()
+    );
+}
+
+#[test]
+fn test_chat_request_from_messages() {
+    let request = ChatRequest::from_messages("command-r", &[Message::new_user("user", "Hello")]);
+    assert_eq!(request.message, "Hello");
+    assert!(request.chat_history.is_empty());
+    assert!(request.preamble.is_none());
+}
+
+#[test]
+fn test_chat_request_without_tools() {
+    let call = crate::models::ToolCall {
+        tool: "search".to_string(),
+        provider: "web".to_string(),
+        args: None,
+        result: "No results".to_string(),
+        is_error: false,
+    };
+    let request = ChatRequest::from_messages(
+        "command-r",
+        &[
+            Message::new_user("user", "Search the web"),
+            Message::new_tool(&call),
+            Message::new_system("command-r", "Nothing was found"),
+            Message::new_user("user", "Thanks"),
+        ],
+    );
+    let roles = request
+        .chat_history
+        .iter()
+        .map(|m| m.role)
+        .collect::<Vec<_>>();
+    assert_eq!(roles, vec![ChatRole::User, ChatRole::Chatbot]);
+    assert_eq!(request.message, "Thanks");
+}
+
+async fn collect_responses(
+    rx: &mut UnboundedReceiver<Event>,
+    want_len: usize,
+) -> Vec<BackendResponse> {
+    let mut responses = Vec::new();
+    while responses.len() < want_len {
+        match rx.recv().await {
+            Some(Event::ChatCompletionResponse(resp)) => responses.push(resp),
+            Some(event) => panic!("Unexpected event: {:?}", event),
+            None => break,
+        }
+    }
+    responses
+}
+
+fn setup_backend(url: String) -> Cohere {
+    Cohere::default()
+        .with_endpoint(&url)
+        .with_api_key("test_token")
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+#[path = "error_test.rs"]
+mod tests;
+
+use std::fmt::Display;
+
+use super::{bedrock::BedrockError, cohere::CohereError, gemini::GeminiError, openai::OpenAIError};
+
+/// The error answered by a provider, with the HTTP status of the response
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderError {
+    pub provider: &'static str,
+    pub http_code: u16,
+    /// The error code or type of the provider, e.g. `invalid_api_key`
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl ProviderError {
+    /// Find the error of the provider in the chain of the error
+    pub fn find(err: &eyre::Report) -> Option<Self> {
+        err.chain().find_map(|cause| {
+            if let Some(err) = cause.downcast_ref::<OpenAIError>() {
+                let code = err
+                    .code
+                    .clone()
+                    .or_else(|| Some(err.err_type.clone()))
+                    .filter(|code| !code.is_empty());
+                return Some(Self::new("OpenAI", err.http_code, code, &err.message));
+            }
+            if let Some(err) = cause.downcast_ref::<GeminiError>() {
+                let code = err.status.clone();
+                return Some(Self::new("Gemini", err.http_code, code, &err.message));
+            }
+            if let Some(err) = cause.downcast_ref::<CohereError>() {
+                return Some(Self::new("Cohere", err.http_code, None, &err.message));
+            }
+            if let Some(err) = cause.downcast_ref::<BedrockError>() {
+                return Some(Self::new("Bedrock", err.http_code, None, &err.message));
+            }
+            None
+        })
+    }
+
+    fn new(provider: &'static str, http_code: u16, code: Option<String>, message: &str) -> Self {
+        Self {
+            provider,
+            http_code,
+            code,
+            message: message.trim().to_string(),
+        }
+    }
+
+    /// What the user may do about the error, by the kind of the status
+    pub fn hint(&self) -> Option<&'static str> {
+        match self.http_code {
+            401 | 403 => Some("Check the API key of the connection and its access to the model"),
+            429 => Some("The rate limit or the quota is exceeded, wait a moment and try again"),
+            500..=599 => Some("The provider failed to answer, try again later"),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.provider, self.http_code)?;
+        if let Some(code) = &self.code {
+            write!(f, " {}", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
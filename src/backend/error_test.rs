@@ -0,0 +1,54 @@
+use eyre::WrapErr;
+
+use super::*;
+
+#[test]
+fn test_find() {
+    let err: eyre::Result<()> = Err(OpenAIError {
+        http_code: 401,
+        message: "Incorrect API key provided".to_string(),
+        err_type: "invalid_request_error".to_string(),
+        param: None,
+        code: Some("invalid_api_key".to_string()),
+    }
+    .into());
+    let err = err.wrap_err("sending completion request").unwrap_err();
+
+    let found = ProviderError::find(&err).expect("the OpenAI error is found");
+    assert_eq!(
+        found.to_string(),
+        "OpenAI 401 invalid_api_key: Incorrect API key provided"
+    );
+    assert!(found.hint().is_some_and(|hint| hint.contains("API key")));
+
+    let err = eyre::Report::new(GeminiError {
+        http_code: 429,
+        message: "Resource has been exhausted".to_string(),
+        code: Some(429),
+        status: Some("RESOURCE_EXHAUSTED".to_string()),
+    });
+    let found = ProviderError::find(&err).expect("the Gemini error is found");
+    assert_eq!(
+        found.to_string(),
+        "Gemini 429 RESOURCE_EXHAUSTED: Resource has been exhausted"
+    );
+    assert!(found.hint().is_some_and(|hint| hint.contains("rate limit")));
+
+    let err = eyre::Report::new(CohereError {
+        http_code: 503,
+        message: "unavailable".to_string(),
+    });
+    let found = ProviderError::find(&err).expect("the Cohere error is found");
+    assert_eq!(found.to_string(), "Cohere 503: unavailable");
+    assert!(found.hint().is_some());
+
+    let err = eyre::Report::new(BedrockError {
+        http_code: 400,
+        message: "Malformed input request".to_string(),
+    });
+    let found = ProviderError::find(&err).expect("the Bedrock error is found");
+    assert_eq!(found.to_string(), "Bedrock 400: Malformed input request");
+    assert!(found.hint().is_none());
+
+    assert!(ProviderError::find(&eyre::eyre!("no model is set")).is_none());
+}
@@ -2,27 +2,34 @@
 #[path = "gemini_test.rs"]
 mod tests;
 
-use std::{collections::HashMap, fmt::Display, sync::Arc, time};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{Arc, RwLock},
+    time,
+};
 
 use crate::{
-    backend::{mcp::Tool, utils::context_truncation},
-    config::{self, ModelSetting, user_agent},
+    backend::{
+        mcp::Tool,
+        redact::redact,
+        utils::{LineReader, call_tool_with_approval, context_truncation, with_extra_body},
+    },
+    config::{self, ModelSetting, resolve_path, user_agent},
     info_event,
     models::{
         ArcEventTx, BackendConnection, BackendPrompt, BackendResponse, BackendUsage, Event,
-        Message, Model,
+        Message, Model, ResponseFormat,
     },
     warn_event,
 };
 use async_trait::async_trait;
+use base64::{Engine, prelude::BASE64_STANDARD};
 use eyre::{Context, Result, bail};
-use futures::stream::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::io::AsyncBufReadExt;
-use tokio_util::io::StreamReader;
 
-use crate::backend::{Backend, TITLE_PROMPT};
+use crate::backend::Backend;
 
 use super::mcp::{self, ToolInputSchema};
 
@@ -35,11 +42,73 @@ pub struct Gemini {
 
     want_models: Vec<String>,
     max_output_tokens: Option<usize>,
+    extra_body: Option<serde_json::Map<String, serde_json::Value>>,
 
     model_settings: HashMap<String, ModelSetting>,
+    // Models fetched by the health check or the first listing, reused to
+    // avoid listing them again until they are refreshed
+    models: RwLock<Option<Vec<Model>>>,
 }
 
 impl Gemini {
+    async fn fetch_models(&self) -> Result<Vec<Model>> {
+        let mut params = vec![];
+        if let Some(key) = &self.api_key {
+            params.push(("key", key));
+        }
+
+        let url = reqwest::Url::parse_with_params(
+            format!("{}/models", &self.endpoint).as_str(),
+            params.as_slice(),
+        )
+        .wrap_err("parsing url")?;
+
+        let mut builder = reqwest::Client::new()
+            .get(url)
+            .header("User-Agent", user_agent());
+
+        if let Some(timeout) = &self.timeout {
+            builder = builder.timeout(*timeout);
+        }
+
+        let resp = builder.send().await.map_err(reqwest::Error::without_url)?;
+
+        if !resp.status().is_success() {
+            let http_code = resp.status().as_u16();
+            let err: ErrorResponse = resp.json().await.wrap_err("parsing error response")?;
+            let mut err = err.error;
+            err.http_code = http_code;
+            return Err(err.into());
+        }
+
+        let all = self.want_models.is_empty();
+
+        let mut models = resp
+            .json::<ModelListResponse>()
+            .await
+            .wrap_err("parsing model list response")?
+            .models
+            .into_iter()
+            .filter(|m| {
+                m.supported_generation_methods
+                    .contains(&"generateContent".to_string())
+                    && (all || self.want_models.contains(&m.name))
+            })
+            .map(|m| {
+                Model::new(
+                    m.name
+                        .strip_prefix("models/")
+                        .unwrap_or(&m.name)
+                        .to_string(),
+                )
+                .with_provider(&self.alias)
+            })
+            .collect::<Vec<_>>();
+
+        models.sort_by(|a, b| a.id().cmp(b.id()));
+        Ok(models)
+    }
+
     pub async fn init(&mut self) -> Result<()> {
         let models = self.list_models().await.wrap_err("listing models")?;
         for settings in &config::instance().backend.model_settings {
@@ -67,6 +136,21 @@ impl Gemini {
         self
     }
 
+    /// Drop the cached models, they are fetched again on the next listing
+    fn invalidate_models(&self) {
+        if let Ok(mut cache) = self.models.write() {
+            *cache = None;
+        }
+    }
+
+    /// The endpoint may have changed (e.g. a local server restarted with
+    /// other models) when it cannot be reached
+    fn invalidate_models_on(&self, err: &reqwest::Error) {
+        if err.is_connect() {
+            self.invalidate_models();
+        }
+    }
+
     pub fn with_want_models(mut self, models: Vec<String>) -> Self {
         self.want_models = models
             .into_iter()
@@ -109,9 +193,9 @@ impl Gemini {
     async fn chat_completion(
         &self,
         override_id: Option<String>,
-        init_conversation: bool,
         model: &str,
         contents: &[Content],
+        mut options: RequestOptions,
         event_tx: ArcEventTx,
     ) -> Result<()> {
         let settings = self.model_settings.get(model);
@@ -128,14 +212,43 @@ impl Gemini {
             vec![]
         };
 
+        // Gemini rejects the JSON mime type along with function calling
+        if options.response_format != ResponseFormat::Text && !tools.is_empty() {
+            event_tx
+                .send(warn_event!(format!(
+                    "JSON mode is not supported with tools by model \"{}\", answering in text",
+                    model
+                )))
+                .await?;
+            options.response_format = ResponseFormat::Text;
+        }
+
         let completion_req = CompletionRequest {
+            system_instruction: options.system_instruction.clone(),
             contents: contents.to_vec(),
             generation_config: Some(GenerationConfig {
-                max_output_tokens: self.max_output_tokens,
+                max_output_tokens: settings
+                    .and_then(|s| s.max_tokens)
+                    .or(self.max_output_tokens),
+                temperature: settings.and_then(|s| s.temperature),
+                top_p: settings.and_then(|s| s.top_p),
+                stop_sequences: settings.map(|s| s.stop.clone()).unwrap_or_default(),
+                thinking_config: settings
+                    .and_then(|s| s.thinking_budget)
+                    .map(|thinking_budget| ThinkingConfig { thinking_budget }),
+                response_mime_type: (options.response_format == ResponseFormat::JsonObject)
+                    .then(|| "application/json".to_string()),
             }),
             tools: tools.iter().map(ToolRequest::from).collect(),
             tool_config: None,
         };
+        let completion_req = with_extra_body(
+            &completion_req,
+            &[
+                self.extra_body.as_ref(),
+                settings.and_then(|s| s.extra_body.as_ref()),
+            ],
+        )?;
 
         let mut params = vec![];
         if let Some(key) = &self.api_key {
@@ -149,14 +262,18 @@ impl Gemini {
         .wrap_err("parsing url")?;
 
         let mut builder = reqwest::Client::new()
-            .post(url)
+            .post(url.clone())
             .header("User-Agent", user_agent());
 
         if let Some(timeout) = self.timeout {
             builder = builder.timeout(timeout);
         }
 
-        log::trace!("Sending completion request: {:?}", completion_req);
+        log::trace!(
+            "Sending completion request to {}: {}",
+            redact(url.as_str()),
+            redact(&serde_json::to_string(&completion_req).unwrap_or_default())
+        );
 
         let mut function_calls = vec![];
 
@@ -164,6 +281,9 @@ impl Gemini {
             .json(&completion_req)
             .send()
             .await
+            .inspect_err(|err| self.invalidate_models_on(err))
+            // The URL contains the API key
+            .map_err(reqwest::Error::without_url)
             .wrap_err("sending completion request")?;
 
         if !resp.status().is_success() {
@@ -177,23 +297,15 @@ impl Gemini {
             return Err(err.into());
         }
 
-        let stream = resp.bytes_stream().map_err(|e| {
-            let err_msg = e.to_string();
-            std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg)
-        });
-
-        let mut lines_reader = StreamReader::new(stream).lines();
+        let mut lines_reader = LineReader::new(resp.bytes_stream());
 
         let message_id = override_id.unwrap_or(uuid::Uuid::new_v4().to_string());
         let mut line_buf: Vec<String> = Vec::new();
         let mut completion_text = String::new();
-        while let Ok(line) = lines_reader.next_line().await {
-            if line.is_none() {
-                break;
-            }
-
-            let cleaned_line = line.unwrap().trim().to_string();
-            log::trace!("Received line: {}", cleaned_line);
+        let mut last_content = None;
+        while let Some(line) = lines_reader.next_line().await? {
+            let cleaned_line = line.trim().to_string();
+            log::trace!("Received line: {}", redact(&cleaned_line));
             // Gemini separte array object by a line with a comma
             if cleaned_line != "," {
                 line_buf.push(cleaned_line);
@@ -204,21 +316,14 @@ impl Gemini {
             let content = process_line_buffer(&line_buf)?;
             line_buf.clear();
             if content.candidates.is_empty() || content.candidates[0].finish_reason.is_some() {
+                last_content = Some(content);
                 break;
             }
 
-            let text = match content.candidates[0].content.parts[0] {
-                ContentParts::Text(ref text) => {
-                    completion_text.push_str(text);
-                    text.to_string()
-                }
-                ContentParts::FunctionCall(ref func_call) => {
-                    function_calls.push(func_call.clone());
-                    String::new()
-                }
-                // TODO(vietanhduong): Handle this properly
-                _ => String::new(),
-            };
+            let text = self
+                .process_parts(content.parts(), &mut function_calls, event_tx.clone())
+                .await?;
+            completion_text.push_str(&text);
 
             if text.is_empty() {
                 continue;
@@ -237,39 +342,34 @@ impl Gemini {
 
             event_tx
                 .send(Event::ChatCompletionResponse(
-                    BackendResponse::new(&message_id, model)
-                        .with_text(&text)
-                        .with_init_conversation(init_conversation),
+                    BackendResponse::new(&message_id, model).with_text(&text),
                 ))
                 .await?;
         }
 
-        let content = process_line_buffer(&line_buf)?;
+        let content = match last_content {
+            Some(content) => content,
+            None => process_line_buffer(&line_buf)?,
+        };
         line_buf.clear();
 
-        let text = match content.candidates[0].content.parts[0] {
-            ContentParts::Text(ref text) => text.clone(),
-            ContentParts::FunctionCall(ref func_call) => {
-                function_calls.push(func_call.clone());
-                String::new()
-            }
-            // TODO(vietanhduong): Handle this properly
-            _ => String::new(),
-        };
+        // A blocked response or prompt has no content
+        let text = self
+            .process_parts(content.parts(), &mut function_calls, event_tx.clone())
+            .await?;
+        if let Some(reason) = content.blocked_reason() {
+            log::warn!("Gemini {}", reason);
+            event_tx.send(warn_event!(reason)).await?;
+        }
 
         if function_calls.is_empty() {
-            let usage = BackendUsage {
-                prompt_tokens: content.usage_metadata.prompt_token_count,
-                completion_tokens: content.usage_metadata.candidates_token_count,
-                total_tokens: content.usage_metadata.total_token_count,
-            };
+            let usage = BackendUsage::from(&content.usage_metadata);
 
             event_tx
                 .send(Event::ChatCompletionResponse(
                     BackendResponse::new(&message_id, model)
                         .with_done()
                         .with_text(text)
-                        .with_init_conversation(init_conversation)
                         .with_usage(usage),
                 ))
                 .await?;
@@ -278,9 +378,7 @@ impl Gemini {
 
         event_tx
             .send(Event::ChatCompletionResponse(
-                BackendResponse::new(&message_id, model)
-                    .with_init_conversation(init_conversation)
-                    .with_text("\n"),
+                BackendResponse::new(&message_id, model).with_boundary(),
             ))
             .await?;
 
@@ -310,14 +408,47 @@ impl Gemini {
                 .collect(),
         });
 
-        Box::pin(self.chat_completion(
-            Some(message_id),
-            init_conversation,
-            model,
-            &contents,
-            event_tx,
-        ))
-        .await
+        Box::pin(self.chat_completion(Some(message_id), model, &contents, options, event_tx)).await
+    }
+
+    /// Collect the text of the given parts. Function calls are pushed into
+    /// `function_calls` and inline data (e.g. generated images) are saved
+    /// to the media directory and referenced in the returned text.
+    async fn process_parts(
+        &self,
+        parts: &[ContentParts],
+        function_calls: &mut Vec<FunctionCall>,
+        event_tx: ArcEventTx,
+    ) -> Result<String> {
+        let mut text = String::new();
+        for part in parts {
+            match part {
+                ContentParts::Text(value) => text.push_str(value),
+                ContentParts::FunctionCall(func_call) => function_calls.push(func_call.clone()),
+                ContentParts::InlineData(blob) => match save_inline_data(blob) {
+                    Ok(path) => {
+                        event_tx
+                            .send(info_event!(format!(
+                                "Received {} and saved to {}",
+                                blob.mime_type, path
+                            )))
+                            .await?;
+                        text.push_str(&format!("\n![{}](file://{})\n", blob.mime_type, path));
+                    }
+                    Err(err) => {
+                        log::error!("Failed to save inline data: {:?}", err);
+                        event_tx
+                            .send(warn_event!(format!(
+                                "Received {} but unable to save it: {}",
+                                blob.mime_type, err
+                            )))
+                            .await?;
+                    }
+                },
+                ContentParts::FunctionResponse(_) => {}
+            }
+        }
+        Ok(text)
     }
 
     async fn call_tool(
@@ -339,12 +470,11 @@ impl Gemini {
         let mut results = vec![];
         for call in calls {
             let tool_name = call.name.clone();
+            let provider = match tools.iter().find(|t| t.name == tool_name) {
+                Some(tool) => tool.provider.clone(),
+                None => "unknown".to_string(),
+            };
             if notice_on_call {
-                let provider = match tools.iter().find(|t| t.name == tool_name) {
-                    Some(tool) => tool.provider.clone(),
-                    None => "unknown".to_string(),
-                };
-
                 event_tx
                     .send(info_event!(format!(
                         "Calling tool \"{}\" (provider: {})",
@@ -356,14 +486,15 @@ impl Gemini {
             // TODO: should we log the full description of the tool?
             log::debug!("Calling tool {} with args: {:?}", tool_name, call.args);
 
-            let resp = self
-                .mcp
-                .as_ref()
-                .unwrap()
-                .clone()
-                .call_tool(&call.name, call.args.clone())
-                .await
-                .wrap_err("calling tool")?;
+            let resp = call_tool_with_approval(
+                self.mcp.as_ref().unwrap(),
+                &call.name,
+                &provider,
+                call.args.clone(),
+                &event_tx,
+            )
+            .await
+            .wrap_err("calling tool")?;
             let mut response = HashMap::new();
             response.insert("result".to_string(), resp.content);
             let result = serde_json::to_value(&response).wrap_err("serializing tool result")?;
@@ -383,94 +514,106 @@ impl Backend for Gemini {
         &self.alias
     }
 
-    async fn list_models(&self) -> Result<Vec<Model>> {
-        let mut params = vec![];
-        if let Some(key) = &self.api_key {
-            params.push(("key", key));
+    async fn health_check(&self) -> Result<()> {
+        let models = self.fetch_models().await?;
+        if let Ok(mut cache) = self.models.write() {
+            *cache = Some(models);
         }
+        Ok(())
+    }
 
-        let url = reqwest::Url::parse_with_params(
-            format!("{}/models", &self.endpoint).as_str(),
-            params.as_slice(),
-        )
-        .wrap_err("parsing url")?;
-
-        let mut builder = reqwest::Client::new()
-            .get(url)
-            .header("User-Agent", user_agent());
-
-        if let Some(timeout) = &self.timeout {
-            builder = builder.timeout(*timeout);
+    async fn list_models(&self) -> Result<Vec<Model>> {
+        if let Some(models) = self.models.read().ok().and_then(|cache| cache.clone()) {
+            return Ok(models);
         }
-
-        let resp = builder.send().await?;
-
-        if !resp.status().is_success() {
-            let http_code = resp.status().as_u16();
-            let err: ErrorResponse = resp.json().await.wrap_err("parsing error response")?;
-            let mut err = err.error;
-            err.http_code = http_code;
-            return Err(err.into());
+        let models = self.fetch_models().await?;
+        if let Ok(mut cache) = self.models.write() {
+            *cache = Some(models.clone());
         }
-
-        let all = self.want_models.is_empty();
-
-        let mut models = resp
-            .json::<ModelListResponse>()
-            .await
-            .wrap_err("parsing model list response")?
-            .models
-            .into_iter()
-            .filter(|m| {
-                m.supported_generation_methods
-                    .contains(&"generateContent".to_string())
-                    && (all || self.want_models.contains(&m.name))
-            })
-            .map(|m| {
-                Model::new(
-                    m.name
-                        .strip_prefix("models/")
-                        .unwrap_or(&m.name)
-                        .to_string(),
-                )
-                .with_provider(&self.alias)
-            })
-            .collect::<Vec<_>>();
-
-        models.sort_by(|a, b| a.id().cmp(b.id()));
         Ok(models)
     }
 
+    async fn refresh_models(&self) -> Result<Vec<Model>> {
+        self.invalidate_models();
+        self.list_models().await
+    }
+
     async fn get_completion(&self, prompt: BackendPrompt, event_tx: ArcEventTx) -> Result<()> {
         if prompt.model().is_empty() {
             bail!("no model is set");
         }
 
-        let init_conversation = prompt.context().is_empty();
-        let content = if init_conversation && !prompt.no_generate_title() {
-            format!("{}\n{}", prompt.text(), TITLE_PROMPT)
-        } else {
-            prompt.text().to_string()
-        };
-
         let mut messages = prompt.context().to_vec();
-        messages.push(Message::new_user("user", content));
+        messages.push(Message::new_user("user", prompt.text()));
 
         if let Some(max_output_tokens) = self.max_output_tokens {
             context_truncation(&mut messages, max_output_tokens);
         }
 
-        let contents = messages
-            .into_iter()
-            .map(|m| Content::from(&m))
-            .collect::<Vec<_>>();
+        // The system prompt and the context summaries are sent as the system
+        // instruction, only the turns are left in the contents
+        let (context, messages): (Vec<_>, Vec<_>) =
+            messages.into_iter().partition(|m| m.is_context());
+        let system_instruction = (!context.is_empty()).then(|| Content {
+            role: String::new(),
+            parts: context
+                .iter()
+                .map(|m| ContentParts::Text(m.text().to_string()))
+                .collect(),
+        });
+        let contents = messages.iter().map(Content::from).collect::<Vec<_>>();
 
-        self.chat_completion(None, init_conversation, prompt.model(), &contents, event_tx)
-            .await?;
+        let response_format = prompt
+            .response_format()
+            .or_else(|| {
+                self.model_settings
+                    .get(prompt.model())
+                    .and_then(|s| s.response_format)
+            })
+            .unwrap_or_default();
+        self.chat_completion(
+            None,
+            prompt.model(),
+            &contents,
+            RequestOptions {
+                system_instruction,
+                response_format,
+            },
+            event_tx,
+        )
+        .await?;
         Ok(())
     }
 }
 
+/// Decode the base64 payload of an inline data part and write it into the
+/// configured media directory. Returns the absolute path of the saved file.
+fn save_inline_data(blob: &ContentPartsBlob) -> Result<String> {
+    let data = BASE64_STANDARD
+        .decode(blob.data.as_bytes())
+        .wrap_err("decoding inline data")?;
+
+    let dir = match config::instance().backend.media_dir.as_deref() {
+        Some(dir) if !dir.is_empty() => resolve_path(dir).wrap_err("resolving media dir")?,
+        _ => std::env::temp_dir()
+            .join(config::APP_NAME)
+            .to_string_lossy()
+            .to_string(),
+    };
+    std::fs::create_dir_all(&dir).wrap_err(format!("creating directory {}", dir))?;
+
+    let extension = blob
+        .mime_type
+        .split_once('/')
+        .map(|(_, ext)| ext.split(['+', ';']).next().unwrap_or(ext))
+        .filter(|ext| !ext.is_empty())
+        .unwrap_or("bin");
+
+    let path = std::path::Path::new(&dir).join(format!("{}.{}", uuid::Uuid::new_v4(), extension));
+    std::fs::write(&path, data).wrap_err(format!("writing {}", path.display()))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
 fn process_line_buffer(lines: &[String]) -> Result<GenerateContentResponse> {
     let json_raw = lines.join("").trim().to_string();
     let json_raw = json_raw.strip_prefix("[").unwrap_or(&json_raw).trim();
@@ -486,6 +629,7 @@ impl Default for Gemini {
     fn default() -> Self {
         Gemini {
             max_output_tokens: None,
+            extra_body: None,
             alias: "Gemini".to_string(),
             endpoint: "https://generativelanguage.googleapis.com/v1beta".to_string(),
             mcp: None,
@@ -494,6 +638,7 @@ impl Default for Gemini {
 
             model_settings: HashMap::new(),
             want_models: Vec::new(),
+            models: RwLock::new(None),
         }
     }
 }
@@ -517,6 +662,7 @@ impl From<&BackendConnection> for Gemini {
         }
 
         backend.max_output_tokens = value.max_output_tokens();
+        backend.extra_body = value.extra_body().cloned();
 
         backend.with_want_models(value.models().to_vec())
     }
@@ -573,13 +719,24 @@ enum ContentParts {
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 struct Content {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     role: String,
+    #[serde(default)]
     parts: Vec<ContentParts>,
 }
 
+/// The options of a completion request, kept across the tool call turns
+#[derive(Debug, Clone)]
+struct RequestOptions {
+    system_instruction: Option<Content>,
+    response_format: ResponseFormat,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CompletionRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Content>,
     contents: Vec<Content>,
     #[serde(skip_serializing_if = "Option::is_none")]
     generation_config: Option<GenerationConfig>,
@@ -608,30 +765,115 @@ struct FunctionDeclerationRequest {
 #[serde(rename_all = "camelCase")]
 struct GenerationConfig {
     max_output_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_config: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ThinkingConfig {
+    thinking_budget: usize,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GenerateContentResponse {
+    #[serde(default)]
     candidates: Vec<GenerateCandidate>,
+    #[serde(default)]
     usage_metadata: GenerateUsageMetadata,
+    #[serde(default)]
     model_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prompt_feedback: Option<PromptFeedback>,
+}
+
+impl GenerateContentResponse {
+    /// The parts of the first candidate, empty if the candidate is blocked
+    fn parts(&self) -> &[ContentParts] {
+        self.candidates
+            .first()
+            .map(|candidate| candidate.content.parts.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The reason the prompt or the response is blocked, if any
+    fn blocked_reason(&self) -> Option<String> {
+        if let Some(reason) = self
+            .prompt_feedback
+            .as_ref()
+            .and_then(|feedback| feedback.block_reason.as_deref())
+        {
+            return Some(format!("prompt blocked: {}", reason));
+        }
+
+        let reason = self.candidates.first()?.finish_reason.as_deref()?;
+        BLOCK_FINISH_REASONS
+            .contains(&reason)
+            .then(|| format!("response blocked: {}", reason))
+    }
 }
 
+/// The finish reasons of the candidates stopped by the content filters
+const BLOCK_FINISH_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GenerateCandidate {
+    #[serde(default)]
     content: Content,
     finish_reason: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GenerateUsageMetadata {
+    #[serde(default)]
     prompt_token_count: usize,
     #[serde(default)]
     candidates_token_count: usize,
+    #[serde(default)]
     total_token_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cached_content_token_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thoughts_token_count: Option<usize>,
+}
+
+impl From<&GenerateUsageMetadata> for BackendUsage {
+    fn from(value: &GenerateUsageMetadata) -> Self {
+        // The thoughts are billed as output tokens but are not part of the
+        // candidates
+        let thoughts = value.thoughts_token_count.unwrap_or_default();
+        BackendUsage {
+            prompt_tokens: value.prompt_token_count,
+            completion_tokens: value.candidates_token_count + thoughts,
+            total_tokens: value.total_token_count,
+            cached_prompt_tokens: value.cached_content_token_count,
+            reasoning_tokens: value.thoughts_token_count,
+        }
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
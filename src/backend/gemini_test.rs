@@ -103,7 +103,179 @@ async fn test_get_completion() {
     assert_eq!(last.text, "test");
     assert_eq!(last.done, true);
     assert_eq!(last.model, "gemini-2.0-flash");
-    assert_eq!(last.init_conversation, true);
+}
+
+#[tokio::test]
+async fn test_get_completion_model_settings() {
+    let mut cfg = config::Configuration::default();
+    cfg.backend.model_settings = vec![
+        toml::from_str(
+            r#"
+            model.equals = "gemini-2.0-flash"
+            temperature = 0.25
+            top_p = 0.5
+            max_tokens = 128
+            stop = ["END"]
+            thinking_budget = 512
+            "#,
+        )
+        .expect("Failed to parse model setting"),
+    ];
+    config::init(cfg).expect("Failed to init config");
+
+    let mut server = mockito::Server::new_async().await;
+    let models_handler = server
+        .mock("GET", "/models")
+        .match_query(Matcher::Any)
+        .with_status(200)
+        .with_body(
+            r#"{"models":[{"name":"models/gemini-2.0-flash","supportedGenerationMethods":["generateContent"]},{"name":"models/gemini-2.0-flash-lite","supportedGenerationMethods":["generateContent"]}]}"#,
+        )
+        .create();
+    let completion_handler = server
+        .mock("POST", "/models/gemini-2.0-flash:streamGenerateContent")
+        .match_query(Matcher::Any)
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "generationConfig": {
+                "maxOutputTokens": 128,
+                "temperature": 0.25,
+                "topP": 0.5,
+                "stopSequences": ["END"],
+                "thinkingConfig": {"thinkingBudget": 512},
+            }
+        })))
+        .with_status(200)
+        .with_body(
+            std::fs::read_to_string("./testdata/gemini_response.json")
+                .expect("Failed to read test data"),
+        )
+        .create();
+    // The settings are only applied to the matched model
+    let default_handler = server
+        .mock(
+            "POST",
+            "/models/gemini-2.0-flash-lite:streamGenerateContent",
+        )
+        .match_query(Matcher::Any)
+        .match_request(|req| {
+            let body = String::from_utf8_lossy(req.body().expect("Failed to read body"));
+            !body.contains("thinkingConfig") && !body.contains("temperature")
+        })
+        .with_status(200)
+        .with_body(
+            std::fs::read_to_string("./testdata/gemini_response.json")
+                .expect("Failed to read test data"),
+        )
+        .create();
+
+    let mut backend = setup_backend(server.url()).await;
+    backend.init().await.expect("Failed to init backend");
+    models_handler.assert();
+
+    for model in ["gemini-2.0-flash", "gemini-2.0-flash-lite"] {
+        let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+        let prompt = BackendPrompt::new("Hello").with_model(model);
+        backend
+            .get_completion(prompt, Arc::new(tx))
+            .await
+            .expect("Failed to get completion");
+    }
+    completion_handler.assert();
+    default_handler.assert();
+}
+
+#[tokio::test]
+async fn test_get_completion_system_instruction() {
+    let mut server = mockito::Server::new_async().await;
+    let completion_handler = server
+        .mock("POST", "/models/gemini-2.0-flash:streamGenerateContent")
+        .match_query(Matcher::Any)
+        .match_request(|req| {
+            let body: serde_json::Value =
+                serde_json::from_slice(req.body().expect("Failed to read body"))
+                    .expect("Failed to parse body");
+            body["systemInstruction"] == serde_json::json!({"parts": [{"text": "Be brief"}]})
+                && body["contents"]
+                    == serde_json::json!([
+                        {"role": "user", "parts": [{"text": "Hi"}]},
+                        {"role": "model", "parts": [{"text": "Hello"}]},
+                        {"role": "user", "parts": [{"text": "How are you?"}]},
+                    ])
+        })
+        .with_status(200)
+        .with_body(
+            std::fs::read_to_string("./testdata/gemini_response.json")
+                .expect("Failed to read test data"),
+        )
+        .create();
+
+    let backend = setup_backend(server.url()).await;
+    let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+    let prompt = BackendPrompt::new("How are you?")
+        .with_model("gemini-2.0-flash")
+        .with_context(vec![
+            Message::new_system("system", "Be brief").with_context(true),
+            Message::new_user("user", "Hi"),
+            Message::new_system("gemini-2.0-flash", "Hello"),
+        ]);
+    backend
+        .get_completion(prompt, Arc::new(tx))
+        .await
+        .expect("Failed to get completion");
+    completion_handler.assert();
+}
+
+#[tokio::test]
+async fn test_get_completion_blocked() {
+    for (fixture, reason, text) in [
+        (
+            "./testdata/gemini_safety_response.json",
+            "response blocked: SAFETY",
+            "This ",
+        ),
+        (
+            "./testdata/gemini_prompt_blocked_response.json",
+            "prompt blocked: SAFETY",
+            "",
+        ),
+    ] {
+        let body = std::fs::read_to_string(fixture).expect("Failed to read test data");
+
+        let mut server = mockito::Server::new_async().await;
+        let completion_handler = server
+            .mock("POST", "/models/gemini-2.0-flash:streamGenerateContent")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body(body)
+            .create();
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let backend = setup_backend(server.url()).await;
+        let prompt = BackendPrompt::new("Hello").with_model("gemini-2.0-flash");
+        backend
+            .get_completion(prompt, Arc::new(tx))
+            .await
+            .expect("Failed to get completion");
+        completion_handler.assert();
+
+        let mut notices = vec![];
+        let mut responses = vec![];
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                Event::Notice(notice) => notices.push(notice.message().to_string()),
+                Event::ChatCompletionResponse(resp) => responses.push(resp),
+                event => panic!("Unexpected event: {:?}", event),
+            }
+        }
+        assert_eq!(notices, vec![reason.to_string()]);
+
+        let streamed = responses
+            .iter()
+            .map(|resp| resp.text.as_str())
+            .collect::<String>();
+        assert_eq!(streamed, text);
+        assert!(responses.last().is_some_and(|resp| resp.done));
+    }
 }
 
 async fn collect_responses(
@@ -137,3 +309,29 @@ async fn setup_backend(url: String) -> Gemini {
         ]);
     backend
 }
+
+#[test]
+fn test_save_inline_data() {
+    let dir = std::env::temp_dir().join(format!("chatty-test-{}", uuid::Uuid::new_v4()));
+    let mut cfg = config::Configuration::default();
+    cfg.backend.media_dir = Some(dir.to_string_lossy().to_string());
+    config::init(cfg).expect("failed to init config");
+
+    let blob = ContentPartsBlob {
+        mime_type: "image/png".to_string(),
+        data: BASE64_STANDARD.encode(b"fake image"),
+    };
+
+    let path = save_inline_data(&blob).expect("failed to save inline data");
+    assert!(path.starts_with(dir.to_string_lossy().as_ref()));
+    assert!(path.ends_with(".png"));
+    assert_eq!(std::fs::read(&path).unwrap(), b"fake image");
+
+    let blob = ContentPartsBlob {
+        mime_type: "image/png".to_string(),
+        data: "not base64!".to_string(),
+    };
+    assert!(save_inline_data(&blob).is_err());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+#[path = "inspect_test.rs"]
+mod tests;
+
+use std::fmt::Write;
+
+use eyre::Result;
+use regex::Regex;
+
+use crate::config::ModelSetting;
+use crate::models::Model;
+
+/// The models listed from a connection, used to show how the model settings
+/// apply without starting the app
+pub struct ConnectionModels {
+    pub name: String,
+    /// The `models` list of the connection, the other models are hidden
+    pub want_models: Vec<String>,
+    pub models: Result<Vec<Model>>,
+}
+
+/// Format the models of each connection with the model setting they resolve
+/// to. Only the models matching the pattern are shown if set.
+pub fn format_models(
+    connections: &[ConnectionModels],
+    settings: &[ModelSetting],
+    pattern: Option<&Regex>,
+) -> String {
+    let filters = settings
+        .iter()
+        .map(|setting| setting.model.build().ok())
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    for (i, setting) in settings.iter().enumerate() {
+        if filters[i].is_none() {
+            let _ = writeln!(
+                out,
+                "warning: model_settings[{}] ({}) is not a valid filter",
+                i, setting.model
+            );
+        }
+    }
+
+    for conn in connections {
+        let _ = writeln!(out, "{}", conn.name);
+        let models = match &conn.models {
+            Ok(models) => models,
+            Err(err) => {
+                let _ = writeln!(out, "  error: {:#}", err);
+                continue;
+            }
+        };
+
+        let models = models
+            .iter()
+            .filter(|model| pattern.is_none_or(|re| re.is_match(model.id())))
+            .collect::<Vec<_>>();
+        if models.is_empty() {
+            let _ = writeln!(out, "  (no models)");
+            continue;
+        }
+
+        let width = models
+            .iter()
+            .map(|m| m.id().len())
+            .max()
+            .unwrap_or_default();
+        for model in models {
+            let mut notes = vec![];
+            if !conn.want_models.is_empty() && !conn.want_models.iter().any(|m| m == model.id()) {
+                notes.push("hidden by models".to_string());
+            }
+            // The first matching setting applies, as in `model_setting`
+            if let Some(i) = filters
+                .iter()
+                .position(|re| re.as_ref().is_some_and(|re| re.is_match(model.id())))
+            {
+                notes.push(format!("model_settings[{}] ({})", i, settings[i].model));
+            }
+            let line = format!("  {:width$}  {}", model.id(), notes.join(", "));
+            let _ = writeln!(out, "{}", line.trim_end());
+        }
+    }
+    out
+}
@@ -0,0 +1,53 @@
+use super::*;
+use crate::config::model_filter::ModelFilter;
+
+#[test]
+fn test_format_models() {
+    let connections = vec![
+        ConnectionModels {
+            name: "openai (OpenAI)".to_string(),
+            want_models: vec!["gpt-4o".to_string(), "o1".to_string()],
+            models: Ok(vec![
+                Model::new("gpt-4o").with_provider("openai"),
+                Model::new("gpt-4o-mini").with_provider("openai"),
+                Model::new("o1").with_provider("openai"),
+            ]),
+        },
+        ConnectionModels {
+            name: "Gemini".to_string(),
+            want_models: vec![],
+            models: Err(eyre::eyre!("unauthorized")),
+        },
+    ];
+    let settings = vec![
+        ModelSetting {
+            model: ModelFilter::Regex("gpt-(4".to_string()),
+            ..Default::default()
+        },
+        ModelSetting {
+            model: ModelFilter::Contains("gpt-4o".to_string()),
+            ..Default::default()
+        },
+        ModelSetting {
+            model: ModelFilter::Equals("gpt-4o-mini".to_string()),
+            ..Default::default()
+        },
+    ];
+
+    let out = format_models(&connections, &settings, None);
+    assert_eq!(
+        out,
+        r#"warning: model_settings[0] (regex "gpt-(4") is not a valid filter
+openai (OpenAI)
+  gpt-4o       model_settings[1] (contains "gpt-4o")
+  gpt-4o-mini  hidden by models, model_settings[1] (contains "gpt-4o")
+  o1
+Gemini
+  error: unauthorized
+"#
+    );
+
+    let pattern = Regex::new("^o").unwrap();
+    let out = format_models(&connections[..1], &[], Some(&pattern));
+    assert_eq!(out, "openai (OpenAI)\n  o1\n");
+}
@@ -0,0 +1,59 @@
+#[cfg(test)]
+#[path = "limiter_test.rs"]
+mod tests;
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A token bucket allowing `rpm` requests per minute, with bursts of up to
+/// `rpm` requests. The requests over the limit reserve the next free slots,
+/// so the queued requests are sent in order.
+pub struct RateLimiter {
+    rpm: u32,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Negative when requests are queued
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rpm: u32) -> Self {
+        Self::new_at(rpm, Instant::now())
+    }
+
+    fn new_at(rpm: u32, now: Instant) -> Self {
+        Self {
+            rpm,
+            bucket: Mutex::new(Bucket {
+                tokens: rpm as f64,
+                updated_at: now,
+            }),
+        }
+    }
+
+    /// Reserve a slot for a request and return how long to wait before
+    /// sending it
+    pub fn reserve(&self) -> Duration {
+        self.reserve_at(Instant::now())
+    }
+
+    fn reserve_at(&self, now: Instant) -> Duration {
+        let Ok(mut bucket) = self.bucket.lock() else {
+            return Duration::ZERO;
+        };
+
+        let per_second = self.rpm as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * per_second).min(self.rpm as f64);
+        bucket.updated_at = now;
+
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-bucket.tokens / per_second)
+    }
+}
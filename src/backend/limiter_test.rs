@@ -0,0 +1,25 @@
+use super::*;
+
+#[test]
+fn test_reserve() {
+    let start = Instant::now();
+    let limiter = RateLimiter::new_at(2, start);
+
+    // The burst is allowed
+    assert_eq!(limiter.reserve_at(start), Duration::ZERO);
+    assert_eq!(limiter.reserve_at(start), Duration::ZERO);
+
+    // The next requests are queued one after another
+    assert_eq!(limiter.reserve_at(start), Duration::from_secs(30));
+    assert_eq!(limiter.reserve_at(start), Duration::from_secs(60));
+
+    // The queued requests used the refilled tokens
+    let later = start + Duration::from_secs(60);
+    assert_eq!(limiter.reserve_at(later), Duration::from_secs(30));
+
+    // The bucket doesn't grow over the limit
+    let idle = later + Duration::from_secs(600);
+    assert_eq!(limiter.reserve_at(idle), Duration::ZERO);
+    assert_eq!(limiter.reserve_at(idle), Duration::ZERO);
+    assert!(limiter.reserve_at(idle) > Duration::ZERO);
+}
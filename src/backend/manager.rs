@@ -2,19 +2,79 @@
 #[path = "manager_test.rs"]
 mod tests;
 
+use crate::backend::limiter::RateLimiter;
 use crate::backend::{ArcBackend, Backend};
-use crate::models::{ArcEventTx, BackendPrompt, Model};
+use crate::config::constants::ROUTING_COOLDOWN_SECS;
+use crate::config::{RoutingConfig, RoutingPolicy};
+use crate::models::{ArcEventTx, BackendPrompt, Event, EventTx, Model};
+use crate::{info_event, warn_event};
 use async_trait::async_trait;
 use eyre::{Context, Result, bail};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit, mpsc};
 
-#[derive(Default)]
 pub struct Manager {
     connections: HashMap<String, ArcBackend>, /* Alias - Backend */
-    models: HashMap<String, String>,          /* Model ID - Alias  */
+    models: RwLock<HashMap<String, Vec<String>>>, /* Model ID - Aliases */
+
+    policy: RoutingPolicy,
+    cooldown: Duration,
+    next_connection: AtomicUsize,
+    failures: Mutex<HashMap<String, Instant>>, /* Alias - Last failure */
+
+    concurrency: Option<Semaphore>,
+    rpm: Option<u32>,
+    rate_limiters: HashMap<String, RateLimiter>, /* Alias - Rate limiter */
+
+    embedding_connection: Option<String>,
+}
+
+impl Default for Manager {
+    fn default() -> Self {
+        Self {
+            connections: HashMap::new(),
+            models: RwLock::new(HashMap::new()),
+            policy: RoutingPolicy::default(),
+            cooldown: Duration::from_secs(ROUTING_COOLDOWN_SECS),
+            next_connection: AtomicUsize::new(0),
+            failures: Mutex::new(HashMap::new()),
+            concurrency: None,
+            rpm: None,
+            rate_limiters: HashMap::new(),
+            embedding_connection: None,
+        }
+    }
 }
 
 impl Manager {
+    pub fn with_routing(mut self, config: &RoutingConfig) -> Self {
+        self.policy = config.policy;
+        self.cooldown = Duration::from_secs(config.cooldown_secs.unwrap_or(ROUTING_COOLDOWN_SECS));
+        self
+    }
+
+    /// Limit the number of requests in flight, 0 means no limit
+    pub fn with_max_concurrent(mut self, max_concurrent: Option<usize>) -> Self {
+        self.concurrency = max_concurrent.filter(|n| *n > 0).map(Semaphore::new);
+        self
+    }
+
+    /// Limit the number of requests per minute of each connection added
+    /// afterward, 0 means no limit
+    pub fn with_rpm(mut self, rpm: Option<u32>) -> Self {
+        self.rpm = rpm.filter(|n| *n > 0);
+        self
+    }
+
+    /// The connection computing the embeddings of a model no connection lists
+    pub fn with_embedding_connection(mut self, alias: Option<&str>) -> Self {
+        self.embedding_connection = alias.filter(|a| !a.is_empty()).map(String::from);
+        self
+    }
+
     pub fn len(&self) -> usize {
         self.connections.len()
     }
@@ -30,21 +90,147 @@ impl Manager {
             bail!(format!("connection {} already exists", alias))
         }
 
-        connection
+        let models = connection
             .list_models()
             .await
-            .wrap_err(format!("listing models backend {}", alias))?
-            .into_iter()
-            .for_each(|m| {
-                self.models.insert(m.id().to_string(), alias.clone());
-            });
+            .wrap_err(format!("listing models backend {}", alias))?;
+        if let Ok(mut index) = self.models.write() {
+            index_models(&mut index, &alias, &models);
+        }
 
+        if let Some(rpm) = self.rpm {
+            self.rate_limiters
+                .insert(alias.clone(), RateLimiter::new(rpm));
+        }
         self.connections.insert(alias, connection);
         Ok(())
     }
 
-    pub fn get_connection(&self, model: &str) -> Option<&ArcBackend> {
-        self.connections.get(self.models.get(model)?)
+    /// Return the connection serving the model. If the provider is empty, the
+    /// first connection serving the model is returned.
+    pub fn get_connection(&self, provider: &str, model: &str) -> Option<&ArcBackend> {
+        let alias = {
+            let models = self.models.read().ok()?;
+            let aliases = models.get(model)?;
+            if provider.is_empty() {
+                aliases.first()?.clone()
+            } else {
+                aliases.iter().find(|alias| *alias == provider)?.clone()
+            }
+        };
+        self.connections.get(&alias)
+    }
+
+    /// Return the connections to try in order according to the routing
+    /// policy. The connections which failed recently are moved to the end.
+    fn route(&self, provider: &str, model: &str) -> Vec<&ArcBackend> {
+        let Some(aliases) = self
+            .models
+            .read()
+            .ok()
+            .and_then(|models| models.get(model).cloned())
+        else {
+            return vec![];
+        };
+
+        let mut aliases = match self.policy {
+            RoutingPolicy::Direct => {
+                return self.get_connection(provider, model).into_iter().collect();
+            }
+            RoutingPolicy::Failover => {
+                let mut aliases = aliases;
+                if let Some(pos) = aliases.iter().position(|alias| alias == provider) {
+                    let preferred = aliases.remove(pos);
+                    aliases.insert(0, preferred);
+                }
+                aliases
+            }
+            RoutingPolicy::RoundRobin => {
+                let mut aliases = aliases;
+                let next = self.next_connection.fetch_add(1, Ordering::Relaxed) % aliases.len();
+                aliases.rotate_left(next);
+                aliases
+            }
+        };
+
+        // The sort is stable, so the order of the healthy connections is kept
+        aliases.sort_by_key(|alias| self.is_cooling_down(alias));
+        aliases
+            .iter()
+            .filter_map(|alias| self.connections.get(alias))
+            .collect()
+    }
+
+    fn is_cooling_down(&self, alias: &str) -> bool {
+        self.failures
+            .lock()
+            .ok()
+            .and_then(|failures| failures.get(alias).copied())
+            .is_some_and(|failed_at| failed_at.elapsed() < self.cooldown)
+    }
+
+    /// Wait for a free slot if the maximum number of requests are in flight
+    async fn acquire(&self, event_tx: &ArcEventTx) -> Result<Option<SemaphorePermit<'_>>> {
+        let Some(semaphore) = &self.concurrency else {
+            return Ok(None);
+        };
+        if let Ok(permit) = semaphore.try_acquire() {
+            return Ok(Some(permit));
+        }
+
+        let _ = event_tx
+            .send(info_event!(
+                "Too many requests in flight, the request is queued"
+            ))
+            .await;
+        let permit = semaphore.acquire().await.wrap_err("acquiring permit")?;
+        Ok(Some(permit))
+    }
+
+    /// Wait until the connection is under its requests per minute limit
+    async fn throttle(&self, alias: &str, event_tx: &ArcEventTx) {
+        let Some(limiter) = self.rate_limiters.get(alias) else {
+            return;
+        };
+        let delay = limiter.reserve();
+        if delay.is_zero() {
+            return;
+        }
+
+        let _ = event_tx
+            .send(info_event!(format!(
+                "Rate limit of {} reached, the request is queued for {}s",
+                alias,
+                delay.as_secs_f64().ceil()
+            )))
+            .await;
+        tokio::time::sleep(delay).await;
+    }
+
+    fn set_failed(&self, alias: &str, failed: bool) {
+        if let Ok(mut failures) = self.failures.lock() {
+            if failed {
+                failures.insert(alias.to_string(), Instant::now());
+            } else {
+                failures.remove(alias);
+            }
+        }
+    }
+
+    /// Return the connections to compute the embeddings with: the ones listing
+    /// the model, else the configured embedding connection. An embedding
+    /// model may not be listed (e.g. filtered out by `models`), it is never
+    /// sent to a connection which isn't chosen for it.
+    fn embedding_connections(&self, model: &str) -> Vec<&ArcBackend> {
+        let connections = self.route("", model);
+        if !connections.is_empty() {
+            return connections;
+        }
+        self.embedding_connection
+            .as_deref()
+            .and_then(|alias| self.connections.get(alias))
+            .into_iter()
+            .collect()
     }
 }
 
@@ -54,25 +240,151 @@ impl Backend for Manager {
         "Manager"
     }
 
+    async fn health_check(&self) -> Result<()> {
+        let mut failures = vec![];
+        for (alias, connection) in &self.connections {
+            if let Err(err) = connection.health_check().await {
+                failures.push(format!("{}: {:#}", alias, err));
+            }
+        }
+        if !failures.is_empty() {
+            bail!("unhealthy connections: {}", failures.join("; "));
+        }
+        Ok(())
+    }
+
     async fn list_models(&self) -> Result<Vec<Model>> {
-        Ok(self
+        let models = self
             .models
+            .read()
+            .map_err(|_| eyre::eyre!("models index is poisoned"))?;
+        Ok(models
             .iter()
-            .map(|(id, alias)| Model::new(id).with_provider(alias))
+            .flat_map(|(id, aliases)| {
+                aliases
+                    .iter()
+                    .map(move |alias| Model::new(id).with_provider(alias))
+            })
             .collect())
     }
 
+    /// Fetch the models of every connection again. The connections which
+    /// fail to list their models keep the previously known ones.
+    async fn refresh_models(&self) -> Result<Vec<Model>> {
+        let mut index = HashMap::new();
+        for (alias, connection) in &self.connections {
+            match connection.refresh_models().await {
+                Ok(models) => index_models(&mut index, alias, &models),
+                Err(err) => {
+                    log::warn!("Failed to refresh models of backend {}: {:#}", alias, err);
+                    let previous = self.list_models().await?;
+                    let models = previous
+                        .into_iter()
+                        .filter(|model| model.provider() == alias)
+                        .collect::<Vec<_>>();
+                    index_models(&mut index, alias, &models);
+                }
+            }
+        }
+        if let Ok(mut models) = self.models.write() {
+            *models = index;
+        }
+        self.list_models().await
+    }
+
     async fn get_completion(&self, prompt: BackendPrompt, event_tx: ArcEventTx) -> Result<()> {
-        let connection = match self.get_connection(prompt.model()) {
-            Some(connection) => connection,
-            None => {
-                return Err(eyre::eyre!("model is not available"));
+        let connections = self.route(prompt.provider(), prompt.model());
+        if connections.is_empty() {
+            return Err(eyre::eyre!("model is not available"));
+        }
+        let _permit = self.acquire(&event_tx).await?;
+
+        let last = connections.len() - 1;
+        for (i, connection) in connections.into_iter().enumerate() {
+            self.throttle(connection.name(), &event_tx).await;
+            let tracked = Arc::new(TrackedEventTx::new(event_tx.clone()));
+            let result = connection
+                .get_completion(prompt.clone(), tracked.clone())
+                .await
+                .wrap_err(format!("get completion from backend {}", connection.name()));
+            self.set_failed(connection.name(), result.is_err());
+
+            let err = match result {
+                Ok(_) => return Ok(()),
+                Err(err) => err,
+            };
+
+            // The response can't be resumed on another connection once it
+            // started streaming
+            if i == last || tracked.streamed() {
+                return Err(err);
             }
-        };
-        connection
-            .get_completion(prompt, event_tx)
-            .await
-            .wrap_err(format!("get completion from backend {}", connection.name()))?;
+
+            log::warn!("{:?}, trying the next connection", err);
+            let _ = event_tx
+                .send(warn_event!(format!(
+                    "Backend {} failed, trying the next connection",
+                    connection.name()
+                )))
+                .await;
+        }
         Ok(())
     }
+
+    async fn embed(&self, model: &str, input: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut failures = vec![];
+        for connection in self.embedding_connections(model) {
+            match connection.embed(model, input).await {
+                Ok(embeddings) => return Ok(embeddings),
+                Err(err) => failures.push(format!("{}: {:#}", connection.name(), err)),
+            }
+        }
+        if failures.is_empty() {
+            bail!(
+                "no connection serves the embedding model {}, set search.embedding_connection",
+                model
+            );
+        }
+        bail!("failed to compute the embeddings: {}", failures.join("; "))
+    }
+}
+
+/// Forward the events and remember whether the response started streaming.
+/// The notices don't count, they are sent before the response starts.
+struct TrackedEventTx {
+    inner: ArcEventTx,
+    streamed: AtomicBool,
+}
+
+impl TrackedEventTx {
+    fn new(inner: ArcEventTx) -> Self {
+        Self {
+            inner,
+            streamed: AtomicBool::new(false),
+        }
+    }
+
+    fn streamed(&self) -> bool {
+        self.streamed.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl EventTx for TrackedEventTx {
+    async fn send(&self, event: Event) -> Result<(), mpsc::error::SendError<Event>> {
+        if !matches!(event, Event::Notice(_)) {
+            self.streamed.store(true, Ordering::Relaxed);
+        }
+        self.inner.send(event).await
+    }
+}
+
+/// Add the connection to the aliases of its models
+fn index_models(index: &mut HashMap<String, Vec<String>>, alias: &str, models: &[Model]) {
+    for model in models {
+        index
+            .entry(model.id().to_string())
+            .or_default()
+            .push(alias.to_string());
+    }
 }
@@ -17,9 +17,15 @@ async fn test_add_connection() {
     let mut manager = Manager::default();
     let result = manager.add_connection(Arc::new(mock)).await;
     assert!(result.is_ok());
-    assert_eq!(manager.models.len(), 2);
-    assert_eq!(manager.models.get("model1"), Some(&"test".to_string()));
-    assert_eq!(manager.models.get("model2"), Some(&"test".to_string()));
+    assert_eq!(manager.models.read().unwrap().len(), 2);
+    assert_eq!(
+        manager.models.read().unwrap().get("model1"),
+        Some(&vec!["test".to_string()])
+    );
+    assert_eq!(
+        manager.models.read().unwrap().get("model2"),
+        Some(&vec!["test".to_string()])
+    );
 
     assert_eq!(manager.connections.len(), 1);
     assert!(manager.connections.contains_key("test"));
@@ -40,3 +46,280 @@ async fn test_add_connection_with_error() {
     let root_cause = err.root_cause();
     assert_eq!(root_cause.to_string(), "test error");
 }
+
+#[tokio::test]
+async fn test_refresh_models() {
+    let mut openai = mock_backend("openai", &["gpt-4o"], 0);
+    openai.expect_refresh_models().times(1).returning(|| {
+        Box::pin(async {
+            Ok(vec![
+                Model::new("gpt-4o").with_provider("openai"),
+                Model::new("o3").with_provider("openai"),
+            ])
+        })
+    });
+    let mut gemini = mock_backend("gemini", &["gemini-2.0-flash"], 0);
+    gemini
+        .expect_refresh_models()
+        .times(1)
+        .returning(|| Box::pin(async { Err(eyre::eyre!("connection refused")) }));
+
+    let mut manager = Manager::default();
+    manager.add_connection(Arc::new(openai)).await.unwrap();
+    manager.add_connection(Arc::new(gemini)).await.unwrap();
+
+    let mut models = manager
+        .refresh_models()
+        .await
+        .expect("Failed to refresh models")
+        .into_iter()
+        .map(|m| format!("{}/{}", m.provider(), m.id()))
+        .collect::<Vec<_>>();
+    models.sort();
+    // The connection which failed keeps its previous models
+    assert_eq!(
+        models,
+        vec!["gemini/gemini-2.0-flash", "openai/gpt-4o", "openai/o3"]
+    );
+    assert!(manager.get_connection("", "o3").is_some());
+}
+
+fn mock_backend(
+    name: &'static str,
+    models: &'static [&'static str],
+    completions: usize,
+) -> MockBackend {
+    let mut mock = MockBackend::new();
+    mock.expect_name().return_const(name.to_string());
+    mock.expect_list_models().times(1).returning(move || {
+        Box::pin(async move {
+            Ok(models
+                .iter()
+                .map(|m| Model::new(*m).with_provider(name))
+                .collect())
+        })
+    });
+    mock.expect_get_completion()
+        .times(completions)
+        .returning(|_, _| Box::pin(async { Ok(()) }));
+    mock
+}
+
+#[tokio::test]
+async fn test_get_completion_by_provider() {
+    let mut manager = Manager::default();
+    manager
+        .add_connection(Arc::new(mock_backend("openai", &["gpt-4o", "o1"], 1)))
+        .await
+        .expect("Failed to add connection");
+    manager
+        .add_connection(Arc::new(mock_backend("azure", &["gpt-4o"], 2)))
+        .await
+        .expect("Failed to add connection");
+
+    let mut models = manager.list_models().await.expect("Failed to list models");
+    models.sort_by(|a, b| (a.id(), a.provider()).cmp(&(b.id(), b.provider())));
+    assert_eq!(
+        models,
+        vec![
+            Model::new("gpt-4o").with_provider("azure"),
+            Model::new("gpt-4o").with_provider("openai"),
+            Model::new("o1").with_provider("openai"),
+        ]
+    );
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let event_tx: crate::models::ArcEventTx = Arc::new(tx);
+    for (provider, model) in [("azure", "gpt-4o"), ("azure", "gpt-4o"), ("openai", "o1")] {
+        let prompt = BackendPrompt::new("Hello")
+            .with_model(model)
+            .with_provider(provider);
+        manager
+            .get_completion(prompt, event_tx.clone())
+            .await
+            .expect("Failed to get completion");
+    }
+
+    let prompt = BackendPrompt::new("Hello")
+        .with_model("o1")
+        .with_provider("azure");
+    assert!(manager.get_completion(prompt, event_tx).await.is_err());
+}
+
+fn failing_backend(name: &'static str, completions: usize, streamed: bool) -> MockBackend {
+    let mut mock = MockBackend::new();
+    mock.expect_name().return_const(name.to_string());
+    mock.expect_list_models().times(1).returning(move || {
+        Box::pin(async move { Ok(vec![Model::new("gpt-4o").with_provider(name)]) })
+    });
+    mock.expect_get_completion()
+        .times(completions)
+        .returning(move |_, event_tx| {
+            Box::pin(async move {
+                if streamed {
+                    let _ = event_tx
+                        .send(crate::models::Event::ChatCompletionResponse(
+                            crate::models::BackendResponse::new("1", "gpt-4o").with_text("Hel"),
+                        ))
+                        .await;
+                }
+                Err(eyre::eyre!("rate limited"))
+            })
+        });
+    mock
+}
+
+#[tokio::test]
+async fn test_get_completion_failover() {
+    let mut manager = Manager::default().with_routing(&RoutingConfig {
+        policy: RoutingPolicy::Failover,
+        cooldown_secs: Some(60),
+    });
+    manager
+        .add_connection(Arc::new(failing_backend("primary", 1, false)))
+        .await
+        .expect("Failed to add connection");
+    manager
+        .add_connection(Arc::new(mock_backend("secondary", &["gpt-4o"], 2)))
+        .await
+        .expect("Failed to add connection");
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let event_tx: crate::models::ArcEventTx = Arc::new(tx);
+    // The primary connection is tried last during the cooldown after failing
+    for _ in 0..2 {
+        let prompt = BackendPrompt::new("Hello")
+            .with_model("gpt-4o")
+            .with_provider("primary");
+        manager
+            .get_completion(prompt, event_tx.clone())
+            .await
+            .expect("Failed to get completion");
+    }
+}
+
+#[tokio::test]
+async fn test_get_completion_no_failover_after_streaming() {
+    let mut manager = Manager::default().with_routing(&RoutingConfig {
+        policy: RoutingPolicy::Failover,
+        cooldown_secs: None,
+    });
+    manager
+        .add_connection(Arc::new(failing_backend("primary", 1, true)))
+        .await
+        .expect("Failed to add connection");
+    manager
+        .add_connection(Arc::new(mock_backend("secondary", &["gpt-4o"], 0)))
+        .await
+        .expect("Failed to add connection");
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let event_tx: crate::models::ArcEventTx = Arc::new(tx);
+    let prompt = BackendPrompt::new("Hello")
+        .with_model("gpt-4o")
+        .with_provider("primary");
+    let err = manager
+        .get_completion(prompt, event_tx)
+        .await
+        .expect_err("Expected the error of the primary connection");
+    assert_eq!(err.to_string(), "get completion from backend primary");
+}
+
+#[tokio::test]
+async fn test_get_completion_round_robin() {
+    let mut manager = Manager::default().with_routing(&RoutingConfig {
+        policy: RoutingPolicy::RoundRobin,
+        cooldown_secs: None,
+    });
+    manager
+        .add_connection(Arc::new(mock_backend("first", &["gpt-4o"], 2)))
+        .await
+        .expect("Failed to add connection");
+    manager
+        .add_connection(Arc::new(mock_backend("second", &["gpt-4o"], 2)))
+        .await
+        .expect("Failed to add connection");
+
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let event_tx: crate::models::ArcEventTx = Arc::new(tx);
+    for _ in 0..4 {
+        let prompt = BackendPrompt::new("Hello")
+            .with_model("gpt-4o")
+            .with_provider("first");
+        manager
+            .get_completion(prompt, event_tx.clone())
+            .await
+            .expect("Failed to get completion");
+    }
+}
+
+#[tokio::test]
+async fn test_get_completion_max_concurrent() {
+    let mut mock = MockBackend::new();
+    mock.expect_name().return_const("openai".to_string());
+    mock.expect_list_models()
+        .times(1)
+        .returning(|| Box::pin(async { Ok(vec![Model::new("gpt-4o").with_provider("openai")]) }));
+    mock.expect_get_completion().times(2).returning(|_, _| {
+        Box::pin(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(())
+        })
+    });
+
+    let mut manager = Manager::default().with_max_concurrent(Some(1));
+    manager
+        .add_connection(Arc::new(mock))
+        .await
+        .expect("Failed to add connection");
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let event_tx: ArcEventTx = Arc::new(tx);
+    let prompt = BackendPrompt::new("hello").with_model("gpt-4o");
+    let (first, second) = tokio::join!(
+        manager.get_completion(prompt.clone(), event_tx.clone()),
+        manager.get_completion(prompt, event_tx),
+    );
+    assert!(first.is_ok());
+    assert!(second.is_ok());
+
+    // The second request waited for the first one
+    let notice = rx.try_recv().expect("Failed to receive notice");
+    assert!(matches!(notice, Event::Notice(_)));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_embed_connection() {
+    let mut openai = mock_backend("openai", &["gpt-4o"], 0);
+    openai
+        .expect_embed()
+        .times(1)
+        .returning(|_, _| Box::pin(async { Ok(vec![vec![1.0]]) }));
+    // Never asked for an embedding it isn't chosen for
+    let ollama = mock_backend("ollama", &["llama3"], 0);
+
+    let mut manager = Manager::default();
+    manager
+        .add_connection(Arc::new(ollama))
+        .await
+        .expect("Failed to add connection");
+    manager
+        .add_connection(Arc::new(openai))
+        .await
+        .expect("Failed to add connection");
+
+    let input = vec!["hello".to_string()];
+    let err = manager
+        .embed("text-embedding-3-small", &input)
+        .await
+        .expect_err("no connection is chosen for the model");
+    assert!(err.to_string().contains("search.embedding_connection"));
+
+    let manager = manager.with_embedding_connection(Some("openai"));
+    let embeddings = manager
+        .embed("text-embedding-3-small", &input)
+        .await
+        .expect("Failed to embed");
+    assert_eq!(embeddings, vec![vec![1.0]]);
+}
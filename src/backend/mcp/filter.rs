@@ -0,0 +1,45 @@
+#[cfg(test)]
+#[path = "filter_test.rs"]
+mod tests;
+
+use eyre::{Context, Result};
+use regex::Regex;
+
+/// Decide which tools of a MCP server are advertised to the backends. The
+/// patterns are globs where `*` matches any sequence of characters and `?`
+/// matches a single character. A tool matching a `deny` pattern is always
+/// filtered, otherwise it must match one of the `allow` patterns if any.
+#[derive(Debug, Clone, Default)]
+pub struct ToolFilter {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+}
+
+impl ToolFilter {
+    pub fn new(allow: &[String], deny: &[String]) -> Result<Self> {
+        let build = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| glob_to_regex(p).wrap_err(format!("building glob {}", p)))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            allow: build(allow)?,
+            deny: build(deny)?,
+        })
+    }
+
+    pub fn is_allowed(&self, tool: &str) -> bool {
+        if self.deny.iter().any(|re| re.is_match(tool)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|re| re.is_match(tool))
+    }
+}
+
+fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let pattern = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+    Ok(Regex::new(&format!("^{}$", pattern))?)
+}
@@ -0,0 +1,26 @@
+use super::*;
+
+#[test]
+fn test_tool_filter() {
+    let filter = ToolFilter::default();
+    assert!(filter.is_allowed("delete_file"));
+
+    let filter = ToolFilter::new(&[], &["delete_*".to_string(), "rm?".to_string()])
+        .expect("Failed to build filter");
+    assert!(!filter.is_allowed("delete_file"));
+    assert!(!filter.is_allowed("rmd"));
+    assert!(filter.is_allowed("rm"));
+    assert!(filter.is_allowed("read_file"));
+
+    let filter = ToolFilter::new(
+        &["read_*".to_string(), "list.dir".to_string()],
+        &["read_secret".to_string()],
+    )
+    .expect("Failed to build filter");
+    assert!(filter.is_allowed("read_file"));
+    assert!(!filter.is_allowed("read_secret"));
+    assert!(filter.is_allowed("list.dir"));
+    // The dot is not a wildcard
+    assert!(!filter.is_allowed("list_dir"));
+    assert!(!filter.is_allowed("write_file"));
+}
@@ -2,20 +2,53 @@
 #[path = "manager_test.rs"]
 mod tests;
 
-use super::{CallToolResult, Tool};
-use super::{McpClient, client::Client};
+use super::{CallToolResult, CallToolResultContent, Tool};
+use super::{McpClient, client::Client, filter::ToolFilter};
 use crate::app::Initializer;
 use crate::config::McpServerConfig;
-use crate::task_success;
+use crate::{info_notice, task_success};
 use eyre::{Context, Result};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+type ToolMap = HashMap<Tool, Arc<dyn McpClient>>;
 
 #[derive(Default)]
 pub struct Manager {
-    tools: HashMap<Tool, Arc<dyn McpClient>>, // Tool name - MCP Client
+    tools: ToolMap, // Tool name - MCP Client
+    clients: Vec<(Arc<dyn McpClient>, ToolFilter)>,
+    /// The name of the tools filtered by the allow/deny lists. A tool denied
+    /// on a server may be allowed on another one, it is called there.
+    denied: RwLock<HashSet<String>>,
+
+    /// The tools listed from the servers at the given time. It is used
+    /// instead of `tools` until it expires.
+    cache: RwLock<Option<(Instant, ToolMap)>>,
+    /// How long the listed tools are cached. If not set, the tools are only
+    /// listed when the servers are added.
+    cache_ttl: Option<Duration>,
+    invalidated: AtomicBool,
 }
 
 impl Manager {
+    pub fn with_cache_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Mark the cached tools as stale, e.g. after a tool call failed because
+    /// the server restarted. The servers are queried again on the next
+    /// `list_tools` call.
+    fn invalidate(&self) {
+        self.invalidated.store(true, Ordering::SeqCst);
+    }
+
     pub async fn from(mut self, servers: &[McpServerConfig]) -> Result<Self> {
         for server in servers.iter().filter(|s| s.enabled.unwrap_or(true)) {
             Initializer::add_task(
@@ -25,43 +58,147 @@ impl Manager {
             let client = Client::new(&server.provider, &server.server)
                 .await
                 .wrap_err("creating client")?;
-            self.add_server(Arc::new(client)).await?;
+            let filter = ToolFilter::new(&server.allow, &server.deny)
+                .wrap_err(format!("building tool filter of {}", server.provider))?;
+            let filtered = self
+                .add_server_with_filter(Arc::new(client), filter)
+                .await?;
+            if filtered > 0 {
+                Initializer::add_notice(info_notice!(format!(
+                    "Filtered {} tool(s) of MCP server \"{}\"",
+                    filtered, server.provider
+                )));
+            }
             task_success!(format!("init_mcp_{}", server.provider).as_str())
         }
         Ok(self)
     }
 
     pub async fn add_server(&mut self, client: Arc<dyn McpClient>) -> Result<()> {
-        client
-            .list_tools()
-            .await
-            .wrap_err("listing tools")?
-            .into_iter()
-            .for_each(|tool| {
-                if let Some((k, _)) = self.tools.get_key_value(&tool) {
-                    let k = k.clone();
-                    // If the key already exists, we will compare which one has longer
-                    // description and keep the one with longest description
-                    if k.description.as_deref().unwrap_or_default().len()
-                        > tool.description.as_deref().unwrap_or_default().len()
-                    {
-                        return;
-                    }
-                    // Otherwise, we delete the old one and insert the new one
-                    self.tools.remove(&k);
-                }
-                self.tools.insert(tool, client.clone());
-            });
+        self.add_server_with_filter(client, ToolFilter::default())
+            .await?;
         Ok(())
     }
+
+    /// Add the server and return the number of its tools which are filtered
+    pub async fn add_server_with_filter(
+        &mut self,
+        client: Arc<dyn McpClient>,
+        filter: ToolFilter,
+    ) -> Result<usize> {
+        let tools = client.list_tools().await.wrap_err("listing tools")?;
+        let denied = merge_tools(&mut self.tools, tools, &client, &filter);
+        let filtered = denied.len();
+        if let Ok(mut names) = self.denied.write() {
+            names.extend(denied);
+        }
+        self.clients.push((client, filter));
+        if let Ok(mut cache) = self.cache.write() {
+            *cache = Some((Instant::now(), self.tools.clone()));
+        }
+        Ok(filtered)
+    }
+
+    fn is_denied(&self, tool: &str) -> bool {
+        self.denied
+            .read()
+            .map(|names| names.contains(tool))
+            .unwrap_or_default()
+    }
+
+    fn cached_tools(&self) -> Option<ToolMap> {
+        if self.invalidated.load(Ordering::SeqCst) {
+            return None;
+        }
+        let cache = self.cache.read().ok()?;
+        let (fetched_at, tools) = cache.as_ref()?;
+        match self.cache_ttl {
+            Some(ttl) if fetched_at.elapsed() > ttl => None,
+            _ => Some(tools.clone()),
+        }
+    }
+
+    /// List the tools from all servers. If a server fails, its previously
+    /// known tools are kept.
+    async fn refresh_tools(&self) -> ToolMap {
+        let previous = self.cached_tools_unchecked();
+        let mut tools = ToolMap::new();
+        let mut denied = HashSet::new();
+        for (client, filter) in &self.clients {
+            match client.list_tools().await {
+                Ok(listed) => denied.extend(merge_tools(&mut tools, listed, client, filter)),
+                Err(err) => {
+                    log::warn!("Failed to list tools, keep the cached ones: {}", err);
+                    let known = previous
+                        .iter()
+                        .filter(|(_, c)| Arc::ptr_eq(c, client))
+                        .map(|(tool, _)| tool.clone())
+                        .collect();
+                    merge_tools(&mut tools, known, client, filter);
+                }
+            }
+        }
+
+        if let Ok(mut names) = self.denied.write() {
+            names.extend(denied);
+        }
+
+        if let Ok(mut cache) = self.cache.write() {
+            *cache = Some((Instant::now(), tools.clone()));
+        }
+        self.invalidated.store(false, Ordering::SeqCst);
+        tools
+    }
+
+    fn cached_tools_unchecked(&self) -> ToolMap {
+        self.cache
+            .read()
+            .ok()
+            .and_then(|cache| cache.as_ref().map(|(_, tools)| tools.clone()))
+            .unwrap_or_else(|| self.tools.clone())
+    }
+}
+
+/// Merge the listed tools of the client into the map and return the name of
+/// the tools which are filtered
+fn merge_tools(
+    tools: &mut ToolMap,
+    listed: Vec<Tool>,
+    client: &Arc<dyn McpClient>,
+    filter: &ToolFilter,
+) -> Vec<String> {
+    let mut denied = vec![];
+    for tool in listed {
+        if !filter.is_allowed(&tool.name) {
+            denied.push(tool.name);
+            continue;
+        }
+        if let Some((k, _)) = tools.get_key_value(&tool) {
+            let k = k.clone();
+            // If the key already exists, we will compare which one has longer
+            // description and keep the one with longest description
+            if k.description.as_deref().unwrap_or_default().len()
+                > tool.description.as_deref().unwrap_or_default().len()
+            {
+                continue;
+            }
+            // Otherwise, we delete the old one and insert the new one
+            tools.remove(&k);
+        }
+        tools.insert(tool, client.clone());
+    }
+    denied
 }
 
 #[async_trait::async_trait]
 impl McpClient for Manager {
     /// List all available tools
     async fn list_tools(&self) -> Result<Vec<Tool>> {
-        // FIXME: Should we apply a TTL cache for this?
-        Ok(self.tools.keys().cloned().collect::<Vec<_>>())
+        let tools = match self.cached_tools() {
+            Some(tools) => tools,
+            None => self.refresh_tools().await,
+        };
+        Ok(tools.keys().cloned().collect::<Vec<_>>())
     }
 
     /// Call a tool with the given name and arguments
@@ -71,17 +208,35 @@ impl McpClient for Manager {
         args: Option<serde_json::Value>,
     ) -> Result<CallToolResult> {
         let client = self
-            .tools
+            .cached_tools_unchecked()
             .iter()
             .find(|(k, _)| k.name.as_str() == tool)
-            .ok_or_else(|| eyre::eyre!("tool {} not found", tool))?
-            .1
-            .clone();
-        Ok(client.call_tool(tool, args).await?)
+            .map(|(_, client)| client.clone());
+        if let Some(client) = client {
+            // The tools of the server may have changed, e.g. it restarted
+            return client
+                .call_tool(tool, args)
+                .await
+                .inspect_err(|_| self.invalidate());
+        }
+
+        // The model may still request a filtered tool from a stale context,
+        // the refusal is returned to the model instead of failing the request
+        if self.is_denied(tool) {
+            log::warn!("Refused to call the denied tool {}", tool);
+            return Ok(CallToolResult {
+                provider: String::new(),
+                content: vec![CallToolResultContent::Text {
+                    text: format!("The tool \"{}\" is not allowed to be called", tool),
+                }],
+                is_error: true,
+            });
+        }
+        eyre::bail!("tool {} not found", tool)
     }
 
     async fn shutdown(&self) -> Result<()> {
-        for client in self.tools.values() {
+        for (client, _) in &self.clients {
             if let Err(e) = client.shutdown().await {
                 log::error!("Error shutting down client: {}", e);
             }
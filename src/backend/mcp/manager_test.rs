@@ -63,6 +63,141 @@ async fn test_add_server() {
     );
 }
 
+#[tokio::test]
+async fn test_list_tools_cache() {
+    let mut mock_mcp = MockMcpClient::new();
+    let mut seq = mockall::Sequence::new();
+    mock_mcp
+        .expect_list_tools()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|| Box::pin(async { Ok(vec![fake_tool("test_tool", "test tool")]) }));
+    mock_mcp
+        .expect_list_tools()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|| {
+            Box::pin(async {
+                Ok(vec![
+                    fake_tool("test_tool", "test tool"),
+                    fake_tool("new_tool", "new tool"),
+                ])
+            })
+        });
+    mock_mcp
+        .expect_list_tools()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|| Box::pin(async { Err(eyre::eyre!("server is down")) }));
+
+    let mut manager = Manager::default().with_cache_ttl(Some(Duration::from_secs(60)));
+    manager
+        .add_server(Arc::new(mock_mcp))
+        .await
+        .expect("add server");
+
+    // Served from the cache, the server is not queried again
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 1);
+
+    manager.invalidate();
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 2);
+    assert!(tools.iter().any(|t| t.name == "new_tool"));
+
+    // The known tools are kept when the server fails
+    manager.invalidate();
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 2);
+}
+
+#[tokio::test]
+async fn test_tool_filter() {
+    let mut mock_mcp = MockMcpClient::new();
+    mock_mcp.expect_list_tools().returning(|| {
+        Box::pin(async {
+            Ok(vec![
+                fake_tool("read_file", "read a file"),
+                fake_tool("delete_file", "delete a file"),
+                fake_tool("delete_dir", "delete a directory"),
+            ])
+        })
+    });
+    mock_mcp.expect_call_tool().never();
+
+    let filter = ToolFilter::new(&[], &["delete_*".to_string()]).expect("build filter");
+    let mut manager = Manager::default();
+    let filtered = manager
+        .add_server_with_filter(Arc::new(mock_mcp), filter)
+        .await
+        .expect("add server");
+    assert_eq!(filtered, 2);
+
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0].name, "read_file");
+
+    let result = manager
+        .call_tool("delete_file", None)
+        .await
+        .expect("call tool");
+    assert!(result.is_error);
+    assert!(matches!(
+        &result.content[0],
+        CallToolResultContent::Text { text } if text.contains("not allowed")
+    ));
+}
+
+#[tokio::test]
+async fn test_tool_filter_by_server() {
+    // The tool is denied on the first server only
+    let mut denying = MockMcpClient::new();
+    denying.expect_list_tools().returning(|| {
+        Box::pin(async { Ok(vec![server_tool("first", "search", "search the web")]) })
+    });
+    denying.expect_call_tool().never();
+    let mut allowing = MockMcpClient::new();
+    allowing.expect_list_tools().returning(|| {
+        Box::pin(async { Ok(vec![server_tool("second", "search", "search the files")]) })
+    });
+    allowing.expect_call_tool().times(1).returning(|_, _| {
+        Box::pin(async {
+            Ok(CallToolResult {
+                provider: "second".to_string(),
+                content: vec![],
+                is_error: false,
+            })
+        })
+    });
+
+    let filter = ToolFilter::new(&[], &["search".to_string()]).expect("build filter");
+    let mut manager = Manager::default();
+    let filtered = manager
+        .add_server_with_filter(Arc::new(denying), filter)
+        .await
+        .expect("add server");
+    assert_eq!(filtered, 1);
+    manager
+        .add_server(Arc::new(allowing))
+        .await
+        .expect("add server");
+
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0].provider, "second");
+
+    let result = manager.call_tool("search", None).await.expect("call tool");
+    assert!(!result.is_error);
+    assert_eq!(result.provider, "second");
+}
+
+fn server_tool(provider: &str, name: &str, desc: &str) -> Tool {
+    Tool {
+        provider: provider.to_string(),
+        ..fake_tool(name, desc)
+    }
+}
+
 fn fake_tool(name: &str, desc: &str) -> Tool {
     Tool {
         provider: "test".to_string(),
@@ -71,3 +206,29 @@ fn fake_tool(name: &str, desc: &str) -> Tool {
         input_schema: ToolInputSchema::default(),
     }
 }
+
+#[tokio::test]
+async fn test_failed_call_invalidates_cache() {
+    let mut mock_mcp = MockMcpClient::new();
+    mock_mcp
+        .expect_list_tools()
+        .times(2)
+        .returning(|| Box::pin(async { Ok(vec![fake_tool("test_tool", "test tool")]) }));
+    mock_mcp
+        .expect_call_tool()
+        .times(1)
+        .returning(|_, _| Box::pin(async { Err(eyre::eyre!("connection closed")) }));
+
+    let mut manager = Manager::default().with_cache_ttl(Some(Duration::from_secs(60)));
+    manager
+        .add_server(Arc::new(mock_mcp))
+        .await
+        .expect("add server");
+
+    assert!(manager.call_tool("test_tool", None).await.is_err());
+    // The server is queried again
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 1);
+    let tools = manager.list_tools().await.expect("list tools");
+    assert_eq!(tools.len(), 1);
+}
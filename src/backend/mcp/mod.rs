@@ -1,4 +1,5 @@
 pub mod client;
+pub mod filter;
 pub mod manager;
 pub mod models;
 mod transport;
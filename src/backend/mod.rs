@@ -1,9 +1,17 @@
+pub mod bedrock;
+pub mod cohere;
+pub mod error;
 pub mod gemini;
+pub mod inspect;
+pub(crate) mod limiter;
 pub mod manager;
 pub mod mcp;
 pub mod openai;
+pub(crate) mod redact;
 pub(crate) mod utils;
 
+pub use bedrock::Bedrock;
+pub use cohere::Cohere;
 pub use gemini::Gemini;
 pub use manager::Manager;
 pub use mcp::McpClient;
@@ -14,31 +22,37 @@ use mockall::{automock, predicate::*};
 
 use crate::{
     app::Initializer,
-    config::BackendConfig,
+    config::{BackendConfig, SearchConfig},
     models::{ArcEventTx, BackendConnection, BackendKind, BackendPrompt, Model},
-    task_failure, task_success, warn_notice,
+    task_failure, task_success,
 };
 use async_trait::async_trait;
 use eyre::{Context, Result};
-use std::sync::Arc;
-
-const TITLE_PROMPT: &str = r#"
-
----
-This is initial message. Please give name a title for this conversation.
-The title should be placed at the top of the response, in separate line and starts with #"#;
+use std::{sync::Arc, time::Duration};
 
 #[async_trait]
 #[cfg_attr(test, automock)]
 pub trait Backend {
     fn name(&self) -> &str;
+    /// Check the connection is usable, e.g. the endpoint is reachable and the
+    /// credentials are accepted
+    async fn health_check(&self) -> Result<()>;
     async fn list_models(&self) -> Result<Vec<Model>>;
+    /// Fetch the models again instead of returning the cached ones
+    async fn refresh_models(&self) -> Result<Vec<Model>> {
+        self.list_models().await
+    }
     async fn get_completion(&self, prompt: BackendPrompt, event_tx: ArcEventTx) -> Result<()>;
+    /// Return the embedding of each input computed by the model, in order
+    async fn embed(&self, model: &str, input: &[String]) -> Result<Vec<Vec<f32>>> {
+        let _ = input;
+        eyre::bail!("{} does not support embeddings with {}", self.name(), model)
+    }
 }
 
 pub type ArcBackend = Arc<dyn Backend + Send + Sync>;
 
-pub async fn new_manager(config: &BackendConfig) -> Result<ArcBackend> {
+pub async fn new_manager(config: &BackendConfig, search: &SearchConfig) -> Result<ArcBackend> {
     let connections = config
         .connections
         .iter()
@@ -53,6 +67,7 @@ pub async fn new_manager(config: &BackendConfig) -> Result<ArcBackend> {
         Initializer::add_task("init_mcp", "Initializing MCP manager...");
     }
     let mcp_manager = mcp::Manager::default()
+        .with_cache_ttl(config.mcp.tool_cache_ttl.map(Duration::from_secs))
         .from(&config.mcp.servers)
         .await
         .wrap_err("creating mcp manager")?;
@@ -71,34 +86,34 @@ pub async fn new_manager(config: &BackendConfig) -> Result<ArcBackend> {
         format!("Available {} tool(s)", avail_tools.len())
     );
 
-    let mut manager = manager::Manager::default();
+    let mut manager = manager::Manager::default()
+        .with_routing(&config.routing)
+        .with_max_concurrent(config.max_concurrent)
+        .with_rpm(config.rpm)
+        .with_embedding_connection(search.embedding_connection.as_deref());
     for connection in connections {
+        let name = connection.name().to_string();
+        let task_id = format!("setup_backend_{}", name);
+        Initializer::add_task(
+            task_id.as_str(),
+            format!("Setting up backend connection {}...", name).as_str(),
+        );
+
         let backend = match new_backend(connection, mcp_manager.clone()).await {
             Ok(backend) => backend,
-            Err(e) => {
-                Initializer::add_notice(warn_notice!(format!(
-                    "Failed to initialize backend: {}",
-                    e
-                )));
-                log::warn!("Failed to initialize backend: {}", e);
+            Err(err) => {
+                task_failure!(task_id.as_str(), format!("Failed: {:#}", err));
+                log::warn!("Failed to initialize backend {}: {:#}", name, err);
                 continue;
             }
         };
 
-        Initializer::add_task(
-            format!("setup_backend_{}", backend.name()).as_str(),
-            format!("Setting up backend connection {}...", backend.name()).as_str(),
-        );
-        let name = backend.name().to_string();
         if let Err(err) = manager.add_connection(backend).await {
-            task_failure!(
-                format!("setup_backend_{}", name).as_str(),
-                format!("Failed: {}", err)
-            );
+            task_failure!(task_id.as_str(), format!("Failed: {}", err));
             log::warn!("Failed to add backend connection: {}", err);
             continue;
         }
-        task_success!(format!("setup_backend_{}", name).as_str());
+        task_success!(task_id.as_str());
         log::debug!("Added backend connection: {}", name);
     }
 
@@ -109,16 +124,35 @@ pub async fn new_manager(config: &BackendConfig) -> Result<ArcBackend> {
     Ok(Arc::new(manager))
 }
 
+/// List all the models of the connection, including the ones which are not
+/// in its `models` list. The backend is neither initialized nor connected to
+/// the MCP servers.
+pub async fn list_all_models(conn: &BackendConnection) -> Result<Vec<Model>> {
+    let conn = conn.clone().with_models(vec![]);
+    match conn.kind() {
+        BackendKind::OpenAI => OpenAI::from(&conn).list_models().await,
+        BackendKind::Gemini => Gemini::from(&conn).list_models().await,
+        BackendKind::Bedrock => Bedrock::from(&conn).list_models().await,
+        BackendKind::Cohere => Cohere::from(&conn).list_models().await,
+    }
+}
+
 async fn new_backend(
     conn: &BackendConnection,
     mcp: Option<Arc<dyn McpClient>>,
 ) -> Result<ArcBackend> {
     match conn.kind() {
         BackendKind::OpenAI => {
+            openai::validate_api_base(conn.endpoint(), conn.path_prefix())
+                .wrap_err("validating OpenAI endpoint")?;
             let mut openai: OpenAI = conn.into();
             if let Some(mcp) = mcp {
                 openai = openai.with_mcp(mcp);
             }
+            openai
+                .health_check()
+                .await
+                .wrap_err("checking OpenAI connection")?;
             openai.init().await.wrap_err("initializing OpenAI")?;
             Ok(Arc::new(openai))
         }
@@ -127,8 +161,30 @@ async fn new_backend(
             if let Some(mcp) = mcp {
                 gemini = gemini.with_mcp(mcp);
             }
+            gemini
+                .health_check()
+                .await
+                .wrap_err("checking Gemini connection")?;
             gemini.init().await.wrap_err("initializing Gemini")?;
             Ok(Arc::new(gemini))
         }
+        BackendKind::Bedrock => {
+            let mut bedrock: Bedrock = conn.into();
+            bedrock.init().await.wrap_err("initializing Bedrock")?;
+            bedrock
+                .health_check()
+                .await
+                .wrap_err("checking Bedrock connection")?;
+            Ok(Arc::new(bedrock))
+        }
+        BackendKind::Cohere => {
+            let mut cohere: Cohere = conn.into();
+            cohere
+                .health_check()
+                .await
+                .wrap_err("checking Cohere connection")?;
+            cohere.init().await.wrap_err("initializing Cohere")?;
+            Ok(Arc::new(cohere))
+        }
     }
 }
@@ -3,32 +3,40 @@
 mod tests;
 
 use crate::backend::mcp::{Tool, ToolInputSchema};
-use crate::backend::utils::context_truncation;
-use crate::backend::{ArcBackend, Backend, TITLE_PROMPT};
+use crate::backend::redact::redact;
+use crate::backend::utils::{
+    LineReader, call_tool_with_approval, context_truncation, with_extra_body,
+};
+use crate::backend::{ArcBackend, Backend};
 use crate::config::{self, ModelSetting, user_agent};
 use crate::models::{
     ArcEventTx, BackendConnection, BackendPrompt, BackendResponse, BackendUsage, Event, Message,
-    Model,
+    MessageRoles, Model, ResponseFormat, TokenLogprob, ToolCallStyle,
 };
 use crate::{info_event, warn_event};
 use async_trait::async_trait;
 use eyre::{Context, Result, bail};
-use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::{fmt::Display, time};
 use thiserror::Error;
-use tokio::io::AsyncBufReadExt;
-use tokio_util::io::StreamReader;
 
 use super::mcp;
 
+/// The path of the API under the endpoint if `path_prefix` is not set
+const DEFAULT_PATH_PREFIX: &str = "/v1";
+
+/// Appended to the prompt in JSON mode, the API requires the word "JSON"
+/// in the messages
+const JSON_MODE_PROMPT: &str = "\n\nAnswer with a valid JSON object.";
+
 pub struct OpenAI {
     alias: String,
     endpoint: String,
+    path_prefix: Option<String>,
     api_key: Option<String>,
     timeout: Option<time::Duration>,
     mcp: Option<Arc<dyn mcp::McpClient>>,
@@ -37,6 +45,18 @@ pub struct OpenAI {
     model_settings: HashMap<String, ModelSetting>,
 
     max_output_tokens: Option<usize>,
+    tool_call_style: ToolCallStyle,
+    roles: MessageRoles,
+    extra_body: Option<serde_json::Map<String, serde_json::Value>>,
+
+    stream: bool,
+    // Models which rejected streaming requests during this session
+    non_streaming_models: RwLock<HashSet<String>>,
+    // Models which rejected the logprobs during this session
+    no_logprobs_models: RwLock<HashSet<String>>,
+    // Models fetched by the health check or the first listing, reused to
+    // avoid listing them again until they are refreshed
+    models: RwLock<Option<Vec<Model>>>,
 }
 
 #[async_trait]
@@ -45,59 +65,49 @@ impl Backend for OpenAI {
         &self.alias
     }
 
-    async fn list_models(&self) -> Result<Vec<Model>> {
-        let mut req = reqwest::Client::new()
-            .get(format!("{}/v1/models", self.endpoint))
-            .header("User-Agent", user_agent());
-
-        if let Some(timeout) = self.timeout {
-            req = req.timeout(timeout);
+    async fn health_check(&self) -> Result<()> {
+        let models = self.fetch_models().await?;
+        if let Ok(mut cache) = self.models.write() {
+            *cache = Some(models);
         }
+        Ok(())
+    }
 
-        if let Some(token) = &self.api_key {
-            req = req.bearer_auth(token);
+    async fn list_models(&self) -> Result<Vec<Model>> {
+        if let Some(models) = self.models.read().ok().and_then(|cache| cache.clone()) {
+            return Ok(models);
         }
-
-        let res = req.send().await.wrap_err("listing models")?;
-
-        if !res.status().is_success() {
-            let http_code = res.status().as_u16();
-            let err: ErrorResponse = res.json().await.wrap_err("parsing error response")?;
-            let mut err = err.error;
-            err.http_code = http_code;
-            return Err(err.into());
+        let models = self.fetch_models().await?;
+        if let Ok(mut cache) = self.models.write() {
+            *cache = Some(models.clone());
         }
-
-        let res = res
-            .json::<ModelListResponse>()
-            .await
-            .wrap_err("parsing model list response")?;
-
-        let all = self.want_models.is_empty();
-
-        let mut models = res
-            .data
-            .into_iter()
-            .filter(|m| all || self.want_models.contains(&m.id))
-            .map(|m| Model::new(m.id).with_provider(&self.alias))
-            .collect::<Vec<_>>();
-
-        models.sort_by(|a, b| a.id().cmp(b.id()));
-
         Ok(models)
     }
 
+    async fn refresh_models(&self) -> Result<Vec<Model>> {
+        self.invalidate_models();
+        self.list_models().await
+    }
+
     async fn get_completion(&self, prompt: BackendPrompt, event_tx: ArcEventTx) -> Result<()> {
         if prompt.model().is_empty() {
             bail!("no model is set");
         }
 
-        let init_conversation = prompt.context().is_empty();
-        let content = if init_conversation && !prompt.no_generate_title() {
-            format!("{}\n{}", prompt.text(), TITLE_PROMPT)
-        } else {
-            prompt.text().to_string()
-        };
+        let mut content = prompt.text().to_string();
+
+        let response_format = prompt
+            .response_format()
+            .or_else(|| {
+                self.model_settings
+                    .get(prompt.model())
+                    .and_then(|s| s.response_format)
+            })
+            .unwrap_or_default();
+        // The JSON mode is rejected unless the messages ask for JSON
+        if response_format == ResponseFormat::JsonObject {
+            content.push_str(JSON_MODE_PROMPT);
+        }
 
         let mut messages = prompt.context().to_vec();
         messages.push(Message::new_user("user", content));
@@ -108,13 +118,52 @@ impl Backend for OpenAI {
 
         let messages = messages
             .into_iter()
-            .map(|m| MessageRequest::from(&m))
+            .map(|m| MessageRequest::new(&m, &self.roles))
             .collect::<Vec<_>>();
 
-        self.chat_completion(None, init_conversation, prompt.model(), &messages, event_tx)
+        self.chat_completion(None, prompt.model(), &messages, response_format, event_tx)
             .await?;
         Ok(())
     }
+
+    async fn embed(&self, model: &str, input: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut req = reqwest::Client::new()
+            .post(self.url("embeddings"))
+            .header("User-Agent", user_agent())
+            .json(&EmbeddingRequest { model, input });
+
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+
+        if let Some(token) = &self.api_key {
+            req = req.bearer_auth(token);
+        }
+
+        let res = req.send().await.wrap_err("sending embedding request")?;
+
+        if !res.status().is_success() {
+            let http_code = res.status().as_u16();
+            let err: ErrorResponse = res.json().await.wrap_err("parsing error response")?;
+            let mut err = err.error;
+            err.http_code = http_code;
+            return Err(err.into());
+        }
+
+        let mut res = res
+            .json::<EmbeddingResponse>()
+            .await
+            .wrap_err("parsing embedding response")?;
+        if res.data.len() != input.len() {
+            bail!(
+                "expected {} embedding(s), got {}",
+                input.len(),
+                res.data.len()
+            );
+        }
+        res.data.sort_by_key(|data| data.index);
+        Ok(res.data.into_iter().map(|data| data.embedding).collect())
+    }
 }
 
 impl From<OpenAI> for ArcBackend {
@@ -126,6 +175,7 @@ impl From<OpenAI> for ArcBackend {
 impl From<&BackendConnection> for OpenAI {
     fn from(value: &BackendConnection) -> Self {
         let mut openai = OpenAI::default().with_endpoint(value.endpoint());
+        openai.path_prefix = value.path_prefix().map(String::from);
 
         if let Some(api_key) = value.api_key() {
             openai.api_key = Some(api_key.to_string());
@@ -140,6 +190,10 @@ impl From<&BackendConnection> for OpenAI {
         }
 
         openai.max_output_tokens = value.max_output_tokens();
+        openai.tool_call_style = value.tool_call_style();
+        openai.roles = value.roles().clone();
+        openai.extra_body = value.extra_body().cloned();
+        openai.stream = value.stream();
 
         openai.want_models = value.models().to_vec();
         openai
@@ -151,6 +205,48 @@ impl OpenAI {
         Self::default()
     }
 
+    async fn fetch_models(&self) -> Result<Vec<Model>> {
+        let mut req = reqwest::Client::new()
+            .get(self.url("models"))
+            .header("User-Agent", user_agent());
+
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+
+        if let Some(token) = &self.api_key {
+            req = req.bearer_auth(token);
+        }
+
+        let res = req.send().await.wrap_err("listing models")?;
+
+        if !res.status().is_success() {
+            let http_code = res.status().as_u16();
+            let err: ErrorResponse = res.json().await.wrap_err("parsing error response")?;
+            let mut err = err.error;
+            err.http_code = http_code;
+            return Err(err.into());
+        }
+
+        let res = res
+            .json::<ModelListResponse>()
+            .await
+            .wrap_err("parsing model list response")?;
+
+        let all = self.want_models.is_empty();
+
+        let mut models = res
+            .data
+            .into_iter()
+            .filter(|m| all || self.want_models.contains(&m.id))
+            .map(|m| Model::new(m.id).with_provider(&self.alias))
+            .collect::<Vec<_>>();
+
+        models.sort_by(|a, b| a.id().cmp(b.id()));
+
+        Ok(models)
+    }
+
     pub async fn init(&mut self) -> Result<()> {
         let models = self.list_models().await.wrap_err("listing models")?;
         for settings in &config::instance().backend.model_settings {
@@ -163,6 +259,21 @@ impl OpenAI {
         Ok(())
     }
 
+    /// Drop the cached models, they are fetched again on the next listing
+    fn invalidate_models(&self) {
+        if let Ok(mut cache) = self.models.write() {
+            *cache = None;
+        }
+    }
+
+    /// The endpoint may have changed (e.g. a local server restarted with
+    /// other models) when it cannot be reached
+    fn invalidate_models_on(&self, err: &reqwest::Error) {
+        if err.is_connect() {
+            self.invalidate_models();
+        }
+    }
+
     pub fn with_want_models(mut self, models: Vec<String>) -> Self {
         self.want_models = models;
         self
@@ -173,6 +284,11 @@ impl OpenAI {
         self
     }
 
+    pub fn with_path_prefix(mut self, path_prefix: &str) -> Self {
+        self.path_prefix = Some(path_prefix.to_string());
+        self
+    }
+
     pub fn with_api_key(mut self, api_key: &str) -> Self {
         self.api_key = Some(api_key.to_string());
         self
@@ -183,10 +299,24 @@ impl OpenAI {
         self
     }
 
+    pub fn with_stream(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
     pub fn endpoint(&self) -> &str {
         &self.endpoint
     }
 
+    /// The URL of the API path, e.g. `chat/completions`
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            api_base(&self.endpoint, self.path_prefix.as_deref()),
+            path
+        )
+    }
+
     pub fn api_key(&self) -> Option<&str> {
         self.api_key.as_deref()
     }
@@ -204,6 +334,42 @@ impl OpenAI {
         self
     }
 
+    fn should_stream(&self, model: &str) -> bool {
+        self.stream
+            && !self
+                .non_streaming_models
+                .read()
+                .map(|models| models.contains(model))
+                .unwrap_or_default()
+    }
+
+    fn mark_non_streaming(&self, model: &str) {
+        if let Ok(mut models) = self.non_streaming_models.write() {
+            models.insert(model.to_string());
+        }
+    }
+
+    /// Return the logprobs and the number of the top logprobs requested for
+    /// the model, none if it rejected them before
+    fn logprobs(&self, settings: Option<&ModelSetting>, model: &str) -> (Option<bool>, Option<u8>) {
+        let rejected = self
+            .no_logprobs_models
+            .read()
+            .is_ok_and(|models| models.contains(model));
+        let top_logprobs = settings.and_then(|s| s.top_logprobs);
+        let logprobs = settings.and_then(|s| s.logprobs).unwrap_or_default();
+        if rejected || !(logprobs || top_logprobs.is_some()) {
+            return (None, None);
+        }
+        (Some(true), top_logprobs)
+    }
+
+    fn mark_no_logprobs(&self, model: &str) {
+        if let Ok(mut models) = self.no_logprobs_models.write() {
+            models.insert(model.to_string());
+        }
+    }
+
     async fn get_mcp_tools(&self, event_tx: ArcEventTx) -> Vec<Tool> {
         if let Some(mcp) = &self.mcp {
             let tools = match mcp.list_tools().await {
@@ -223,9 +389,9 @@ impl OpenAI {
     async fn chat_completion(
         &self,
         override_id: Option<String>,
-        init_conversation: bool,
         model: &str,
         messages: &[MessageRequest],
+        response_format: ResponseFormat,
         event_tx: ArcEventTx,
     ) -> Result<()> {
         let settings = self.model_settings.get(model);
@@ -242,21 +408,46 @@ impl OpenAI {
             vec![]
         };
 
+        let stream = self.should_stream(model);
+        let (logprobs, top_logprobs) = self.logprobs(settings, model);
         let completion_req = CompletionRequest {
             model: model.to_string(),
             messages: messages.to_vec(),
-            stream: true,
-            max_completion_tokens: self.max_output_tokens,
+            stream,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            max_completion_tokens: settings
+                .and_then(|s| s.max_tokens)
+                .or(self.max_output_tokens),
+            temperature: settings.and_then(|s| s.temperature),
+            top_p: settings.and_then(|s| s.top_p),
+            stop: settings.map(|s| s.stop.clone()).unwrap_or_default(),
+            reasoning_effort: settings.and_then(|s| s.reasoning_effort.clone()),
             tool_choice: if !tools.is_empty() {
                 Some("auto".to_string())
             } else {
                 None
             },
             tools: tools.iter().map(ToolRequest::from).collect(),
+            response_format: (response_format != ResponseFormat::Text).then_some(
+                ResponseFormatRequest {
+                    kind: response_format,
+                },
+            ),
+            logprobs,
+            top_logprobs,
         };
+        let completion_req = with_extra_body(
+            &completion_req,
+            &[
+                self.extra_body.as_ref(),
+                settings.and_then(|s| s.extra_body.as_ref()),
+            ],
+        )?;
 
         let mut req = reqwest::Client::new()
-            .post(format!("{}/v1/chat/completions", self.endpoint))
+            .post(self.url("chat/completions"))
             .header("Content-Type", "application/json")
             .header("User-Agent", user_agent());
 
@@ -268,18 +459,83 @@ impl OpenAI {
             req = req.bearer_auth(token);
         }
 
-        log::trace!("Sending completion request: {:?}", completion_req);
+        log::trace!(
+            "Sending completion request: {}",
+            redact(&serde_json::to_string(&completion_req).unwrap_or_default())
+        );
 
         let res = req
             .json(&completion_req)
             .send()
             .await
+            .inspect_err(|err| self.invalidate_models_on(err))
             .wrap_err("sending completion request")?;
 
         if !res.status().is_success() {
             let http_code = res.status().as_u16();
             let resp = res.text().await.wrap_err("parsing error response")?;
             log::error!("Error response: {}", resp);
+
+            if stream && is_streaming_unsupported(http_code, &resp) {
+                // Retry once without streaming, the model is remembered so
+                // the next requests don't need to fail first
+                log::warn!("Model {} doesn't support streaming, retrying", model);
+                self.mark_non_streaming(model);
+                event_tx
+                    .send(info_event!(format!(
+                        "Model \"{}\" doesn't support streaming, switched to non-streaming",
+                        model
+                    )))
+                    .await?;
+                return Box::pin(self.chat_completion(
+                    override_id,
+                    model,
+                    messages,
+                    response_format,
+                    event_tx,
+                ))
+                .await;
+            }
+
+            if response_format != ResponseFormat::Text
+                && is_response_format_unsupported(http_code, &resp)
+            {
+                log::warn!("Model {} rejected the response format, retrying", model);
+                event_tx
+                    .send(warn_event!(format!(
+                        "Model \"{}\" doesn't support JSON mode, answering in text",
+                        model
+                    )))
+                    .await?;
+                return Box::pin(self.chat_completion(
+                    override_id,
+                    model,
+                    messages,
+                    ResponseFormat::Text,
+                    event_tx,
+                ))
+                .await;
+            }
+
+            if logprobs.is_some() && is_logprobs_unsupported(http_code, &resp) {
+                log::warn!("Model {} rejected the logprobs, retrying", model);
+                self.mark_no_logprobs(model);
+                event_tx
+                    .send(warn_event!(format!(
+                        "Model \"{}\" doesn't support logprobs, answering without",
+                        model
+                    )))
+                    .await?;
+                return Box::pin(self.chat_completion(
+                    override_id,
+                    model,
+                    messages,
+                    response_format,
+                    event_tx,
+                ))
+                .await;
+            }
+
             let err = serde_json::from_str::<ErrorResponse>(&resp)
                 .wrap_err(format!("parsing error response: {}", resp))?;
             let mut err = err.error;
@@ -287,95 +543,115 @@ impl OpenAI {
             return Err(err.into());
         }
 
-        let stream = res.bytes_stream().map_err(|e| {
-            let err_msg = e.to_string();
-            std::io::Error::new(std::io::ErrorKind::Interrupted, err_msg)
-        });
-
-        let mut line_readers = StreamReader::new(stream).lines();
-
         let mut message_id = override_id.unwrap_or_default();
         let mut usage: Option<BackendUsage> = None;
 
-        let mut call_tools: BTreeMap<usize, ToolCallResponse> = BTreeMap::new();
+        let mut call_tools: Vec<ToolCallResponse> = vec![];
 
         let mut current_message = MessageRequest {
-            role: "assistant".to_string(),
+            role: self.roles.assistant.clone(),
             content: String::new(),
             tool_call_id: None,
             ..Default::default()
         };
 
-        while let Ok(line) = line_readers.next_line().await {
-            if line.is_none() {
-                break;
+        if !stream {
+            let data = res
+                .json::<FullCompletionResponse>()
+                .await
+                .wrap_err("parsing completion response")?;
+            log::trace!(
+                "completion response: {}",
+                redact(&serde_json::to_string(&data).unwrap_or_default())
+            );
+
+            if message_id.is_empty() {
+                message_id = data.id;
             }
 
-            let mut line = line.unwrap().trim().to_string();
-            log::trace!("streaming response: {}", line);
-            if !line.starts_with("data: ") {
-                continue;
+            if let Some(c) = data.choices.into_iter().next() {
+                call_tools = c.message.tool_calls;
+                let refusal = c.message.refusal.filter(|text| !text.is_empty());
+                let text = match refusal {
+                    Some(ref refusal) => refusal.clone(),
+                    None => c.message.content.unwrap_or_default(),
+                };
+                if !text.is_empty() {
+                    current_message.content.push_str(&text);
+                    event_tx
+                        .send(Event::ChatCompletionResponse(
+                            BackendResponse::new(&message_id, model)
+                                .with_text(&text)
+                                .with_refusal(refusal.is_some())
+                                .with_logprobs(
+                                    c.logprobs.and_then(|l| l.content).unwrap_or_default(),
+                                ),
+                        ))
+                        .await?;
+                }
             }
 
-            line = line[6..].to_string();
-            if line == "[DONE]" {
-                break;
+            if call_tools.is_empty() {
+                usage = data.usage.map(BackendUsage::from);
             }
+        } else {
+            let mut line_reader = LineReader::new(res.bytes_stream());
 
-            let data = serde_json::from_str::<CompletionResponse>(&line)
-                .wrap_err(format!("parsing completion response line: {}", line))?;
+            while let Some(line) = line_reader.next_line().await? {
+                let mut line = line.trim().to_string();
+                log::trace!("streaming response: {}", redact(&line));
+                if !line.starts_with("data: ") {
+                    continue;
+                }
 
-            let c = match data.choices.first() {
-                Some(c) => c,
-                None => continue,
-            };
+                line = line[6..].to_string();
+                if line == "[DONE]" {
+                    break;
+                }
 
-            if message_id.is_empty() {
-                message_id = data.id;
-            }
+                let data = serde_json::from_str::<CompletionResponse>(&line)
+                    .wrap_err(format!("parsing completion response line: {}", line))?;
 
-            c.delta.tool_calls.iter().for_each(|e| {
-                if let Some(tool) = call_tools.get_mut(&e.index) {
-                    tool.function
-                        .arguments
-                        .as_mut()
-                        .unwrap()
-                        .push_str(e.function.arguments.as_deref().unwrap_or_default());
-                    return;
+                // The usage comes in the last chunk which has no choices
+                if let Some(ref usage_data) = data.usage {
+                    usage = Some(BackendUsage::from(usage_data));
                 }
-                call_tools.insert(e.index, e.clone());
-            });
 
-            let text = match c.delta.content {
-                Some(ref text) => text.deref().to_string(),
-                None => continue,
-            };
+                let c = match data.choices.into_iter().next() {
+                    Some(c) => c,
+                    None => continue,
+                };
 
-            current_message.content.push_str(&text);
+                if message_id.is_empty() {
+                    message_id = data.id;
+                }
 
-            event_tx
-                .send(Event::ChatCompletionResponse(
-                    BackendResponse::new(&message_id, model)
-                        .with_text(&text)
-                        .with_init_conversation(init_conversation),
-                ))
-                .await?;
+                c.delta
+                    .tool_calls
+                    .iter()
+                    .for_each(|e| merge_tool_call(&mut call_tools, self.tool_call_style, e));
 
-            if call_tools.is_empty() {
-                if let Some(usage_data) = data.usage {
-                    usage = Some(BackendUsage {
-                        prompt_tokens: usage_data.prompt_tokens,
-                        completion_tokens: usage_data.completion_tokens,
-                        total_tokens: usage_data.total_tokens,
-                    });
-                }
+                let (text, refusal) = match (&c.delta.refusal, &c.delta.content) {
+                    (Some(refusal), _) => (refusal.deref().to_string(), true),
+                    (None, Some(text)) => (text.deref().to_string(), false),
+                    (None, None) => continue,
+                };
+
+                current_message.content.push_str(&text);
+
+                event_tx
+                    .send(Event::ChatCompletionResponse(
+                        BackendResponse::new(&message_id, model)
+                            .with_text(&text)
+                            .with_refusal(refusal)
+                            .with_logprobs(c.logprobs.and_then(|l| l.content).unwrap_or_default()),
+                    ))
+                    .await?;
             }
         }
 
         if call_tools.is_empty() {
-            let mut msg = BackendResponse::new(&message_id, model)
-                .with_done()
-                .with_init_conversation(init_conversation);
+            let mut msg = BackendResponse::new(&message_id, model).with_done();
             if let Some(usage) = usage {
                 msg = msg.with_usage(usage);
             }
@@ -385,13 +661,13 @@ impl OpenAI {
 
         event_tx
             .send(Event::ChatCompletionResponse(
-                BackendResponse::new(&message_id, model)
-                    .with_text("\n")
-                    .with_init_conversation(init_conversation),
+                BackendResponse::new(&message_id, model).with_boundary(),
             ))
             .await?;
 
-        let call_tools = call_tools.into_values().collect::<Vec<_>>();
+        if self.tool_call_style == ToolCallStyle::Indexed {
+            call_tools.sort_by_key(|call| call.index);
+        }
         // If there are any tool calls, we need to send them to the MCP
         // for processing
         let tool_call_messages = self
@@ -405,9 +681,9 @@ impl OpenAI {
 
         Box::pin(self.chat_completion(
             Some(message_id),
-            init_conversation,
             model,
             &messages,
+            response_format,
             event_tx,
         ))
         .await?;
@@ -444,12 +720,11 @@ impl OpenAI {
                 .name
                 .as_ref()
                 .ok_or_else(|| eyre::eyre!("missing tool name"))?;
+            let provider = match tools.iter().find(|t| &t.name == tool_name) {
+                Some(t) => t.provider.clone(),
+                None => "unknown".to_string(),
+            };
             if notice_on_call {
-                let provider = match tools.iter().find(|t| &t.name == tool_name) {
-                    Some(t) => t.provider.clone(),
-                    None => "unknown".to_string(),
-                };
-
                 event_tx
                     .send(info_event!(format!(
                         "Calling tool \"{}\" (provider: {})",
@@ -461,14 +736,15 @@ impl OpenAI {
             // TODO: should we log the full description of the tool?
             log::debug!("Calling tool {} with args: {:?}", tool_name, args);
 
-            let resp = self
-                .mcp
-                .as_ref()
-                .unwrap()
-                .clone()
-                .call_tool(call.function.name.as_ref().unwrap(), args)
-                .await
-                .wrap_err("calling tool")?;
+            let resp = call_tool_with_approval(
+                self.mcp.as_ref().unwrap(),
+                tool_name,
+                &provider,
+                args,
+                &event_tx,
+            )
+            .await
+            .wrap_err("calling tool")?;
             let result =
                 serde_json::to_string(&resp.content).wrap_err("serializing tool result")?;
             results.push(MessageRequest {
@@ -486,8 +762,16 @@ impl Default for OpenAI {
     fn default() -> Self {
         Self {
             max_output_tokens: None,
+            tool_call_style: ToolCallStyle::default(),
+            roles: MessageRoles::default(),
+            extra_body: None,
+            stream: true,
+            non_streaming_models: RwLock::new(HashSet::new()),
+            no_logprobs_models: RwLock::new(HashSet::new()),
+            models: RwLock::new(None),
             alias: "OpenAI".to_string(),
             endpoint: "https://api.openai.com".to_string(),
+            path_prefix: None,
             api_key: None,
             timeout: None,
             want_models: vec![],
@@ -507,6 +791,24 @@ struct ModelListResponse {
     data: Vec<ModelResponse>,
 }
 
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Default, Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 struct MessageRequest {
     role: String,
@@ -523,11 +825,38 @@ struct CompletionRequest {
     messages: Vec<MessageRequest>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     max_completion_tokens: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     tool_choice: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tools: Vec<ToolRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormatRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ResponseFormatRequest {
+    #[serde(rename = "type")]
+    kind: ResponseFormat,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -548,6 +877,9 @@ struct FunctionRequest {
 #[derive(Default, Debug, Serialize, Deserialize)]
 struct CompletionDeltaResponse {
     content: Option<String>,
+    // Set instead of the content when the model declines to answer
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    refusal: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     tool_calls: Vec<ToolCallResponse>,
 }
@@ -556,10 +888,20 @@ struct CompletionDeltaResponse {
 struct CompletionChoiceResponse {
     delta: CompletionDeltaResponse,
     finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    logprobs: Option<LogprobsResponse>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct LogprobsResponse {
+    // Null for the refusals
+    #[serde(default)]
+    content: Option<Vec<TokenLogprob>>,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 struct ToolCallResponse {
+    #[serde(default)]
     index: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     id: Option<String>,
@@ -583,11 +925,76 @@ struct CompletionResponse {
     usage: Option<CompletionUsageResponse>,
 }
 
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct FullCompletionChoiceResponse {
+    message: FullCompletionMessageResponse,
+    finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    logprobs: Option<LogprobsResponse>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct FullCompletionMessageResponse {
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    refusal: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    tool_calls: Vec<ToolCallResponse>,
+}
+
+/// The response of a non-streaming completion request
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct FullCompletionResponse {
+    id: String,
+    choices: Vec<FullCompletionChoiceResponse>,
+    usage: Option<CompletionUsageResponse>,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize)]
 struct CompletionUsageResponse {
     prompt_tokens: usize,
     completion_tokens: usize,
     total_tokens: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prompt_tokens_details: Option<PromptTokensDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: Option<usize>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct CompletionTokensDetails {
+    #[serde(default)]
+    reasoning_tokens: Option<usize>,
+}
+
+impl From<&CompletionUsageResponse> for BackendUsage {
+    fn from(value: &CompletionUsageResponse) -> Self {
+        BackendUsage {
+            prompt_tokens: value.prompt_tokens,
+            completion_tokens: value.completion_tokens,
+            total_tokens: value.total_tokens,
+            cached_prompt_tokens: value
+                .prompt_tokens_details
+                .as_ref()
+                .and_then(|details| details.cached_tokens),
+            reasoning_tokens: value
+                .completion_tokens_details
+                .as_ref()
+                .and_then(|details| details.reasoning_tokens),
+        }
+    }
+}
+
+impl From<CompletionUsageResponse> for BackendUsage {
+    fn from(value: CompletionUsageResponse) -> Self {
+        BackendUsage::from(&value)
+    }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -612,15 +1019,112 @@ impl Display for OpenAIError {
     }
 }
 
-impl From<&Message> for MessageRequest {
-    fn from(msg: &Message) -> Self {
+/// The base URL of the API, the endpoint joined with the path prefix. An
+/// empty prefix puts the API at the root of the endpoint.
+pub fn api_base(endpoint: &str, path_prefix: Option<&str>) -> String {
+    let prefix = path_prefix.unwrap_or(DEFAULT_PATH_PREFIX).trim_matches('/');
+    let endpoint = endpoint.trim_end_matches('/');
+    if prefix.is_empty() {
+        return endpoint.to_string();
+    }
+    format!("{}/{}", endpoint, prefix)
+}
+
+/// Check that the endpoint joined with the path prefix is a valid HTTP URL
+pub fn validate_api_base(endpoint: &str, path_prefix: Option<&str>) -> Result<()> {
+    let base = api_base(endpoint, path_prefix);
+    let url = reqwest::Url::parse(&base).wrap_err(format!("invalid api base {}", base))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!(
+            "unsupported scheme {} of api base {}, expected http or https",
+            url.scheme(),
+            base
+        );
+    }
+    Ok(())
+}
+
+/// Check whether the error response is caused by the model (or endpoint)
+/// not supporting streaming
+fn is_streaming_unsupported(http_code: u16, resp: &str) -> bool {
+    if http_code != 400 && http_code != 422 {
+        return false;
+    }
+    let resp = resp.to_lowercase();
+    resp.contains("stream")
+        && ["not support", "unsupported", "not allowed", "not available"]
+            .iter()
+            .any(|pattern| resp.contains(pattern))
+}
+
+/// Check whether the error response is caused by the model not supporting
+/// the requested `response_format`. Other errors mentioning it, e.g. the
+/// messages not asking for JSON, are not fixed by answering in text.
+fn is_response_format_unsupported(http_code: u16, resp: &str) -> bool {
+    if http_code != 400 && http_code != 422 {
+        return false;
+    }
+    let resp = resp.to_lowercase();
+    resp.contains("response_format")
+        && ["not support", "unsupported", "not allowed", "not available"]
+            .iter()
+            .any(|pattern| resp.contains(pattern))
+}
+
+/// Check whether the error response is caused by the model rejecting the
+/// logprobs, e.g. the reasoning models
+fn is_logprobs_unsupported(http_code: u16, resp: &str) -> bool {
+    if http_code != 400 && http_code != 422 {
+        return false;
+    }
+    let resp = resp.to_lowercase();
+    resp.contains("logprobs")
+        && ["not support", "unsupported"]
+            .iter()
+            .any(|pattern| resp.contains(pattern))
+}
+
+/// Merge a streamed tool call delta into the accumulated calls. With the
+/// [`ToolCallStyle::IdBased`] style, a delta carrying a new `id` starts a new
+/// call and a delta without `id` continues the last one.
+fn merge_tool_call(
+    calls: &mut Vec<ToolCallResponse>,
+    style: ToolCallStyle,
+    delta: &ToolCallResponse,
+) {
+    let existing = match style {
+        ToolCallStyle::Indexed => calls.iter_mut().find(|call| call.index == delta.index),
+        ToolCallStyle::IdBased => match delta.id.as_deref().filter(|id| !id.is_empty()) {
+            Some(id) => calls.iter_mut().find(|call| call.id.as_deref() == Some(id)),
+            None => calls.last_mut(),
+        },
+    };
+
+    let Some(call) = existing else {
+        calls.push(delta.clone());
+        return;
+    };
+
+    if let Some(args) = delta.function.arguments.as_deref() {
+        call.function
+            .arguments
+            .get_or_insert_with(String::new)
+            .push_str(args);
+    }
+    if call.function.name.is_none() {
+        call.function.name = delta.function.name.clone();
+    }
+}
+
+impl MessageRequest {
+    fn new(msg: &Message, roles: &MessageRoles) -> Self {
         Self {
             role: if msg.is_context() {
-                "system".to_string()
+                roles.system.clone()
             } else if msg.is_system() {
-                "assistant".to_string()
+                roles.assistant.clone()
             } else {
-                "user".to_string()
+                roles.user.clone()
             },
             content: msg.text().to_string(),
             tool_call_id: None,
@@ -1,6 +1,7 @@
 use tokio::sync::mpsc::{self, UnboundedReceiver};
 
 use super::*;
+use crate::models::{NoticeKind, TopLogprob};
 
 #[tokio::test]
 async fn test_list_models() {
@@ -53,6 +54,7 @@ async fn test_get_completion() {
                     ..Default::default()
                 },
                 finish_reason: None,
+                logprobs: None,
             }],
             ..Default::default()
         })
@@ -66,6 +68,7 @@ async fn test_get_completion() {
                 ..Default::default()
             },
             finish_reason: Some("stop".to_string()),
+            logprobs: None,
         }],
         ..Default::default()
     });
@@ -110,13 +113,537 @@ async fn test_get_completion() {
 
     assert_eq!(events[0].text, "Hello ");
     assert_eq!(events[0].done, false);
-    assert_eq!(events[0].init_conversation, true);
     assert_eq!(events[1].text, "there!");
     assert_eq!(events[1].done, false);
-    assert_eq!(events[1].init_conversation, true);
     assert_eq!(events[2].text, "");
     assert_eq!(events[2].done, true);
-    assert_eq!(events[2].init_conversation, true);
+}
+
+#[tokio::test]
+async fn test_get_completion_usage_chunk() {
+    let body = [
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{"content":"Hello there!"},"finish_reason":null}],"usage":null}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{},"finish_reason":"stop"}],"usage":null}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[],"usage":{"prompt_tokens":5,"completion_tokens":3,"total_tokens":8}}"#,
+        "data: [DONE]",
+    ]
+    .join("\n");
+
+    let mut server = mockito::Server::new_async().await;
+    let completion_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "stream": true,
+            "stream_options": {"include_usage": true},
+        })))
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let sender: ArcEventTx = Arc::new(tx);
+
+    let backend = setup_backend(server.url()).await;
+    let prompt = BackendPrompt::new("Hello").with_model("gpt-3.5-turbo");
+    backend
+        .get_completion(prompt, sender)
+        .await
+        .expect("Failed to get completion");
+    completion_handler.assert();
+
+    let events = collect_responses(&mut rx, time::Duration::from_secs(5), 2)
+        .await
+        .expect("Failed to collect events");
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].text, "Hello there!");
+    assert!(events[1].done);
+
+    let usage = events[1].usage.as_ref().expect("Usage is not captured");
+    assert_eq!(usage.prompt_tokens, 5);
+    assert_eq!(usage.completion_tokens, 3);
+    assert_eq!(usage.total_tokens, 8);
+}
+
+#[tokio::test]
+async fn test_get_completion_split_utf8() {
+    let body = [
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{"content":"Hi 👋 there"},"finish_reason":null}],"usage":null}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{},"finish_reason":"stop"}],"usage":null}"#,
+        "data: [DONE]",
+    ]
+    .join("\n");
+    // Split the body in the middle of the emoji
+    let split = body.find('👋').unwrap() + 2;
+    let (first, second) = body.as_bytes().split_at(split);
+    let (first, second) = (first.to_vec(), second.to_vec());
+
+    let mut server = mockito::Server::new_async().await;
+    let completion_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_chunked_body(move |w| {
+            w.write_all(&first)?;
+            w.flush()?;
+            std::thread::sleep(time::Duration::from_millis(50));
+            w.write_all(&second)
+        })
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let sender: ArcEventTx = Arc::new(tx);
+
+    let backend = setup_backend(server.url()).await;
+    let prompt = BackendPrompt::new("Hello").with_model("gpt-3.5-turbo");
+    backend
+        .get_completion(prompt, sender)
+        .await
+        .expect("Failed to get completion");
+    completion_handler.assert();
+
+    let events = collect_responses(&mut rx, time::Duration::from_secs(5), 2)
+        .await
+        .expect("Failed to collect events");
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].text, "Hi 👋 there");
+    assert!(events[1].done);
+}
+
+#[tokio::test]
+async fn test_get_completion_refusal() {
+    let body = [
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{"content":null,"refusal":"I'm sorry, "},"finish_reason":null}],"usage":null}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{"refusal":"I can't help with that."},"finish_reason":null}],"usage":null}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[{"delta":{},"finish_reason":"stop"}],"usage":null}"#,
+        r#"data: {"id":"chatcmpl-1","choices":[],"usage":{"prompt_tokens":5,"completion_tokens":8,"total_tokens":13}}"#,
+        "data: [DONE]",
+    ]
+    .join("\n");
+
+    let mut server = mockito::Server::new_async().await;
+    let completion_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .with_status(200)
+        .with_body(body)
+        .create();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let sender: ArcEventTx = Arc::new(tx);
+
+    let backend = setup_backend(server.url()).await;
+    let prompt = BackendPrompt::new("Hello").with_model("gpt-3.5-turbo");
+    backend
+        .get_completion(prompt, sender)
+        .await
+        .expect("Failed to get completion");
+    completion_handler.assert();
+
+    let events = collect_responses(&mut rx, time::Duration::from_secs(5), 3)
+        .await
+        .expect("Failed to collect events");
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].text, "I'm sorry, ");
+    assert!(events[0].refusal);
+    assert_eq!(events[1].text, "I can't help with that.");
+    assert!(events[1].refusal);
+    assert!(events[2].done);
+
+    let usage = events[2].usage.as_ref().expect("Usage is not captured");
+    assert_eq!(usage.completion_tokens, 8);
+}
+
+#[tokio::test]
+async fn test_get_completion_streaming_unsupported() {
+    let mut server = mockito::Server::new_async().await;
+    let stream_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"stream": true}),
+        ))
+        .with_status(400)
+        .with_body(
+            r#"{"error":{"message":"Streaming is not supported for this model","type":"invalid_request_error"}}"#,
+        )
+        .expect(1)
+        .create();
+    let completion_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"stream": false}),
+        ))
+        .with_status(200)
+        .with_body(
+            r#"{"id":"chatcmpl-1","choices":[{"message":{"role":"assistant","content":"Hello there!"},"finish_reason":"stop"}],"usage":{"prompt_tokens":5,"completion_tokens":3,"total_tokens":8}}"#,
+        )
+        .expect(2)
+        .create();
+
+    let backend = setup_backend(server.url()).await;
+
+    for _ in 0..2 {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let sender: ArcEventTx = Arc::new(tx);
+        let prompt = BackendPrompt::new("Hello").with_model("gpt-3.5-turbo");
+        backend
+            .get_completion(prompt, sender)
+            .await
+            .expect("Failed to get completion");
+
+        // Skip the notice about switching to non-streaming
+        let events = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|event| match event {
+                Event::ChatCompletionResponse(msg) => Some(msg),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].text, "Hello there!");
+        assert!(!events[0].done);
+        assert!(events[1].done);
+        assert_eq!(events[1].usage.as_ref().map(|u| u.total_tokens), Some(8));
+    }
+
+    // The model is remembered as non-streaming after the first failure
+    stream_handler.assert();
+    completion_handler.assert();
+}
+
+#[tokio::test]
+async fn test_get_completion_model_settings() {
+    let mut cfg = config::Configuration::default();
+    cfg.backend.model_settings = vec![
+        toml::from_str(
+            r#"
+            model.equals = "gpt-4"
+            temperature = 0.5
+            top_p = 0.9
+            max_tokens = 256
+            stop = ["END"]
+            reasoning_effort = "high"
+            extra_body = { seed = 42 }
+            "#,
+        )
+        .expect("Failed to parse model setting"),
+    ];
+    config::init(cfg).expect("Failed to init config");
+
+    let mut server = mockito::Server::new_async().await;
+    let models_handler = server
+        .mock("GET", "/v1/models")
+        .with_status(200)
+        .with_body(r#"{"data":[{"id":"gpt-3.5-turbo"},{"id":"gpt-4"}]}"#)
+        .create();
+
+    let body = [
+        r#"data: {"id":"1","choices":[{"delta":{"content":"Hi"},"finish_reason":"stop"}]}"#,
+        "data: [DONE]",
+    ]
+    .join("\n");
+    let configured_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "gpt-4",
+            "temperature": 0.5,
+            "top_p": 0.9,
+            "max_completion_tokens": 256,
+            "stop": ["END"],
+            "reasoning_effort": "high",
+            "seed": 42,
+        })))
+        .with_status(200)
+        .with_body(&body)
+        .create();
+    // The settings are only applied to the matched model
+    let default_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"model": "gpt-3.5-turbo"}),
+        ))
+        .match_request(|req| {
+            let body = String::from_utf8_lossy(req.body().expect("Failed to read body"));
+            !body.contains("temperature")
+                && !body.contains("stop")
+                && !body.contains("reasoning_effort")
+                && !body.contains("seed")
+        })
+        .with_status(200)
+        .with_body(&body)
+        .create();
+
+    let mut backend = setup_backend(server.url()).await;
+    backend.init().await.expect("Failed to init backend");
+    models_handler.assert();
+
+    for model in ["gpt-4", "gpt-3.5-turbo"] {
+        let (tx, _rx) = mpsc::unbounded_channel::<Event>();
+        let prompt = BackendPrompt::new("Hello").with_model(model);
+        backend
+            .get_completion(prompt, Arc::new(tx))
+            .await
+            .expect("Failed to get completion");
+    }
+    configured_handler.assert();
+    default_handler.assert();
+}
+
+#[tokio::test]
+async fn test_get_completion_json_mode() {
+    let mut server = mockito::Server::new_async().await;
+    let body = [
+        r#"data: {"id":"1","choices":[{"delta":{"content":"{}"},"finish_reason":"stop"}]}"#,
+        "data: [DONE]",
+    ]
+    .join("\n");
+    let json_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "gpt-4",
+            "response_format": {"type": "json_object"},
+            "messages": [{"role": "user", "content": "Hello\n\nAnswer with a valid JSON object."}],
+        })))
+        .with_status(200)
+        .with_body(&body)
+        .create();
+    // An unsupported model answers in text after a warning
+    let rejected_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "gpt-3.5-turbo",
+            "response_format": {"type": "json_object"},
+        })))
+        .with_status(400)
+        .with_body(
+            r#"{"error":{"message":"Invalid parameter: 'response_format' of type 'json_object' is not supported with this model.","type":"invalid_request_error"}}"#,
+        )
+        .create();
+    let text_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"model": "gpt-3.5-turbo"}),
+        ))
+        .match_request(|req| {
+            let body = String::from_utf8_lossy(req.body().expect("Failed to read body"));
+            !body.contains("response_format")
+        })
+        .with_status(200)
+        .with_body(&body)
+        .create();
+
+    let backend = setup_backend(server.url()).await;
+    for model in ["gpt-4", "gpt-3.5-turbo"] {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let prompt = BackendPrompt::new("Hello")
+            .with_model(model)
+            .with_response_format(ResponseFormat::JsonObject);
+        backend
+            .get_completion(prompt, Arc::new(tx))
+            .await
+            .expect("Failed to get completion");
+
+        let warned = std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|event| matches!(event, Event::Notice(notice) if matches!(notice.kind(), NoticeKind::Warning)));
+        assert_eq!(warned, model == "gpt-3.5-turbo", "model: {}", model);
+    }
+    json_handler.assert();
+    rejected_handler.assert();
+    text_handler.assert();
+}
+
+#[test]
+fn test_is_response_format_unsupported() {
+    let unsupported = r#"{"error":{"message":"Invalid parameter: 'response_format' of type 'json_object' is not supported with this model."}}"#;
+    assert!(is_response_format_unsupported(400, unsupported));
+    assert!(!is_response_format_unsupported(500, unsupported));
+
+    // Fixed by asking for JSON, not by answering in text
+    let no_json = r#"{"error":{"message":"'messages' must contain the word 'json' in some form, to use 'response_format' of type 'json_object'."}}"#;
+    assert!(!is_response_format_unsupported(400, no_json));
+}
+
+#[test]
+fn test_is_logprobs_unsupported() {
+    let unsupported =
+        r#"{"error":{"message":"This model does not support the 'logprobs' parameter."}}"#;
+    assert!(is_logprobs_unsupported(400, unsupported));
+    assert!(is_logprobs_unsupported(422, "Logprobs are UNSUPPORTED"));
+    assert!(!is_logprobs_unsupported(500, unsupported));
+
+    // A bad value is not fixed by dropping the logprobs
+    let invalid = r#"{"error":{"message":"Invalid value for 'top_logprobs': must be less than or equal to 20."}}"#;
+    assert!(!is_logprobs_unsupported(400, invalid));
+}
+
+#[tokio::test]
+async fn test_get_completion_logprobs() {
+    let mut cfg = config::Configuration::default();
+    cfg.backend.model_settings = ["gpt-4", "gpt-3.5-turbo"]
+        .iter()
+        .map(|model| {
+            toml::from_str(&format!("model.equals = \"{}\"\ntop_logprobs = 1", model))
+                .expect("Failed to parse model setting")
+        })
+        .collect();
+    config::init(cfg).expect("Failed to init config");
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/v1/models")
+        .with_status(200)
+        .with_body(r#"{"data":[{"id":"gpt-3.5-turbo"},{"id":"gpt-4"}]}"#)
+        .create();
+
+    let body = [
+        r#"data: {"id":"1","choices":[{"delta":{"content":"Hi"},"logprobs":{"content":[{"token":"Hi","logprob":-0.5,"bytes":[72,105],"top_logprobs":[{"token":"Hello","logprob":-1.0,"bytes":[]}]}]},"finish_reason":null}]}"#,
+        r#"data: {"id":"1","choices":[{"delta":{"content":"!"},"logprobs":{"content":[{"token":"!","logprob":0.0,"top_logprobs":[]}]},"finish_reason":"stop"}]}"#,
+        "data: [DONE]",
+    ]
+    .join("\n");
+    let logprobs_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "gpt-4",
+            "logprobs": true,
+            "top_logprobs": 1,
+        })))
+        .with_status(200)
+        .with_body(&body)
+        .create();
+    // The model rejecting the logprobs answers without after a warning
+    let rejected_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "gpt-3.5-turbo",
+            "logprobs": true,
+        })))
+        .with_status(400)
+        .with_body(
+            r#"{"error":{"message":"This model does not support the 'logprobs' parameter.","type":"invalid_request_error"}}"#,
+        )
+        .expect(1)
+        .create();
+    let text_handler = server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"model": "gpt-3.5-turbo"}),
+        ))
+        .match_request(|req| {
+            let body = String::from_utf8_lossy(req.body().expect("Failed to read body"));
+            !body.contains("logprobs")
+        })
+        .with_status(200)
+        .with_body(
+            r#"data: {"id":"2","choices":[{"delta":{"content":"Hi"},"finish_reason":"stop"}]}"#,
+        )
+        .expect(2)
+        .create();
+
+    let mut backend = setup_backend(server.url()).await;
+    backend.init().await.expect("Failed to init backend");
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let prompt = BackendPrompt::new("Hello").with_model("gpt-4");
+    backend
+        .get_completion(prompt, Arc::new(tx))
+        .await
+        .expect("Failed to get completion");
+    let logprobs = std::iter::from_fn(|| rx.try_recv().ok())
+        .flat_map(|event| match event {
+            Event::ChatCompletionResponse(resp) => resp.logprobs,
+            _ => vec![],
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        logprobs,
+        vec![
+            TokenLogprob {
+                token: "Hi".to_string(),
+                logprob: -0.5,
+                top_logprobs: vec![TopLogprob {
+                    token: "Hello".to_string(),
+                    logprob: -1.0,
+                }],
+            },
+            TokenLogprob {
+                token: "!".to_string(),
+                logprob: 0.0,
+                top_logprobs: vec![],
+            },
+        ]
+    );
+
+    // The rejection is remembered for the next requests
+    for warning in [true, false] {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let prompt = BackendPrompt::new("Hello").with_model("gpt-3.5-turbo");
+        backend
+            .get_completion(prompt, Arc::new(tx))
+            .await
+            .expect("Failed to get completion");
+        let warned = std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|event| matches!(event, Event::Notice(notice) if matches!(notice.kind(), NoticeKind::Warning)));
+        assert_eq!(warned, warning);
+    }
+    logprobs_handler.assert();
+    rejected_handler.assert();
+    text_handler.assert();
+}
+
+#[test]
+fn test_message_roles() {
+    let connection: BackendConnection = toml::from_str(
+        r#"
+        kind = "openai"
+        roles = { assistant = "model", system = "developer" }
+        "#,
+    )
+    .expect("Failed to parse connection");
+    let backend = OpenAI::from(&connection);
+
+    let cases = [
+        (Message::new_user("user", "Hi"), "user"),
+        (Message::new_system("gpt-4", "Hello"), "model"),
+        (
+            Message::new_system("system", "Be brief").with_context(true),
+            "developer",
+        ),
+    ];
+    for (msg, want) in cases {
+        assert_eq!(MessageRequest::new(&msg, &backend.roles).role, want);
+    }
+}
+
+#[test]
+fn test_merge_tool_call_id_based() {
+    let lines = [
+        r#"{"index":0,"id":"call_1","type":"function","function":{"name":"get_weather","arguments":""}}"#,
+        r#"{"index":0,"function":{"arguments":"{\"city\":"}}"#,
+        r#"{"index":0,"function":{"arguments":"\"Hanoi\"}"}}"#,
+        r#"{"index":0,"id":"call_2","type":"function","function":{"name":"get_time","arguments":"{}"}}"#,
+    ];
+
+    let mut calls = vec![];
+    for line in lines {
+        let delta: ToolCallResponse = serde_json::from_str(line).expect("invalid delta");
+        merge_tool_call(&mut calls, ToolCallStyle::IdBased, &delta);
+    }
+
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].id.as_deref(), Some("call_1"));
+    assert_eq!(calls[0].function.name.as_deref(), Some("get_weather"));
+    assert_eq!(
+        calls[0].function.arguments.as_deref(),
+        Some(r#"{"city":"Hanoi"}"#)
+    );
+    assert_eq!(calls[1].id.as_deref(), Some("call_2"));
+    assert_eq!(calls[1].function.name.as_deref(), Some("get_time"));
+    assert_eq!(calls[1].function.arguments.as_deref(), Some("{}"));
+
+    // The indexed style merges everything into a single call
+    let mut calls = vec![];
+    for line in lines {
+        let delta: ToolCallResponse = serde_json::from_str(line).expect("invalid delta");
+        merge_tool_call(&mut calls, ToolCallStyle::Indexed, &delta);
+    }
+    assert_eq!(calls.len(), 1);
 }
 
 async fn collect_responses(
@@ -147,3 +674,112 @@ async fn setup_backend(url: String) -> OpenAI {
         .with_want_models(vec!["gpt-3.5-turbo".to_string(), "gpt-4".to_string()]);
     backend
 }
+
+#[tokio::test]
+async fn test_health_check() {
+    let body = serde_json::to_string(&ModelListResponse {
+        data: vec![ModelResponse {
+            id: "gpt-4".to_string(),
+        }],
+    });
+
+    let mut server = mockito::Server::new_async().await;
+    let models_handler = server
+        .mock("GET", "/v1/models")
+        .with_status(200)
+        .with_body(body.unwrap())
+        .expect(1)
+        .create();
+
+    let backend = OpenAI::default().with_endpoint(&server.url());
+    backend
+        .health_check()
+        .await
+        .expect("Failed to check health");
+
+    // The models fetched by the health check are reused
+    let res = backend.list_models().await.expect("Failed to list models");
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].id(), "gpt-4");
+    models_handler.assert();
+
+    let server = mockito::Server::new_async().await;
+    let backend = OpenAI::default().with_endpoint(&server.url());
+    assert!(backend.health_check().await.is_err());
+}
+
+#[test]
+fn test_api_base() {
+    let cases = [
+        ("https://api.openai.com", None, "https://api.openai.com/v1"),
+        ("https://api.openai.com/", None, "https://api.openai.com/v1"),
+        ("http://gateway", Some("/api/v1/"), "http://gateway/api/v1"),
+        ("http://gateway/", Some("api"), "http://gateway/api"),
+        ("http://gateway/openai", Some(""), "http://gateway/openai"),
+    ];
+    for (endpoint, prefix, want) in cases {
+        assert_eq!(
+            api_base(endpoint, prefix),
+            want,
+            "{} {:?}",
+            endpoint,
+            prefix
+        );
+    }
+
+    assert!(validate_api_base("https://api.openai.com", None).is_ok());
+    assert!(validate_api_base("api.openai.com", None).is_err());
+    assert!(validate_api_base("localhost:8080", Some("/v1")).is_err());
+}
+
+#[tokio::test]
+async fn test_list_models_with_path_prefix() {
+    let body = serde_json::to_string(&ModelListResponse {
+        data: vec![ModelResponse {
+            id: "gpt-4".to_string(),
+        }],
+    });
+
+    let mut server = mockito::Server::new_async().await;
+    let models_handler = server
+        .mock("GET", "/api/v1/models")
+        .with_status(200)
+        .with_body(body.unwrap())
+        .expect(1)
+        .create();
+
+    let backend = OpenAI::default()
+        .with_endpoint(&server.url())
+        .with_path_prefix("/api/v1");
+    let res = backend.list_models().await.expect("Failed to list models");
+    assert_eq!(res.len(), 1);
+    models_handler.assert();
+}
+
+#[test]
+fn test_usage_details() {
+    let usage: CompletionUsageResponse = serde_json::from_str(
+        r#"{"prompt_tokens":100,"completion_tokens":50,"total_tokens":150,
+        "prompt_tokens_details":{"cached_tokens":80},
+        "completion_tokens_details":{"reasoning_tokens":20}}"#,
+    )
+    .unwrap();
+    let usage = BackendUsage::from(usage);
+    assert_eq!(usage.cached_prompt_tokens, Some(80));
+    assert_eq!(usage.reasoning_tokens, Some(20));
+    assert_eq!(
+        usage.to_string(),
+        "Prompt Tokens: 100 (cached: 80), Completion Token: 50 (reasoning: 20), Total: 150"
+    );
+
+    // The details are not reported by every provider
+    let usage: CompletionUsageResponse =
+        serde_json::from_str(r#"{"prompt_tokens":1,"completion_tokens":2,"total_tokens":3}"#)
+            .unwrap();
+    let usage = BackendUsage::from(usage);
+    assert_eq!(usage.cached_prompt_tokens, None);
+    assert_eq!(
+        usage.to_string(),
+        "Prompt Tokens: 1, Completion Token: 2, Total: 3"
+    );
+}
@@ -0,0 +1,61 @@
+#[cfg(test)]
+#[path = "redact_test.rs"]
+mod tests;
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use crate::config::{self, ContentRedaction};
+
+static SECRET_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // Authorization: Bearer <token>
+        r"(?i)(bearer\s+)[^\s,]+",
+        // API keys passed in the query string, e.g. Gemini
+        r"(?i)([?&](?:key|api_key|access_token)=)[^&\s]+",
+        // API keys in headers or JSON bodies
+        r#"(?i)("?(?:api[_-]?key|x-goog-api-key)"?\s*[:=]\s*"?)[^\s",]+"#,
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("invalid secret pattern"))
+    .collect()
+});
+
+static CONTENT_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""(content|text|arguments)"(\s*:\s*)"((?:[^"\\]|\\.)*)""#)
+        .expect("invalid content pattern")
+});
+
+/// Mask the secrets of a request or response before it is logged. The
+/// message content is hashed or omitted according to
+/// `log.redact_message_content`.
+pub(crate) fn redact(text: &str) -> String {
+    redact_with(text, config::instance().log.redact_message_content)
+}
+
+fn redact_with(text: &str, content: ContentRedaction) -> String {
+    let mut text = text.to_string();
+    for pattern in SECRET_PATTERNS.iter() {
+        text = pattern.replace_all(&text, "${1}***").into_owned();
+    }
+
+    if content == ContentRedaction::None {
+        return text;
+    }
+    CONTENT_PATTERN
+        .replace_all(&text, |caps: &Captures| {
+            let value = &caps[3];
+            let redacted = match content {
+                ContentRedaction::Hash => {
+                    let mut hasher = DefaultHasher::new();
+                    value.hash(&mut hasher);
+                    format!("<hash:{:016x} len:{}>", hasher.finish(), value.len())
+                }
+                _ => "<omitted>".to_string(),
+            };
+            format!("\"{}\"{}\"{}\"", &caps[1], &caps[2], redacted)
+        })
+        .into_owned()
+}
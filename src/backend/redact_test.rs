@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn test_redact_secrets() {
+    assert_eq!(
+        redact_with("Authorization: Bearer sk-123456", ContentRedaction::None),
+        "Authorization: Bearer ***"
+    );
+    assert_eq!(
+        redact_with(
+            "https://example.com/models/gemini:streamGenerateContent?key=AIza123&alt=sse",
+            ContentRedaction::None
+        ),
+        "https://example.com/models/gemini:streamGenerateContent?key=***&alt=sse"
+    );
+    assert_eq!(
+        redact_with(
+            r#"{"api_key":"secret","model":"gpt-4o"}"#,
+            ContentRedaction::None
+        ),
+        r#"{"api_key":"***","model":"gpt-4o"}"#
+    );
+}
+
+#[test]
+fn test_redact_content() {
+    let line = r#"{"messages":[{"role":"user","content":"Hello \"world\""}],"model":"gpt-4o"}"#;
+    assert_eq!(redact_with(line, ContentRedaction::None), line);
+    assert_eq!(
+        redact_with(line, ContentRedaction::Omit),
+        r#"{"messages":[{"role":"user","content":"<omitted>"}],"model":"gpt-4o"}"#
+    );
+
+    let hashed = redact_with(line, ContentRedaction::Hash);
+    assert!(hashed.contains(r#""content":"<hash:"#));
+    assert!(!hashed.contains("world"));
+    // The same content is hashed the same way to correlate the logs
+    assert_eq!(hashed, redact_with(line, ContentRedaction::Hash));
+
+    let line = r#"{"parts": [{"text": "secret plan"}]"#;
+    assert_eq!(
+        redact_with(line, ContentRedaction::Omit),
+        r#"{"parts": [{"text": "<omitted>"}]"#
+    );
+}
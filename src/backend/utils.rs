@@ -2,7 +2,24 @@
 #[path = "utils_test.rs"]
 mod tests;
 
-use crate::{config, models::Message};
+use std::sync::Arc;
+
+use aws_smithy_runtime_api::client::result::ConnectorError;
+use eyre::{Context, Result};
+use futures::{Stream, TryStreamExt};
+
+use crate::{
+    backend::mcp::{CallToolResult, CallToolResultContent, McpClient},
+    config,
+    models::{ArcEventTx, Event, Message, ToolApproval, ToolCall},
+};
+
+/// The tool result returned to the model when the user denies the call
+pub(crate) const TOOL_CALL_DENIED: &str = "The user denied the tool call";
+
+/// The result of a tool call displayed in the conversation is truncated to
+/// this number of characters
+const MAX_TOOL_RESULT_SUMMARY_LENGTH: usize = 500;
 
 pub(crate) fn context_truncation(context: &mut Vec<Message>, max_output_tokens: usize) {
     if !config::instance().context.truncation.enabled || max_output_tokens == 0 {
@@ -33,3 +50,216 @@ pub(crate) fn context_truncation(context: &mut Vec<Message>, max_output_tokens:
         context.pop();
     }
 }
+
+/// Serialize the request body with the extra fields of the connection and
+/// then of the model settings merged last, so they override the defaults.
+/// The nested objects are merged. The values are not checked, the provider
+/// rejects the malformed ones.
+pub(crate) fn with_extra_body(
+    body: &impl serde::Serialize,
+    extras: &[Option<&serde_json::Map<String, serde_json::Value>>],
+) -> Result<serde_json::Value> {
+    // Through the text, `to_value` widens the f32 fields, e.g. 0.9 becomes
+    // 0.8999999761581421
+    let body = serde_json::to_string(body).wrap_err("serializing request body")?;
+    let mut body: serde_json::Value =
+        serde_json::from_str(&body).wrap_err("serializing request body")?;
+    for extra in extras.iter().flatten() {
+        merge_object(&mut body, extra);
+    }
+    Ok(body)
+}
+
+fn merge_object(
+    target: &mut serde_json::Value,
+    extra: &serde_json::Map<String, serde_json::Value>,
+) {
+    let Some(target) = target.as_object_mut() else {
+        return;
+    };
+    for (key, value) in extra {
+        match (target.get_mut(key), value) {
+            (Some(existing @ serde_json::Value::Object(_)), serde_json::Value::Object(value)) => {
+                merge_object(existing, value);
+            }
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Whether the request failed because the backend couldn't be reached, e.g.
+/// the network is down, rather than being rejected by the API. The prompt
+/// can be sent again as is once the backend is back online. A timeout is not
+/// one, the backend may still be processing the request.
+pub(crate) fn is_connection_error(err: &eyre::Report) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return err.is_connect();
+        }
+        // The AWS SDK failed to dispatch a request, e.g. resolving the
+        // Bedrock credentials
+        if let Some(err) = cause.downcast_ref::<ConnectorError>() {
+            return err.is_io();
+        }
+        cause.downcast_ref::<std::io::Error>().is_some_and(|err| {
+            matches!(
+                err.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::NotConnected
+                    | std::io::ErrorKind::HostUnreachable
+                    | std::io::ErrorKind::NetworkUnreachable
+            )
+        })
+    })
+}
+
+/// Read the lines of a streamed response body. The bytes are buffered until
+/// the line ends, so a multibyte character split across the network chunks
+/// is decoded whole. A line which is not valid UTF-8 is decoded lossily
+/// rather than ending the stream.
+pub(crate) struct LineReader<S> {
+    stream: S,
+    buf: Vec<u8>,
+    /// The buffered bytes already searched for a line ending
+    scanned: usize,
+    ended: bool,
+}
+
+impl<S, B, E> LineReader<S>
+where
+    S: Stream<Item = std::result::Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    pub(crate) fn new(stream: S) -> Self {
+        Self {
+            stream,
+            buf: vec![],
+            scanned: 0,
+            ended: false,
+        }
+    }
+
+    /// Return the next line without its line ending, `None` once the body
+    /// is read. The last line may not end with a line ending.
+    pub(crate) async fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            if let Some(pos) = self.buf[self.scanned..].iter().position(|b| *b == b'\n') {
+                let line = self.buf.drain(..=self.scanned + pos).collect::<Vec<_>>();
+                self.scanned = 0;
+                return Ok(Some(decode_line(&line)));
+            }
+            self.scanned = self.buf.len();
+
+            if self.ended {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                self.scanned = 0;
+                return Ok(Some(decode_line(&std::mem::take(&mut self.buf))));
+            }
+
+            match self.stream.try_next().await.wrap_err("reading response")? {
+                Some(chunk) => self.buf.extend_from_slice(chunk.as_ref()),
+                None => self.ended = true,
+            }
+        }
+    }
+}
+
+fn decode_line(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).into_owned()
+}
+
+/// Call the tool through the MCP client. If `require_approval` is enabled,
+/// the user is asked first and a denial is returned to the model as the tool
+/// result. The call is reported to the app to be displayed in the
+/// conversation.
+pub(crate) async fn call_tool_with_approval(
+    mcp: &Arc<dyn McpClient>,
+    tool: &str,
+    provider: &str,
+    args: Option<serde_json::Value>,
+    event_tx: &ArcEventTx,
+) -> Result<CallToolResult> {
+    let result = approve_and_call_tool(mcp, tool, provider, args.clone(), event_tx).await?;
+    event_tx
+        .send(Event::ToolCalled(ToolCall {
+            tool: tool.to_string(),
+            provider: provider.to_string(),
+            args,
+            result: summarize_tool_result(&result),
+            is_error: result.is_error,
+        }))
+        .await
+        .wrap_err("sending tool call")?;
+    Ok(result)
+}
+
+async fn approve_and_call_tool(
+    mcp: &Arc<dyn McpClient>,
+    tool: &str,
+    provider: &str,
+    args: Option<serde_json::Value>,
+    event_tx: &ArcEventTx,
+) -> Result<CallToolResult> {
+    let require_approval = config::instance()
+        .backend
+        .mcp
+        .require_approval
+        .unwrap_or_default();
+
+    if require_approval {
+        let (approval, rx) = ToolApproval::new(tool, provider, args.clone());
+        event_tx
+            .send(Event::ToolApprovalRequest(approval))
+            .await
+            .wrap_err("sending tool approval request")?;
+        // A dropped request (e.g. the app is closing) is treated as denied
+        if !rx.await.unwrap_or_default() {
+            log::debug!("The user denied calling tool {}", tool);
+            return Ok(CallToolResult {
+                provider: provider.to_string(),
+                content: vec![CallToolResultContent::Text {
+                    text: TOOL_CALL_DENIED.to_string(),
+                }],
+                is_error: true,
+            });
+        }
+    }
+
+    mcp.call_tool(tool, args).await
+}
+
+fn summarize_tool_result(result: &CallToolResult) -> String {
+    let summary = result
+        .content
+        .iter()
+        .map(|content| match content {
+            CallToolResultContent::Text { text } => text.trim().to_string(),
+            CallToolResultContent::Image { mime_type, .. } => format!("[{}]", mime_type),
+            CallToolResultContent::Resource { resource } => resource
+                .text
+                .as_deref()
+                .map(|text| text.trim().to_string())
+                .unwrap_or_else(|| format!("[{}]", resource.uri)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if summary.chars().count() <= MAX_TOOL_RESULT_SUMMARY_LENGTH {
+        return summary;
+    }
+    let mut summary = summary
+        .chars()
+        .take(MAX_TOOL_RESULT_SUMMARY_LENGTH)
+        .collect::<String>();
+    summary.push('…');
+    summary
+}
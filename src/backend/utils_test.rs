@@ -36,3 +36,173 @@ fn test_context_truncation() {
     assert_eq!(context[1].text(), "No, i'm not ok");
     assert_eq!(context[2].text(), "urmom");
 }
+
+#[tokio::test]
+async fn test_call_tool_with_approval() {
+    let mut config = Configuration::default();
+    config.backend.mcp.require_approval = Some(true);
+    crate::config::init(config).expect("failed to init config");
+
+    let mut mock_mcp = crate::backend::mcp::MockMcpClient::new();
+    mock_mcp
+        .expect_call_tool()
+        .withf(|tool, _| tool == "read_file")
+        .times(1)
+        .returning(|_, _| {
+            Box::pin(async {
+                Ok(CallToolResult {
+                    provider: "fs".to_string(),
+                    content: vec![CallToolResultContent::Text {
+                        text: "content".to_string(),
+                    }],
+                    is_error: false,
+                })
+            })
+        });
+    let mcp: Arc<dyn McpClient> = Arc::new(mock_mcp);
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
+    let event_tx: ArcEventTx = Arc::new(tx);
+
+    let answers = tokio::spawn(async move {
+        let mut calls = vec![];
+        for approved in [false, true] {
+            match rx.recv().await {
+                Some(Event::ToolApprovalRequest(approval)) => {
+                    assert_eq!(approval.tool, "read_file");
+                    assert_eq!(approval.provider, "fs");
+                    approval.respond(approved);
+                }
+                _ => panic!("Expected a tool approval request"),
+            }
+            match rx.recv().await {
+                Some(Event::ToolCalled(call)) => calls.push(call),
+                _ => panic!("Expected a tool call"),
+            }
+        }
+        calls
+    });
+
+    let denied = call_tool_with_approval(&mcp, "read_file", "fs", None, &event_tx)
+        .await
+        .expect("Failed to call tool");
+    assert!(denied.is_error);
+    assert!(matches!(
+        &denied.content[0],
+        CallToolResultContent::Text { text } if text == TOOL_CALL_DENIED
+    ));
+
+    let approved = call_tool_with_approval(&mcp, "read_file", "fs", None, &event_tx)
+        .await
+        .expect("Failed to call tool");
+    assert!(!approved.is_error);
+
+    let calls = answers.await.expect("Failed to answer the approvals");
+    assert_eq!(calls.len(), 2);
+    assert!(calls[0].is_error);
+    assert_eq!(calls[0].result, TOOL_CALL_DENIED);
+    assert!(!calls[1].is_error);
+    assert_eq!(calls[1].result, "content");
+}
+
+#[tokio::test]
+async fn test_is_connection_error() {
+    // Nothing listens on the port
+    let err = reqwest::Client::new()
+        .get("http://127.0.0.1:1")
+        .send()
+        .await
+        .unwrap_err();
+    let err = eyre::Report::new(err).wrap_err("sending completion request");
+    assert!(is_connection_error(&err));
+
+    let err = eyre::Report::new(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+    assert!(is_connection_error(&err));
+    let err = eyre::Report::new(std::io::Error::from(std::io::ErrorKind::TimedOut));
+    assert!(!is_connection_error(&err));
+
+    let err = eyre::Report::new(ConnectorError::io("connection refused".into()))
+        .wrap_err("resolving AWS credentials");
+    assert!(is_connection_error(&err));
+    let err = eyre::Report::new(ConnectorError::timeout("timed out".into()));
+    assert!(!is_connection_error(&err));
+
+    let err = eyre::Report::new(crate::backend::openai::OpenAIError {
+        http_code: 401,
+        message: "Invalid API key".to_string(),
+        ..Default::default()
+    });
+    assert!(!is_connection_error(&err));
+    assert!(!is_connection_error(&eyre::eyre!("no model is set")));
+}
+
+#[test]
+fn test_with_extra_body() {
+    let body = serde_json::json!({
+        "model": "gpt-4",
+        "temperature": 0.5,
+        "stop": ["END"],
+        "generationConfig": {"topP": 0.9},
+    });
+    let connection = serde_json::json!({
+        "seed": 1,
+        "generationConfig": {"seed": 1},
+    });
+    let settings = serde_json::json!({
+        "seed": 42,
+        "temperature": 0.7,
+        "response_format": {"type": "json_object"},
+    });
+
+    let actual =
+        with_extra_body(&body, &[connection.as_object(), None, settings.as_object()]).unwrap();
+    assert_eq!(
+        actual,
+        serde_json::json!({
+            "model": "gpt-4",
+            "temperature": 0.7,
+            "stop": ["END"],
+            "seed": 42,
+            "response_format": {"type": "json_object"},
+            "generationConfig": {"topP": 0.9, "seed": 1},
+        })
+    );
+
+    assert_eq!(with_extra_body(&body, &[]).unwrap(), body);
+}
+
+#[tokio::test]
+async fn test_line_reader() {
+    let mut body = "data: Hi 👋\r\n\n".as_bytes().to_vec();
+    // Not valid UTF-8
+    body.extend_from_slice(b"data: \xff\ndata: end");
+    // The emoji is split across the chunks
+    let split = "data: Hi ".len() + 1;
+    let chunks = vec![
+        Ok::<_, std::io::Error>(body[..split].to_vec()),
+        Ok(body[split..split + 2].to_vec()),
+        Ok(body[split + 2..].to_vec()),
+    ];
+
+    let mut reader = LineReader::new(futures::stream::iter(chunks));
+    let mut lines = vec![];
+    while let Some(line) = reader.next_line().await.unwrap() {
+        lines.push(line);
+    }
+    assert_eq!(
+        lines,
+        vec!["data: Hi 👋", "", "data: \u{fffd}", "data: end"]
+    );
+
+    let chunks = vec![
+        Ok(b"data: Hi\n".to_vec()),
+        Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+    ];
+    let mut reader = LineReader::new(futures::stream::iter(chunks));
+    assert_eq!(
+        reader.next_line().await.unwrap(),
+        Some("data: Hi".to_string())
+    );
+    let err = reader.next_line().await.unwrap_err();
+    assert!(is_connection_error(&err));
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use eyre::{Context, Result};
 
 use crate::config::{self, Configuration, load_configuration, lookup_config_path};
@@ -24,6 +24,104 @@ pub struct Command {
     /// Show the version
     #[arg(short, long)]
     version: bool,
+
+    /// Validate the configuration and exit, the backends are not contacted
+    #[arg(long)]
+    config_check: bool,
+
+    /// Disable the colors, also disabled if `NO_COLOR` is set
+    #[arg(long)]
+    no_color: bool,
+
+    /// Send the prompt and print the response instead of opening the UI,
+    /// `-` reads the prompt from the standard input
+    #[arg(short, long, value_name = "TEXT")]
+    prompt: Option<String>,
+
+    /// The model of the prompt, the default model is used if not set
+    #[arg(short, long, value_name = "MODEL", requires = "prompt")]
+    model: Option<String>,
+
+    /// Flush the response after every chunk even if the output is not a
+    /// terminal, e.g. to follow it through a pipe
+    #[arg(long, requires = "prompt")]
+    flush: bool,
+
+    #[command(subcommand)]
+    command: Option<SubCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SubCommand {
+    /// Import conversations from an archive or other applications
+    Import(ImportArgs),
+    /// Export conversations to an archive
+    Export(ExportArgs),
+    /// List the models of the backends and the model settings they resolve to
+    Models(ModelsArgs),
+    /// Show the usage statistics of the conversations
+    Stats,
+    /// Search the conversations by their title and messages
+    Search(SearchArgs),
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct ImportArgs {
+    /// The `conversations.json` file of a ChatGPT data export
+    #[arg(long, value_name = "FILE")]
+    pub chatgpt: Option<String>,
+
+    /// An archive created by `chatty export`
+    #[arg(long, value_name = "FILE")]
+    pub archive: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Export all conversations
+    #[arg(long, required = true)]
+    pub all: bool,
+
+    /// The archive format
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+
+    /// The output file path
+    #[arg(value_name = "PATH")]
+    pub path: String,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = true)]
+pub struct ModelsArgs {
+    /// List the models of each connection
+    #[arg(long)]
+    pub list: bool,
+
+    /// Only list the models matching this regex pattern
+    #[arg(long = "match", value_name = "PATTERN")]
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Rank the conversations by meaning with their embeddings, see the
+    /// `search` configuration
+    #[arg(long)]
+    pub semantic: bool,
+
+    /// The number of conversations to show
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    #[arg(value_name = "QUERY")]
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
 }
 
 impl Command {
@@ -33,11 +131,14 @@ impl Command {
             .clone()
             .unwrap_or_else(|| lookup_config_path().unwrap_or_default());
 
-        let config = if !config_path.is_empty() {
+        let mut config = if !config_path.is_empty() {
             load_configuration(config_path.as_str()).wrap_err("loading configuration")?
         } else {
             Configuration::default()
         };
+        if self.no_color() {
+            config.theme.no_color = Some(true);
+        }
 
         config::init(config).wrap_err("initializing configuration")?;
         Ok(config::instance())
@@ -47,6 +148,32 @@ impl Command {
         self.version
     }
 
+    pub fn config_check(&self) -> bool {
+        self.config_check
+    }
+
+    /// Whether the colors are disabled by `--no-color` or by a non-empty
+    /// `NO_COLOR`, see https://no-color.org
+    pub fn no_color(&self) -> bool {
+        self.no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+    }
+
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    pub fn flush(&self) -> bool {
+        self.flush
+    }
+
+    pub fn subcommand(&self) -> Option<&SubCommand> {
+        self.command.as_ref()
+    }
+
     pub fn print_version(&self) {
         println!("{}", config::version())
     }
@@ -0,0 +1,130 @@
+#[cfg(test)]
+#[path = "background_test.rs"]
+mod tests;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How long to wait for the terminal to report its background color
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    /// Query the terminal for its background color
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+/// Query the background color of the terminal with `OSC 11`. A `DA1` query is
+/// sent right after it, almost all terminals answer it so the terminals
+/// which don't support `OSC 11` don't need to wait for the timeout. Returns
+/// `None` if the background cannot be detected.
+///
+/// The answer is read from the terminal directly, so it must be called
+/// before anything else starts reading the terminal events.
+pub fn detect_background() -> Option<Background> {
+    let response = query_terminal("\x1b]11;?\x1b\\\x1b[c", QUERY_TIMEOUT)?;
+    let background = parse_background_response(&response);
+    log::debug!("Detected terminal background: {:?}", background);
+    background
+}
+
+/// Parse the `OSC 11` answer e.g. `ESC ] 11 ; rgb:ffff/ffff/ffff ESC \`. The
+/// background is light if its relative luminance is over 50%.
+pub fn parse_background_response(response: &str) -> Option<Background> {
+    let start = response.find("]11;rgb:")? + "]11;rgb:".len();
+    let end = response[start..]
+        .find(['\x1b', '\x07'])
+        .map(|i| start + i)
+        .unwrap_or(response.len());
+
+    let channels = response[start..end]
+        .split('/')
+        .map(parse_channel)
+        .collect::<Option<Vec<_>>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    if luminance > 0.5 {
+        Some(Background::Light)
+    } else {
+        Some(Background::Dark)
+    }
+}
+
+/// Parse a color channel of 1 to 4 hex digits into a value between 0 and 1
+fn parse_channel(value: &str) -> Option<f32> {
+    if value.is_empty() || value.len() > 4 {
+        return None;
+    }
+    let max = (1u32 << (4 * value.len())) - 1;
+    let value = u32::from_str_radix(value, 16).ok()?;
+    Some(value as f32 / max as f32)
+}
+
+#[cfg(unix)]
+fn query_terminal(query: &str, timeout: Duration) -> Option<String> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+
+    // The answer is only readable in raw mode, otherwise the terminal waits
+    // for a new line
+    let raw_mode = crossterm::terminal::is_raw_mode_enabled().unwrap_or_default();
+    if !raw_mode {
+        crossterm::terminal::enable_raw_mode().ok()?;
+    }
+
+    let mut response = String::new();
+    if tty
+        .write_all(query.as_bytes())
+        .and_then(|_| tty.flush())
+        .is_ok()
+    {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut buf = [0u8; 256];
+        // Read until the answer of the DA1 query which ends with `c`
+        while !response.contains("\x1b[?") || !response.ends_with('c') {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let mut fds = libc::pollfd {
+                fd: tty.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `fds` is a single valid pollfd living across the call,
+            // matching the count of 1, and `tty` keeps its descriptor open
+            let ready = unsafe { libc::poll(&mut fds, 1, remaining.as_millis() as libc::c_int) };
+            if ready <= 0 {
+                break;
+            }
+            match tty.read(&mut buf) {
+                Ok(n) if n > 0 => response.push_str(&String::from_utf8_lossy(&buf[..n])),
+                _ => break,
+            }
+        }
+    }
+
+    if !raw_mode {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+    Some(response)
+}
+
+#[cfg(not(unix))]
+fn query_terminal(_query: &str, _timeout: Duration) -> Option<String> {
+    None
+}
@@ -0,0 +1,27 @@
+use super::*;
+
+#[test]
+fn test_parse_background_response() {
+    let cases = [
+        (
+            "\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b[?62;22c",
+            Some(Background::Light),
+        ),
+        ("\x1b]11;rgb:fdfd/f6f6/e3e3\x07", Some(Background::Light)),
+        ("\x1b]11;rgb:0000/2b2b/3636\x1b\\", Some(Background::Dark)),
+        ("\x1b]11;rgb:1e/1e/1e\x07", Some(Background::Dark)),
+        ("\x1b[?62;22c", None),
+        ("\x1b]11;rgb:ffff/ffff\x07", None),
+        ("\x1b]11;rgb:zzzz/ffff/ffff\x07", None),
+        ("", None),
+    ];
+
+    for (response, expected) in cases {
+        assert_eq!(
+            parse_background_response(response),
+            expected,
+            "response: {:?}",
+            response
+        );
+    }
+}
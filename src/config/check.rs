@@ -0,0 +1,273 @@
+#[cfg(test)]
+#[path = "check_test.rs"]
+mod tests;
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use log::LevelFilter;
+
+use crate::backend::openai;
+use crate::models::{BackendConnection, BackendKind};
+
+use super::{
+    Configuration, McpServer, McpServerConfig, StorageConfig, init_color_theme, resolve_path,
+    select_code_theme, select_theme,
+};
+
+/// The result of validating the configuration, one check per section
+#[derive(Default, Debug)]
+pub struct ConfigReport {
+    checks: Vec<Check>,
+}
+
+#[derive(Debug)]
+struct Check {
+    name: String,
+    problems: Vec<String>,
+}
+
+impl ConfigReport {
+    pub fn has_problems(&self) -> bool {
+        self.checks.iter().any(|check| !check.problems.is_empty())
+    }
+
+    /// Return the problems of the given section
+    pub fn problems(&self, name: &str) -> &[String] {
+        self.checks
+            .iter()
+            .find(|check| check.name == name)
+            .map(|check| check.problems.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn add(&mut self, name: impl Into<String>, problems: Vec<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            problems,
+        });
+    }
+}
+
+impl Display for ConfigReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            if check.problems.is_empty() {
+                writeln!(f, "[OK]    {}", check.name)?;
+                continue;
+            }
+            writeln!(f, "[ERROR] {}", check.name)?;
+            for problem in &check.problems {
+                writeln!(f, "        - {}", problem)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validate the configuration without contacting the backends
+pub fn check_configuration(config: &Configuration) -> ConfigReport {
+    let mut report = ConfigReport::default();
+    report.add("log", check_log(config));
+    report.add("theme", check_theme(config));
+
+    let connections = &config.backend.connections;
+    if !connections.iter().any(|conn| conn.enabled()) {
+        report.add("backend", vec!["no backend connection enabled".to_string()]);
+    }
+
+    let mut aliases = HashSet::new();
+    for (i, conn) in connections.iter().enumerate() {
+        let alias = conn.name();
+        let mut problems = check_connection(conn);
+        if conn.enabled() && !aliases.insert(alias) {
+            problems.push(format!("connection {} already exists", alias));
+        }
+        report.add(format!("backend.connections[{}] ({})", i, alias), problems);
+    }
+
+    for (i, settings) in config.backend.model_settings.iter().enumerate() {
+        let problems = match settings.model.build() {
+            Ok(_) => vec![],
+            Err(err) => vec![format!("invalid model filter: {:#}", err)],
+        };
+        report.add(format!("backend.model_settings[{}]", i), problems);
+    }
+
+    let mut providers = HashSet::new();
+    for (i, server) in config.backend.mcp.servers.iter().enumerate() {
+        let mut problems = check_mcp_server(server);
+        if !providers.insert(server.provider.as_str()) {
+            problems.push(format!("provider {} already exists", server.provider));
+        }
+        report.add(
+            format!("backend.mcp.servers[{}] ({})", i, server.provider),
+            problems,
+        );
+    }
+
+    for (i, hook) in config.hooks.webhooks.iter().enumerate() {
+        let problems = match reqwest::Url::parse(&hook.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => vec![],
+            Ok(url) => vec![format!(
+                "unsupported webhook scheme {}, expected http or https",
+                url.scheme()
+            )],
+            Err(err) => vec![format!("invalid webhook url {}: {}", hook.url, err)],
+        };
+        report.add(format!("hooks.webhooks[{}]", i), problems);
+    }
+
+    report.add("storage", check_storage(&config.storage));
+    report
+}
+
+fn check_log(config: &Configuration) -> Vec<String> {
+    let mut problems = vec![];
+    let levels = config
+        .log
+        .filters
+        .iter()
+        .flatten()
+        .filter_map(|filter| filter.level.as_deref())
+        .chain(config.log.level.as_deref());
+    for level in levels {
+        if LevelFilter::from_str(level).is_err() {
+            problems.push(format!("invalid log level {}", level));
+        }
+    }
+
+    match resolve_path(&config.log.file.path) {
+        // The missing parent directories of the log file are created
+        Ok(path) => problems.extend(check_writable(&path, true)),
+        Err(err) => problems.push(format!("invalid log file path: {:#}", err)),
+    }
+    problems
+}
+
+fn check_theme(config: &Configuration) -> Vec<String> {
+    let mut problems = vec![];
+    if let Err(err) = select_theme(&config.theme, None) {
+        problems.push(format!("{:#}", err));
+    }
+    if let Some(name) = config
+        .ui
+        .code_theme
+        .as_deref()
+        .filter(|name| !name.is_empty())
+    {
+        if let Err(err) = select_code_theme(&config.theme, name) {
+            problems.push(format!("{:#}", err));
+        }
+    }
+    if let Err(err) = init_color_theme(&config.theme) {
+        problems.push(format!("{:#}", err));
+    }
+    problems
+}
+
+fn check_connection(conn: &BackendConnection) -> Vec<String> {
+    let mut problems = vec![];
+    let endpoint = conn.endpoint();
+    match conn.kind() {
+        // The Bedrock endpoint is resolved from the region if not set
+        BackendKind::Bedrock if endpoint.is_empty() => {}
+        _ if endpoint.is_empty() => problems.push("endpoint is empty".to_string()),
+        _ => {
+            if let Err(err) = reqwest::Url::parse(endpoint) {
+                problems.push(format!("invalid endpoint {}: {}", endpoint, err));
+            } else if *conn.kind() == BackendKind::OpenAI {
+                if let Err(err) = openai::validate_api_base(endpoint, conn.path_prefix()) {
+                    problems.push(format!("{:#}", err));
+                }
+            }
+        }
+    }
+    problems
+}
+
+fn check_mcp_server(server: &McpServerConfig) -> Vec<String> {
+    let mut problems = vec![];
+    if server.provider.is_empty() {
+        problems.push("provider is empty".to_string());
+    }
+
+    match &server.server {
+        McpServer::Binary(binary) => {
+            if binary.filename.is_empty() {
+                problems.push("binary filename is empty".to_string());
+            } else if !binary_exists(&binary.filename) {
+                problems.push(format!("binary {} not found", binary.filename));
+            }
+        }
+        McpServer::WebSocket(websocket) => match reqwest::Url::parse(&websocket.url) {
+            Ok(url) if matches!(url.scheme(), "ws" | "wss") => {}
+            Ok(url) => problems.push(format!(
+                "unsupported websocket scheme {}, expected ws or wss",
+                url.scheme()
+            )),
+            Err(err) => problems.push(format!("invalid websocket url {}: {}", websocket.url, err)),
+        },
+    }
+    problems
+}
+
+/// A binary without a path separator is looked up in `PATH`
+fn binary_exists(filename: &str) -> bool {
+    if filename.contains('/') {
+        return resolve_path(filename).is_ok_and(|path| Path::new(&path).is_file());
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(filename).is_file()))
+}
+
+fn check_storage(config: &StorageConfig) -> Vec<String> {
+    match config {
+        StorageConfig::Sqlite(sqlite) => match sqlite.path.as_deref() {
+            // In-memory database
+            None => vec![],
+            Some("") => vec!["sqlite path is empty".to_string()],
+            Some(path) => match resolve_path(path) {
+                Ok(path) => check_writable(&path, false),
+                Err(err) => vec![format!("invalid sqlite path: {:#}", err)],
+            },
+        },
+    }
+}
+
+/// Check the file can be created or written. If `create_dirs` is set, the
+/// nearest existing ancestor directory must be writable instead.
+fn check_writable(path: &str, create_dirs: bool) -> Vec<String> {
+    let path = Path::new(path);
+    if path.is_dir() {
+        return vec![format!("{} is a directory", path.display())];
+    }
+    if path.exists() {
+        return match std::fs::OpenOptions::new().append(true).open(path) {
+            Ok(_) => vec![],
+            Err(err) => vec![format!("{} is not writable: {}", path.display(), err)],
+        };
+    }
+
+    let mut dir = path.parent();
+    if create_dirs {
+        while let Some(parent) = dir.filter(|dir| !dir.exists()) {
+            dir = parent.parent();
+        }
+    }
+    let Some(dir) = dir.filter(|dir| dir.is_dir()) else {
+        return vec![format!("directory of {} does not exist", path.display())];
+    };
+
+    // Probe the directory with a temporary file
+    let probe = dir.join(format!(".chatty-config-check-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            vec![]
+        }
+        Err(err) => vec![format!("{} is not writable: {}", dir.display(), err)],
+    }
+}
@@ -0,0 +1,59 @@
+use super::*;
+use crate::config::load_configuration;
+
+#[test]
+fn test_check_configuration() {
+    let config = load_configuration("./testdata/invalid_config.toml").expect("failed to load");
+    let report = check_configuration(&config);
+    assert!(report.has_problems());
+
+    assert_eq!(report.problems("log"), ["invalid log level loud"]);
+    assert!(report.problems("theme").is_empty());
+    assert!(
+        report
+            .problems("backend.connections[0] (openai)")
+            .is_empty()
+    );
+
+    let problems = report.problems("backend.connections[1] (openai)");
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("invalid endpoint api.openai.com"));
+    assert_eq!(problems[1], "connection openai already exists");
+
+    assert_eq!(
+        report.problems("backend.connections[2] (Gemini)"),
+        ["endpoint is empty"]
+    );
+    assert_eq!(
+        report.problems("backend.connections[3] (gateway)"),
+        ["unsupported scheme localhost of api base localhost:8080/api/v1, expected http or https"]
+    );
+    assert_eq!(report.problems("backend.model_settings[0]").len(), 1);
+    assert_eq!(
+        report.problems("backend.mcp.servers[0] (fs)"),
+        ["binary /nonexistent/mcp-server not found"]
+    );
+    assert_eq!(
+        report.problems("backend.mcp.servers[1] (web)"),
+        ["unsupported websocket scheme http, expected ws or wss"]
+    );
+    assert_eq!(
+        report.problems("hooks.webhooks[0]"),
+        ["unsupported webhook scheme ftp, expected http or https"]
+    );
+    assert_eq!(
+        report.problems("storage"),
+        ["directory of /nonexistent/chatty/chat.db does not exist"]
+    );
+}
+
+#[test]
+fn test_check_default_configuration() {
+    let mut config = Configuration::default();
+    config.backend.connections.push(
+        BackendConnection::new(BackendKind::OpenAI, "https://api.openai.com").with_enabled(true),
+    );
+
+    let report = check_configuration(&config);
+    assert!(!report.has_problems(), "{}", report);
+}
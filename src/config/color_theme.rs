@@ -0,0 +1,212 @@
+#[cfg(test)]
+#[path = "color_theme_test.rs"]
+mod tests;
+
+use eyre::{Context, Result};
+use ratatui::style::{Color, Style};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_COLOR_THEME: &str = "dark";
+
+/// The colors of a message role. `fg` is used for the bubble border and the
+/// issuer, `bg` fills the bubble if set.
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RoleColors {
+    #[serde(default)]
+    pub fg: Option<Color>,
+
+    #[serde(default)]
+    pub bg: Option<Color>,
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct NoticeColors {
+    #[serde(default)]
+    pub border: Option<Color>,
+
+    #[serde(default)]
+    pub text: Option<Color>,
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct NoticeTheme {
+    #[serde(default)]
+    pub info: NoticeColors,
+
+    #[serde(default)]
+    pub warning: NoticeColors,
+
+    #[serde(default)]
+    pub error: NoticeColors,
+}
+
+/// The colors of the chat UI. The `system` colors are used for the messages
+/// issued by the app itself, e.g. the hello message, and the `error` colors
+/// for the ones reporting an error.
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ColorTheme {
+    #[serde(default)]
+    pub user: RoleColors,
+
+    #[serde(default)]
+    pub assistant: RoleColors,
+
+    #[serde(default)]
+    pub system: RoleColors,
+
+    #[serde(default)]
+    pub error: RoleColors,
+
+    #[serde(default)]
+    pub tool: RoleColors,
+
+    /// The colors of the selected text
+    #[serde(default)]
+    pub highlight: RoleColors,
+
+    #[serde(default)]
+    pub notice: NoticeTheme,
+}
+
+impl ColorTheme {
+    /// Return the built-in preset with the given name
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "solarized" => Some(Self::solarized()),
+            _ => None,
+        }
+    }
+
+    pub fn dark() -> Self {
+        Self {
+            user: RoleColors::fg(Color::Rgb(64, 224, 208)),
+            assistant: RoleColors::fg(Color::Rgb(255, 140, 105)),
+            system: RoleColors::fg(Color::Rgb(255, 140, 105)),
+            error: RoleColors::fg(Color::Rgb(255, 82, 82)),
+            tool: RoleColors::fg(Color::Rgb(160, 160, 160)),
+            highlight: RoleColors {
+                fg: Some(Color::Black),
+                bg: Some(Color::Cyan),
+            },
+            notice: NoticeTheme {
+                info: NoticeColors::new(Color::Rgb(30, 136, 229), Color::Rgb(144, 202, 249)),
+                warning: NoticeColors::new(Color::Rgb(251, 140, 0), Color::Rgb(255, 213, 79)),
+                error: NoticeColors::new(Color::Rgb(211, 47, 47), Color::Rgb(255, 138, 128)),
+            },
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            user: RoleColors::fg(Color::Rgb(0, 128, 128)),
+            assistant: RoleColors::fg(Color::Rgb(191, 79, 36)),
+            system: RoleColors::fg(Color::Rgb(106, 90, 205)),
+            error: RoleColors::fg(Color::Rgb(198, 40, 40)),
+            tool: RoleColors::fg(Color::Rgb(110, 110, 110)),
+            highlight: RoleColors {
+                fg: Some(Color::White),
+                bg: Some(Color::Blue),
+            },
+            notice: NoticeTheme {
+                info: NoticeColors::new(Color::Rgb(30, 136, 229), Color::Rgb(21, 101, 192)),
+                warning: NoticeColors::new(Color::Rgb(251, 140, 0), Color::Rgb(191, 54, 12)),
+                error: NoticeColors::new(Color::Rgb(211, 47, 47), Color::Rgb(183, 28, 28)),
+            },
+        }
+    }
+
+    pub fn solarized() -> Self {
+        Self {
+            user: RoleColors::fg(Color::Rgb(42, 161, 152)),
+            assistant: RoleColors::fg(Color::Rgb(203, 75, 22)),
+            system: RoleColors::fg(Color::Rgb(108, 113, 196)),
+            error: RoleColors::fg(Color::Rgb(220, 50, 47)),
+            tool: RoleColors::fg(Color::Rgb(147, 161, 161)),
+            highlight: RoleColors {
+                fg: Some(Color::Rgb(0, 43, 54)),
+                bg: Some(Color::Rgb(181, 137, 0)),
+            },
+            notice: NoticeTheme {
+                info: NoticeColors::new(Color::Rgb(38, 139, 210), Color::Rgb(147, 161, 161)),
+                warning: NoticeColors::new(Color::Rgb(181, 137, 0), Color::Rgb(181, 137, 0)),
+                error: NoticeColors::new(Color::Rgb(220, 50, 47), Color::Rgb(220, 50, 47)),
+            },
+        }
+    }
+
+    /// Load the theme from a TOML file. The colors which are not set in the
+    /// file are taken from the given preset.
+    pub fn load(path: &str, preset: Self) -> Result<Self> {
+        let content =
+            std::fs::read_to_string(path).wrap_err(format!("reading color theme {}", path))?;
+        let theme =
+            toml::from_str::<Self>(&content).wrap_err(format!("parsing color theme {}", path))?;
+        Ok(preset.merge(theme))
+    }
+
+    /// Override the colors of this theme by the ones set in `other`
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            user: self.user.merge(other.user),
+            assistant: self.assistant.merge(other.assistant),
+            system: self.system.merge(other.system),
+            error: self.error.merge(other.error),
+            tool: self.tool.merge(other.tool),
+            highlight: self.highlight.merge(other.highlight),
+            notice: NoticeTheme {
+                info: self.notice.info.merge(other.notice.info),
+                warning: self.notice.warning.merge(other.notice.warning),
+                error: self.notice.error.merge(other.notice.error),
+            },
+        }
+    }
+
+    pub fn highlight_style(&self) -> Style {
+        self.highlight.style()
+    }
+}
+
+impl RoleColors {
+    fn fg(fg: Color) -> Self {
+        Self {
+            fg: Some(fg),
+            bg: None,
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            fg: other.fg.or(self.fg),
+            bg: other.bg.or(self.bg),
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        let mut style = Style::default();
+        if let Some(fg) = self.fg {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = self.bg {
+            style = style.bg(bg);
+        }
+        style
+    }
+}
+
+impl NoticeColors {
+    fn new(border: Color, text: Color) -> Self {
+        Self {
+            border: Some(border),
+            text: Some(text),
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            border: other.border.or(self.border),
+            text: other.text.or(self.text),
+        }
+    }
+}
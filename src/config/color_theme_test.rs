@@ -0,0 +1,35 @@
+use super::*;
+
+#[test]
+fn test_color_theme_preset() {
+    assert_eq!(ColorTheme::preset("dark"), Some(ColorTheme::dark()));
+    assert_eq!(ColorTheme::preset("light"), Some(ColorTheme::light()));
+    assert_eq!(
+        ColorTheme::preset("solarized"),
+        Some(ColorTheme::solarized())
+    );
+    assert_eq!(ColorTheme::preset("unknown"), None);
+
+    let highlight = ColorTheme::dark().highlight_style();
+    assert_eq!(highlight.fg, Some(Color::Black));
+    assert_eq!(highlight.bg, Some(Color::Cyan));
+}
+
+#[test]
+fn test_load_color_theme() {
+    let theme = ColorTheme::load("./testdata/colors.toml", ColorTheme::dark())
+        .expect("failed to load color theme");
+    let dark = ColorTheme::dark();
+
+    assert_eq!(theme.user.fg, Some(Color::Rgb(255, 0, 0)));
+    assert_eq!(theme.user.bg, Some(Color::Black));
+    // The colors not set in the file are taken from the preset
+    assert_eq!(theme.assistant, dark.assistant);
+    assert_eq!(theme.highlight.fg, dark.highlight.fg);
+    assert_eq!(theme.highlight.bg, Some(Color::Yellow));
+    assert_eq!(theme.notice.error.text, Some(Color::Indexed(42)));
+    assert_eq!(theme.notice.error.border, dark.notice.error.border);
+    assert_eq!(theme.notice.info, dark.notice.info);
+
+    assert!(ColorTheme::load("./testdata/not_found.toml", ColorTheme::dark()).is_err());
+}
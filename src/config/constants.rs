@@ -9,6 +9,9 @@ pub const MAX_CONVO_LENGTH: usize = 50; // 50 messages
 /// Keep N lastest messages and compress the rest
 pub const KEEP_N_MESSAGES: usize = 5; // Keep last 5 messages
 
+/// Max tokens of the messages summarized in a single compression request
+pub const COMPRESSION_CHUNK_TOKENS: usize = 16 * 1024; // 16k tokens
+
 pub const HELLO_MESSAGE: &str = "Hello! How can I help you? 😊";
 
 pub const LOG_FILE_PATH: &str = "/tmp/chatty.log";
@@ -19,4 +22,65 @@ pub const MAX_BUBBLE_WIDTH_PERCENT: usize = 95; // 80% of the screen width
 
 pub const MIN_BUBBLE_WIDTH_PERCENT: usize = 50; // 20% of the screen width
 
+/// The minimum width of the window, `ui.max_line_width` can't be less
+pub const MIN_LINE_WIDTH: usize = 80;
+
+/// The maximum height of the input box in lines if not configured
+pub const MAX_INPUT_LINES: usize = 10;
+
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+// The files attached with @file are truncated to this size
+pub const MAX_ATTACHMENT_SIZE: usize = 100 * 1024; // 100 KiB
+pub const INPUT_HISTORY_SIZE: usize = 100;
+
 pub const FRAME_DURATION: time::Duration = time::Duration::from_millis(1000 / 60);
+
+/// The streamed response chunks are coalesced and handled at most once per
+/// interval, so fast streams don't rebuild the messages on every token
+pub const STREAM_FLUSH_INTERVAL: time::Duration = time::Duration::from_millis(1000 / 30);
+
+/// Key events arriving closer than this are treated as part of a paste
+pub const PASTE_BURST_INTERVAL: time::Duration = time::Duration::from_millis(5);
+
+/// The response being streamed is saved at this interval, so it survives an
+/// unexpected exit
+pub const PARTIAL_FLUSH_INTERVAL: time::Duration = time::Duration::from_secs(2);
+
+/// The drafts of the input box are saved at this interval, so they survive
+/// an unexpected exit
+pub const DRAFT_SAVE_INTERVAL: time::Duration = time::Duration::from_secs(5);
+
+/// The prompt is resent at this interval while the backend is unreachable,
+/// doubled after each failed attempt up to the maximum
+pub const OFFLINE_RETRY_INTERVAL: time::Duration = time::Duration::from_secs(5);
+pub const OFFLINE_RETRY_MAX_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+/// Soft-deleted conversations older than this are purged at startup
+pub const PURGE_DELETED_AFTER_DAYS: u64 = 30;
+
+/// How long a write waits for the database locked by another one
+pub const SQLITE_BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// The syntax theme used on a light terminal background if not configured
+pub const SYNTAX_THEME_LIGHT: &str = "InspiredGitHub";
+
+/// A connection which failed is tried last for this number of seconds
+pub const ROUTING_COOLDOWN_SECS: u64 = 60;
+
+/// The model computing the embeddings of the semantic search if not
+/// configured
+pub const EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// The number of conversations returned by a semantic search
+pub const SEMANTIC_SEARCH_RESULTS: usize = 10;
+
+/// The conversations are embedded by this number at once
+pub const EMBEDDING_BATCH_SIZE: usize = 16;
+
+/// The text of a conversation is cut to this number of characters before
+/// computing its embedding, to fit in the input of the embedding models
+pub const EMBEDDING_MAX_CHARS: usize = 8000;
+
+/// A request to a webhook is given up after this number of seconds
+pub const HOOK_TIMEOUT_SECS: u64 = 5;
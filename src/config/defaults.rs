@@ -12,6 +12,10 @@ pub fn max_convo_length() -> usize {
     MAX_CONVO_LENGTH
 }
 
+pub fn compression_chunk_tokens() -> usize {
+    COMPRESSION_CHUNK_TOKENS
+}
+
 pub fn keep_n_messages() -> usize {
     KEEP_N_MESSAGES
 }
@@ -1,9 +1,15 @@
+pub mod background;
+pub mod check;
+pub mod color_theme;
 pub mod constants;
 pub mod defaults;
 pub mod model_filter;
+pub mod model_info;
 pub mod models;
 pub mod utils;
 
+pub use background::Background;
+pub use color_theme::ColorTheme;
 use eyre::Result;
 pub use models::*;
 pub use utils::*;
@@ -33,3 +33,13 @@ impl ModelFilter {
         .wrap_err("building regex")
     }
 }
+
+impl std::fmt::Display for ModelFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelFilter::Contains(value) => write!(f, "contains {:?}", value),
+            ModelFilter::Equals(value) => write!(f, "equals {:?}", value),
+            ModelFilter::Regex(value) => write!(f, "regex {:?}", value),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+#[cfg(test)]
+#[path = "model_info_test.rs"]
+mod tests;
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Model, ModelPricing};
+
+use super::{BackendConfig, resolve_path};
+
+/// The context window and the prices of the well known models, the prices
+/// are in USD per million tokens. A model id matches the longest entry it
+/// starts with, so the dated versions match their base model.
+const BUNDLED_MODEL_INFO: &[(&str, usize, f64, f64)] = &[
+    // OpenAI
+    ("gpt-3.5-turbo", 16_385, 0.5, 1.5),
+    ("gpt-4-turbo", 128_000, 10.0, 30.0),
+    ("gpt-4o", 128_000, 2.5, 10.0),
+    ("gpt-4o-mini", 128_000, 0.15, 0.6),
+    ("gpt-4.1", 1_047_576, 2.0, 8.0),
+    ("gpt-4.1-mini", 1_047_576, 0.4, 1.6),
+    ("gpt-4.1-nano", 1_047_576, 0.1, 0.4),
+    ("o1", 200_000, 15.0, 60.0),
+    ("o1-mini", 128_000, 1.1, 4.4),
+    ("o3", 200_000, 2.0, 8.0),
+    ("o3-mini", 200_000, 1.1, 4.4),
+    ("o4-mini", 200_000, 1.1, 4.4),
+    // Gemini
+    ("gemini-1.5-flash", 1_048_576, 0.075, 0.3),
+    ("gemini-1.5-pro", 2_097_152, 1.25, 5.0),
+    ("gemini-2.0-flash", 1_048_576, 0.1, 0.4),
+    ("gemini-2.0-flash-lite", 1_048_576, 0.075, 0.3),
+    ("gemini-2.5-flash", 1_048_576, 0.3, 2.5),
+    ("gemini-2.5-pro", 1_048_576, 1.25, 10.0),
+    // Anthropic
+    ("claude-3-haiku", 200_000, 0.25, 1.25),
+    ("claude-3-opus", 200_000, 15.0, 75.0),
+    ("claude-3-5-haiku", 200_000, 0.8, 4.0),
+    ("claude-3-5-sonnet", 200_000, 3.0, 15.0),
+    ("claude-3-7-sonnet", 200_000, 3.0, 15.0),
+    ("claude-sonnet-4", 200_000, 3.0, 15.0),
+    ("claude-opus-4", 200_000, 15.0, 75.0),
+];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+
+    #[serde(default)]
+    pub context_window: Option<usize>,
+
+    /// USD per million input tokens
+    #[serde(default)]
+    pub input_price: Option<f64>,
+
+    /// USD per million output tokens
+    #[serde(default)]
+    pub output_price: Option<f64>,
+
+    /// USD per million input tokens read from the prompt cache
+    #[serde(default)]
+    pub cached_input_price: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ModelInfoFile {
+    #[serde(default)]
+    models: Vec<ModelInfo>,
+}
+
+/// The metadata of the models, the entries of the user file take precedence
+/// over the bundled ones
+#[derive(Debug)]
+pub struct ModelCatalog {
+    user: Vec<ModelInfo>,
+    bundled: Vec<ModelInfo>,
+}
+
+impl Default for ModelCatalog {
+    fn default() -> Self {
+        let bundled = BUNDLED_MODEL_INFO
+            .iter()
+            .map(|(id, context_window, input, output)| ModelInfo {
+                id: id.to_string(),
+                context_window: Some(*context_window),
+                input_price: Some(*input),
+                output_price: Some(*output),
+                cached_input_price: None,
+            })
+            .collect();
+        Self {
+            user: vec![],
+            bundled,
+        }
+    }
+}
+
+impl ModelCatalog {
+    /// Load the bundled metadata and the user file if set
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            return Ok(Self::default());
+        };
+
+        let path = resolve_path(path).wrap_err("resolving model info path")?;
+        let content = std::fs::read_to_string(&path).wrap_err(format!("reading {}", path))?;
+        Self::default().with_user_content(&content)
+    }
+
+    fn with_user_content(mut self, content: &str) -> Result<Self> {
+        let file: ModelInfoFile = toml::from_str(content).wrap_err("parsing model info")?;
+        self.user = file.models;
+        Ok(self)
+    }
+
+    /// Return the metadata of the model. The model id may be prefixed by a
+    /// vendor, e.g. `openai/gpt-4o` or `us.anthropic.claude-3-5-sonnet-v1:0`.
+    pub fn lookup(&self, model_id: &str) -> Option<&ModelInfo> {
+        let base = model_id.rsplit('/').next().unwrap_or(model_id);
+        let base = match base.find("anthropic.") {
+            Some(i) => &base[i + "anthropic.".len()..],
+            None => base,
+        };
+
+        longest_match(&self.user, model_id, base)
+            .or_else(|| longest_match(&self.bundled, model_id, base))
+    }
+
+    /// Set the metadata of the model. The model settings take precedence over
+    /// the catalog.
+    pub fn annotate(&self, model: Model, config: &BackendConfig) -> Model {
+        let info = self.lookup(model.id());
+        let setting = config.model_setting(model.id());
+
+        let context_window = config
+            .context_window(model.id())
+            .or_else(|| info.and_then(|info| info.context_window));
+        let input = setting
+            .and_then(|setting| setting.input_price)
+            .or_else(|| info.and_then(|info| info.input_price));
+        let output = setting
+            .and_then(|setting| setting.output_price)
+            .or_else(|| info.and_then(|info| info.output_price));
+        let cached_input = setting
+            .and_then(|setting| setting.cached_input_price)
+            .or_else(|| info.and_then(|info| info.cached_input_price));
+        let pricing = match (input, output) {
+            (Some(input), Some(output)) => Some(ModelPricing {
+                input,
+                output,
+                cached_input,
+            }),
+            _ => None,
+        };
+
+        model
+            .with_context_window(context_window)
+            .with_pricing(pricing)
+    }
+}
+
+fn longest_match<'a>(entries: &'a [ModelInfo], id: &str, base: &str) -> Option<&'a ModelInfo> {
+    entries
+        .iter()
+        .filter(|info| matches_id(&info.id, id) || matches_id(&info.id, base))
+        .max_by_key(|info| info.id.len())
+}
+
+/// The id matches if it is the entry or a version of it, e.g. `gpt-4o-2024-08-06`
+fn matches_id(entry: &str, id: &str) -> bool {
+    match id.strip_prefix(entry) {
+        Some(rest) => rest.is_empty() || rest.starts_with(['-', ':', '@']),
+        None => false,
+    }
+}
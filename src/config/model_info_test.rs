@@ -0,0 +1,126 @@
+use crate::config::ModelSetting;
+use crate::models::BackendUsage;
+
+use super::*;
+
+#[test]
+fn test_lookup() {
+    let catalog = ModelCatalog::default();
+    let cases = [
+        ("gpt-4o", Some("gpt-4o")),
+        ("gpt-4o-2024-08-06", Some("gpt-4o")),
+        ("gpt-4o-mini-2024-07-18", Some("gpt-4o-mini")),
+        ("o1-mini", Some("o1-mini")),
+        ("openai/gpt-4.1", Some("gpt-4.1")),
+        ("models/gemini-2.0-flash-001", Some("gemini-2.0-flash")),
+        (
+            "us.anthropic.claude-3-5-sonnet-20240620-v1:0",
+            Some("claude-3-5-sonnet"),
+        ),
+        ("gpt-4oo", None),
+        ("unknown", None),
+    ];
+    for (id, want) in cases {
+        let got = catalog.lookup(id).map(|info| info.id.as_str());
+        assert_eq!(got, want, "model: {}", id);
+    }
+}
+
+#[test]
+fn test_user_content() {
+    let catalog = ModelCatalog::default()
+        .with_user_content(
+            r#"
+            [[models]]
+            id = "gpt-4o"
+            input_price = 1.0
+            output_price = 2.0
+
+            [[models]]
+            id = "local-llama"
+            context_window = 8192
+            "#,
+        )
+        .unwrap();
+
+    let info = catalog.lookup("gpt-4o-2024-08-06").unwrap();
+    assert_eq!(info.input_price, Some(1.0));
+    assert_eq!(info.context_window, None);
+    assert_eq!(
+        catalog.lookup("local-llama").unwrap().context_window,
+        Some(8192)
+    );
+
+    assert!(
+        ModelCatalog::default()
+            .with_user_content("models = 1")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_annotate() {
+    let catalog = ModelCatalog::default();
+    let setting: ModelSetting = toml::from_str(
+        r#"
+        model.equals = "gpt-4o-mini"
+        context_window = 1000
+        input_price = 0.5
+        output_price = 1.5
+        cached_input_price = 0.25
+        "#,
+    )
+    .expect("Failed to parse model setting");
+    let config = BackendConfig {
+        model_settings: vec![setting],
+        ..Default::default()
+    };
+
+    let model = catalog.annotate(Model::new("gpt-4o"), &config);
+    assert_eq!(model.context_window(), Some(128_000));
+    assert_eq!(
+        model.pricing(),
+        Some(ModelPricing {
+            input: 2.5,
+            output: 10.0,
+            cached_input: None,
+        })
+    );
+
+    let model = catalog.annotate(Model::new("gpt-4o-mini"), &config);
+    assert_eq!(model.context_window(), Some(1000));
+    assert_eq!(
+        model.pricing(),
+        Some(ModelPricing {
+            input: 0.5,
+            output: 1.5,
+            cached_input: Some(0.25),
+        })
+    );
+
+    let model = catalog.annotate(Model::new("unknown"), &config);
+    assert_eq!(model.context_window(), None);
+    assert_eq!(model.pricing(), None);
+}
+
+#[test]
+fn test_pricing_cost() {
+    let mut pricing = ModelPricing {
+        input: 2.0,
+        output: 10.0,
+        cached_input: None,
+    };
+    let mut usage = BackendUsage {
+        prompt_tokens: 1000,
+        completion_tokens: 500,
+        total_tokens: 1500,
+        ..Default::default()
+    };
+    assert!((pricing.cost(&usage) - 0.007).abs() < 1e-9);
+
+    // The cached tokens are charged at the input price unless set
+    usage.cached_prompt_tokens = Some(800);
+    assert!((pricing.cost(&usage) - 0.007).abs() < 1e-9);
+    pricing.cached_input = Some(0.5);
+    assert!((pricing.cost(&usage) - 0.0058).abs() < 1e-9);
+}
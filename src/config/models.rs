@@ -1,16 +1,26 @@
+#[cfg(test)]
+#[path = "models_test.rs"]
+mod tests;
+
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::config::constants::{KEEP_N_MESSAGES, MAX_CONTEXT_LENGTH, MAX_CONVO_LENGTH};
-use crate::models::BackendConnection;
+use crate::config::constants::{
+    COMPRESSION_CHUNK_TOKENS, KEEP_N_MESSAGES, MAX_CONTEXT_LENGTH, MAX_CONVO_LENGTH,
+};
+use crate::models::{BackendConnection, Budget, ResponseFormat};
 
 #[allow(unused_imports)]
 use super::CONFIG;
+use super::background::Background;
 use super::model_filter::ModelFilter;
 
 use super::constants::{
-    HELLO_MESSAGE, LOG_FILE_PATH, MAX_BUBBLE_WIDTH_PERCENT, MIN_BUBBLE_WIDTH_PERCENT,
+    EMBEDDING_MODEL, HELLO_MESSAGE, HOOK_TIMEOUT_SECS, INPUT_HISTORY_SIZE, LOG_FILE_PATH,
+    MAX_ATTACHMENT_SIZE, MAX_BUBBLE_WIDTH_PERCENT, MAX_INPUT_LINES, MIN_BUBBLE_WIDTH_PERCENT,
+    MIN_LINE_WIDTH, PURGE_DELETED_AFTER_DAYS, SEMANTIC_SEARCH_RESULTS, SQLITE_BUSY_TIMEOUT_MS,
+    TIMESTAMP_FORMAT,
 };
 use super::defaults::*;
 
@@ -33,11 +43,21 @@ pub struct Configuration {
 
     #[serde(default)]
     pub context: ContextConfig,
+
+    #[serde(default)]
+    pub ui: UiConfig,
+
+    #[serde(default)]
+    pub search: SearchConfig,
+
+    #[serde(default)]
+    pub hooks: HooksConfig,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GeneralConfig {
-    #[serde(default = "hello_message")]
+    /// The greeting of a new conversation, none if empty
+    #[serde(default = "hello_message", alias = "welcome_message")]
     pub hello_message: Option<String>,
 
     #[serde(default)]
@@ -58,8 +78,286 @@ pub struct GeneralConfig {
     #[serde(default = "default_option_true")]
     pub show_wrapped_indicator: Option<bool>,
 
+    #[serde(default)]
+    pub show_timestamps: Option<bool>,
+
+    #[serde(default)]
+    pub timestamp_format: Option<String>,
+
     #[serde(default)]
     pub copy_on_select: Option<bool>,
+
+    #[serde(default)]
+    pub copy_tool_calls: Option<bool>,
+
+    #[serde(default)]
+    pub copy_context: Option<bool>,
+
+    /// Include the token logprobs of the responses in the copied transcript
+    #[serde(default)]
+    pub copy_logprobs: Option<bool>,
+
+    /// `generate_title` is the deprecated boolean form of the strategy
+    #[serde(
+        default,
+        alias = "generate_title",
+        deserialize_with = "deserialize_title_strategy"
+    )]
+    pub title_strategy: Option<TitleStrategy>,
+
+    #[serde(default)]
+    pub title_model: Option<String>,
+
+    #[serde(default)]
+    pub title_prompt: Option<String>,
+
+    #[serde(default)]
+    pub auto_retitle_after: Option<usize>,
+
+    #[serde(default)]
+    pub restore_last_conversation: Option<bool>,
+
+    #[serde(default)]
+    pub restore_last_backend: Option<bool>,
+
+    #[serde(default)]
+    pub max_attachment_size: Option<usize>,
+
+    #[serde(default)]
+    pub input_history_size: Option<usize>,
+
+    #[serde(default)]
+    pub persist_input_history: Option<bool>,
+
+    #[serde(default)]
+    pub language: Option<String>,
+
+    #[serde(default)]
+    pub loading_message: Option<String>,
+
+    #[serde(default)]
+    pub spinner: SpinnerStyle,
+
+    #[serde(default)]
+    pub clipboard: ClipboardMode,
+
+    #[serde(default)]
+    pub default_conversation_budget: Option<Budget>,
+
+    #[serde(default)]
+    pub block_over_budget: Option<bool>,
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+pub struct UiConfig {
+    /// Wrap the messages at this column even when the window is wider
+    #[serde(default)]
+    pub max_line_width: Option<MaxLineWidth>,
+
+    #[serde(default)]
+    pub align: LineAlign,
+
+    /// Always scroll to the streamed response, even when scrolled up
+    #[serde(default)]
+    pub follow_output: Option<bool>,
+
+    #[serde(default)]
+    pub history_sort: HistorySort,
+
+    /// Group the history by date, by default unless sorted by title
+    #[serde(default)]
+    pub history_group_by_date: Option<bool>,
+
+    /// The date groups of the history, the default ones if empty
+    #[serde(default)]
+    pub history_groups: Vec<HistoryGroup>,
+
+    /// The syntax theme of the code blocks, over the one of `theme`
+    #[serde(default)]
+    pub code_theme: Option<String>,
+
+    /// Number the lines of the code blocks
+    #[serde(default)]
+    pub code_line_numbers: Option<bool>,
+
+    #[serde(default)]
+    pub code_overflow: CodeOverflow,
+
+    /// Render the math of the responses with Unicode symbols
+    #[serde(default)]
+    pub render_math: Option<bool>,
+
+    #[serde(default)]
+    pub notice_position: NoticePosition,
+
+    /// Show at most this number of notices, the others are collapsed into a
+    /// "+N more" line
+    #[serde(default)]
+    pub notice_max_stacked: Option<usize>,
+
+    /// The input box grows up to this number of lines, the longer inputs
+    /// are scrolled within it
+    #[serde(default)]
+    pub max_input_lines: Option<usize>,
+}
+
+/// A date group of the history, the conversations at most `max_days` old
+/// which are not in a more recent group
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryGroup {
+    pub title: String,
+    pub max_days: u32,
+}
+
+/// The wrap width of the messages, either for both sides or per side
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MaxLineWidth {
+    All(usize),
+    PerSide {
+        #[serde(default)]
+        user: Option<usize>,
+        #[serde(default)]
+        assistant: Option<usize>,
+    },
+}
+
+/// Where the messages are placed when the window is wider than
+/// `ui.max_line_width`
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineAlign {
+    #[default]
+    Center,
+    Left,
+}
+
+/// How the code lines wider than the message are fitted
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeOverflow {
+    /// Continue on the next lines
+    #[default]
+    Wrap,
+    /// Cut at the edge of the message
+    Truncate,
+}
+
+/// The corner of the window where the notices are stacked
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NoticePosition {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl NoticePosition {
+    pub fn is_bottom(&self) -> bool {
+        matches!(self, Self::BottomLeft | Self::BottomRight)
+    }
+
+    pub fn is_left(&self) -> bool {
+        matches!(self, Self::TopLeft | Self::BottomLeft)
+    }
+}
+
+/// The order of the conversations in the history
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistorySort {
+    /// The last updated first
+    #[default]
+    Recent,
+    /// The last created first
+    Created,
+    /// Alphabetically by title
+    Title,
+}
+
+/// The semantic search of the history, the conversations are ranked by the
+/// similarity of their embeddings to the one of the query
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+pub struct SearchConfig {
+    #[serde(default)]
+    pub semantic: bool,
+
+    /// The model computing the embeddings, it must be served by one of the
+    /// connections
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+
+    /// The connection computing the embeddings if none of the connections
+    /// lists the embedding model
+    #[serde(default)]
+    pub embedding_connection: Option<String>,
+
+    /// The number of conversations returned by a semantic search
+    #[serde(default)]
+    pub max_results: Option<usize>,
+}
+
+impl SearchConfig {
+    pub fn embedding_model(&self) -> &str {
+        self.embedding_model
+            .as_deref()
+            .filter(|model| !model.is_empty())
+            .unwrap_or(EMBEDDING_MODEL)
+    }
+
+    pub fn max_results(&self) -> usize {
+        self.max_results.unwrap_or(SEMANTIC_SEARCH_RESULTS)
+    }
+}
+
+/// The webhooks notified of the events of the conversations
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Give up a request to a webhook after this number of seconds
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl HooksConfig {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout_secs.unwrap_or(HOOK_TIMEOUT_SECS))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+
+    /// The events posted to the URL, all of them if empty
+    #[serde(default)]
+    pub events: Vec<HookEvent>,
+}
+
+impl WebhookConfig {
+    pub fn subscribes(&self, event: HookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    ConversationCreated,
+    MessageCompleted,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::ConversationCreated => "conversation_created",
+            HookEvent::MessageCompleted => "message_completed",
+        }
+    }
 }
 
 #[derive(Default, Deserialize, Serialize, Debug, Clone)]
@@ -76,7 +374,7 @@ pub struct ContextCompression {
     #[serde(default)]
     pub enabled: bool,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(alias = "model", skip_serializing_if = "Option::is_none")]
     pub compress_model: Option<String>,
 
     #[serde(default = "max_context_length")]
@@ -87,6 +385,12 @@ pub struct ContextCompression {
 
     #[serde(default = "keep_n_messages")]
     pub keep_n_messages: usize,
+
+    #[serde(default = "compression_chunk_tokens")]
+    pub chunk_tokens: usize,
+
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -108,6 +412,77 @@ pub struct LogConfig {
 
     #[serde(default)]
     pub file: LogFile,
+
+    #[serde(default)]
+    pub redact_message_content: ContentRedaction,
+}
+
+/// How the message content of the requests and responses is written in the
+/// trace logs. The secrets are always masked.
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRedaction {
+    #[default]
+    None,
+    Hash,
+    Omit,
+}
+
+/// The frames of the spinner shown while waiting for the backend
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpinnerStyle {
+    #[default]
+    Braille,
+    Dots,
+    Line,
+}
+
+/// Where the copied text goes
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardMode {
+    /// OSC 52 over SSH, the system clipboard otherwise
+    #[default]
+    Auto,
+    System,
+    /// The clipboard of the terminal through the OSC 52 escape sequence
+    Osc52,
+}
+
+/// How the conversation titles are derived
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleStrategy {
+    /// Ask the model for a title
+    #[default]
+    Model,
+    /// Use the first line of the first message
+    FirstMessage,
+    /// Leave the conversation untitled until it is renamed
+    None,
+}
+
+/// Accept the deprecated `generate_title = true/false` as the strategy,
+/// `false` derived the title from the first message
+fn deserialize_title_strategy<'de, D>(deserializer: D) -> Result<Option<TitleStrategy>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Strategy(TitleStrategy),
+        Enabled(bool),
+    }
+
+    Ok(
+        Option::<Value>::deserialize(deserializer)?.map(|value| match value {
+            Value::Strategy(strategy) => strategy,
+            Value::Enabled(true) => TitleStrategy::Model,
+            Value::Enabled(false) => TitleStrategy::FirstMessage,
+        }),
+    )
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -135,6 +510,31 @@ pub struct ThemeConfig {
 
     #[serde(default)]
     pub folder_path: Option<String>,
+
+    /// The syntax theme used on a light terminal background
+    #[serde(default)]
+    pub syntax_light: Option<String>,
+
+    /// The syntax theme used on a dark terminal background, `name` is used if
+    /// not set
+    #[serde(default)]
+    pub syntax_dark: Option<String>,
+
+    /// The terminal background, detected if `auto`
+    #[serde(default)]
+    pub background: Option<Background>,
+
+    /// The built-in color preset of the chat UI
+    #[serde(default)]
+    pub colors: Option<String>,
+
+    /// A TOML file overriding the colors of the preset
+    #[serde(default)]
+    pub colors_path: Option<String>,
+
+    /// Render without any color, also set by `--no-color` or `NO_COLOR`
+    #[serde(default)]
+    pub no_color: Option<bool>,
 }
 
 #[derive(Default, Deserialize, Serialize, Debug, Clone)]
@@ -151,8 +551,48 @@ pub struct BackendConfig {
     #[serde(default)]
     pub mcp: McpConfig,
 
+    #[serde(default)]
+    pub routing: RoutingConfig,
+
+    #[serde(default)]
+    pub max_concurrent: Option<usize>,
+
+    #[serde(default)]
+    pub rpm: Option<u32>,
+
     #[serde(default)]
     pub model_settings: Vec<ModelSetting>,
+
+    /// A TOML file overriding the bundled context window and pricing of the
+    /// models
+    #[serde(default)]
+    pub model_info_path: Option<String>,
+
+    #[serde(default)]
+    pub media_dir: Option<String>,
+}
+
+/// How the prompts are spread over the connections serving the same model
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingPolicy {
+    /// Only use the connection of the selected model
+    #[default]
+    Direct,
+    /// Try the connections in order, starting with the selected one
+    Failover,
+    /// Distribute the prompts over the connections
+    RoundRobin,
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone)]
+pub struct RoutingConfig {
+    #[serde(default)]
+    pub policy: RoutingPolicy,
+
+    /// How long a failed connection is tried last, in seconds
+    #[serde(default)]
+    pub cooldown_secs: Option<u64>,
 }
 
 #[derive(Default, Deserialize, Serialize, Debug, Clone)]
@@ -160,6 +600,14 @@ pub struct McpConfig {
     #[serde(default)]
     pub notice_on_call_tool: Option<bool>,
 
+    /// How long the listed tools are cached, in seconds
+    #[serde(default)]
+    pub tool_cache_ttl: Option<u64>,
+
+    /// Ask the user before calling a tool
+    #[serde(default)]
+    pub require_approval: Option<bool>,
+
     #[serde(default)]
     pub servers: Vec<McpServerConfig>,
 }
@@ -169,6 +617,66 @@ pub struct ModelSetting {
     pub model: ModelFilter,
     #[serde(default = "default_option_true")]
     pub enable_mcp: Option<bool>,
+
+    #[serde(default)]
+    pub max_context_tokens: Option<usize>,
+
+    #[serde(default)]
+    pub context_window: Option<usize>,
+
+    #[serde(default)]
+    pub temperature: Option<f32>,
+
+    #[serde(default)]
+    pub top_p: Option<f32>,
+
+    /// Override the `max_output_tokens` of the backend connection
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+
+    #[serde(default)]
+    pub stop: Vec<String>,
+
+    /// The reasoning effort of the OpenAI reasoning models: low, medium or
+    /// high
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+
+    /// The number of thinking tokens of the Gemini thinking models
+    #[serde(default)]
+    pub thinking_budget: Option<usize>,
+
+    /// Override the price of the model in USD per million input tokens
+    #[serde(default)]
+    pub input_price: Option<f64>,
+
+    /// Override the price of the model in USD per million output tokens
+    #[serde(default)]
+    pub output_price: Option<f64>,
+
+    /// Override the price of the model in USD per million input tokens read
+    /// from the prompt cache
+    #[serde(default)]
+    pub cached_input_price: Option<f64>,
+
+    /// The fields merged into the request body, over the ones of the
+    /// connection
+    #[serde(default)]
+    pub extra_body: Option<serde_json::Map<String, serde_json::Value>>,
+
+    /// The format of the answers, `/json` sets it for the next prompt only
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+
+    /// Return the log probability of each token of the responses, only by
+    /// the OpenAI connections
+    #[serde(default)]
+    pub logprobs: Option<bool>,
+
+    /// The number of the most likely tokens returned at each position with
+    /// their log probabilities, it implies `logprobs`
+    #[serde(default)]
+    pub top_logprobs: Option<u8>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -186,6 +694,14 @@ pub struct McpServerConfig {
     pub provider: String,
     #[serde(flatten)]
     pub server: McpServer,
+
+    /// Only advertise the tools matching these glob patterns
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    /// Never advertise the tools matching these glob patterns
+    #[serde(default)]
+    pub deny: Vec<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -213,21 +729,142 @@ pub enum StorageConfig {
 #[derive(Default, Deserialize, Serialize, Debug, Clone)]
 pub struct SqliteStorage {
     pub path: Option<String>,
+
+    #[serde(default)]
+    pub purge_deleted_after_days: Option<u64>,
+
+    #[serde(default)]
+    pub max_conversations: Option<usize>,
+
+    #[serde(default)]
+    pub retention_days: Option<u64>,
+
+    /// Use the write-ahead log journal, so reads and writes don't block each
+    /// other. Default is true
+    #[serde(default)]
+    pub wal: Option<bool>,
+
+    /// How long (in milliseconds) a write waits for the database locked by
+    /// another one before failing
+    #[serde(default)]
+    pub busy_timeout_ms: Option<u64>,
+
+    /// Keep the previous texts of the edited messages
+    #[serde(default)]
+    pub track_edits: Option<bool>,
+}
+
+impl SqliteStorage {
+    pub fn wal(&self) -> bool {
+        self.wal.unwrap_or(true)
+    }
+
+    pub fn busy_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.busy_timeout_ms.unwrap_or(SQLITE_BUSY_TIMEOUT_MS))
+    }
 }
 
 impl GeneralConfig {
+    /// The greeting of a new conversation, if any
+    pub fn welcome_message(&self) -> Option<&str> {
+        match self.hello_message.as_deref() {
+            Some(message) => Some(message).filter(|m| !m.trim().is_empty()),
+            None => Some(HELLO_MESSAGE),
+        }
+    }
+
+    /// The maximum size in bytes of a file attached with `@file`
+    pub fn get_max_attachment_size(&self) -> usize {
+        self.max_attachment_size.unwrap_or(MAX_ATTACHMENT_SIZE)
+    }
+
+    /// The number of sent prompts recalled with the arrow keys
+    pub fn get_input_history_size(&self) -> usize {
+        self.input_history_size.unwrap_or(INPUT_HISTORY_SIZE)
+    }
+
+    /// The format of the timestamps shown in the message headers
+    pub fn get_timestamp_format(&self) -> &str {
+        self.timestamp_format
+            .as_deref()
+            .filter(|format| !format.is_empty())
+            .unwrap_or(TIMESTAMP_FORMAT)
+    }
+
     pub fn get_bubble_width_percent(&self) -> usize {
         let percent = self.bubble_width_percent.min(MAX_BUBBLE_WIDTH_PERCENT);
         percent.max(MIN_BUBBLE_WIDTH_PERCENT)
     }
 }
 
+impl UiConfig {
+    /// The wrap width of the user or the assistant messages. It is never
+    /// less than the minimum width of the window.
+    pub fn get_max_line_width(&self, user: bool) -> Option<usize> {
+        let width = match self.max_line_width? {
+            MaxLineWidth::All(width) => width,
+            MaxLineWidth::PerSide { user: width, .. } if user => width?,
+            MaxLineWidth::PerSide { assistant, .. } => assistant?,
+        };
+        (width > 0).then(|| width.max(MIN_LINE_WIDTH))
+    }
+
+    /// The maximum height of the input box in lines, not limited if 0
+    pub fn get_max_input_lines(&self) -> usize {
+        match self.max_input_lines.unwrap_or(MAX_INPUT_LINES) {
+            0 => usize::MAX,
+            lines => lines,
+        }
+    }
+
+    pub fn history_group_by_date(&self) -> bool {
+        self.history_group_by_date
+            .unwrap_or(self.history_sort != HistorySort::Title)
+    }
+}
+
+impl ThemeConfig {
+    pub fn no_color(&self) -> bool {
+        self.no_color.unwrap_or_default()
+    }
+}
+
+impl BackendConfig {
+    /// Return the first model setting whose filter matches the model
+    pub fn model_setting(&self, model: &str) -> Option<&ModelSetting> {
+        self.model_settings.iter().find(|settings| {
+            settings
+                .model
+                .build()
+                .map(|re| re.is_match(model))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Return the maximum number of tokens of the context sent along with
+    /// the prompt of the model, if any
+    pub fn max_context_tokens(&self, model: &str) -> Option<usize> {
+        self.model_setting(model)
+            .and_then(|settings| settings.max_context_tokens)
+            .filter(|max| *max > 0)
+    }
+
+    /// Return the maximum number of tokens of the prompt and its context
+    /// accepted by the model, if known
+    pub fn context_window(&self, model: &str) -> Option<usize> {
+        self.model_setting(model)
+            .and_then(|settings| settings.context_window)
+            .filter(|max| *max > 0)
+    }
+}
+
 impl Default for LogConfig {
     fn default() -> Self {
         Self {
             level: Some("info".to_string()),
             file: LogFile::default(),
             filters: None,
+            redact_message_content: ContentRedaction::default(),
         }
     }
 }
@@ -246,6 +883,12 @@ impl Default for ThemeConfig {
         Self {
             name: Some("base16-ocean.dark".to_string()),
             folder_path: None,
+            syntax_light: None,
+            syntax_dark: None,
+            background: None,
+            colors: None,
+            colors_path: None,
+            no_color: None,
         }
     }
 }
@@ -258,6 +901,8 @@ impl Default for ContextCompression {
             max_tokens: MAX_CONTEXT_LENGTH,
             max_messages: MAX_CONVO_LENGTH,
             keep_n_messages: KEEP_N_MESSAGES,
+            chunk_tokens: COMPRESSION_CHUNK_TOKENS,
+            timeout_secs: None,
         }
     }
 }
@@ -271,6 +916,31 @@ impl Default for TokenTruncation {
     }
 }
 
+impl Default for ModelSetting {
+    /// The setting matching every model, without overrides
+    fn default() -> Self {
+        Self {
+            model: ModelFilter::Contains(String::new()),
+            enable_mcp: default_option_true(),
+            max_context_tokens: None,
+            context_window: None,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            stop: vec![],
+            reasoning_effort: None,
+            thinking_budget: None,
+            input_price: None,
+            output_price: None,
+            cached_input_price: None,
+            extra_body: None,
+            response_format: None,
+            logprobs: None,
+            top_logprobs: None,
+        }
+    }
+}
+
 impl Default for GeneralConfig {
     fn default() -> Self {
         Self {
@@ -281,7 +951,27 @@ impl Default for GeneralConfig {
             auto_start: None,
             bubble: default_option_true(),
             show_wrapped_indicator: default_option_true(),
+            show_timestamps: None,
+            timestamp_format: None,
             copy_on_select: None,
+            copy_tool_calls: None,
+            copy_context: None,
+            copy_logprobs: None,
+            title_strategy: None,
+            title_model: None,
+            title_prompt: None,
+            auto_retitle_after: None,
+            restore_last_conversation: None,
+            restore_last_backend: None,
+            max_attachment_size: None,
+            input_history_size: None,
+            persist_input_history: None,
+            language: None,
+            loading_message: None,
+            spinner: SpinnerStyle::default(),
+            clipboard: ClipboardMode::default(),
+            default_conversation_budget: None,
+            block_over_budget: None,
         }
     }
 }
@@ -291,3 +981,37 @@ impl Default for StorageConfig {
         Self::Sqlite(SqliteStorage::default())
     }
 }
+
+impl StorageConfig {
+    /// Whether the previous texts of the edited messages are kept
+    pub fn track_edits(&self) -> bool {
+        match self {
+            Self::Sqlite(sqlite) => sqlite.track_edits.unwrap_or_default(),
+        }
+    }
+
+    /// The number of days a deleted conversation is kept before it is purged
+    pub fn purge_deleted_after_days(&self) -> u64 {
+        match self {
+            Self::Sqlite(sqlite) => sqlite
+                .purge_deleted_after_days
+                .unwrap_or(PURGE_DELETED_AFTER_DAYS),
+        }
+    }
+
+    /// The maximum number of conversations kept, the oldest ones are pruned
+    /// at startup. Pruning is disabled if not set.
+    pub fn max_conversations(&self) -> Option<usize> {
+        match self {
+            Self::Sqlite(sqlite) => sqlite.max_conversations,
+        }
+    }
+
+    /// The number of days a conversation is kept after its last update, the
+    /// older ones are pruned at startup. Pruning is disabled if not set.
+    pub fn retention_days(&self) -> Option<u64> {
+        match self {
+            Self::Sqlite(sqlite) => sqlite.retention_days,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+#[test]
+fn test_get_max_line_width() {
+    let ui: UiConfig = toml::from_str("").unwrap();
+    assert_eq!(ui.get_max_line_width(true), None);
+
+    let ui: UiConfig = toml::from_str("max_line_width = 100").unwrap();
+    assert_eq!(ui.get_max_line_width(true), Some(100));
+    assert_eq!(ui.get_max_line_width(false), Some(100));
+    assert_eq!(ui.align, LineAlign::Center);
+
+    let ui: UiConfig =
+        toml::from_str("max_line_width = { assistant = 120 }\nalign = \"left\"").unwrap();
+    assert_eq!(ui.get_max_line_width(true), None);
+    assert_eq!(ui.get_max_line_width(false), Some(120));
+    assert_eq!(ui.align, LineAlign::Left);
+
+    // Never less than the minimum width of the window
+    let ui: UiConfig = toml::from_str("max_line_width = { user = 20, assistant = 0 }").unwrap();
+    assert_eq!(ui.get_max_line_width(true), Some(MIN_LINE_WIDTH));
+    assert_eq!(ui.get_max_line_width(false), None);
+}
+
+#[test]
+fn test_history_group_by_date() {
+    let ui: UiConfig = toml::from_str("").unwrap();
+    assert_eq!(ui.history_sort, HistorySort::Recent);
+    assert!(ui.history_group_by_date());
+
+    let ui: UiConfig = toml::from_str("history_sort = \"title\"").unwrap();
+    assert!(!ui.history_group_by_date());
+
+    let ui: UiConfig =
+        toml::from_str("history_sort = \"title\"\nhistory_group_by_date = true").unwrap();
+    assert!(ui.history_group_by_date());
+}
+
+#[test]
+fn test_get_max_input_lines() {
+    let ui: UiConfig = toml::from_str("").unwrap();
+    assert_eq!(ui.get_max_input_lines(), MAX_INPUT_LINES);
+
+    let ui: UiConfig = toml::from_str("max_input_lines = 4").unwrap();
+    assert_eq!(ui.get_max_input_lines(), 4);
+
+    let ui: UiConfig = toml::from_str("max_input_lines = 0").unwrap();
+    assert_eq!(ui.get_max_input_lines(), usize::MAX);
+}
+
+#[test]
+fn test_welcome_message() {
+    let general: GeneralConfig = toml::from_str("").unwrap();
+    assert_eq!(general.welcome_message(), Some(HELLO_MESSAGE));
+
+    let general: GeneralConfig = toml::from_str("welcome_message = \"Hi there\"").unwrap();
+    assert_eq!(general.welcome_message(), Some("Hi there"));
+
+    // A blank conversation
+    let general: GeneralConfig = toml::from_str("hello_message = \"\"").unwrap();
+    assert_eq!(general.welcome_message(), None);
+}
+
+#[test]
+fn test_title_strategy() {
+    let general: GeneralConfig = toml::from_str("").unwrap();
+    assert_eq!(general.title_strategy, None);
+
+    let general: GeneralConfig = toml::from_str("title_strategy = \"none\"").unwrap();
+    assert_eq!(general.title_strategy, Some(TitleStrategy::None));
+
+    // The deprecated boolean form
+    let general: GeneralConfig = toml::from_str("generate_title = false").unwrap();
+    assert_eq!(general.title_strategy, Some(TitleStrategy::FirstMessage));
+
+    let general: GeneralConfig = toml::from_str("generate_title = true").unwrap();
+    assert_eq!(general.title_strategy, Some(TitleStrategy::Model));
+
+    assert!(toml::from_str::<GeneralConfig>("title_strategy = \"other\"").is_err());
+}
@@ -9,7 +9,9 @@ use regex::Regex;
 use std::{io::Write, str::FromStr};
 use syntect::highlighting::{Theme, ThemeSet};
 
-use super::{Configuration, LogConfig, ThemeConfig};
+use super::background::{Background, detect_background};
+use super::constants::SYNTAX_THEME_LIGHT;
+use super::{ColorTheme, Configuration, LogConfig, ThemeConfig, color_theme};
 
 pub fn load_configuration(config_path: &str) -> Result<Configuration> {
     let config =
@@ -61,13 +63,53 @@ pub fn init_logger(config: &LogConfig) -> Result<()> {
     Ok(())
 }
 
-pub fn init_theme(config: &ThemeConfig) -> Result<Theme> {
-    let themes = match config.folder_path.as_deref() {
-        Some(path) => {
-            ThemeSet::load_from_folder(path).wrap_err(format!("loading theme from {}", path))?
+/// Select the syntax theme, `code_theme` (`ui.code_theme`) is used over the
+/// theme matching the background if it is found
+pub fn init_theme(config: &ThemeConfig, code_theme: Option<&str>) -> Result<Theme> {
+    if let Some(name) = code_theme.filter(|name| !name.is_empty()) {
+        match select_code_theme(config, name) {
+            Ok(theme) => return Ok(theme),
+            Err(err) => log::warn!("{:#}, using the syntax theme", err),
         }
-        None => syntect::highlighting::ThemeSet::load_defaults(),
+    }
+
+    // The colors of the theme are never shown, no need to query the
+    // terminal background
+    let background = match config.background.unwrap_or_default() {
+        _ if config.no_color() => None,
+        Background::Auto => detect_background(),
+        background => Some(background),
     };
+    select_theme(config, background)
+}
+
+/// Select the syntax theme matching the terminal background. The theme set in
+/// `name` is used if the background is unknown or the matching theme is not
+/// found.
+pub fn select_theme(config: &ThemeConfig, background: Option<Background>) -> Result<Theme> {
+    let themes = load_themes(config)?;
+
+    let configured = match background {
+        Some(Background::Light) => config.syntax_light.as_deref(),
+        Some(Background::Dark) => config.syntax_dark.as_deref(),
+        _ => None,
+    }
+    .filter(|name| !name.is_empty());
+
+    let background_name = match background {
+        Some(Background::Light) => configured.or(Some(SYNTAX_THEME_LIGHT)),
+        _ => configured,
+    };
+
+    if let Some(name) = background_name {
+        match themes.themes.get(name) {
+            Some(theme) => return Ok(theme.clone()),
+            None if configured.is_some() => {
+                log::warn!("Theme {} not found, using the default theme", name)
+            }
+            None => {}
+        }
+    }
 
     let theme_name = config.name.as_deref().unwrap_or_default();
     let theme = themes
@@ -77,6 +119,41 @@ pub fn init_theme(config: &ThemeConfig) -> Result<Theme> {
     Ok(theme.clone())
 }
 
+/// Select the syntax theme of the code blocks by its name
+pub fn select_code_theme(config: &ThemeConfig, name: &str) -> Result<Theme> {
+    load_themes(config)?
+        .themes
+        .remove(name)
+        .ok_or_else(|| eyre::eyre!("code theme {} not found", name))
+}
+
+fn load_themes(config: &ThemeConfig) -> Result<ThemeSet> {
+    match config.folder_path.as_deref() {
+        Some(path) => {
+            ThemeSet::load_from_folder(path).wrap_err(format!("loading theme from {}", path))
+        }
+        None => Ok(ThemeSet::load_defaults()),
+    }
+}
+
+pub fn init_color_theme(config: &ThemeConfig) -> Result<ColorTheme> {
+    let name = config
+        .colors
+        .as_deref()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(color_theme::DEFAULT_COLOR_THEME);
+    let preset =
+        ColorTheme::preset(name).ok_or_else(|| eyre::eyre!("color theme {} not found", name))?;
+
+    match config.colors_path.as_deref().filter(|p| !p.is_empty()) {
+        Some(path) => {
+            let path = resolve_path(path).wrap_err(format!("resolving path {}", path))?;
+            ColorTheme::load(&path, preset)
+        }
+        None => Ok(preset),
+    }
+}
+
 pub fn basename(path: &str) -> String {
     path.split('/').next_back().unwrap_or(path).to_string()
 }
@@ -99,3 +99,37 @@ fn test_resolve_path() {
     let ret = resolve_path("$TEST_PATH/${USER_PATH}/config.toml").expect("failed to resolve path");
     assert_eq!(ret, format!("{dir}/{user_path}/config.toml"));
 }
+
+#[test]
+fn test_select_theme() {
+    let config = ThemeConfig {
+        syntax_dark: Some("Solarized (dark)".to_string()),
+        ..Default::default()
+    };
+
+    let theme = select_theme(&config, Some(Background::Light)).expect("failed to select theme");
+    assert_eq!(theme.name.as_deref(), Some("GitHub"));
+
+    let theme = select_theme(&config, Some(Background::Dark)).expect("failed to select theme");
+    assert_eq!(theme.name.as_deref(), Some("Solarized (dark)"));
+
+    let theme = select_theme(&config, None).expect("failed to select theme");
+    assert_eq!(theme.name.as_deref(), Some("Base16 Ocean Dark"));
+
+    let config = ThemeConfig {
+        syntax_light: Some("unknown".to_string()),
+        ..Default::default()
+    };
+    let theme = select_theme(&config, Some(Background::Light)).expect("failed to select theme");
+    assert_eq!(theme.name.as_deref(), Some("Base16 Ocean Dark"));
+}
+
+#[test]
+fn test_select_code_theme() {
+    let config = ThemeConfig::default();
+    let theme = select_code_theme(&config, "Solarized (light)").expect("failed to select theme");
+    assert_eq!(theme.name.as_deref(), Some("Solarized (light)"));
+
+    let err = select_code_theme(&config, "unknown").unwrap_err();
+    assert_eq!(err.to_string(), "code theme unknown not found");
+}
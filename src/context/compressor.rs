@@ -4,20 +4,26 @@ mod tests;
 
 use crate::backend::ArcBackend;
 use crate::config::ContextCompression;
-use crate::config::constants::{KEEP_N_MESSAGES, MAX_CONTEXT_LENGTH, MAX_CONVO_LENGTH};
+use crate::config::constants::{
+    COMPRESSION_CHUNK_TOKENS, KEEP_N_MESSAGES, MAX_CONTEXT_LENGTH, MAX_CONVO_LENGTH,
+};
 use crate::models::{
     ArcEventTx, BackendPrompt, Context as ConvoContext, Conversation, Event, Message,
 };
 use eyre::{Context, Result, bail};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 pub struct Compressor {
     enabled: bool,
+    model: Option<String>,
 
     max_context_length: usize,
     max_convo_length: usize,
     keep_n_messages: usize,
+    chunk_tokens: usize,
+    timeout: Option<Duration>,
 
     backend: ArcBackend,
 }
@@ -26,18 +32,27 @@ impl Compressor {
     pub fn new(backend: ArcBackend) -> Self {
         Self {
             enabled: false,
+            model: None,
             backend,
             max_context_length: MAX_CONTEXT_LENGTH,
             max_convo_length: MAX_CONVO_LENGTH,
             keep_n_messages: KEEP_N_MESSAGES,
+            chunk_tokens: COMPRESSION_CHUNK_TOKENS,
+            timeout: None,
         }
     }
 
     pub fn from_config(mut self, cfg: &ContextCompression) -> Self {
         self.enabled = cfg.enabled;
+        self.model = cfg.compress_model.clone().filter(|m| !m.is_empty());
         self.max_context_length = cfg.max_tokens;
         self.max_convo_length = cfg.max_messages;
         self.keep_n_messages = cfg.keep_n_messages.max(KEEP_N_MESSAGES);
+        self.chunk_tokens = cfg.chunk_tokens.max(1);
+        self.timeout = cfg
+            .timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
         self
     }
 
@@ -50,6 +65,22 @@ impl Compressor {
         self
     }
 
+    /// The maximum duration of a compression, if any
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Return the model used to compress conversations, falling back to the
+    /// given chat model when no dedicated model is configured.
+    pub fn model<'a>(&'a self, chat_model: &'a str) -> &'a str {
+        self.model.as_deref().unwrap_or(chat_model)
+    }
+
     pub fn with_context_length(mut self, length: usize) -> Self {
         self.max_context_length = length;
         self
@@ -65,12 +96,24 @@ impl Compressor {
         self
     }
 
+    pub fn with_chunk_tokens(mut self, tokens: usize) -> Self {
+        self.chunk_tokens = tokens.max(1);
+        self
+    }
+
     pub fn should_compress(&self, convo: &Conversation) -> bool {
         if !self.enabled || convo.len() < self.keep_n_messages {
             return false;
         }
 
-        let total_tokens = convo.token_count();
+        // Only the messages after the latest context are sent as is, the
+        // compressed ones are already summarized in the context. Both the
+        // tokens and the number of these messages trigger the compression.
+        let uncompressed = uncompressed_messages(convo);
+        let total_tokens = uncompressed
+            .iter()
+            .map(|msg| msg.estimated_token_count())
+            .sum::<usize>();
         // Calculate the offset of the message, we will ignore the last 2
         // messages (1 user and 1 system) in-case user is asking for
         // regeneration response.
@@ -84,18 +127,44 @@ impl Compressor {
             }
             _ => 0,
         } as isize;
-        let message_count = (calculate_convo_len(convo) as isize - offset).max(0) as usize;
+        let message_count = (uncompressed.len() as isize - offset).max(0) as usize;
         total_tokens > self.max_context_length || message_count > self.max_convo_length
     }
 
     pub async fn compress(
         &self,
-        model: &str,
+        chat_model: &str,
         convo: &Conversation,
     ) -> Result<Option<ConvoContext>> {
         if !self.should_compress(convo) {
             return Ok(None);
         }
+        self.force_compress(chat_model, convo).await
+    }
+
+    /// Compress the conversation regardless of [`Compressor::should_compress`].
+    /// Returns `None` when there are not enough messages to compress.
+    pub async fn force_compress(
+        &self,
+        chat_model: &str,
+        convo: &Conversation,
+    ) -> Result<Option<ConvoContext>> {
+        self.force_compress_with_progress(chat_model, convo, None)
+            .await
+    }
+
+    /// Same as [`Compressor::force_compress`], the progress is reported with
+    /// [`Event::CompressionProgress`] events.
+    pub async fn force_compress_with_progress(
+        &self,
+        chat_model: &str,
+        convo: &Conversation,
+        progress: Option<&mpsc::UnboundedSender<Event>>,
+    ) -> Result<Option<ConvoContext>> {
+        let model = self.model(chat_model);
+        if convo.len() <= self.keep_n_messages + 1 {
+            return Ok(None);
+        }
 
         let end_checkpoint = match find_checkpoint(convo, self.keep_n_messages) {
             Some(checkpoint) => checkpoint,
@@ -114,6 +183,11 @@ impl Compressor {
             _ => 0,
         };
 
+        // Nothing new since the last compression
+        if end_checkpoint < start_checkpoint {
+            return Ok(None);
+        }
+
         let last_message_id = convo.messages()[end_checkpoint].id();
         let mut messages = convo
             .contexts()
@@ -123,22 +197,71 @@ impl Compressor {
 
         messages.extend(convo.messages()[start_checkpoint..end_checkpoint + 1].to_vec());
 
-        let message = messages
+        let lines = messages
             .iter()
-            .map(|msg| format!("{}: {}", message_categorize(msg), msg.text()))
-            .collect::<Vec<_>>()
-            .join("\n");
+            .filter(|msg| !msg.is_tool())
+            .map(|msg| {
+                let line = format!("{}: {}", message_categorize(msg), msg.text());
+                (line, msg.estimated_token_count())
+            })
+            .collect::<Vec<_>>();
 
-        let prompt = BackendPrompt::new(format!(
-            r#"Summarize the following conversation in a compact yet comprehensive manner.
-Focus on the key points, decisions, and any critical information exchanged, while omitting trivial or redundant details. Include specific actions or plans that were agreed upon.
-Ensure that the summary is understandable on its own, providing enough context for someone who hasn't read the entire conversation.
-Aim to capture the essence of the discussion while keeping the summary as concise as possible.
-The summary should be started with Summary: and end with a period.
----
-{}"#,
-            message
-        )).with_model(model).with_no_generate_title();
+        // Summarize each chunk then summarize the summaries until they fit
+        // in a single request
+        let mut chunks = split_chunks(lines, self.chunk_tokens);
+        let mut reduce = false;
+        while chunks.len() > 1 {
+            let total = chunks.len();
+            let mut summaries = Vec::with_capacity(total);
+            for (i, chunk) in chunks.iter().enumerate() {
+                report_progress(
+                    progress,
+                    convo,
+                    format!("summarizing chunk {}/{}", i + 1, total),
+                );
+                let prompt = summarize_prompt(chunk, reduce);
+                let summary = self.summarize(model, prompt, last_message_id).await?;
+                if summary.content().is_empty() {
+                    return Ok(None);
+                }
+                let token_count = Message::from(&summary).estimated_token_count();
+                // The prompt asks for the prefix, it is added if the model
+                // left it out
+                let content = summary.content().trim();
+                let content = content
+                    .strip_prefix("Summary:")
+                    .map_or(content, str::trim_start);
+                summaries.push((format!("Summary: {}", content), token_count));
+            }
+
+            reduce = true;
+            chunks = split_chunks(summaries, self.chunk_tokens);
+            // The summaries are not getting shorter, summarize them at once
+            if chunks.len() >= total {
+                chunks = vec![chunks.concat()];
+            }
+        }
+
+        report_progress(progress, convo, "summarizing".to_string());
+        let chunk = chunks.concat();
+        let context = self
+            .summarize(model, summarize_prompt(&chunk, reduce), last_message_id)
+            .await?;
+
+        if context.content().is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(context))
+    }
+
+    async fn summarize(
+        &self,
+        model: &str,
+        prompt: String,
+        last_message_id: &str,
+    ) -> Result<ConvoContext> {
+        let prompt = BackendPrompt::new(prompt).with_model(model);
 
         let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
         let sender: ArcEventTx = Arc::new(tx);
@@ -160,15 +283,61 @@ The summary should be started with Summary: and end with a period.
                         break;
                     }
                 }
+                // e.g. the request is queued by the rate limits
+                Event::Notice(_) => {}
                 _ => bail!("Unexpected event: {:?}", event),
             }
         }
+        Ok(context)
+    }
+}
 
-        if context.content().is_empty() {
-            return Ok(None);
+/// Split the lines into chunks of at most `max_tokens` tokens. A line larger
+/// than `max_tokens` is kept in its own chunk.
+fn split_chunks(lines: Vec<(String, usize)>, max_tokens: usize) -> Vec<Vec<String>> {
+    let mut chunks: Vec<Vec<String>> = vec![];
+    let mut current = vec![];
+    let mut current_tokens = 0;
+    for (line, tokens) in lines {
+        if !current.is_empty() && current_tokens + tokens > max_tokens {
+            chunks.push(std::mem::take(&mut current));
+            current_tokens = 0;
         }
+        current.push(line);
+        current_tokens += tokens;
+    }
+    if !current.is_empty() || chunks.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
 
-        Ok(Some(context))
+fn summarize_prompt(lines: &[String], reduce: bool) -> String {
+    let instruction = if reduce {
+        "The following are summaries of consecutive parts of a conversation. Combine them into a single summary of the whole conversation in a compact yet comprehensive manner."
+    } else {
+        "Summarize the following conversation in a compact yet comprehensive manner."
+    };
+    format!(
+        r#"{}
+Focus on the key points, decisions, and any critical information exchanged, while omitting trivial or redundant details. Include specific actions or plans that were agreed upon.
+Ensure that the summary is understandable on its own, providing enough context for someone who hasn't read the entire conversation.
+Aim to capture the essence of the discussion while keeping the summary as concise as possible.
+The summary should be started with Summary: and end with a period.
+---
+{}"#,
+        instruction,
+        lines.join("\n")
+    )
+}
+
+fn report_progress(
+    progress: Option<&mpsc::UnboundedSender<Event>>,
+    convo: &Conversation,
+    status: String,
+) {
+    if let Some(progress) = progress {
+        let _ = progress.send(Event::CompressionProgress(convo.id().to_string(), status));
     }
 }
 
@@ -193,15 +362,18 @@ fn message_categorize(message: &Message) -> String {
     }
 }
 
-fn calculate_convo_len(convo: &Conversation) -> usize {
-    if convo.contexts().is_empty() {
-        return convo.len();
-    }
-    let last_message_id = convo.contexts().last().unwrap().last_message_id();
-    let last_message_index = convo
+/// Return the messages after the last message of the latest context, or the
+/// whole conversation if it has not been compressed yet.
+fn uncompressed_messages(convo: &Conversation) -> &[Message] {
+    let Some(ctx) = convo.contexts().last() else {
+        return convo.messages();
+    };
+    match convo
         .messages()
         .iter()
-        .position(|msg| msg.id() == last_message_id)
-        .unwrap_or(convo.len());
-    convo.len() - last_message_index
+        .position(|msg| msg.id() == ctx.last_message_id())
+    {
+        Some(index) => &convo.messages()[index + 1..],
+        None => convo.messages(),
+    }
 }
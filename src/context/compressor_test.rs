@@ -54,16 +54,19 @@ The summary should be started with Summary: and end with a period.
                         id: "test_id".to_string(),
                         text: s.to_string(),
                         model: "test_model".to_string(),
-                        init_conversation: false,
                         usage: if *s == "" {
                             Some(BackendUsage {
                                 completion_tokens: 5,
                                 prompt_tokens: 10,
                                 total_tokens: 15,
+                                ..Default::default()
                             })
                         } else {
                             None
                         },
+                        refusal: false,
+                        boundary: false,
+                        logprobs: vec![],
                     })
                     .collect::<Vec<_>>();
                 for msg in resp {
@@ -95,6 +98,186 @@ The summary should be started with Summary: and end with a period.
     assert_eq!(context.last_message_id(), "9");
 }
 
+#[tokio::test]
+async fn test_force_compress_not_enough_messages() {
+    let mut backend = MockBackend::new();
+    backend.expect_get_completion().never();
+
+    let compressor = Compressor::new(Arc::new(backend)).with_keep_n_messages(5);
+
+    let mut convo = Conversation::new_hello();
+    convo.append_message(Message::new_user("user", "Hello"));
+    convo.append_message(Message::new_system("system", "Hi!"));
+
+    assert!(!compressor.should_compress(&convo));
+    let context = compressor
+        .force_compress("test_model", &convo)
+        .await
+        .expect("Failed to compress conversation");
+    assert!(context.is_none());
+}
+
+#[tokio::test]
+async fn test_compress_in_chunks() {
+    let convo = build_convo();
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let mut backend = MockBackend::new();
+    let counter = calls.clone();
+    backend
+        .expect_get_completion()
+        .times(3)
+        .returning(move |prompt, event_tx| {
+            let call = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                let text = match call {
+                    // The context and the messages 5..=9 are 6 lines of 5
+                    // tokens, they are split into 2 chunks of 15 tokens
+                    1 => {
+                        assert!(prompt.text().contains("Context: This is a checkpoint at 4"));
+                        assert!(!prompt.text().contains("Message 7"));
+                        "Part one".to_string()
+                    }
+                    // The prefix is not repeated
+                    2 => {
+                        assert!(prompt.text().contains("Message 7"));
+                        assert!(prompt.text().contains("Message 9"));
+                        assert!(!prompt.text().contains("Message 10"));
+                        "Summary: Part two".to_string()
+                    }
+                    _ => {
+                        assert!(prompt.text().starts_with("The following are summaries"));
+                        assert!(
+                            prompt
+                                .text()
+                                .contains("Summary: Part one\nSummary: Part two")
+                        );
+                        "Summary: The whole conversation.".to_string()
+                    }
+                };
+                event_tx
+                    .send(Event::ChatCompletionResponse(BackendResponse {
+                        done: true,
+                        id: format!("test_id_{}", call),
+                        text,
+                        model: "test_model".to_string(),
+                        usage: Some(BackendUsage {
+                            completion_tokens: 5,
+                            prompt_tokens: 15,
+                            total_tokens: 20,
+                            ..Default::default()
+                        }),
+                        refusal: false,
+                        boundary: false,
+                        logprobs: vec![],
+                    }))
+                    .await
+                    .expect("Failed to send event");
+                Ok(())
+            })
+        });
+
+    let compressor = Compressor::new(Arc::new(backend)).with_chunk_tokens(15);
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let context = compressor
+        .force_compress_with_progress("test_model", &convo, Some(&tx))
+        .await
+        .expect("Failed to compress conversation")
+        .unwrap();
+
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert_eq!(context.id(), "test_id_3");
+    assert_eq!(context.content(), "Summary: The whole conversation.");
+    assert_eq!(context.last_message_id(), "9");
+
+    drop(tx);
+    let mut progress = vec![];
+    while let Some(event) = rx.recv().await {
+        if let Event::CompressionProgress(_, status) = event {
+            progress.push(status);
+        }
+    }
+    assert_eq!(
+        progress,
+        vec![
+            "summarizing chunk 1/2",
+            "summarizing chunk 2/2",
+            "summarizing"
+        ]
+    );
+}
+
+#[test]
+fn test_split_chunks() {
+    let lines = [("a", 4), ("b", 4), ("c", 12), ("d", 1)]
+        .iter()
+        .map(|(line, tokens)| (line.to_string(), *tokens))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        split_chunks(lines, 10),
+        vec![vec!["a", "b"], vec!["c"], vec!["d"]]
+    );
+    assert_eq!(split_chunks(vec![], 10), vec![Vec::<String>::new()]);
+}
+
+#[test]
+fn test_should_compress_only_counts_uncompressed_messages() {
+    let mut convo = build_convo();
+    // Messages 10..=15 are after the latest context, 30 tokens in total
+    convo.append_context(
+        ConvoContext::new("9")
+            .with_content("This is a checkpoint at 9")
+            .with_token_count(5),
+    );
+
+    let compressor = Compressor::new(Arc::new(MockBackend::new()))
+        .with_context_length(30)
+        .with_enabled(true);
+
+    assert!(convo.len() * 5 > 30);
+    assert!(!compressor.should_compress(&convo));
+
+    convo.append_message(
+        Message::new_user("user", "Message 16")
+            .with_id("16")
+            .with_token_count(5),
+    );
+    assert!(compressor.should_compress(&convo));
+}
+
+#[test]
+fn test_should_compress_by_message_count() {
+    let compressor = Compressor::new(Arc::new(MockBackend::new()))
+        .with_context_length(1000)
+        .with_conversation_length(10)
+        .with_enabled(true);
+
+    // Short messages far below the token threshold
+    let mut convo = Conversation::default();
+    for i in 0..12 {
+        let msg = if i % 2 == 0 {
+            Message::new_user("user", "Hi")
+        } else {
+            Message::new_system("system", "Hello")
+        };
+        convo.append_message(msg.with_id(i.to_string()).with_token_count(1));
+    }
+    // The last exchange is left out in case it is regenerated
+    assert!(!compressor.should_compress(&convo));
+
+    convo.append_message(
+        Message::new_user("user", "Hi")
+            .with_id("12")
+            .with_token_count(1),
+    );
+    assert!(compressor.should_compress(&convo));
+
+    // Only the messages after the latest context are counted
+    convo.append_context(ConvoContext::new("10").with_content("Summary"));
+    assert!(!compressor.should_compress(&convo));
+}
+
 fn build_convo() -> Conversation {
     let mut convo = Conversation::new_hello();
     for i in 0..=15 {
@@ -1,3 +1,6 @@
 pub mod compressor;
+pub mod title;
+pub mod window;
 
 pub use compressor::Compressor;
+pub use title::TitleGenerator;
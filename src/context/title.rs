@@ -0,0 +1,246 @@
+#[cfg(test)]
+#[path = "title_test.rs"]
+mod tests;
+
+use crate::backend::ArcBackend;
+use crate::config::{GeneralConfig, TitleStrategy};
+use crate::models::{ArcEventTx, BackendPrompt, Event, Message};
+use eyre::{Context, Result, bail};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// The maximum number of characters of a title
+const MAX_TITLE_LENGTH: usize = 80;
+
+const TITLE_PROMPT: &str = r#"Give a short title (no more than 10 words) for a conversation that starts with the following message.
+Reply with the title only, without quotes or any other text.
+---
+"#;
+
+const RETITLE_PROMPT: &str = r#"Give a short title (no more than 10 words) for the following conversation.
+Reply with the title only, without quotes or any other text.
+---
+"#;
+
+/// The number of latest messages used to regenerate the title
+const RETITLE_MAX_MESSAGES: usize = 10;
+
+/// The maximum number of characters of each message used to regenerate the
+/// title
+const RETITLE_MAX_MESSAGE_LENGTH: usize = 500;
+
+/// What the title is generated from
+pub enum TitleSource {
+    /// The first message of the conversation
+    Message(String),
+    /// The latest messages of the conversation, used to regenerate the title
+    Conversation(Vec<Message>),
+}
+
+/// Generate conversation titles with a separate, lightweight request instead
+/// of asking the chat model to include the title in its first response.
+pub struct TitleGenerator {
+    strategy: TitleStrategy,
+    model: Option<String>,
+    prompt: Option<String>,
+    backend: ArcBackend,
+}
+
+impl TitleGenerator {
+    pub fn new(backend: ArcBackend) -> Self {
+        Self {
+            strategy: TitleStrategy::Model,
+            model: None,
+            prompt: None,
+            backend,
+        }
+    }
+
+    pub fn from_config(mut self, cfg: &GeneralConfig) -> Self {
+        self.strategy = cfg.title_strategy.unwrap_or_default();
+        self.model = cfg.title_model.clone().filter(|m| !m.is_empty());
+        self.prompt = cfg.title_prompt.clone().filter(|p| !p.trim().is_empty());
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.strategy = if enabled {
+            TitleStrategy::Model
+        } else {
+            TitleStrategy::FirstMessage
+        };
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: TitleStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn strategy(&self) -> TitleStrategy {
+        self.strategy
+    }
+
+    /// Whether the titles are generated by the model. If not, the title is
+    /// derived locally according to the strategy.
+    pub fn is_enabled(&self) -> bool {
+        self.strategy == TitleStrategy::Model
+    }
+
+    /// Return the model used to generate titles, falling back to the given
+    /// chat model when no dedicated model is configured.
+    pub fn model<'a>(&'a self, chat_model: &'a str) -> &'a str {
+        self.model.as_deref().unwrap_or(chat_model)
+    }
+
+    pub async fn generate(&self, chat_model: &str, message: &str) -> Result<Option<String>> {
+        let prompt = match self.prompt.as_deref() {
+            Some(prompt) => format!("{}\n---\n", prompt.trim_end()),
+            None => TITLE_PROMPT.to_string(),
+        };
+        self.request(chat_model, format!("{}{}", prompt, message))
+            .await
+    }
+
+    /// Generate a title which fits the latest messages of the conversation
+    pub async fn regenerate(
+        &self,
+        chat_model: &str,
+        messages: &[Message],
+    ) -> Result<Option<String>> {
+        self.request(
+            chat_model,
+            format!("{}{}", RETITLE_PROMPT, build_transcript(messages)),
+        )
+        .await
+    }
+
+    pub async fn generate_from(
+        &self,
+        chat_model: &str,
+        source: &TitleSource,
+    ) -> Result<Option<String>> {
+        match source {
+            TitleSource::Message(message) => self.generate(chat_model, message).await,
+            TitleSource::Conversation(messages) => self.regenerate(chat_model, messages).await,
+        }
+    }
+
+    async fn request(&self, chat_model: &str, text: String) -> Result<Option<String>> {
+        let prompt = BackendPrompt::new(text).with_model(self.model(chat_model));
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        let sender: ArcEventTx = Arc::new(tx);
+        self.backend
+            .get_completion(prompt, sender)
+            .await
+            .wrap_err("getting completion")?;
+
+        let mut text = String::new();
+        while let Some(event) = rx.recv().await {
+            match event {
+                Event::ChatCompletionResponse(msg) => {
+                    text.push_str(&msg.text);
+                    if msg.done {
+                        break;
+                    }
+                }
+                // e.g. the request is queued by the rate limits
+                Event::Notice(_) => {}
+                _ => bail!("Unexpected event: {:?}", event),
+            }
+        }
+
+        Ok(parse_title(&text))
+    }
+}
+
+/// Build a transcript from the latest messages of the conversation. The
+/// messages are truncated to keep the request small.
+fn build_transcript(messages: &[Message]) -> String {
+    let messages = messages
+        .iter()
+        .filter(|msg| !msg.is_context() && !msg.is_tool())
+        .collect::<Vec<_>>();
+    messages[messages.len().saturating_sub(RETITLE_MAX_MESSAGES)..]
+        .iter()
+        .map(|msg| {
+            let role = if msg.is_system() { "Assistant" } else { "User" };
+            let text = msg
+                .text()
+                .chars()
+                .take(RETITLE_MAX_MESSAGE_LENGTH)
+                .collect::<String>();
+            format!("{}: {}", role, text.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Build the title from the first non-empty line of the message, used when
+/// the title generation is disabled. The markdown is stripped.
+pub fn fallback_title(message: &str) -> Option<String> {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.starts_with("```"))
+        .map(|l| l.trim_start_matches(|c: char| matches!(c, '>' | '-' | '+') || c.is_whitespace()))
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find_map(|l| clean_title(&l))
+}
+
+/// Extract the title from the model response: the first non-empty line
+/// without markdown heading markers and surrounding quotes. The lines of the
+/// code blocks, e.g. a `# comment`, are only used if there is nothing else.
+pub fn parse_title(text: &str) -> Option<String> {
+    let mut in_codeblock = false;
+    let mut fenced = None;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("```") {
+            in_codeblock = !in_codeblock;
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if !in_codeblock {
+            return clean_title(line);
+        }
+        fenced = fenced.or(Some(line));
+    }
+    clean_title(fenced?)
+}
+
+/// Strip the markdown from the title and cap its length
+fn clean_title(line: &str) -> Option<String> {
+    let line = line.trim_start_matches('#').trim();
+    let line = match line.get(..6) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("title:") => &line[6..],
+        _ => line,
+    };
+    let title = line
+        .replace(['*', '`', '~'], "")
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '_')
+        .trim()
+        .to_string();
+    if title.is_empty() {
+        return None;
+    }
+
+    if title.chars().count() <= MAX_TITLE_LENGTH {
+        return Some(title);
+    }
+    let title = title.chars().take(MAX_TITLE_LENGTH).collect::<String>();
+    Some(title.trim_end().to_string())
+}
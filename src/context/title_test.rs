@@ -0,0 +1,172 @@
+use super::*;
+use crate::backend::MockBackend;
+use crate::models::BackendResponse;
+
+#[test]
+fn test_parse_title() {
+    assert_eq!(parse_title("Rust Lifetimes"), Some("Rust Lifetimes".into()));
+    assert_eq!(
+        parse_title("\n# Rust Lifetimes\nbody"),
+        Some("Rust Lifetimes".into())
+    );
+    assert_eq!(
+        parse_title("\"Rust Lifetimes\""),
+        Some("Rust Lifetimes".into())
+    );
+    assert_eq!(
+        parse_title("**Rust Lifetimes**"),
+        Some("Rust Lifetimes".into())
+    );
+    assert_eq!(
+        parse_title("Title: `Rust` Lifetimes"),
+        Some("Rust Lifetimes".into())
+    );
+    assert_eq!(
+        parse_title("```sh\n# install\n```\nInstalling Rust"),
+        Some("Installing Rust".into())
+    );
+    assert_eq!(
+        parse_title("```\nRust Lifetimes\n```"),
+        Some("Rust Lifetimes".into())
+    );
+    assert_eq!(parse_title("  \n  "), None);
+    assert_eq!(parse_title("#"), None);
+
+    let title = parse_title(&"word ".repeat(40)).expect("title");
+    assert!(title.chars().count() <= MAX_TITLE_LENGTH);
+    assert!(!title.ends_with(' '));
+}
+
+#[tokio::test]
+async fn test_generate() {
+    let mut backend = MockBackend::new();
+    backend
+        .expect_get_completion()
+        .returning(move |prompt, event_tx| {
+            Box::pin(async move {
+                assert_eq!(prompt.model(), "small-model");
+                assert!(prompt.text().ends_with("How do lifetimes work?"));
+                for (text, done) in [("# Rust ", false), ("Lifetimes", false), ("", true)] {
+                    let mut resp = BackendResponse::new("id", "small-model").with_text(text);
+                    if done {
+                        resp = resp.with_done();
+                    }
+                    event_tx
+                        .send(Event::ChatCompletionResponse(resp))
+                        .await
+                        .expect("Failed to send event");
+                }
+                Ok(())
+            })
+        });
+
+    let generator = TitleGenerator::new(Arc::new(backend)).with_model("small-model");
+    assert!(generator.is_enabled());
+
+    let title = generator
+        .generate("chat-model", "How do lifetimes work?")
+        .await
+        .expect("Failed to generate title");
+    assert_eq!(title.as_deref(), Some("Rust Lifetimes"));
+}
+
+#[test]
+fn test_build_transcript() {
+    let mut messages = vec![Message::new_system("system", "summary").with_context(true)];
+    for i in 0..12 {
+        messages.push(Message::new_user("user", format!("question {}", i)));
+        messages.push(Message::new_system("system", format!("answer {}", i)));
+    }
+    messages.push(Message::new_user("user", "x".repeat(600)));
+
+    let transcript = build_transcript(&messages);
+    let lines = transcript.split("\n\n").collect::<Vec<_>>();
+    assert_eq!(lines.len(), RETITLE_MAX_MESSAGES);
+    assert_eq!(lines[0], "Assistant: answer 7");
+    assert_eq!(
+        lines[RETITLE_MAX_MESSAGES - 1],
+        format!("User: {}", "x".repeat(RETITLE_MAX_MESSAGE_LENGTH))
+    );
+    assert!(!transcript.contains("summary"));
+}
+
+#[tokio::test]
+async fn test_generate_with_prompt() {
+    let mut backend = MockBackend::new();
+    backend
+        .expect_get_completion()
+        .returning(move |prompt, event_tx| {
+            Box::pin(async move {
+                assert_eq!(prompt.model(), "chat-model");
+                assert_eq!(
+                    prompt.text(),
+                    "Name this conversation.\n---\nHow do lifetimes work?"
+                );
+                event_tx
+                    .send(Event::ChatCompletionResponse(
+                        BackendResponse::new("id", "chat-model")
+                            .with_text("Lifetimes")
+                            .with_done(),
+                    ))
+                    .await
+                    .expect("Failed to send event");
+                Ok(())
+            })
+        });
+
+    let generator = TitleGenerator::new(Arc::new(backend)).with_prompt("Name this conversation.\n");
+    let title = generator
+        .generate("chat-model", "How do lifetimes work?")
+        .await
+        .expect("Failed to generate title");
+    assert_eq!(title.as_deref(), Some("Lifetimes"));
+}
+
+#[test]
+fn test_fallback_title() {
+    assert_eq!(
+        fallback_title("\n  How do   lifetimes work?\nSome code"),
+        Some("How do lifetimes work?".into())
+    );
+    assert_eq!(fallback_title(" \n "), None);
+
+    let title = fallback_title(&"word ".repeat(40)).expect("title");
+    assert!(title.chars().count() <= MAX_TITLE_LENGTH);
+    assert!(!title.ends_with(' '));
+
+    let generator = TitleGenerator::new(Arc::new(MockBackend::new())).with_enabled(false);
+    assert!(!generator.is_enabled());
+}
+
+#[test]
+fn test_fallback_title_strips_markdown() {
+    assert_eq!(
+        fallback_title("## **How** do `lifetimes` work?"),
+        Some("How do lifetimes work?".into())
+    );
+    assert_eq!(
+        fallback_title("```rust\nfn main() {}\n```"),
+        Some("fn main() {}".into())
+    );
+    assert_eq!(
+        fallback_title("> - quoted item"),
+        Some("quoted item".into())
+    );
+}
+
+#[test]
+fn test_title_strategy_from_config() {
+    let backend: ArcBackend = Arc::new(MockBackend::new());
+    let mut cfg = GeneralConfig::default();
+    let generator = TitleGenerator::new(backend.clone()).from_config(&cfg);
+    assert_eq!(generator.strategy(), TitleStrategy::Model);
+
+    cfg.title_strategy = Some(TitleStrategy::FirstMessage);
+    let generator = TitleGenerator::new(backend.clone()).from_config(&cfg);
+    assert_eq!(generator.strategy(), TitleStrategy::FirstMessage);
+
+    cfg.title_strategy = Some(TitleStrategy::None);
+    let generator = TitleGenerator::new(backend).from_config(&cfg);
+    assert_eq!(generator.strategy(), TitleStrategy::None);
+    assert!(!generator.is_enabled());
+}
@@ -0,0 +1,69 @@
+#[cfg(test)]
+#[path = "window_test.rs"]
+mod tests;
+
+use crate::models::{Conversation, Message, message::estimate_tokens};
+
+/// How the prompt fits the context window of the model
+#[derive(Debug)]
+pub enum ContextFit {
+    Fits(Vec<Message>),
+    /// The oldest messages are dropped to fit the context window
+    Truncated {
+        context: Vec<Message>,
+        estimated: usize,
+        allowed: usize,
+    },
+    /// The prompt doesn't fit the context window even without the history
+    Exceeded {
+        estimated: usize,
+        allowed: usize,
+    },
+}
+
+/// Build the context sent along with the prompt and compare the estimated
+/// token count with the context window of the model. The prompt must not be
+/// added to the conversation yet. If `truncate` is set, the oldest messages
+/// are dropped to fit the window instead of rejecting the prompt.
+pub fn check_context_window(
+    convo: &Conversation,
+    prompt: &str,
+    max_context_tokens: Option<usize>,
+    context_window: Option<usize>,
+    truncate: bool,
+) -> ContextFit {
+    let prompt_tokens = estimate_tokens(prompt);
+    let context = convo
+        .build_context_with_budget(max_context_tokens.map(|max| max.saturating_sub(prompt_tokens)));
+    let Some(allowed) = context_window else {
+        return ContextFit::Fits(context);
+    };
+
+    let estimated = prompt_tokens + count_tokens(&context);
+    if estimated <= allowed {
+        return ContextFit::Fits(context);
+    }
+    if !truncate || prompt_tokens >= allowed {
+        return ContextFit::Exceeded { estimated, allowed };
+    }
+
+    // The configured limit may be below the prompt alone
+    let budget = max_context_tokens
+        .unwrap_or(allowed)
+        .min(allowed)
+        .saturating_sub(prompt_tokens);
+    let context = convo.build_context_with_budget(Some(budget));
+    // The system prompt and the context summary are always kept
+    if prompt_tokens + count_tokens(&context) > allowed {
+        return ContextFit::Exceeded { estimated, allowed };
+    }
+    ContextFit::Truncated {
+        context,
+        estimated,
+        allowed,
+    }
+}
+
+fn count_tokens(messages: &[Message]) -> usize {
+    messages.iter().map(|msg| msg.estimated_token_count()).sum()
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+fn build_convo() -> Conversation {
+    let mut convo = Conversation::default();
+    convo.append_message(Message::new_system("system", "Hello"));
+    for i in 0..4 {
+        // 100 tokens each
+        convo.append_message(Message::new_user("user", format!("{:0400}", i)));
+        convo.append_message(Message::new_system("system", format!("{:0400}", i)));
+    }
+    convo
+}
+
+#[test]
+fn test_check_context_window() {
+    let convo = build_convo();
+    let prompt = "x".repeat(400);
+
+    let fit = check_context_window(&convo, &prompt, None, None, false);
+    assert!(matches!(fit, ContextFit::Fits(ref context) if context.len() == 8));
+
+    let fit = check_context_window(&convo, &prompt, None, Some(1000), false);
+    assert!(matches!(fit, ContextFit::Fits(ref context) if context.len() == 8));
+
+    let fit = check_context_window(&convo, &prompt, None, Some(500), false);
+    assert!(matches!(
+        fit,
+        ContextFit::Exceeded {
+            estimated: 900,
+            allowed: 500
+        }
+    ));
+
+    // The oldest messages are dropped to fit the window
+    let fit = check_context_window(&convo, &prompt, None, Some(500), true);
+    let ContextFit::Truncated {
+        context,
+        estimated,
+        allowed,
+    } = fit
+    else {
+        panic!("The context is not truncated: {:?}", fit);
+    };
+    assert_eq!(context.len(), 4);
+    assert_eq!((estimated, allowed), (900, 500));
+
+    // The prompt alone doesn't fit
+    let fit = check_context_window(&convo, &prompt, None, Some(50), true);
+    assert!(matches!(fit, ContextFit::Exceeded { .. }));
+}
+
+#[test]
+fn test_check_context_window_max_below_prompt() {
+    let convo = build_convo().with_system_prompt("x".repeat(1800));
+    let prompt = "x".repeat(400);
+
+    // The history is dropped but the system prompt alone exceeds the window
+    let fit = check_context_window(&convo, &prompt, Some(50), Some(500), true);
+    assert!(matches!(fit, ContextFit::Exceeded { allowed: 500, .. }));
+}
@@ -0,0 +1,146 @@
+#[cfg(test)]
+#[path = "mod_test.rs"]
+mod tests;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+use crate::config;
+
+/// The language used when the configured one is not bundled, the messages
+/// missing in the other languages are taken from it.
+const DEFAULT_LANGUAGE: &str = "en";
+
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.toml")),
+    ("vi", include_str!("locales/vi.toml")),
+];
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Look up the message of the key in the configured language, e.g.
+/// `t!("history.title")`. The named arguments replace the `{name}`
+/// placeholders, e.g. `t!("notice.switching", title = "Rust")`.
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::translate($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            $crate::i18n::translate($key),
+            &[$((stringify!($name), $value.to_string())),+],
+        )
+    };
+}
+
+/// Return the message of the key in the configured language, see [`t!`]
+pub fn translate(key: &'static str) -> &'static str {
+    catalog().get(key)
+}
+
+/// Replace the `{name}` placeholders of the message with the arguments
+pub fn format(message: &str, args: &[(&str, String)]) -> String {
+    args.iter()
+        .fold(message.to_string(), |message, (name, value)| {
+            message.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| {
+        let language = config::instance()
+            .general
+            .language
+            .as_deref()
+            .unwrap_or(DEFAULT_LANGUAGE);
+        Catalog::new(language)
+    })
+}
+
+pub struct Catalog {
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+    // The missing keys which are already logged
+    missing: Mutex<HashSet<String>>,
+}
+
+impl Catalog {
+    /// Load the bundled messages of the language, e.g. `vi` or `vi_VN`.
+    /// English is used if the language is not bundled.
+    pub fn new(language: &str) -> Self {
+        let language = normalize_language(language);
+        let messages = match LOCALES.iter().find(|(name, _)| *name == language) {
+            Some((_, content)) => parse_locale(content),
+            None => {
+                log::warn!("Language \"{}\" is not supported, using English", language);
+                HashMap::new()
+            }
+        };
+        Self {
+            messages,
+            fallback: LOCALES
+                .iter()
+                .find(|(name, _)| *name == DEFAULT_LANGUAGE)
+                .map(|(_, content)| parse_locale(content))
+                .unwrap_or_default(),
+            missing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Return the message of the key, falling back to English and then to
+    /// the key itself
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        if let Some(message) = self.messages.get(key) {
+            return message;
+        }
+        if let Ok(mut missing) = self.missing.lock() {
+            if missing.insert(key.to_string()) {
+                log::warn!("Missing translation of \"{}\", using English", key);
+            }
+        }
+        self.fallback.get(key).map(String::as_str).unwrap_or(key)
+    }
+}
+
+/// Take the language code of a locale, e.g. `vi` of `vi_VN.UTF-8`
+fn normalize_language(language: &str) -> String {
+    language
+        .split(['_', '-', '.'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// Flatten the tables of the locale file into dotted keys, e.g. the `title`
+/// of the `[help]` table is `help.title`
+fn parse_locale(content: &str) -> HashMap<String, String> {
+    let table = match content.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(err) => {
+            log::error!("Failed to parse the locale: {}", err);
+            return HashMap::new();
+        }
+    };
+    let mut messages = HashMap::new();
+    flatten(&mut messages, "", &table);
+    messages
+}
+
+fn flatten(messages: &mut HashMap<String, String>, prefix: &str, table: &toml::Table) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::String(message) => {
+                messages.insert(key, message.to_string());
+            }
+            toml::Value::Table(table) => flatten(messages, &key, table),
+            _ => log::warn!("Ignore the non-string message \"{}\"", key),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use super::*;
+use crate::t;
+
+#[test]
+fn test_catalog() {
+    let catalog = Catalog::new("vi_VN.UTF-8");
+    assert_eq!(catalog.get("history.today"), "Hôm nay");
+
+    let catalog = Catalog::new("en");
+    assert_eq!(catalog.get("history.today"), "Today");
+    assert_eq!(catalog.get("unknown.key"), "unknown.key");
+
+    // Fall back to English if the language is not bundled
+    let catalog = Catalog::new("xx");
+    assert_eq!(catalog.get("history.today"), "Today");
+}
+
+#[test]
+fn test_locales_have_english_messages() {
+    let english = parse_locale(LOCALES[0].1);
+    assert!(!english.is_empty());
+    for (language, content) in LOCALES {
+        let messages = parse_locale(content);
+        assert!(!messages.is_empty(), "{} has no messages", language);
+        for key in messages.keys() {
+            assert!(english.contains_key(key), "{} is not in English", key);
+        }
+    }
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(
+        format("Switching to \"{title}\"", &[("title", "Rust".to_string())]),
+        "Switching to \"Rust\""
+    );
+    assert_eq!(
+        t!("notice.switching", title = "Rust"),
+        "Switching to \"Rust\""
+    );
+}
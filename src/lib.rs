@@ -3,5 +3,7 @@ pub mod backend;
 pub mod cli;
 pub mod config;
 pub mod context;
+pub mod i18n;
 pub mod models;
+pub mod oneshot;
 pub mod storage;
@@ -6,17 +6,30 @@ use std::time;
 use chatty_rs::app::Initializer;
 use chatty_rs::app::app::InitProps;
 use chatty_rs::app::services::action::ActionService;
-use chatty_rs::app::services::{ClipboardService, EventService, ShutdownCoordinator};
-use chatty_rs::backend::new_manager;
-use chatty_rs::config::{init_logger, init_theme};
-use chatty_rs::context::Compressor;
-use chatty_rs::models::Conversation;
+use chatty_rs::app::services::{
+    ClipboardService, EventService, Hooks, ShutdownCoordinator, SignalService,
+};
+use chatty_rs::app::ui::models::resolve_default_model;
+use chatty_rs::backend::inspect::{ConnectionModels, format_models};
+use chatty_rs::backend::{list_all_models, new_manager};
+use chatty_rs::config::check::check_configuration;
+use chatty_rs::config::model_info::ModelCatalog;
+use chatty_rs::config::{StorageConfig, init_color_theme, init_logger, init_theme};
+use chatty_rs::context::{Compressor, TitleGenerator};
 use chatty_rs::models::action::Action;
 use chatty_rs::models::storage::FilterConversation;
-use chatty_rs::storage::new_storage;
+use chatty_rs::models::{BackendPrompt, Conversation};
+use chatty_rs::oneshot;
+use chatty_rs::storage::archive::{Archive, export_archive};
+use chatty_rs::storage::import::{chatgpt, import_conversations};
+use chatty_rs::storage::semantic::SemanticIndex;
+use chatty_rs::storage::{
+    ArcStorage, DRAFTS_STATE, INPUT_HISTORY_STATE, LAST_BACKEND_STATE, LAST_CONVERSATION_STATE,
+    new_storage,
+};
 use chatty_rs::{
     app::{App, destruct_terminal},
-    cli::Command,
+    cli::{Command, ExportArgs, ExportFormat, ImportArgs, ModelsArgs, SearchArgs, SubCommand},
 };
 use chatty_rs::{info_notice, task_success, warn_notice};
 use eyre::{Context, Result};
@@ -31,30 +44,54 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    if cmd.config_check() {
+        return run_config_check(&cmd);
+    }
+
+    if let Some(prompt) = cmd.prompt() {
+        return run_prompt(&cmd, prompt).await;
+    }
+
+    match cmd.subcommand() {
+        Some(SubCommand::Import(args)) => return run_import(&cmd, args).await,
+        Some(SubCommand::Export(args)) => return run_export(&cmd, args).await,
+        Some(SubCommand::Models(args)) => return run_models(&cmd, args).await,
+        Some(SubCommand::Stats) => return run_stats(&cmd).await,
+        Some(SubCommand::Search(args)) => return run_search(&cmd, args).await,
+        None => {}
+    }
+
     std::panic::set_hook(Box::new(|panic_info| {
         destruct_terminal();
         better_panic::Settings::auto().create_panic_handler()(panic_info);
     }));
 
+    let config = cmd.get_config()?;
+    let logger = init_logger(&config.log);
+    // The theme is selected before the initialization screen starts because
+    // the terminal background is queried by reading the terminal directly,
+    // which would race with the event stream of the screen
+    let theme = init_theme(&config.theme, config.ui.code_theme.as_deref());
+
     let init_handler = task::spawn(async move { Initializer::default().run().await });
     // Wait until the initialization screen is ready
     while !Initializer::ready() {
         tokio::time::sleep(time::Duration::from_millis(100)).await;
     }
     Initializer::add_task("init_logger", "Initializing logger..");
-    let config = cmd.get_config()?;
-    init_logger(&config.log)?;
+    logger?;
     task_success!("init_logger");
 
     Initializer::add_task("init_theme", "Initializing theme...");
-    let theme = init_theme(&config.theme)?;
+    let theme = theme?;
+    let colors = init_color_theme(&config.theme)?;
     task_success!("init_theme");
 
     if config.backend.connections.is_empty() {
         eyre::bail!("No backend configured");
     }
 
-    let backend = new_manager(&config.backend).await?;
+    let backend = new_manager(&config.backend, &config.search).await?;
 
     if !config.context.compression.enabled && !config.context.truncation.enabled {
         Initializer::add_notice(warn_notice!(
@@ -64,6 +101,18 @@ async fn main() -> Result<()> {
 
     Initializer::add_task("listing_models", "Fetching models...");
     let models = backend.list_models().await.wrap_err("getting models")?;
+    let catalog =
+        ModelCatalog::load(config.backend.model_info_path.as_deref()).unwrap_or_else(|err| {
+            Initializer::add_notice(warn_notice!(format!(
+                "Failed to load the model info, using the bundled one: {:#}",
+                err
+            )));
+            ModelCatalog::default()
+        });
+    let models = models
+        .into_iter()
+        .map(|model| catalog.annotate(model, &config.backend))
+        .collect::<Vec<_>>();
     task_success!(
         "listing_models",
         format!("Available {} model(s)", models.len())
@@ -81,6 +130,16 @@ async fn main() -> Result<()> {
     let storage = new_storage(&config.storage)
         .await
         .wrap_err("initializing storage")?;
+    let purge_before = chrono::Utc::now()
+        - chrono::Duration::days(config.storage.purge_deleted_after_days() as i64);
+    match storage.purge_deleted_conversations(purge_before).await {
+        Ok(purged) if purged > 0 => {
+            log::info!("Purged {} deleted conversation(s)", purged)
+        }
+        Ok(_) => {}
+        Err(err) => log::warn!("Failed to purge deleted conversations: {}", err),
+    }
+    prune_conversations(&storage, &config.storage).await;
     task_success!("init_storage");
 
     Initializer::add_task("listing_conversations", "Fetching conversations...");
@@ -104,6 +163,29 @@ async fn main() -> Result<()> {
         format!("Total {} conversation(s)", conversations.len())
     );
 
+    let restore_last_conversation = config.general.restore_last_conversation.unwrap_or_default();
+    let last_conversation = if restore_last_conversation {
+        get_last_conversation(&storage).await
+    } else {
+        None
+    };
+
+    let restore_last_backend = config.general.restore_last_backend.unwrap_or_default();
+    let last_backend = if restore_last_backend {
+        get_last_backend(&storage).await
+    } else {
+        None
+    };
+
+    let persist_input_history = config.general.persist_input_history.unwrap_or_default();
+    let input_history = if persist_input_history {
+        get_input_history(&storage).await
+    } else {
+        vec![]
+    };
+
+    let drafts = get_drafts(&storage, &conversations).await;
+
     // Mark complete tasks. We assume that all tasks are completed
     Initializer::complete();
     if let Err(err) = init_handler.await {
@@ -123,6 +205,9 @@ async fn main() -> Result<()> {
     let compressor =
         Arc::new(Compressor::new(backend.clone()).from_config(&config.context.compression));
 
+    let title_generator =
+        Arc::new(TitleGenerator::new(backend.clone()).from_config(&config.general));
+
     let mut action_service = ActionService::new(
         backend.clone(),
         storage.clone(),
@@ -131,23 +216,36 @@ async fn main() -> Result<()> {
         events.event_tx(),
         token.clone(),
         pending_tasks.clone(),
-    );
+    )
+    .with_title_generator(Arc::clone(&title_generator))
+    .with_hooks(Hooks::new(&config.hooks));
 
     task_set.spawn(async move { return action_service.run().await });
 
+    // A SIGINT or SIGTERM quits like Ctrl+Q, the conversation is saved
+    task_set.spawn(SignalService::start(events.event_tx()));
+
     let mut app = App::new(
         theme,
         action_tx,
         &mut events,
         Arc::clone(&compressor),
+        Arc::clone(&title_generator),
         token.clone(),
         InitProps {
             conversations,
             models,
+            colors,
+            conversation: last_conversation,
+            input_history,
+            drafts,
+            backend: last_backend,
         },
     );
 
-    if let Err(err) = ClipboardService::init() {
+    if !ClipboardService::uses_system() {
+        log::debug!("Copying to the terminal clipboard with OSC 52");
+    } else if let Err(err) = ClipboardService::init() {
         log::warn!("Clipboard service is not available: {err}");
     } else {
         let token_clone = token.clone();
@@ -166,6 +264,43 @@ async fn main() -> Result<()> {
         eprintln!("Error: {}", err);
     }
 
+    if restore_last_conversation {
+        if let Err(err) = storage
+            .set_app_state(LAST_CONVERSATION_STATE, app.current_conversation_id())
+            .await
+        {
+            log::error!("Failed to save the last conversation: {:#}", err);
+        }
+    }
+
+    if restore_last_backend {
+        if let Err(err) = storage
+            .set_app_state(LAST_BACKEND_STATE, app.current_provider())
+            .await
+        {
+            log::error!("Failed to save the last backend: {:#}", err);
+        }
+    }
+
+    match serde_json::to_string(app.drafts()) {
+        Ok(drafts) => {
+            if let Err(err) = storage.set_app_state(DRAFTS_STATE, &drafts).await {
+                log::error!("Failed to save the drafts: {:#}", err);
+            }
+        }
+        Err(err) => log::error!("Failed to serialize the drafts: {}", err),
+    }
+
+    if persist_input_history {
+        let input_history = serde_json::to_string(&app.input_history())?;
+        if let Err(err) = storage
+            .set_app_state(INPUT_HISTORY_STATE, &input_history)
+            .await
+        {
+            log::error!("Failed to save the input history: {:#}", err);
+        }
+    }
+
     match tokio::time::timeout(time::Duration::from_secs(15), shutdown_rx).await {
         Ok(Ok(_)) => {}
         Ok(Err(e)) => eprintln!("Shutdown error: {}", e),
@@ -182,3 +317,322 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Remove the conversations beyond the configured limits, pruning is opt-in
+async fn prune_conversations(storage: &ArcStorage, config: &StorageConfig) {
+    let max_conversations = config.max_conversations();
+    let updated_before = config
+        .retention_days()
+        .map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
+    if max_conversations.is_none() && updated_before.is_none() {
+        return;
+    }
+
+    match storage
+        .prune_conversations(max_conversations, updated_before)
+        .await
+    {
+        Ok(pruned) if pruned > 0 => Initializer::add_notice(info_notice!(format!(
+            "Pruned {} old conversation(s)",
+            pruned
+        ))),
+        Ok(_) => {}
+        Err(err) => Initializer::add_notice(warn_notice!(format!(
+            "Failed to prune conversations: {}",
+            err
+        ))),
+    }
+}
+
+/// Load the conversation of the last session. A new conversation is started
+/// if it was not saved or has been deleted since.
+async fn get_last_conversation(storage: &ArcStorage) -> Option<Conversation> {
+    let id = match storage.get_app_state(LAST_CONVERSATION_STATE).await {
+        Ok(Some(id)) if !id.is_empty() => id,
+        Ok(_) => return None,
+        Err(err) => {
+            log::warn!("Failed to get the last conversation: {:#}", err);
+            return None;
+        }
+    };
+    match storage.get_conversation(&id).await {
+        Ok(convo) => convo,
+        Err(err) => {
+            log::warn!("Failed to load the last conversation {}: {:#}", id, err);
+            None
+        }
+    }
+}
+
+/// The connection of the model used in the previous session
+async fn get_last_backend(storage: &ArcStorage) -> Option<String> {
+    match storage.get_app_state(LAST_BACKEND_STATE).await {
+        Ok(backend) => backend.filter(|backend| !backend.is_empty()),
+        Err(err) => {
+            log::warn!("Failed to get the last backend: {:#}", err);
+            None
+        }
+    }
+}
+
+/// Load the unsent inputs of the previous sessions. The drafts of the
+/// conversations deleted since are dropped.
+async fn get_drafts(
+    storage: &ArcStorage,
+    conversations: &HashMap<String, Conversation>,
+) -> HashMap<String, String> {
+    let drafts = match storage.get_app_state(DRAFTS_STATE).await {
+        Ok(Some(drafts)) => drafts,
+        Ok(None) => return HashMap::new(),
+        Err(err) => {
+            log::warn!("Failed to get the drafts: {:#}", err);
+            return HashMap::new();
+        }
+    };
+    serde_json::from_str::<HashMap<String, String>>(&drafts)
+        .unwrap_or_else(|err| {
+            log::warn!("Failed to parse the drafts: {}", err);
+            HashMap::new()
+        })
+        .into_iter()
+        .filter(|(id, _)| id.is_empty() || conversations.contains_key(id))
+        .collect()
+}
+
+/// Load the prompts sent in the previous sessions
+async fn get_input_history(storage: &ArcStorage) -> Vec<String> {
+    match storage.get_app_state(INPUT_HISTORY_STATE).await {
+        Ok(Some(history)) => serde_json::from_str(&history).unwrap_or_else(|err| {
+            log::warn!("Failed to parse the input history: {}", err);
+            vec![]
+        }),
+        Ok(None) => vec![],
+        Err(err) => {
+            log::warn!("Failed to get the input history: {:#}", err);
+            vec![]
+        }
+    }
+}
+
+fn run_config_check(cmd: &Command) -> Result<()> {
+    let config = match cmd.get_config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("[ERROR] configuration\n        - {:#}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let report = check_configuration(config);
+    print!("{}", report);
+    if report.has_problems() {
+        std::process::exit(1);
+    }
+    println!("Configuration is valid");
+    Ok(())
+}
+
+async fn run_import(cmd: &Command, args: &ImportArgs) -> Result<()> {
+    let config = cmd.get_config()?;
+    init_logger(&config.log)?;
+
+    let conversations = match (args.chatgpt.as_deref(), args.archive.as_deref()) {
+        (Some(path), _) => {
+            let data = std::fs::read_to_string(path).wrap_err(format!("reading {}", path))?;
+            chatgpt::parse(&data)?
+        }
+        (_, Some(path)) => {
+            let data = std::fs::read_to_string(path).wrap_err(format!("reading {}", path))?;
+            Archive::parse(&data)?.into_conversations()?
+        }
+        _ => eyre::bail!("No import source specified"),
+    };
+
+    let storage = new_storage(&config.storage)
+        .await
+        .wrap_err("initializing storage")?;
+    let stats = import_conversations(&storage, conversations).await?;
+    println!(
+        "Imported {} conversation(s) with {} message(s), skipped {} duplicate(s)",
+        stats.conversations, stats.messages, stats.skipped
+    );
+    Ok(())
+}
+
+async fn run_export(cmd: &Command, args: &ExportArgs) -> Result<()> {
+    let config = cmd.get_config()?;
+    init_logger(&config.log)?;
+
+    let storage = new_storage(&config.storage)
+        .await
+        .wrap_err("initializing storage")?;
+    let archive = export_archive(&storage).await?;
+    let data = match args.format {
+        ExportFormat::Json => archive.to_json()?,
+    };
+    std::fs::write(&args.path, data).wrap_err(format!("writing {}", args.path))?;
+
+    let messages = archive
+        .conversations
+        .iter()
+        .map(|convo| convo.messages.len())
+        .sum::<usize>();
+    println!(
+        "Exported {} conversation(s) with {} message(s) to {}",
+        archive.conversations.len(),
+        messages,
+        args.path
+    );
+    Ok(())
+}
+
+async fn run_stats(cmd: &Command) -> Result<()> {
+    let config = cmd.get_config()?;
+    init_logger(&config.log)?;
+
+    let storage = new_storage(&config.storage)
+        .await
+        .wrap_err("initializing storage")?;
+    print!("{}", storage.stats().await?);
+    Ok(())
+}
+
+async fn run_search(cmd: &Command, args: &SearchArgs) -> Result<()> {
+    let config = cmd.get_config()?;
+    init_logger(&config.log)?;
+
+    let storage = new_storage(&config.storage)
+        .await
+        .wrap_err("initializing storage")?;
+    let limit = args.limit.unwrap_or(config.search.max_results());
+
+    let results = if args.semantic {
+        if !config.search.semantic {
+            eyre::bail!("Semantic search is disabled, enable it with `search.semantic`");
+        }
+        let backend = new_manager(&config.backend, &config.search).await?;
+        let index = SemanticIndex::new(backend, storage.clone()).from_config(&config.search);
+        let ranked = index.search(&args.query, limit).await?;
+        let mut conversations = storage
+            .get_conversations(FilterConversation::default())
+            .await
+            .wrap_err("getting conversations")?;
+        ranked
+            .into_iter()
+            .filter_map(|(id, score)| Some((conversations.remove(&id)?, Some(score))))
+            .collect::<Vec<_>>()
+    } else {
+        let mut conversations = storage
+            .get_conversations(FilterConversation::default().with_title(&args.query))
+            .await
+            .wrap_err("searching conversations")?;
+        conversations.extend(
+            storage
+                .get_conversations(FilterConversation::default().with_message_contains(&args.query))
+                .await
+                .wrap_err("searching conversations")?,
+        );
+        let mut conversations = conversations.into_values().collect::<Vec<_>>();
+        conversations.sort_by_key(|convo| std::cmp::Reverse(convo.updated_at()));
+        conversations.truncate(limit);
+        conversations
+            .into_iter()
+            .map(|convo| (convo, None))
+            .collect::<Vec<_>>()
+    };
+
+    if results.is_empty() {
+        println!("No conversation found");
+        return Ok(());
+    }
+    for (convo, score) in results {
+        let updated_at = convo.updated_at().with_timezone(&chrono::Local);
+        let score = score
+            .map(|score| format!("{:.3}  ", score))
+            .unwrap_or_default();
+        println!(
+            "{}{}  {}  ({})",
+            score,
+            updated_at.format("%Y-%m-%d %H:%M"),
+            convo.title(),
+            convo.id()
+        );
+    }
+    Ok(())
+}
+
+async fn run_models(cmd: &Command, args: &ModelsArgs) -> Result<()> {
+    let config = cmd.get_config()?;
+    init_logger(&config.log)?;
+
+    let pattern = args
+        .pattern
+        .as_deref()
+        .map(regex::Regex::new)
+        .transpose()
+        .wrap_err("parsing pattern")?;
+
+    let mut connections = vec![];
+    for conn in config.backend.connections.iter().filter(|c| c.enabled()) {
+        let name = conn
+            .alias()
+            .map(|alias| format!("{} ({:?})", alias, conn.kind()))
+            .unwrap_or_else(|| format!("{:?}", conn.kind()));
+        connections.push(ConnectionModels {
+            name,
+            want_models: conn.models().to_vec(),
+            models: list_all_models(conn).await,
+        });
+    }
+
+    print!(
+        "{}",
+        format_models(
+            &connections,
+            &config.backend.model_settings,
+            pattern.as_ref()
+        )
+    );
+    Ok(())
+}
+
+async fn run_prompt(cmd: &Command, prompt: &str) -> Result<()> {
+    let config = cmd.get_config()?;
+    init_logger(&config.log)?;
+
+    let prompt = if prompt == "-" {
+        std::io::read_to_string(std::io::stdin()).wrap_err("reading prompt")?
+    } else {
+        prompt.to_string()
+    };
+    if prompt.trim().is_empty() {
+        eyre::bail!("The prompt is empty");
+    }
+    if config.backend.connections.is_empty() {
+        eyre::bail!("No backend configured");
+    }
+
+    let backend = new_manager(&config.backend, &config.search).await?;
+    let models = backend.list_models().await.wrap_err("getting models")?;
+    let model = match cmd.model() {
+        Some(id) => models
+            .iter()
+            .find(|model| model.id() == id)
+            .ok_or_else(|| eyre::eyre!("Model {} is not available", id))?,
+        None => {
+            // The same model as the one selected at the startup of the UI
+            let (model, warnings) = resolve_default_model(&models, &config.backend);
+            for warning in warnings {
+                eprintln!("{}", warning);
+            }
+            model
+                .or(models.first())
+                .ok_or_else(|| eyre::eyre!("No model is available"))?
+        }
+    };
+
+    let prompt = BackendPrompt::new(prompt)
+        .with_model(model.id())
+        .with_provider(model.provider());
+    oneshot::run(backend, prompt, oneshot::should_flush(cmd.flush())).await
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use super::{BackendPrompt, Context, Conversation, Message};
 
 pub enum Action {
@@ -6,11 +8,21 @@ pub enum Action {
 
     SetConversation(String),
     UpsertConversation(UpsertConvoRequest),
-    DeleteConversation(String),           // Conversation ID
-    UpsertMessage(String, Message),       // Conversation ID, Message
-    UpsertConvoContext(String, Context),  // Conversation ID, Context
-    DeleteMessage(String),                // Message ID
-    CompressConversation(String, String), // Conversation ID, Model ID
+    DeleteConversation(String),                    // Conversation ID
+    RestoreConversation(String),                   // Conversation ID
+    UpsertMessage(String, Message),                // Conversation ID, Message
+    MessageCompleted(String, Message),             // Conversation ID, Message
+    UpsertConvoContext(String, Context),           // Conversation ID, Context
+    DeleteMessage(String),                         // Message ID
+    ClearMessages(String),                         // Conversation ID
+    GetMessageEdits(String, String),               // Conversation ID, Message ID
+    CompressConversation(String, String),          // Conversation ID, Model ID
+    CancelCompression(String),                     // Conversation ID
+    GenerateTitle(String, String, String),         // Conversation ID, Model ID, First message
+    RegenerateTitle(String, String, Vec<Message>), // Conversation ID, Model ID, Messages
+    RefreshModels,
+    SemanticSearch(String),              // Query
+    SaveDrafts(HashMap<String, String>), // Conversation ID - Draft
 
     CopyMessages(Vec<Message>),
     CopyText { content: String, notice: bool },
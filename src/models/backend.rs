@@ -1,4 +1,4 @@
-use crate::models::Message;
+use crate::models::{Message, TokenLogprob};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, time};
 
@@ -12,6 +12,32 @@ pub struct CodeContext {
 pub struct Model {
     id: String,
     provider: String,
+
+    // Metadata, not part of the identity of the model
+    context_window: Option<usize>,
+    pricing: Option<ModelPricing>,
+}
+
+/// The price of the model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    /// The price of the input tokens read from the prompt cache, the input
+    /// price if not set
+    pub cached_input: Option<f64>,
+}
+
+impl ModelPricing {
+    /// The estimated cost of the usage in USD
+    pub fn cost(&self, usage: &BackendUsage) -> f64 {
+        let cached = usage.cached_prompt_tokens().min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        (uncached as f64 * self.input
+            + cached as f64 * self.cached_input.unwrap_or(self.input)
+            + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
 }
 
 #[derive(Debug)]
@@ -20,8 +46,14 @@ pub struct BackendResponse {
     pub id: String,
     pub text: String,
     pub done: bool,
-    pub init_conversation: bool,
     pub usage: Option<BackendUsage>,
+    /// The text is the reason the model declined to answer
+    pub refusal: bool,
+    /// The message ends with this response, e.g. before a tool call. The
+    /// response resumes in a new message.
+    pub boundary: bool,
+    /// The logprobs of the tokens of the text, if requested
+    pub logprobs: Vec<TokenLogprob>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -29,13 +61,31 @@ pub struct BackendUsage {
     pub prompt_tokens: usize,
     pub completion_tokens: usize,
     pub total_tokens: usize,
+    /// The prompt tokens read from the prompt cache, part of `prompt_tokens`.
+    /// `None` if the provider doesn't report them.
+    pub cached_prompt_tokens: Option<usize>,
+    /// The thinking tokens of the reasoning models, part of
+    /// `completion_tokens`
+    pub reasoning_tokens: Option<usize>,
+}
+
+/// The format the model is asked to answer in
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    /// A valid JSON object
+    JsonObject,
 }
 
+#[derive(Clone)]
 pub struct BackendPrompt {
     model: String,
+    provider: String,
     text: String,
     context: Vec<Message>,
-    no_generate_title: bool,
+    response_format: Option<ResponseFormat>,
 }
 
 impl BackendResponse {
@@ -45,8 +95,10 @@ impl BackendResponse {
             id: id.into(),
             text: String::new(),
             done: false,
-            init_conversation: false,
             usage: None,
+            refusal: false,
+            boundary: false,
+            logprobs: vec![],
         }
     }
 
@@ -60,24 +112,47 @@ impl BackendResponse {
         self
     }
 
-    pub fn with_init_conversation(mut self, init: bool) -> Self {
-        self.init_conversation = init;
+    pub fn with_usage(mut self, usage: BackendUsage) -> Self {
+        self.usage = Some(usage);
         self
     }
 
-    pub fn with_usage(mut self, usage: BackendUsage) -> Self {
-        self.usage = Some(usage);
+    pub fn with_refusal(mut self, refusal: bool) -> Self {
+        self.refusal = refusal;
+        self
+    }
+
+    pub fn with_logprobs(mut self, logprobs: Vec<TokenLogprob>) -> Self {
+        self.logprobs = logprobs;
+        self
+    }
+
+    pub fn with_boundary(mut self) -> Self {
+        self.boundary = true;
         self
     }
+
+    /// Append the next chunk of the same response
+    pub fn merge(&mut self, next: BackendResponse) {
+        self.text.push_str(&next.text);
+        self.done = next.done;
+        self.refusal |= next.refusal;
+        self.boundary = next.boundary;
+        self.logprobs.extend(next.logprobs);
+        if next.usage.is_some() {
+            self.usage = next.usage;
+        }
+    }
 }
 
 impl BackendPrompt {
     pub fn new(text: impl Into<String>) -> BackendPrompt {
         BackendPrompt {
             model: String::new(),
+            provider: String::new(),
             text: text.into(),
             context: vec![],
-            no_generate_title: false,
+            response_format: None,
         }
     }
 
@@ -86,13 +161,21 @@ impl BackendPrompt {
         self
     }
 
+    /// Send the prompt to the given connection. The first connection serving
+    /// the model is used if not set.
+    pub fn with_provider(mut self, provider: &str) -> Self {
+        self.provider = provider.to_string();
+        self
+    }
+
     pub fn with_context(mut self, ctx: Vec<Message>) -> Self {
         self.context = ctx;
         self
     }
 
-    pub fn with_no_generate_title(mut self) -> Self {
-        self.no_generate_title = true;
+    /// Override the `response_format` of the model settings
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
         self
     }
 
@@ -100,6 +183,10 @@ impl BackendPrompt {
         &self.model
     }
 
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
     pub fn text(&self) -> &str {
         &self.text
     }
@@ -108,8 +195,8 @@ impl BackendPrompt {
         &self.context
     }
 
-    pub fn no_generate_title(&self) -> bool {
-        self.no_generate_title
+    pub fn response_format(&self) -> Option<ResponseFormat> {
+        self.response_format
     }
 }
 
@@ -122,15 +209,41 @@ pub struct BackendConnection {
     alias: Option<String>,
     #[serde(default)]
     endpoint: String,
+    /// The path of the API under the endpoint, OpenAI only
+    #[serde(default)]
+    path_prefix: Option<String>,
     #[serde(default)]
     api_key: Option<String>,
     #[serde(default)]
     timeout: Option<time::Duration>,
     #[serde(default)]
     models: Vec<String>,
+    /// The model of the connection selected at startup, unless
+    /// `backend.default_model` is served
+    #[serde(default)]
+    default_model: Option<String>,
 
     #[serde(default)]
     max_output_tokens: Option<usize>,
+
+    #[serde(default)]
+    tool_call_style: ToolCallStyle,
+
+    /// The role names of the messages, OpenAI only
+    #[serde(default)]
+    roles: MessageRoles,
+
+    #[serde(default)]
+    stream: Option<bool>,
+
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    profile: Option<String>,
+
+    /// The fields merged into the request body, OpenAI and Gemini only
+    #[serde(default)]
+    extra_body: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 impl BackendConnection {
@@ -140,10 +253,18 @@ impl BackendConnection {
             kind,
             alias: None,
             endpoint: endpoint.into(),
+            path_prefix: None,
             api_key: None,
             timeout: None,
             models: Vec::new(),
+            default_model: None,
             max_output_tokens: None,
+            tool_call_style: ToolCallStyle::default(),
+            roles: MessageRoles::default(),
+            stream: None,
+            region: None,
+            profile: None,
+            extra_body: None,
         }
     }
 
@@ -162,11 +283,21 @@ impl BackendConnection {
         self
     }
 
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = Some(model.into());
+        self
+    }
+
     pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
         self.alias = Some(alias.into());
         self
     }
 
+    pub fn with_path_prefix(mut self, path_prefix: impl Into<String>) -> Self {
+        self.path_prefix = Some(path_prefix.into());
+        self
+    }
+
     pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
         self.api_key = Some(api_key.into());
         self
@@ -177,6 +308,39 @@ impl BackendConnection {
         self
     }
 
+    pub fn with_tool_call_style(mut self, style: ToolCallStyle) -> Self {
+        self.tool_call_style = style;
+        self
+    }
+
+    pub fn with_roles(mut self, roles: MessageRoles) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    pub fn with_stream(mut self, stream: bool) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn with_extra_body(
+        mut self,
+        extra_body: serde_json::Map<String, serde_json::Value>,
+    ) -> Self {
+        self.extra_body = Some(extra_body);
+        self
+    }
+
     pub fn kind(&self) -> &BackendKind {
         &self.kind
     }
@@ -185,10 +349,24 @@ impl BackendConnection {
         self.alias.as_deref()
     }
 
+    /// The name of the backend created from the connection, the alias if set
+    pub fn name(&self) -> &str {
+        self.alias().unwrap_or(match self.kind {
+            BackendKind::OpenAI => "OpenAI",
+            BackendKind::Gemini => "Gemini",
+            BackendKind::Bedrock => "Bedrock",
+            BackendKind::Cohere => "Cohere",
+        })
+    }
+
     pub fn endpoint(&self) -> &str {
         &self.endpoint
     }
 
+    pub fn path_prefix(&self) -> Option<&str> {
+        self.path_prefix.as_deref()
+    }
+
     pub fn api_key(&self) -> Option<&str> {
         self.api_key.as_deref()
     }
@@ -201,6 +379,12 @@ impl BackendConnection {
         &self.models
     }
 
+    pub fn default_model(&self) -> Option<&str> {
+        self.default_model
+            .as_deref()
+            .filter(|model| !model.is_empty())
+    }
+
     pub fn enabled(&self) -> bool {
         self.enabled
     }
@@ -208,6 +392,31 @@ impl BackendConnection {
     pub fn max_output_tokens(&self) -> Option<usize> {
         self.max_output_tokens
     }
+
+    pub fn tool_call_style(&self) -> ToolCallStyle {
+        self.tool_call_style
+    }
+
+    pub fn roles(&self) -> &MessageRoles {
+        &self.roles
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn extra_body(&self) -> Option<&serde_json::Map<String, serde_json::Value>> {
+        self.extra_body.as_ref()
+    }
+
+    /// Whether the responses should be streamed, default to true
+    pub fn stream(&self) -> bool {
+        self.stream.unwrap_or(true)
+    }
 }
 
 impl Model {
@@ -215,6 +424,8 @@ impl Model {
         Self {
             id: id.into(),
             provider: String::new(),
+            context_window: None,
+            pricing: None,
         }
     }
 
@@ -223,6 +434,24 @@ impl Model {
         self
     }
 
+    pub fn with_context_window(mut self, context_window: Option<usize>) -> Self {
+        self.context_window = context_window;
+        self
+    }
+
+    pub fn with_pricing(mut self, pricing: Option<ModelPricing>) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    pub fn context_window(&self) -> Option<usize> {
+        self.context_window
+    }
+
+    pub fn pricing(&self) -> Option<ModelPricing> {
+        self.pricing
+    }
+
     pub fn id(&self) -> &str {
         &self.id
     }
@@ -238,6 +467,42 @@ pub enum BackendKind {
     OpenAI,
     #[serde(rename = "gemini")]
     Gemini,
+    #[serde(rename = "bedrock")]
+    Bedrock,
+    #[serde(rename = "cohere")]
+    Cohere,
+}
+
+/// How streamed tool call deltas are identified. Some OpenAI compatible
+/// providers (e.g. Mistral, Together) don't send a stable `index`, in that
+/// case the deltas must be accumulated by the tool call `id` instead.
+#[derive(Default, PartialEq, Eq, Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStyle {
+    #[default]
+    Indexed,
+    IdBased,
+}
+
+/// The role names sent with the messages. Some OpenAI compatible servers
+/// (e.g. certain llama.cpp builds) expect other names than the OpenAI ones.
+#[derive(PartialEq, Eq, Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct MessageRoles {
+    pub user: String,
+    pub assistant: String,
+    /// The role of the system prompt and the context summaries
+    pub system: String,
+}
+
+impl Default for MessageRoles {
+    fn default() -> Self {
+        Self {
+            user: "user".to_string(),
+            assistant: "assistant".to_string(),
+            system: "system".to_string(),
+        }
+    }
 }
 
 impl Display for BackendKind {
@@ -245,20 +510,44 @@ impl Display for BackendKind {
         match self {
             BackendKind::OpenAI => write!(f, "open_ai"),
             BackendKind::Gemini => write!(f, "gemini"),
+            BackendKind::Bedrock => write!(f, "bedrock"),
+            BackendKind::Cohere => write!(f, "cohere"),
         }
     }
 }
 
+impl BackendUsage {
+    pub fn cached_prompt_tokens(&self) -> usize {
+        self.cached_prompt_tokens.unwrap_or_default()
+    }
+
+    pub fn reasoning_tokens(&self) -> usize {
+        self.reasoning_tokens.unwrap_or_default()
+    }
+}
+
 impl Display for BackendUsage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Prompt Tokens: {}, Completion Token: {}, Total: {}",
-            self.prompt_tokens, self.completion_tokens, self.total_tokens
-        )
+        write!(f, "Prompt Tokens: {}", self.prompt_tokens)?;
+        if self.cached_prompt_tokens() > 0 {
+            write!(f, " (cached: {})", self.cached_prompt_tokens())?;
+        }
+        write!(f, ", Completion Token: {}", self.completion_tokens)?;
+        if self.reasoning_tokens() > 0 {
+            write!(f, " (reasoning: {})", self.reasoning_tokens())?;
+        }
+        write!(f, ", Total: {}", self.total_tokens)
     }
 }
 
+impl PartialEq for Model {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.provider == other.provider
+    }
+}
+
+impl Eq for Model {}
+
 impl Display for Model {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
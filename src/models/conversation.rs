@@ -2,33 +2,92 @@
 #[path = "conversation_test.rs"]
 mod tests;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     config,
-    config::constants::HELLO_MESSAGE,
-    models::{Message, message::Issuer},
+    models::{Message, TokenLogprob, message::Issuer},
 };
 
+/// Message id used for the conversation system prompt when it is
+/// prepended to the context
+pub const SYSTEM_PROMPT_ID: &str = "system_prompt";
+
+/// The limits of the tokens and the cost (in USD) spent on a conversation.
+/// A budget without any limit turns off the default budget.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    #[serde(default)]
+    pub tokens: Option<usize>,
+    #[serde(default)]
+    pub cost: Option<f64>,
+}
+
+impl Budget {
+    pub fn is_unlimited(&self) -> bool {
+        self.tokens.is_none() && self.cost.is_none()
+    }
+
+    /// The highest fraction of the limits used by the spending, e.g. `0.5`
+    /// if half of the tokens are spent. `None` if there is no limit.
+    pub fn used(&self, spending: &Spending) -> Option<f64> {
+        let tokens = self
+            .tokens
+            .map(|tokens| spending.tokens as f64 / tokens.max(1) as f64);
+        let cost = self.cost.map(|cost| spending.cost / cost.max(f64::EPSILON));
+        match (tokens, cost) {
+            (Some(tokens), Some(cost)) => Some(tokens.max(cost)),
+            (tokens, cost) => tokens.or(cost),
+        }
+    }
+}
+
+/// The running total of the tokens and the estimated cost (in USD) of the
+/// responses of a conversation
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Spending {
+    pub tokens: usize,
+    pub cost: f64,
+}
+
+/// The messages written in the Markdown transcript of a conversation
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TranscriptOptions {
+    /// Only the responses of the assistant
+    pub assistant_only: bool,
+    pub tool_calls: bool,
+    /// The system prompt, the summary of the compressed messages and the
+    /// welcome message
+    pub context: bool,
+    /// The token logprobs of the responses, a line per token
+    pub logprobs: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Conversation {
     id: String,
     title: String,
+    system_prompt: Option<String>,
+    budget: Option<Budget>,
+    spending: Spending,
     messages: Vec<Message>,
     contexts: Vec<Context>,
     created_at: chrono::DateTime<chrono::Utc>,
     updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    // A scratch conversation, kept in memory only and never saved
+    ephemeral: bool,
 }
 
 impl Conversation {
+    /// A new conversation starting with the welcome message, blank if the
+    /// message is disabled
     pub fn new_hello() -> Self {
         let mut conversation = Self::default();
-        conversation.messages.push(Message::new_system(
-            "system",
-            config::instance()
-                .general
-                .hello_message
-                .as_deref()
-                .unwrap_or(HELLO_MESSAGE),
-        ));
+        if let Some(hello) = config::instance().general.welcome_message() {
+            conversation
+                .messages
+                .push(Message::new_system("system", hello));
+        }
         conversation
     }
 
@@ -55,6 +114,26 @@ impl Conversation {
         self
     }
 
+    pub fn with_system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.set_system_prompt(system_prompt);
+        self
+    }
+
+    pub fn with_budget(mut self, budget: Option<Budget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    pub fn with_spending(mut self, spending: Spending) -> Self {
+        self.spending = spending;
+        self
+    }
+
     pub fn set_updated_at(&mut self, timestamp: chrono::DateTime<chrono::Utc>) {
         self.updated_at = Some(timestamp);
     }
@@ -87,6 +166,28 @@ impl Conversation {
         self.title = title.into();
     }
 
+    /// Set the system prompt of the conversation. An empty prompt removes it.
+    pub fn set_system_prompt(&mut self, system_prompt: impl Into<String>) {
+        let system_prompt = system_prompt.into();
+        self.system_prompt = if system_prompt.trim().is_empty() {
+            None
+        } else {
+            Some(system_prompt)
+        };
+    }
+
+    /// Override the default budget of the conversation, `None` falls back
+    /// to the default one
+    pub fn set_budget(&mut self, budget: Option<Budget>) {
+        self.budget = budget;
+    }
+
+    /// Add the tokens and the cost of a response to the spending
+    pub fn add_spending(&mut self, tokens: usize, cost: f64) {
+        self.spending.tokens += tokens;
+        self.spending.cost += cost;
+    }
+
     pub fn append_message(&mut self, message: Message) {
         self.messages.push(message);
         self.messages.sort_by(|a, b| {
@@ -122,6 +223,27 @@ impl Conversation {
         &self.title
     }
 
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    /// The budget set on the conversation, see [`Conversation::effective_budget`]
+    pub fn budget(&self) -> Option<Budget> {
+        self.budget
+    }
+
+    /// The budget of the conversation, or the default one if not set. `None`
+    /// if the conversation is not limited.
+    pub fn effective_budget(&self) -> Option<Budget> {
+        self.budget
+            .or(config::instance().general.default_conversation_budget)
+            .filter(|budget| !budget.is_unlimited())
+    }
+
+    pub fn spending(&self) -> Spending {
+        self.spending
+    }
+
     pub fn id(&self) -> &str {
         &self.id
     }
@@ -142,6 +264,33 @@ impl Conversation {
         self.messages.is_empty()
     }
 
+    /// Whether the conversation is a scratch one, which is never saved
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Whether nothing has been sent in the conversation, it has the
+    /// welcome message at most
+    pub fn is_blank(&self) -> bool {
+        self.messages.iter().all(Message::is_app_message)
+    }
+
+    /// Whether the next prompt is the first one and names the conversation.
+    /// A conversation whose messages are cleared keeps its ID and its title,
+    /// a scratch one is named after its first prompt.
+    pub fn awaits_title(&self) -> bool {
+        self.is_blank() && (self.id.is_empty() || self.ephemeral)
+    }
+
+    /// Remove the messages and the contexts, the welcome message is shown
+    /// again. The ID, the title and the settings are kept.
+    pub fn clear_messages(&mut self) {
+        self.messages.clear();
+        self.contexts.clear();
+        self.messages.extend(Self::new_hello().messages);
+        self.updated_at = Some(chrono::Utc::now());
+    }
+
     pub fn messages_mut(&mut self) -> &mut Vec<Message> {
         &mut self.messages
     }
@@ -154,9 +303,104 @@ impl Conversation {
         &self.contexts
     }
 
+    /// Write the conversation as a Markdown transcript, a heading per
+    /// message followed by its text as is, so the code blocks are kept
+    pub fn to_markdown(&self, options: TranscriptOptions) -> String {
+        let mut sections = vec![];
+        if !self.title.is_empty() {
+            sections.push(format!("# {}", self.title));
+        }
+        if options.context {
+            if let Some(system_prompt) = &self.system_prompt {
+                sections.push(format!("## System prompt\n\n{}", system_prompt));
+            }
+            if let Some(context) = self.contexts.last() {
+                sections.push(format!("## Summary\n\n{}", context.content()));
+            }
+        }
+
+        for message in &self.messages {
+            if message.is_app_message() && !options.context {
+                continue;
+            }
+            if let Some(call) = message.tool_call() {
+                if options.assistant_only || !options.tool_calls {
+                    continue;
+                }
+                let mut section = format!("## Tool: {} ({})\n\n", call.tool, call.provider);
+                if let Some(args) = &call.args {
+                    let args = serde_json::to_string_pretty(args).unwrap_or_default();
+                    section.push_str(&format!("Arguments:\n\n```json\n{}\n```\n\n", args));
+                }
+                let label = if call.is_error { "Error" } else { "Result" };
+                section.push_str(&format!("{}: {}", label, call.result));
+                sections.push(section);
+                continue;
+            }
+            if (options.assistant_only && !message.is_system()) || message.text().trim().is_empty()
+            {
+                continue;
+            }
+
+            let role = if message.is_system() {
+                "Assistant"
+            } else {
+                "User"
+            };
+            sections.push(format!(
+                "## {} ({})\n\n{}",
+                role,
+                message.issuer_str(),
+                message.text().trim_end()
+            ));
+            if options.logprobs && !message.logprobs().is_empty() {
+                sections.push(format_logprobs(message.logprobs()));
+            }
+        }
+        sections.join("\n\n") + "\n"
+    }
+
     /// Return a vector of messages. The return vector is always end up
-    /// with a message from system
+    /// with a message from system. If the conversation has a system prompt,
+    /// it is always the first message of the context.
     pub fn build_context(&self) -> Vec<Message> {
+        self.build_context_with_budget(None)
+    }
+
+    /// Same as [`Conversation::build_context`], but if `max_context_tokens`
+    /// is set, only the latest context summary and as many recent messages
+    /// as fit within the budget are kept. The tokens of the system prompt and
+    /// the latest user message (which is always sent) are reserved first.
+    pub fn build_context_with_budget(&self, max_context_tokens: Option<usize>) -> Vec<Message> {
+        let mut context = vec![];
+        if let Some(system_prompt) = &self.system_prompt {
+            context.push(
+                Message::new_system("system", system_prompt)
+                    .with_id(SYSTEM_PROMPT_ID)
+                    .with_created_at(self.created_at)
+                    .with_context(true),
+            );
+        }
+
+        let messages = self.build_messages_context();
+        let Some(max_context_tokens) = max_context_tokens else {
+            context.extend(messages);
+            return context;
+        };
+
+        let reserved = context
+            .iter()
+            .chain(self.messages.last().filter(|msg| !msg.is_system()))
+            .map(|msg| msg.estimated_token_count())
+            .sum::<usize>();
+        context.extend(fit_context_window(
+            messages,
+            max_context_tokens.saturating_sub(reserved),
+        ));
+        context
+    }
+
+    fn build_messages_context(&self) -> Vec<Message> {
         // If the conversation has less than 3 messages, return an empty vector
         // 1 for hello message and 1 for user message so which means the conversation
         // is not started yet. No context is needed.
@@ -181,6 +425,7 @@ impl Conversation {
             None => context.extend(self.messages[1..].to_vec()),
         }
 
+        let mut context = strip_tool_messages(context);
         if !context.last().unwrap().is_system() {
             context.pop();
         }
@@ -223,10 +468,14 @@ impl Default for Conversation {
         Self {
             id: "".to_string(),
             title: "New Chat".to_string(),
+            system_prompt: None,
+            budget: None,
+            spending: Spending::default(),
             messages: vec![],
             contexts: vec![],
             created_at: chrono::Utc::now(),
             updated_at: None,
+            ephemeral: false,
         }
     }
 }
@@ -310,24 +559,79 @@ impl From<&Context> for Message {
     }
 }
 
+/// Keep the latest context summary and as many of the most recent messages
+/// as fit within `max_tokens`, dropping the oldest messages first.
+fn fit_context_window(messages: Vec<Message>, max_tokens: usize) -> Vec<Message> {
+    let summary = messages.iter().rposition(|msg| msg.is_context());
+    let mut used = summary
+        .map(|i| messages[i].estimated_token_count())
+        .unwrap_or_default();
+
+    // The context summaries always come first, so stop at the first one
+    let mut start = messages.len();
+    for (i, msg) in messages.iter().enumerate().rev() {
+        let tokens = msg.estimated_token_count();
+        if msg.is_context() || used + tokens > max_tokens {
+            break;
+        }
+        used += tokens;
+        start = i;
+    }
+
+    let mut window = summary
+        .map(|i| vec![messages[i].clone()])
+        .unwrap_or_default();
+    window.extend_from_slice(&messages[start..]);
+    window
+}
+
+/// Remove the tool messages from the context. A response interrupted by tool
+/// calls is split around the tool messages, its parts are merged back so the
+/// backends receive a single assistant message.
+fn strip_tool_messages(messages: Vec<Message>) -> Vec<Message> {
+    let mut context: Vec<Message> = vec![];
+    let mut after_tool = false;
+    for msg in messages {
+        if msg.is_tool() {
+            after_tool = true;
+            continue;
+        }
+        match context.last_mut() {
+            Some(last)
+                if after_tool && last.is_system() && !last.is_context() && msg.is_system() =>
+            {
+                last.append(msg.text());
+                last.set_token_count(last.token_count() + msg.token_count());
+            }
+            _ => context.push(msg),
+        }
+        after_tool = false;
+    }
+    context
+}
+
 pub fn filter_issuer(issuer: Option<&Issuer>, msg: &Message) -> bool {
     if issuer.is_none() {
         return true;
     }
 
     let value;
-    let is_system = match issuer.unwrap() {
+    let matched = match issuer.unwrap() {
         Issuer::System(sys) => {
             value = sys.to_string();
-            true
+            msg.is_system()
         }
         Issuer::User(val) => {
             value = val.to_string();
-            false
+            !msg.is_system() && !msg.is_tool()
+        }
+        Issuer::Tool(tool) => {
+            value = tool.to_string();
+            msg.is_tool()
         }
     };
 
-    if is_system != msg.is_system() {
+    if !matched {
         return false;
     }
 
@@ -362,3 +666,19 @@ impl FindMessage for Conversation {
         self.messages.last_message_of_mut(issuer)
     }
 }
+
+/// Write the logprobs in a code block, a line per token followed by the most
+/// likely tokens at its position. The tokens are quoted to show whitespaces.
+fn format_logprobs(logprobs: &[TokenLogprob]) -> String {
+    let lines = logprobs
+        .iter()
+        .map(|logprob| {
+            let mut line = format!("{:?} {:.4}", logprob.token, logprob.logprob);
+            for top in &logprob.top_logprobs {
+                line.push_str(&format!(" | {:?} {:.4}", top.token, top.logprob));
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+    format!("Logprobs:\n\n```\n{}\n```", lines.join("\n"))
+}
@@ -1,4 +1,5 @@
 use crate::config::Configuration;
+use crate::models::{ToolCall, TopLogprob};
 
 use super::*;
 
@@ -58,6 +59,109 @@ fn test_conversation_build_context() {
     assert_eq!(context[2].is_context(), false);
 }
 
+#[test]
+fn test_conversation_build_context_with_tool_calls() {
+    crate::config::init(Configuration::default()).expect("failed to init default config");
+
+    let call = ToolCall {
+        tool: "read_file".to_string(),
+        provider: "fs".to_string(),
+        args: Some(serde_json::json!({"path": "README.md"})),
+        result: "# Chatty".to_string(),
+        is_error: false,
+    };
+    let mut convo = Conversation::new_hello();
+    convo.append_message(Message::new_user("user", "Read the README"));
+    convo.append_message(Message::new_system("system", "Let me read it.\n"));
+
+    let tool_message = Message::new_tool(&call);
+    assert!(tool_message.is_tool());
+    assert!(!tool_message.is_system());
+    assert_eq!(tool_message.issuer_str(), "read_file");
+    assert_eq!(tool_message.tool_call(), Some(call));
+    convo.append_message(tool_message);
+    convo.append_message(Message::new_system("system", "It's about Chatty."));
+    convo.append_message(Message::new_user("user", "Thanks"));
+
+    let context = convo.build_context();
+    assert_eq!(context.len(), 2);
+    assert_eq!(context[0].text(), "Read the README");
+    assert_eq!(context[1].text(), "Let me read it.\nIt's about Chatty.");
+
+    let issuer = Issuer::tool();
+    assert_eq!(
+        convo.last_message_of(Some(issuer)).unwrap().issuer_str(),
+        "read_file"
+    );
+    let msg = convo.last_message_of(Some(Issuer::user())).unwrap();
+    assert_eq!(msg.text(), "Thanks");
+}
+
+#[test]
+fn test_conversation_build_context_with_system_prompt() {
+    crate::config::init(Configuration::default()).expect("failed to init default config");
+
+    let mut convo = Conversation::new_hello().with_system_prompt("You are a pirate");
+    let context = convo.build_context();
+    assert_eq!(context.len(), 1);
+    assert_eq!(context[0].id(), SYSTEM_PROMPT_ID);
+    assert!(context[0].is_context());
+
+    convo.append_message(Message::new_user("user", "Hello, world!"));
+    convo.append_message(Message::new_system("system", "Ahoy!"));
+    convo.append_message(Message::new_user("user", "How are you?"));
+
+    let context = convo.build_context();
+    assert_eq!(context.len(), 3);
+    assert_eq!(context[0].text(), "You are a pirate");
+    assert_eq!(context[1].text(), "Hello, world!");
+    assert_eq!(context[2].text(), "Ahoy!");
+
+    convo.set_system_prompt("  ");
+    assert!(convo.system_prompt().is_none());
+    assert_eq!(convo.build_context().len(), 2);
+}
+
+#[test]
+fn test_conversation_build_context_with_budget() {
+    crate::config::init(Configuration::default()).expect("failed to init default config");
+
+    let mut convo = Conversation::new_hello();
+    convo.append_message(Message::new_user("user", "first").with_token_count(10));
+    convo.append_message(
+        Message::new_system("system", "first reply")
+            .with_id("first-reply")
+            .with_token_count(10),
+    );
+    convo.append_message(Message::new_user("user", "second").with_token_count(10));
+    convo.append_message(Message::new_system("system", "second reply").with_token_count(10));
+    convo.append_message(Message::new_user("user", "third").with_token_count(15));
+
+    // The latest user message is reserved, the rest fits 2 messages
+    let context = convo.build_context_with_budget(Some(40));
+    assert_eq!(context.len(), 2);
+    assert_eq!(context[0].text(), "second");
+    assert_eq!(context[1].text(), "second reply");
+
+    // The latest user message alone is over the budget
+    let context = convo.build_context_with_budget(Some(10));
+    assert!(context.is_empty());
+
+    assert_eq!(convo.build_context_with_budget(None).len(), 4);
+
+    convo.append_context(
+        Context::new("first-reply")
+            .with_content("summary")
+            .with_token_count(5),
+    );
+    // The summary is always kept, the oldest message after it is dropped
+    let context = convo.build_context_with_budget(Some(30));
+    assert_eq!(context.len(), 2);
+    assert_eq!(context[0].text(), "summary");
+    assert!(context[0].is_context());
+    assert_eq!(context[1].text(), "second reply");
+}
+
 #[test]
 pub fn test_conversation_last_message_of() {
     let mut convo = Conversation::new_hello();
@@ -141,3 +245,163 @@ pub fn test_conversation_token_count() {
     let token_count = convo.token_count();
     assert_eq!(token_count, 12);
 }
+
+#[test]
+fn test_budget_used() {
+    let spending = Spending {
+        tokens: 1_000,
+        cost: 0.5,
+    };
+    assert_eq!(Budget::default().used(&spending), None);
+    assert!(Budget::default().is_unlimited());
+
+    let budget = Budget {
+        tokens: Some(4_000),
+        cost: None,
+    };
+    assert_eq!(budget.used(&spending), Some(0.25));
+
+    // The highest fraction of the limits is used
+    let budget = Budget {
+        tokens: Some(4_000),
+        cost: Some(0.625),
+    };
+    assert_eq!(budget.used(&spending), Some(0.8));
+}
+
+#[test]
+fn test_conversation_add_spending() {
+    let mut convo = Conversation::default();
+    convo.add_spending(100, 0.01);
+    convo.add_spending(50, 0.02);
+    assert_eq!(convo.spending().tokens, 150);
+    assert!((convo.spending().cost - 0.03).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_to_markdown() {
+    let call = ToolCall {
+        tool: "search".to_string(),
+        provider: "web".to_string(),
+        args: None,
+        result: "3 results".to_string(),
+        is_error: false,
+    };
+    let mut convo = Conversation::default()
+        .with_title("Rust")
+        .with_system_prompt("Be brief");
+    convo.append_message(Message::new_system("system", "Hello!"));
+    convo.append_message(Message::new_user("user", "Show a loop"));
+    convo.append_message(Message::new_tool(&call));
+    convo.append_message(Message::new_system("gpt-4o", "```rust\nloop {}\n```\n"));
+
+    assert_eq!(
+        convo.to_markdown(TranscriptOptions::default()),
+        "# Rust\n\n## User (user)\n\nShow a loop\n\n## Assistant (gpt-4o)\n\n```rust\nloop {}\n```\n"
+    );
+
+    let options = TranscriptOptions {
+        assistant_only: true,
+        tool_calls: true,
+        context: false,
+        logprobs: false,
+    };
+    assert_eq!(
+        convo.to_markdown(options),
+        "# Rust\n\n## Assistant (gpt-4o)\n\n```rust\nloop {}\n```\n"
+    );
+
+    let options = TranscriptOptions {
+        assistant_only: false,
+        tool_calls: true,
+        context: true,
+        logprobs: false,
+    };
+    let markdown = convo.to_markdown(options);
+    assert!(markdown.contains("## System prompt\n\nBe brief"));
+    assert!(markdown.contains("## Assistant (system)\n\nHello!"));
+    assert!(markdown.contains("## Tool: search (web)\n\nResult: 3 results"));
+}
+
+#[test]
+fn test_to_markdown_logprobs() {
+    let mut convo = Conversation::default().with_title("Greeting");
+    convo.append_message(Message::new_user("user", "Hi"));
+    let mut message = Message::new_system("gpt-4o", "Hello!");
+    message.append_logprobs(&[
+        TokenLogprob {
+            token: "Hello".to_string(),
+            logprob: -0.01,
+            top_logprobs: vec![TopLogprob {
+                token: "Hi".to_string(),
+                logprob: -4.5,
+            }],
+        },
+        TokenLogprob {
+            token: "!".to_string(),
+            logprob: 0.0,
+            top_logprobs: vec![],
+        },
+    ]);
+    convo.append_message(message);
+
+    let options = TranscriptOptions {
+        assistant_only: true,
+        logprobs: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        convo.to_markdown(options),
+        "# Greeting\n\n## Assistant (gpt-4o)\n\nHello!\n\nLogprobs:\n\n```\n\"Hello\" -0.0100 | \"Hi\" -4.5000\n\"!\" 0.0000\n```\n"
+    );
+
+    // Not written unless asked
+    assert!(
+        !convo
+            .to_markdown(TranscriptOptions::default())
+            .contains("Logprobs")
+    );
+}
+
+#[test]
+fn test_is_blank() {
+    let mut convo = Conversation::default();
+    assert!(convo.is_blank());
+
+    convo.append_message(Message::new_system("system", "Hello!"));
+    assert!(convo.is_blank());
+
+    convo.append_message(Message::new_user("user", "Hi"));
+    assert!(!convo.is_blank());
+
+    let convo = Conversation::default().with_messages(vec![Message::new_user("user", "Hi")]);
+    assert!(!convo.is_blank());
+}
+
+#[test]
+fn test_clear_messages() {
+    let mut convo = Conversation::new_hello();
+    assert!(convo.awaits_title());
+
+    convo.append_message(Message::new_user("user", "Hi"));
+    convo.set_id("convo1");
+    convo.set_title("Greetings");
+    convo.append_context(Context::new("msg1").with_content("summary"));
+    assert!(!convo.awaits_title());
+
+    convo.clear_messages();
+    assert!(convo.is_blank());
+    assert!(convo.contexts().is_empty());
+    assert_eq!(convo.title(), "Greetings");
+    // The next prompt doesn't rename the conversation
+    assert!(!convo.awaits_title());
+    convo.append_message(Message::new_user("user", "Hello again"));
+    assert_eq!(convo.title(), "Greetings");
+
+    // A scratch conversation is named after its first prompt
+    let scratch = Conversation::new_hello()
+        .with_id("convo2")
+        .with_title("Scratch")
+        .with_ephemeral(true);
+    assert!(scratch.awaits_title());
+}
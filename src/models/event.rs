@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tui_textarea::Input;
 
 use super::Conversation;
@@ -11,11 +11,26 @@ pub enum Event {
 
     BackendAbort,
     BackendMessage(crate::models::Message),
+    /// The backend couldn't be reached, the prompt can be sent again later
+    BackendUnreachable(String),
     ChatCompletionResponse(crate::models::BackendResponse),
 
     SetConversation(Option<Conversation>),
     ConversationDeleted(String),
+    ConversationRestored(Conversation),
     ConversationUpdated(Conversation),
+    ConversationTitleUpdated(String, String), // Conversation ID, Title
+    SetSystemPrompt(String),
+    EditMessage(String, String),         // Message ID, Text
+    CompressionStarted(String),          // Conversation ID
+    CompressionFinished(String),         // Conversation ID
+    CompressionProgress(String, String), // Conversation ID, Status
+    ModelsUpdated(Vec<crate::models::Model>),
+    SemanticSearchResults(String, Vec<String>), // Query, Conversation IDs
+    MessageEdits(String, Vec<crate::models::storage::MessageEdit>), // Message ID, Edits
+
+    ToolApprovalRequest(ToolApproval),
+    ToolCalled(crate::models::ToolCall),
 
     KeyboardCharInput(Input),
     KeyboardEsc,
@@ -27,8 +42,21 @@ pub enum Event {
     KeyboardCtrlE,
     KeyboardCtrlL,
     KeyboardCtrlH,
+    KeyboardCtrlP,
+    KeyboardCtrlF,
+    KeyboardCtrlT,
+    KeyboardCtrlO,
+    KeyboardCtrlG,
+    KeyboardCtrlY,
+    KeyboardCtrlK,
+    KeyboardAltY,
+    KeyboardUp,
+    KeyboardDown,
     KeyboardF1,
+    KeyboardF2,
     KeyboardPaste(String),
+    KeyboardPasteStart,
+    KeyboardPasteEnd,
 
     Quit,
 
@@ -54,6 +82,38 @@ pub enum Event {
     },
 }
 
+/// A tool call waiting for the user's approval. The decision is sent back to
+/// the backend task which requested it.
+#[derive(Debug)]
+pub struct ToolApproval {
+    pub tool: String,
+    pub provider: String,
+    pub args: Option<serde_json::Value>,
+    responder: oneshot::Sender<bool>,
+}
+
+impl ToolApproval {
+    pub fn new(
+        tool: impl Into<String>,
+        provider: impl Into<String>,
+        args: Option<serde_json::Value>,
+    ) -> (Self, oneshot::Receiver<bool>) {
+        let (responder, rx) = oneshot::channel();
+        let approval = Self {
+            tool: tool.into(),
+            provider: provider.into(),
+            args,
+            responder,
+        };
+        (approval, rx)
+    }
+
+    pub fn respond(self, approved: bool) {
+        // The backend task may have been aborted in the meantime
+        let _ = self.responder.send(approved);
+    }
+}
+
 #[macro_export]
 macro_rules! info_event {
     ($($arg:tt)*) => {
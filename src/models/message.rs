@@ -1,7 +1,51 @@
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, Clone)]
 pub enum Issuer {
     System(String),
     User(String),
+    /// A tool called by the backend, the value is the tool name
+    Tool(String),
+}
+
+/// The record of a tool call made by the backend on behalf of the model. It
+/// is stored as the text of a tool message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub tool: String,
+    pub provider: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<serde_json::Value>,
+    /// The summary of the returned result
+    #[serde(default)]
+    pub result: String,
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+/// The log probability of a token of a response, with the most likely
+/// tokens at its position if requested
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f64,
+}
+
+/// The severity of a message of the app, an error is rendered apart from
+/// the informative messages such as "Aborted!"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Severity {
+    #[default]
+    Info,
+    Error,
 }
 
 #[derive(Debug, Clone)]
@@ -13,6 +57,13 @@ pub struct Message {
     created_at: chrono::DateTime<chrono::Utc>,
     /// Indicates if the message is part of a context
     context: bool,
+    /// Indicates if the response was interrupted before it completed
+    incomplete: bool,
+    /// Indicates if the model declined to answer
+    refusal: bool,
+    severity: Severity,
+    /// The token logprobs of the response if requested, they are not stored
+    logprobs: Vec<TokenLogprob>,
 }
 
 impl Message {
@@ -24,6 +75,10 @@ impl Message {
             token_count: 0,
             created_at: chrono::Utc::now(),
             context: false,
+            incomplete: false,
+            refusal: false,
+            severity: Severity::default(),
+            logprobs: vec![],
         }
     }
 
@@ -35,6 +90,15 @@ impl Message {
         Self::new(Issuer::User(user.to_string()), text)
     }
 
+    /// Create a tool message from the tool call. Tool messages are only
+    /// displayed, they are never sent to the backends.
+    pub fn new_tool(call: &ToolCall) -> Self {
+        Self::new(
+            Issuer::Tool(call.tool.clone()),
+            serde_json::to_string(call).unwrap_or_default(),
+        )
+    }
+
     pub fn with_context(mut self, context: bool) -> Self {
         if matches!(self.issuer, Issuer::System(_)) {
             self.context = context;
@@ -50,6 +114,56 @@ impl Message {
         self.context
     }
 
+    pub fn with_incomplete(mut self, incomplete: bool) -> Self {
+        self.set_incomplete(incomplete);
+        self
+    }
+
+    pub fn set_incomplete(&mut self, incomplete: bool) {
+        self.incomplete = incomplete;
+    }
+
+    /// Indicates if the response was saved while streaming and never
+    /// completed, e.g. the request was aborted or the app exited
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    pub fn set_refusal(&mut self, refusal: bool) {
+        self.refusal = refusal;
+    }
+
+    pub fn with_refusal(mut self, refusal: bool) -> Self {
+        self.set_refusal(refusal);
+        self
+    }
+
+    /// Indicates if the text is the reason the model declined to answer
+    pub fn is_refusal(&self) -> bool {
+        self.refusal
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn append_logprobs(&mut self, logprobs: &[TokenLogprob]) {
+        self.logprobs.extend_from_slice(logprobs);
+    }
+
+    pub fn logprobs(&self) -> &[TokenLogprob] {
+        &self.logprobs
+    }
+
     pub fn with_id(mut self, id: impl Into<String>) -> Self {
         self.id = id.into();
         self
@@ -65,6 +179,10 @@ impl Message {
         self
     }
 
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+    }
+
     pub fn with_token_count(mut self, token_count: usize) -> Self {
         self.set_token_count(token_count);
         self
@@ -78,6 +196,15 @@ impl Message {
         self.token_count
     }
 
+    /// Return the token count reported by the backend, or an estimation
+    /// based on the text if the message has not been counted yet
+    pub fn estimated_token_count(&self) -> usize {
+        if self.token_count > 0 {
+            return self.token_count;
+        }
+        estimate_tokens(&self.text)
+    }
+
     pub fn id(&self) -> &str {
         &self.id
     }
@@ -86,6 +213,24 @@ impl Message {
         matches!(self.issuer, Issuer::System(_))
     }
 
+    pub fn is_tool(&self) -> bool {
+        matches!(self.issuer, Issuer::Tool(_))
+    }
+
+    /// Whether it is a message of the app, e.g. the welcome message or an
+    /// error, rather than of the assistant
+    pub fn is_app_message(&self) -> bool {
+        self.is_system() && self.issuer_str() == "system"
+    }
+
+    /// Return the tool call recorded in a tool message
+    pub fn tool_call(&self) -> Option<ToolCall> {
+        if !self.is_tool() {
+            return None;
+        }
+        serde_json::from_str(&self.text).ok()
+    }
+
     pub fn issuer(&self) -> &Issuer {
         &self.issuer
     }
@@ -102,6 +247,7 @@ impl Message {
         match &self.issuer {
             Issuer::System(s) => s,
             Issuer::User(u) => u,
+            Issuer::Tool(t) => t,
         }
     }
 
@@ -111,6 +257,12 @@ impl Message {
     }
 }
 
+/// Roughly estimate the number of tokens of a text, assuming a token is
+/// about 4 characters
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
 impl Issuer {
     pub fn user() -> Self {
         Self::User("".to_string())
@@ -127,4 +279,8 @@ impl Issuer {
     pub fn system_with_name(name: impl Into<String>) -> Self {
         Self::System(name.into())
     }
+
+    pub fn tool() -> Self {
+        Self::Tool("".to_string())
+    }
 }
@@ -8,9 +8,9 @@ pub mod storage;
 pub mod task;
 
 pub use backend::*;
-pub use conversation::{Context, Conversation};
-pub use message::Message;
+pub use conversation::{Budget, Context, Conversation, Spending, TranscriptOptions};
+pub use message::{Message, Severity, TokenLogprob, ToolCall, TopLogprob};
 pub use notice::*;
 
 pub use action::*;
-pub use event::{ArcEventTx, Event, EventTx};
+pub use event::{ArcEventTx, Event, EventTx, ToolApproval};
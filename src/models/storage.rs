@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use crate::models::Conversation;
 
 #[derive(Debug, Clone, Default)]
@@ -125,3 +127,73 @@ impl FilterConversation {
         true
     }
 }
+
+/// The embedding of a conversation computed by the model for the semantic
+/// search, as of the last update of the conversation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Embedding {
+    pub model: String,
+    pub vector: Vec<f32>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A previous text of an edited message, replaced at `edited_at`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageEdit {
+    pub text: String,
+    pub edited_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The usage statistics of the stored conversations, the deleted ones are
+/// left out
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub conversations: usize,
+    /// The messages of the users, the models and the tools
+    pub messages: usize,
+    /// The tokens of the messages by role: `user`, `assistant` and `tool`
+    pub tokens_by_role: Vec<(String, usize)>,
+    /// The day with the most messages and the number of its messages
+    pub busiest_day: Option<(chrono::NaiveDate, usize)>,
+    /// The model with the most responses and the number of its responses
+    pub top_model: Option<(String, usize)>,
+}
+
+impl Stats {
+    pub fn avg_messages_per_conversation(&self) -> f64 {
+        if self.conversations == 0 {
+            return 0.0;
+        }
+        self.messages as f64 / self.conversations as f64
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<24}{}", "Conversations", self.conversations)?;
+        writeln!(f, "{:<24}{}", "Messages", self.messages)?;
+        writeln!(
+            f,
+            "{:<24}{:.1}",
+            "Messages/conversation",
+            self.avg_messages_per_conversation()
+        )?;
+        for (role, tokens) in &self.tokens_by_role {
+            writeln!(f, "{:<24}{}", format!("Tokens ({})", role), tokens)?;
+        }
+        match &self.busiest_day {
+            Some((day, messages)) => {
+                writeln!(f, "{:<24}{} ({} messages)", "Busiest day", day, messages)?
+            }
+            None => writeln!(f, "{:<24}-", "Busiest day")?,
+        }
+        match &self.top_model {
+            Some((model, responses)) => writeln!(
+                f,
+                "{:<24}{} ({} responses)",
+                "Most used model", model, responses
+            ),
+            None => writeln!(f, "{:<24}-", "Most used model"),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(test)]
+#[path = "oneshot_test.rs"]
+mod tests;
+
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::sync::Arc;
+
+use eyre::{Context, Result};
+use tokio::sync::mpsc;
+
+use crate::{
+    backend::ArcBackend,
+    models::{ArcEventTx, BackendPrompt, Event, NoticeKind},
+};
+
+/// Whether the response is flushed after every chunk: when printed to a
+/// terminal, or forced by `--flush` to follow it through a pipe. Otherwise
+/// the output is buffered, e.g. when redirected to a file.
+pub fn should_flush(force: bool) -> bool {
+    force || io::stdout().is_terminal()
+}
+
+/// Print the response of the prompt to the standard output, the notices of
+/// the backend go to the standard error
+pub async fn run(backend: ArcBackend, prompt: BackendPrompt, flush: bool) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let event_tx: ArcEventTx = Arc::new(tx);
+    let completion = tokio::spawn(async move { backend.get_completion(prompt, event_tx).await });
+
+    let mut writer = CompletionWriter::new(io::stdout(), flush);
+    while let Some(event) = rx.recv().await {
+        match event {
+            Event::ChatCompletionResponse(resp) => {
+                if !writer.write(&resp.text).wrap_err("writing response")? {
+                    // The reader is gone, e.g. `head` got enough lines
+                    completion.abort();
+                    return Ok(());
+                }
+                if resp.done {
+                    break;
+                }
+            }
+            Event::Notice(notice) if !matches!(notice.kind(), NoticeKind::Info) => {
+                eprintln!("{}", notice.message());
+            }
+            Event::ToolApprovalRequest(approval) => {
+                // Dropping the request denies the call
+                eprintln!(
+                    "The tool \"{}\" is denied, the tools can't be approved with --prompt",
+                    approval.tool
+                );
+            }
+            _ => {}
+        }
+    }
+    writer.finish().wrap_err("writing response")?;
+
+    completion.await.wrap_err("waiting for the response")?
+}
+
+/// Write the chunks of a streamed response, flushed one by one or buffered
+pub struct CompletionWriter<W: Write> {
+    out: BufWriter<W>,
+    flush: bool,
+    // Whether anything is written and it ends with a new line
+    written: bool,
+    ends_with_newline: bool,
+}
+
+impl<W: Write> CompletionWriter<W> {
+    pub fn new(out: W, flush: bool) -> Self {
+        Self {
+            out: BufWriter::new(out),
+            flush,
+            written: false,
+            ends_with_newline: false,
+        }
+    }
+
+    /// Write a chunk of the response. `false` is returned once the reader is
+    /// closed, the rest of the response is not wanted.
+    pub fn write(&mut self, text: &str) -> io::Result<bool> {
+        if text.is_empty() {
+            return Ok(true);
+        }
+        self.written = true;
+        self.ends_with_newline = text.ends_with('\n');
+
+        let result = self
+            .out
+            .write_all(text.as_bytes())
+            .and_then(|_| if self.flush { self.out.flush() } else { Ok(()) });
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// End the response with a new line and flush what is buffered
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.written && !self.ends_with_newline {
+            self.out.write_all(b"\n")?;
+        }
+        match self.out.flush() {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::*;
+
+/// An output recording what reaches it and how many times it is flushed
+#[derive(Default, Clone)]
+struct Output {
+    data: Rc<RefCell<Vec<u8>>>,
+    flushes: Rc<RefCell<usize>>,
+    closed: bool,
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        self.data.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        *self.flushes.borrow_mut() += 1;
+        Ok(())
+    }
+}
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8(self.data.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn test_completion_writer() {
+    // Every chunk reaches the output right away
+    let output = Output::default();
+    let mut writer = CompletionWriter::new(output.clone(), true);
+    assert!(writer.write("Hello").unwrap());
+    assert_eq!(output.text(), "Hello");
+    assert!(writer.write(" world").unwrap());
+    assert_eq!(output.text(), "Hello world");
+    writer.finish().unwrap();
+    assert_eq!(output.text(), "Hello world\n");
+
+    // Buffered until the end
+    let output = Output::default();
+    let mut writer = CompletionWriter::new(output.clone(), false);
+    assert!(writer.write("Hello\n").unwrap());
+    assert!(writer.write("world\n").unwrap());
+    assert_eq!(output.text(), "");
+    assert_eq!(*output.flushes.borrow(), 0);
+    writer.finish().unwrap();
+    assert_eq!(output.text(), "Hello\nworld\n");
+
+    // Nothing is added to an empty response
+    let output = Output::default();
+    CompletionWriter::new(output.clone(), true)
+        .finish()
+        .unwrap();
+    assert_eq!(output.text(), "");
+}
+
+#[test]
+fn test_completion_writer_closed() {
+    let output = Output {
+        closed: true,
+        ..Default::default()
+    };
+    let mut writer = CompletionWriter::new(output, true);
+    assert!(!writer.write("Hello").unwrap());
+    writer.finish().unwrap();
+}
@@ -0,0 +1,226 @@
+#[cfg(test)]
+#[path = "archive_test.rs"]
+mod tests;
+
+use chrono::{DateTime, Utc};
+use eyre::{Context as _, ContextCompat, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    Budget, Context, Conversation, Message, message::Issuer, storage::FilterConversation,
+};
+
+use super::ArcStorage;
+
+/// The current version of the archive schema. Bump it whenever the schema
+/// changes and add a migration step to `Archive::migrate`.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// A portable dump of the conversations, including their messages and
+/// contexts. Timestamps are stored as milliseconds since the Unix epoch.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub exported_at: i64,
+    pub conversations: Vec<ArchiveConversation>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveConversation {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub budget: Option<Budget>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(default)]
+    pub messages: Vec<ArchiveMessage>,
+    #[serde(default)]
+    pub contexts: Vec<ArchiveContext>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveRole {
+    User,
+    System,
+    Tool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveMessage {
+    pub id: String,
+    pub role: ArchiveRole,
+    pub issuer: String,
+    pub text: String,
+    #[serde(default)]
+    pub token_count: usize,
+    #[serde(default)]
+    pub context: bool,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveContext {
+    pub id: String,
+    pub last_message_id: String,
+    pub content: String,
+    #[serde(default)]
+    pub token_count: usize,
+    pub created_at: i64,
+}
+
+impl Archive {
+    pub fn new(conversations: &[Conversation]) -> Self {
+        Self {
+            version: ARCHIVE_VERSION,
+            exported_at: Utc::now().timestamp_millis(),
+            conversations: conversations
+                .iter()
+                .map(ArchiveConversation::from)
+                .collect(),
+        }
+    }
+
+    /// Parse an archive, migrating it to the current schema if it was written
+    /// by an older version
+    pub fn parse(data: &str) -> Result<Self> {
+        let value = serde_json::from_str::<serde_json::Value>(data).wrap_err("parsing archive")?;
+        let version = value
+            .get("version")
+            .and_then(|v| v.as_u64())
+            .wrap_err("archive version is missing")? as u32;
+        let value = Self::migrate(value, version)?;
+        serde_json::from_value(value).wrap_err("parsing archive")
+    }
+
+    fn migrate(value: serde_json::Value, version: u32) -> Result<serde_json::Value> {
+        match version {
+            ARCHIVE_VERSION => Ok(value),
+            _ => bail!(
+                "unsupported archive version {}, expected {}",
+                version,
+                ARCHIVE_VERSION
+            ),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).wrap_err("serializing archive")
+    }
+
+    pub fn into_conversations(self) -> Result<Vec<Conversation>> {
+        self.conversations
+            .into_iter()
+            .map(Conversation::try_from)
+            .collect()
+    }
+}
+
+/// Read all conversations with their messages and contexts from the storage
+pub async fn export_archive(storage: &ArcStorage) -> Result<Archive> {
+    let mut conversations = storage
+        .get_conversations(FilterConversation::default())
+        .await
+        .wrap_err("getting conversations")?
+        .into_values()
+        .filter(|convo| !convo.id().is_empty())
+        .collect::<Vec<_>>();
+    conversations.sort_by_key(|convo| convo.created_at());
+    Ok(Archive::new(&conversations))
+}
+
+impl From<&Conversation> for ArchiveConversation {
+    fn from(convo: &Conversation) -> Self {
+        Self {
+            id: convo.id().to_string(),
+            title: convo.title().to_string(),
+            system_prompt: convo.system_prompt().map(|s| s.to_string()),
+            budget: convo.budget(),
+            created_at: convo.created_at().timestamp_millis(),
+            updated_at: convo.updated_at().timestamp_millis(),
+            messages: convo
+                .messages()
+                .iter()
+                .map(|msg| ArchiveMessage {
+                    id: msg.id().to_string(),
+                    role: match msg.issuer() {
+                        Issuer::System(_) => ArchiveRole::System,
+                        Issuer::User(_) => ArchiveRole::User,
+                        Issuer::Tool(_) => ArchiveRole::Tool,
+                    },
+                    issuer: msg.issuer_str().to_string(),
+                    text: msg.text().to_string(),
+                    token_count: msg.token_count(),
+                    context: msg.is_context(),
+                    created_at: msg.created_at().timestamp_millis(),
+                })
+                .collect(),
+            contexts: convo
+                .contexts()
+                .iter()
+                .map(|ctx| ArchiveContext {
+                    id: ctx.id().to_string(),
+                    last_message_id: ctx.last_message_id().to_string(),
+                    content: ctx.content().to_string(),
+                    token_count: ctx.token_count(),
+                    created_at: ctx.created_at().timestamp_millis(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<ArchiveConversation> for Conversation {
+    type Error = eyre::Report;
+
+    fn try_from(convo: ArchiveConversation) -> Result<Self> {
+        let messages = convo
+            .messages
+            .into_iter()
+            .map(|msg| {
+                let issuer = match msg.role {
+                    ArchiveRole::User => Issuer::user_with_name(msg.issuer),
+                    ArchiveRole::System => Issuer::system_with_name(msg.issuer),
+                    ArchiveRole::Tool => Issuer::Tool(msg.issuer),
+                };
+                Ok(Message::new(issuer, msg.text)
+                    .with_id(msg.id)
+                    .with_token_count(msg.token_count)
+                    .with_context(msg.context)
+                    .with_created_at(to_datetime(msg.created_at)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let contexts = convo
+            .contexts
+            .into_iter()
+            .map(|ctx| {
+                Ok(Context::new(&ctx.last_message_id)
+                    .with_id(ctx.id)
+                    .with_content(ctx.content)
+                    .with_token_count(ctx.token_count)
+                    .with_created_at(to_datetime(ctx.created_at)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut conversation = Conversation::default()
+            .with_id(convo.id)
+            .with_title(convo.title)
+            .with_created_at(to_datetime(convo.created_at)?)
+            .with_updated_at(to_datetime(convo.updated_at)?)
+            .with_budget(convo.budget)
+            .with_messages(messages)
+            .with_context(contexts);
+        if let Some(system_prompt) = convo.system_prompt {
+            conversation.set_system_prompt(system_prompt);
+        }
+        Ok(conversation)
+    }
+}
+
+fn to_datetime(millis: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(millis).wrap_err(format!("invalid timestamp {}", millis))
+}
@@ -0,0 +1,76 @@
+use super::*;
+
+#[test]
+fn test_archive_roundtrip() {
+    let created_at = DateTime::from_timestamp_millis(1700000000000).unwrap();
+    let messages = vec![
+        Message::new_system("system", "Hello")
+            .with_id("m1")
+            .with_created_at(created_at),
+        Message::new_user("user", "Hi there")
+            .with_id("m2")
+            .with_created_at(created_at + chrono::Duration::seconds(1)),
+        Message::new_system("gpt-4o", "How can I help?")
+            .with_id("m3")
+            .with_token_count(5)
+            .with_created_at(created_at + chrono::Duration::seconds(2)),
+    ];
+    let convo = Conversation::default()
+        .with_id("convo-1")
+        .with_title("Greetings")
+        .with_system_prompt("Be nice")
+        .with_created_at(created_at)
+        .with_updated_at(created_at + chrono::Duration::seconds(2))
+        .with_messages(messages)
+        .with_context(vec![
+            Context::new("m2")
+                .with_id("ctx-1")
+                .with_content("A greeting")
+                .with_token_count(3)
+                .with_created_at(created_at),
+        ]);
+
+    let data = Archive::new(&[convo])
+        .to_json()
+        .expect("Failed to serialize");
+    let archive = Archive::parse(&data).expect("Failed to parse archive");
+    assert_eq!(archive.version, ARCHIVE_VERSION);
+
+    let conversations = archive
+        .into_conversations()
+        .expect("Failed to convert archive");
+    assert_eq!(conversations.len(), 1);
+
+    let convo = &conversations[0];
+    assert_eq!(convo.id(), "convo-1");
+    assert_eq!(convo.title(), "Greetings");
+    assert_eq!(convo.system_prompt(), Some("Be nice"));
+    assert_eq!(convo.created_at(), created_at);
+    assert_eq!(
+        convo.updated_at(),
+        created_at + chrono::Duration::seconds(2)
+    );
+
+    let messages = convo.messages();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[1].id(), "m2");
+    assert!(!messages[1].is_system());
+    assert_eq!(messages[1].issuer_str(), "user");
+    assert_eq!(messages[2].issuer_str(), "gpt-4o");
+    assert_eq!(messages[2].token_count(), 5);
+
+    let contexts = convo.contexts();
+    assert_eq!(contexts.len(), 1);
+    assert_eq!(contexts[0].id(), "ctx-1");
+    assert_eq!(contexts[0].last_message_id(), "m2");
+    assert_eq!(contexts[0].content(), "A greeting");
+}
+
+#[test]
+fn test_archive_unsupported_version() {
+    let err = Archive::parse(r#"{"version": 99, "exported_at": 0, "conversations": []}"#)
+        .expect_err("Expected an error");
+    assert!(err.to_string().contains("unsupported archive version 99"));
+
+    assert!(Archive::parse(r#"{"conversations": []}"#).is_err());
+}
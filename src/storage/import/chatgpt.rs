@@ -0,0 +1,228 @@
+#[cfg(test)]
+#[path = "chatgpt_test.rs"]
+mod tests;
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    config,
+    models::{Conversation, Message},
+};
+
+/// The issuer of the assistant messages when the export doesn't carry the
+/// model name
+const DEFAULT_ASSISTANT: &str = "chatgpt";
+
+#[derive(Debug, Deserialize)]
+struct ExportConversation {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    update_time: Option<f64>,
+    #[serde(default)]
+    current_node: Option<String>,
+    #[serde(default)]
+    mapping: HashMap<String, ExportNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportNode {
+    #[serde(default)]
+    message: Option<ExportMessage>,
+    #[serde(default)]
+    parent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportMessage {
+    id: String,
+    author: ExportAuthor,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    content: Option<ExportContent>,
+    #[serde(default)]
+    metadata: ExportMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportAuthor {
+    role: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportContent {
+    #[serde(default)]
+    parts: Vec<Value>,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ExportMetadata {
+    #[serde(default)]
+    model_slug: Option<String>,
+    #[serde(default)]
+    is_visually_hidden_from_conversation: Option<bool>,
+}
+
+/// Parse the `conversations.json` file of a ChatGPT data export. Only the
+/// branch leading to the current node of each conversation is imported. The
+/// `user` and `assistant` messages are mapped to user and system messages,
+/// the `system` message becomes the conversation system prompt and the tool
+/// messages are skipped.
+pub fn parse(data: &str) -> Result<Vec<Conversation>> {
+    let export =
+        serde_json::from_str::<Vec<ExportConversation>>(data).wrap_err("parsing ChatGPT export")?;
+    Ok(export
+        .into_iter()
+        .filter_map(|convo| convo.into_conversation())
+        .collect())
+}
+
+impl ExportConversation {
+    fn into_conversation(mut self) -> Option<Conversation> {
+        let id = self.id.take().or(self.conversation_id.take())?;
+        let created_at = self.create_time.and_then(to_datetime).unwrap_or_default();
+
+        let mut messages = config::instance()
+            .general
+            .welcome_message()
+            .map(|hello| Message::new_system("system", hello).with_created_at(created_at))
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut system_prompt = None;
+        let mut last_created_at = created_at;
+
+        for msg in self.current_branch() {
+            if msg.metadata.is_visually_hidden_from_conversation == Some(true) {
+                continue;
+            }
+            let text = msg.text();
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            // Keep the messages ordered when the timestamp is missing
+            last_created_at = msg
+                .create_time
+                .and_then(to_datetime)
+                .unwrap_or(last_created_at)
+                .max(last_created_at);
+
+            let message = match msg.author.role.as_str() {
+                "user" => Message::new_user("user", text),
+                "assistant" => Message::new_system(
+                    msg.metadata
+                        .model_slug
+                        .as_deref()
+                        .unwrap_or(DEFAULT_ASSISTANT),
+                    text,
+                ),
+                "system" => {
+                    system_prompt = Some(text);
+                    continue;
+                }
+                _ => continue,
+            };
+            messages.push(message.with_id(&msg.id).with_created_at(last_created_at));
+        }
+
+        // Nothing but the hello message
+        if messages.iter().all(Message::is_app_message) {
+            return None;
+        }
+
+        let mut convo = Conversation::default()
+            .with_id(id)
+            .with_title(
+                self.title
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or("Imported Chat"),
+            )
+            .with_created_at(created_at)
+            .with_updated_at(
+                self.update_time
+                    .and_then(to_datetime)
+                    .unwrap_or(last_created_at),
+            )
+            .with_messages(messages);
+        if let Some(system_prompt) = system_prompt {
+            convo.set_system_prompt(system_prompt);
+        }
+        Some(convo)
+    }
+
+    /// Walk from the current node up to the root and return the messages in
+    /// chronological order
+    fn current_branch(&self) -> Vec<&ExportMessage> {
+        let mut messages = vec![];
+        let mut node_id = self.current_node.as_deref().or_else(|| self.last_leaf());
+        while let Some(node) = node_id.and_then(|id| self.mapping.get(id)) {
+            if let Some(msg) = &node.message {
+                messages.push(msg);
+            }
+            node_id = node.parent.as_deref();
+            // Guard against malformed exports with cycles
+            if messages.len() > self.mapping.len() {
+                break;
+            }
+        }
+        messages.reverse();
+        messages
+    }
+
+    /// Find the latest node without children, used when the export doesn't
+    /// have the current node
+    fn last_leaf(&self) -> Option<&str> {
+        let parents = self
+            .mapping
+            .values()
+            .filter_map(|node| node.parent.as_deref())
+            .collect::<Vec<_>>();
+        self.mapping
+            .iter()
+            .filter(|(id, _)| !parents.contains(&id.as_str()))
+            .max_by(|(_, a), (_, b)| {
+                let a = a.message.as_ref().and_then(|m| m.create_time);
+                let b = b.message.as_ref().and_then(|m| m.create_time);
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+impl ExportMessage {
+    fn text(&self) -> String {
+        let Some(content) = &self.content else {
+            return String::new();
+        };
+        if let Some(text) = &content.text {
+            return text.clone();
+        }
+        // Non-text parts (e.g. images) are skipped
+        content
+            .parts
+            .iter()
+            .filter_map(|part| part.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn to_datetime(secs: f64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_millis((secs * 1000.0) as i64)
+}
@@ -0,0 +1,104 @@
+use super::*;
+
+const EXPORT: &str = r#"[
+  {
+    "title": "Rust lifetimes",
+    "create_time": 1700000000.5,
+    "update_time": 1700000100.0,
+    "current_node": "a2",
+    "conversation_id": "convo-1",
+    "mapping": {
+      "root": {"id": "root", "message": null, "parent": null, "children": ["s1"]},
+      "s1": {
+        "id": "s1",
+        "message": {
+          "id": "s1",
+          "author": {"role": "system"},
+          "create_time": null,
+          "content": {"content_type": "text", "parts": ["You are a helpful assistant"]},
+          "metadata": {}
+        },
+        "parent": "root",
+        "children": ["u1"]
+      },
+      "u1": {
+        "id": "u1",
+        "message": {
+          "id": "u1",
+          "author": {"role": "user"},
+          "create_time": 1700000010.0,
+          "content": {"content_type": "text", "parts": ["What is a lifetime?"]},
+          "metadata": {}
+        },
+        "parent": "s1",
+        "children": ["a1", "a2"]
+      },
+      "a1": {
+        "id": "a1",
+        "message": {
+          "id": "a1",
+          "author": {"role": "assistant"},
+          "create_time": 1700000020.0,
+          "content": {"content_type": "text", "parts": ["A discarded answer"]},
+          "metadata": {"model_slug": "gpt-4"}
+        },
+        "parent": "u1",
+        "children": []
+      },
+      "a2": {
+        "id": "a2",
+        "message": {
+          "id": "a2",
+          "author": {"role": "assistant"},
+          "create_time": 1700000030.0,
+          "content": {"content_type": "text", "parts": ["A lifetime is", " a scope"]},
+          "metadata": {"model_slug": "gpt-4o"}
+        },
+        "parent": "u1",
+        "children": []
+      }
+    }
+  },
+  {
+    "title": "Empty",
+    "create_time": 1700000000.0,
+    "conversation_id": "convo-2",
+    "current_node": "root",
+    "mapping": {
+      "root": {"id": "root", "message": null, "parent": null, "children": []}
+    }
+  }
+]"#;
+
+#[test]
+fn test_parse() {
+    let conversations = parse(EXPORT).expect("Failed to parse export");
+    assert_eq!(conversations.len(), 1);
+
+    let convo = &conversations[0];
+    assert_eq!(convo.id(), "convo-1");
+    assert_eq!(convo.title(), "Rust lifetimes");
+    assert_eq!(convo.system_prompt(), Some("You are a helpful assistant"));
+    assert_eq!(convo.created_at().timestamp_millis(), 1700000000500);
+    assert_eq!(convo.updated_at().timestamp(), 1700000100);
+
+    let messages = convo.messages();
+    assert_eq!(messages.len(), 3);
+    assert!(messages[0].is_system());
+
+    assert_eq!(messages[1].id(), "u1");
+    assert!(!messages[1].is_system());
+    assert_eq!(messages[1].text(), "What is a lifetime?");
+    assert_eq!(messages[1].created_at().timestamp(), 1700000010);
+
+    // Only the branch of the current node is imported
+    assert_eq!(messages[2].id(), "a2");
+    assert!(messages[2].is_system());
+    assert_eq!(messages[2].issuer_str(), "gpt-4o");
+    assert_eq!(messages[2].text(), "A lifetime is\n a scope");
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert!(parse(r#"{"title": "not a list"}"#).is_err());
+}
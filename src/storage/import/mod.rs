@@ -0,0 +1,56 @@
+pub mod chatgpt;
+
+use std::collections::HashSet;
+
+use eyre::{Context, Result};
+
+use crate::models::Conversation;
+
+use super::ArcStorage;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStats {
+    pub conversations: usize,
+    pub messages: usize,
+    /// The number of conversations skipped because they already exist
+    pub skipped: usize,
+}
+
+/// Insert the conversations with their messages and contexts into the
+/// storage at once. The conversations which already exist (by id) are
+/// skipped.
+pub async fn import_conversations(
+    storage: &ArcStorage,
+    conversations: Vec<Conversation>,
+) -> Result<ImportStats> {
+    let mut stats = ImportStats::default();
+    let mut imported = Vec::with_capacity(conversations.len());
+    let mut seen = HashSet::new();
+    for convo in conversations {
+        let id = convo.id().to_string();
+        if seen.contains(&id)
+            || storage
+                .get_conversation(&id)
+                .await
+                .wrap_err(format!("getting conversation {}", id))?
+                .is_some()
+        {
+            log::debug!("Skip importing conversation {}: already exists", id);
+            stats.skipped += 1;
+            continue;
+        }
+
+        stats.conversations += 1;
+        stats.messages += convo.messages().len();
+        seen.insert(id);
+        imported.push(convo);
+    }
+
+    // A single transaction is much faster than a transaction per row for
+    // the large archives
+    storage
+        .bulk_upsert(&imported)
+        .await
+        .wrap_err("inserting conversations")?;
+    Ok(stats)
+}
@@ -1,10 +1,16 @@
+pub mod archive;
+pub mod import;
+pub mod semantic;
 pub mod sqlite;
 
 use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     config::StorageConfig,
-    models::{Context, Conversation, Message, storage::FilterConversation},
+    models::{
+        Context, Conversation, Message,
+        storage::{Embedding, FilterConversation, MessageEdit, Stats},
+    },
 };
 use async_trait::async_trait;
 use eyre::Result;
@@ -18,21 +24,78 @@ pub trait Storage {
         filter: FilterConversation,
     ) -> Result<HashMap<String, Conversation>>;
     async fn get_messages(&self, conversation_id: &str) -> Result<Vec<Message>>;
-    async fn upsert_conversation(&self, conversation: Conversation) -> Result<()>;
+    /// Insert or update the conversation, return whether it is inserted
+    async fn upsert_conversation(&self, conversation: Conversation) -> Result<bool>;
+    /// Mark the conversation as deleted. It is hidden from the queries until
+    /// it is restored or purged.
     async fn delete_conversation(&self, id: &str) -> Result<()>;
+    async fn restore_conversation(&self, id: &str) -> Result<()>;
+    /// Permanently remove the conversations deleted before the given time and
+    /// return the number of removed conversations
+    async fn purge_deleted_conversations(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize>;
+    /// Permanently remove the oldest conversations beyond the maximum number
+    /// of conversations and the ones not updated since the given time. Return
+    /// the number of removed conversations.
+    async fn prune_conversations(
+        &self,
+        max_conversations: Option<usize>,
+        updated_before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<usize>;
     async fn add_messages(&self, conversation_id: &str, message: &[Message]) -> Result<()>;
+    /// Insert or update the message. If the edits are tracked, the previous
+    /// text of a complete message is kept when the text changes.
     async fn upsert_message(&self, conversation_id: &str, message: Message) -> Result<()>;
+    /// Return the previous texts of the message, from the oldest
+    async fn get_message_edits(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<Vec<MessageEdit>>;
     async fn delete_messsage(&self, id: &str) -> Result<()>;
+    /// Remove all the messages of the conversation with their edits, the
+    /// contexts and the embedding. The conversation itself is kept.
+    async fn clear_messages(&self, conversation_id: &str) -> Result<()>;
     async fn upsert_context(&self, conversation_id: &str, context: Context) -> Result<()>;
+    /// Insert or update the conversations with their messages and contexts
+    /// at once, the soft-deleted ones are restored. Nothing is written if
+    /// any of them fails.
+    async fn bulk_upsert(&self, conversations: &[Conversation]) -> Result<()>;
+    /// Return the value of the app state kept across restarts
+    async fn get_app_state(&self, key: &str) -> Result<Option<String>>;
+    async fn set_app_state(&self, key: &str, value: &str) -> Result<()>;
+    /// Return the usage statistics of the conversations
+    async fn stats(&self) -> Result<Stats>;
+    /// Return the embeddings computed by the model by conversation ID, the
+    /// deleted conversations are left out
+    async fn get_embeddings(&self, model: &str) -> Result<HashMap<String, Embedding>>;
+    async fn upsert_embedding(&self, conversation_id: &str, embedding: Embedding) -> Result<()>;
+    /// Return the IDs of the conversations with messages which have no
+    /// embedding computed by the model or were updated since, sorted
+    async fn get_outdated_embeddings(&self, model: &str) -> Result<Vec<String>>;
 }
 
+/// The app state key of the conversation opened when the app exited
+pub const LAST_CONVERSATION_STATE: &str = "last_conversation";
+
+/// The app state key of the connection of the model used when the app exited
+pub const LAST_BACKEND_STATE: &str = "last_backend";
+
+/// The app state key of the unsent inputs, a JSON object of the drafts by
+/// conversation ID. The draft of a new conversation has an empty ID.
+pub const DRAFTS_STATE: &str = "drafts";
+
+/// The app state key of the prompts recalled in the input box, a JSON array
+/// from the oldest to the newest
+pub const INPUT_HISTORY_STATE: &str = "input_history";
+
 pub type ArcStorage = Arc<dyn Storage + Send + Sync>;
 
 pub async fn new_storage(config: &StorageConfig) -> Result<ArcStorage> {
     let storage = match config {
-        StorageConfig::Sqlite(sqlite_config) => {
-            Arc::new(Sqlite::new(sqlite_config.path.as_deref()).await?)
-        }
+        StorageConfig::Sqlite(sqlite_config) => Arc::new(Sqlite::open(sqlite_config).await?),
     };
     Ok(storage)
 }
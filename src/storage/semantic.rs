@@ -0,0 +1,154 @@
+#[cfg(test)]
+#[path = "semantic_test.rs"]
+mod tests;
+
+use eyre::{Context, Result};
+
+use crate::{
+    backend::ArcBackend,
+    config::{
+        SearchConfig,
+        constants::{EMBEDDING_BATCH_SIZE, EMBEDDING_MAX_CHARS},
+    },
+    models::{Conversation, storage::Embedding},
+    storage::ArcStorage,
+};
+
+/// The semantic search of the conversations. The embedding of each
+/// conversation is kept in the storage and computed again once the
+/// conversation is updated.
+pub struct SemanticIndex {
+    backend: ArcBackend,
+    storage: ArcStorage,
+    model: String,
+}
+
+impl SemanticIndex {
+    pub fn new(backend: ArcBackend, storage: ArcStorage) -> Self {
+        Self {
+            backend,
+            storage,
+            model: SearchConfig::default().embedding_model().to_string(),
+        }
+    }
+
+    pub fn from_config(mut self, config: &SearchConfig) -> Self {
+        self.model = config.embedding_model().to_string();
+        self
+    }
+
+    /// Compute the embeddings of the conversations which are new or updated
+    /// since they were indexed, return the number of them. Only these
+    /// conversations are loaded with their messages.
+    pub async fn update(&self) -> Result<usize> {
+        let outdated = self
+            .storage
+            .get_outdated_embeddings(&self.model)
+            .await
+            .wrap_err("getting outdated conversations")?;
+
+        let mut updated = 0;
+        for batch in outdated.chunks(EMBEDDING_BATCH_SIZE) {
+            let mut conversations = vec![];
+            for id in batch {
+                let convo = self
+                    .storage
+                    .get_conversation(id)
+                    .await
+                    .wrap_err("getting conversation")?;
+                // Deleted meanwhile
+                conversations.extend(convo);
+            }
+            if conversations.is_empty() {
+                continue;
+            }
+
+            let input = conversations
+                .iter()
+                .map(conversation_text)
+                .collect::<Vec<_>>();
+            let vectors = self
+                .backend
+                .embed(&self.model, &input)
+                .await
+                .wrap_err("computing conversation embeddings")?;
+            for (convo, vector) in conversations.iter().zip(vectors) {
+                let embedding = Embedding {
+                    model: self.model.clone(),
+                    vector,
+                    updated_at: convo.updated_at(),
+                };
+                self.storage.upsert_embedding(convo.id(), embedding).await?;
+            }
+            updated += conversations.len();
+        }
+        Ok(updated)
+    }
+
+    /// Return the IDs of the conversations most similar to the query with
+    /// their similarity, from the most similar one
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<(String, f32)>> {
+        self.update().await.wrap_err("updating the index")?;
+        let query = self
+            .backend
+            .embed(&self.model, &[query.to_string()])
+            .await
+            .wrap_err("computing query embedding")?
+            .pop()
+            .unwrap_or_default();
+
+        let embeddings = self.storage.get_embeddings(&self.model).await?;
+        Ok(rank(&query, embeddings, limit))
+    }
+}
+
+/// Rank the conversations by the similarity of their embeddings to the query
+fn rank(
+    query: &[f32],
+    embeddings: impl IntoIterator<Item = (String, Embedding)>,
+    limit: usize,
+) -> Vec<(String, f32)> {
+    let mut ranked = embeddings
+        .into_iter()
+        .map(|(id, embedding)| {
+            let score = cosine_similarity(query, &embedding.vector);
+            (id, score)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(limit);
+    ranked
+}
+
+/// Return the cosine similarity of the vectors, 0 if they differ in length
+/// (e.g. computed by another model) or either one is zero
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// The text the embedding of the conversation is computed from: the title
+/// and the messages of the user and the assistant, cut to fit the model
+fn conversation_text(convo: &Conversation) -> String {
+    let mut text = convo.title().to_string();
+    for msg in convo.messages() {
+        if msg.is_tool() || msg.is_app_message() {
+            continue;
+        }
+        text.push_str("\n\n");
+        text.push_str(msg.text());
+    }
+
+    match text.char_indices().nth(EMBEDDING_MAX_CHARS) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text,
+    }
+}
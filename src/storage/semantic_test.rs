@@ -0,0 +1,119 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use super::*;
+use crate::backend::MockBackend;
+use crate::models::Message;
+use crate::storage::sqlite::Sqlite;
+
+/// Embed the texts about Rust and about cooking on different axes
+fn embed_topics(input: &[String]) -> Vec<Vec<f32>> {
+    input
+        .iter()
+        .map(|text| {
+            let text = text.to_lowercase();
+            vec![
+                text.contains("rust") as u8 as f32,
+                text.contains("recipe") as u8 as f32,
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn test_cosine_similarity() {
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]), 1.0);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[-2.0, 0.0]), -1.0);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), 0.0);
+    assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+}
+
+#[test]
+fn test_conversation_text() {
+    let convo = Conversation::default()
+        .with_title("Borrowing")
+        .with_messages(vec![
+            Message::new_user("user", "What is a borrow?"),
+            Message::new_system("system", "Welcome"),
+            Message::new_system("gpt-4o", "A reference"),
+        ]);
+    assert_eq!(
+        conversation_text(&convo),
+        "Borrowing\n\nWhat is a borrow?\n\nA reference"
+    );
+
+    let convo = Conversation::default().with_title("é".repeat(EMBEDDING_MAX_CHARS + 1));
+    assert_eq!(
+        conversation_text(&convo).chars().count(),
+        EMBEDDING_MAX_CHARS
+    );
+}
+
+#[tokio::test]
+async fn test_search() {
+    let storage: ArcStorage = Arc::new(Sqlite::new(None).await.unwrap());
+    for (id, title, text) in [
+        ("convo1", "Lifetimes", "How do Rust lifetimes work?"),
+        ("convo2", "Dinner", "A recipe for pasta"),
+        ("convo3", "Traits", "Rust traits and a recipe"),
+    ] {
+        storage
+            .upsert_conversation(Conversation::default().with_id(id).with_title(title))
+            .await
+            .unwrap();
+        storage
+            .add_messages(id, &[Message::new_user("user", text).with_id("msg1")])
+            .await
+            .unwrap();
+    }
+
+    let embedded = Arc::new(AtomicUsize::new(0));
+    let mut backend = MockBackend::new();
+    let counter = embedded.clone();
+    backend.expect_embed().returning(move |model, input| {
+        assert_eq!(model, "embedding-model");
+        counter.fetch_add(input.len(), Ordering::SeqCst);
+        let vectors = embed_topics(input);
+        Box::pin(async move { Ok(vectors) })
+    });
+
+    let index = SemanticIndex::new(Arc::new(backend), storage.clone()).from_config(&SearchConfig {
+        embedding_model: Some("embedding-model".to_string()),
+        ..Default::default()
+    });
+
+    let results = index.search("Rust", 2).await.unwrap();
+    let ids = results
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec!["convo1", "convo3"]);
+    // The 3 conversations and the query
+    assert_eq!(embedded.load(Ordering::SeqCst), 4);
+
+    // Only the query is embedded while the conversations are unchanged
+    index.search("recipe", 10).await.unwrap();
+    assert_eq!(embedded.load(Ordering::SeqCst), 5);
+
+    // Only the updated conversation is embedded again with the query
+    let convo = storage.get_conversation("convo1").await.unwrap().unwrap();
+    let updated_at = convo.updated_at() + chrono::Duration::seconds(1);
+    storage
+        .upsert_conversation(convo.with_updated_at(updated_at))
+        .await
+        .unwrap();
+    index.search("recipe", 10).await.unwrap();
+    assert_eq!(embedded.load(Ordering::SeqCst), 7);
+
+    // The deleted conversations are left out
+    storage.delete_conversation("convo2").await.unwrap();
+    let results = index.search("recipe", 10).await.unwrap();
+    let ids = results
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec!["convo3", "convo1"]);
+}
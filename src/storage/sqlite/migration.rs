@@ -30,3 +30,76 @@ pub(crate) const MIGRATION: &str = r#"
         FOREIGN KEY (last_message_id, conversation_id) REFERENCES messages(id, conversation_id) ON DELETE CASCADE
     );
 "#;
+
+/// Incremental migrations applied on top of [`MIGRATION`]. The database
+/// `user_version` records how many of them have been applied, so new
+/// migrations must always be appended to the end of the list.
+pub(crate) const MIGRATIONS: &[&str] = &[
+    // v1: conversation level system prompt
+    r#"
+    ALTER TABLE conversations ADD COLUMN system_prompt TEXT;
+    "#,
+    // v2: soft-deleted conversations
+    r#"
+    ALTER TABLE conversations ADD COLUMN deleted_at INTEGER;
+    "#,
+    // v3: tool call messages
+    r#"
+    ALTER TABLE messages ADD COLUMN tool INTEGER NOT NULL DEFAULT 0;
+    "#,
+    // v4: responses interrupted while streaming
+    r#"
+    ALTER TABLE messages ADD COLUMN incomplete INTEGER NOT NULL DEFAULT 0;
+    "#,
+    // v5: app state kept across restarts
+    r#"
+    CREATE TABLE IF NOT EXISTS app_state (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    "#,
+    // v6: conversation budget and spending
+    r#"
+    ALTER TABLE conversations ADD COLUMN budget TEXT;
+    ALTER TABLE conversations ADD COLUMN spent_tokens INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE conversations ADD COLUMN spent_cost REAL NOT NULL DEFAULT 0;
+    "#,
+    // v7: the messages and the contexts are looked up by conversation, their
+    // primary keys are led by their own id. The messages index also serves
+    // the lookups by conversation only and the message search of the
+    // conversation filter.
+    r#"
+    CREATE INDEX IF NOT EXISTS idx_messages_conversation_id ON messages(conversation_id, created_at);
+    CREATE INDEX IF NOT EXISTS idx_contexts_conversation_id ON contexts(conversation_id);
+    "#,
+    // v8: conversation embeddings of the semantic search, the vector is
+    // stored as little-endian f32
+    r#"
+    CREATE TABLE IF NOT EXISTS embeddings (
+        conversation_id TEXT PRIMARY KEY,
+        model TEXT NOT NULL,
+        vector BLOB NOT NULL,
+        updated_at INTEGER NOT NULL,
+        FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+    );
+    "#,
+    // v9: app messages reporting an error
+    r#"
+    ALTER TABLE messages ADD COLUMN error INTEGER NOT NULL DEFAULT 0;
+    "#,
+    // v10: the previous texts of the edited messages
+    r#"
+    CREATE TABLE IF NOT EXISTS message_edits (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        message_id TEXT NOT NULL,
+        conversation_id TEXT NOT NULL,
+        text TEXT NOT NULL,
+        edited_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_message_edits_message_id ON message_edits(message_id);
+    "#,
+    // v11: responses the model declined
+    r#"
+    ALTER TABLE messages ADD COLUMN refusal INTEGER NOT NULL DEFAULT 0;
+    "#,
+];
@@ -6,24 +6,85 @@ pub(crate) mod migration;
 
 use crate::storage::Storage;
 use crate::{
-    config::resolve_path,
+    config::{SqliteStorage, resolve_path},
     models::{
-        Context as ConvoContext, Conversation, Message, message::Issuer,
-        storage::FilterConversation,
+        Budget, Context as ConvoContext, Conversation, Message, Severity, Spending,
+        message::Issuer,
+        storage::{Embedding, FilterConversation, MessageEdit, Stats},
     },
 };
 use async_trait::async_trait;
 use eyre::{Context, Result, bail};
-use migration::MIGRATION;
+use migration::{MIGRATION, MIGRATIONS};
 use std::collections::HashMap;
-use tokio_rusqlite::{Connection, OpenFlags, ToSql, named_params, params};
+use tokio_rusqlite::{Connection, OpenFlags, OptionalExtension, ToSql, named_params, params};
 
 pub struct Sqlite {
     conn: Connection,
+    /// Keep the previous texts of the edited messages
+    track_edits: bool,
 }
 
+const UPSERT_CONVERSATION: &str = r#"
+    INSERT INTO conversations (id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost)
+    VALUES (:id, :title, :created_at, :updated_at, :system_prompt, :budget, :spent_tokens, :spent_cost)
+    ON CONFLICT(id) DO UPDATE SET
+        title = excluded.title,
+        created_at = excluded.created_at,
+        updated_at = excluded.updated_at,
+        system_prompt = excluded.system_prompt,
+        budget = excluded.budget,
+        spent_tokens = excluded.spent_tokens,
+        spent_cost = excluded.spent_cost
+"#;
+
+const UPSERT_MESSAGE: &str = r#"
+    INSERT INTO messages (id, conversation_id, text, issuer, system, token_count, created_at, tool, incomplete, error, refusal)
+    VALUES (:id, :conversation_id, :text, :issuer, :system, :token_count, :created_at, :tool, :incomplete, :error, :refusal)
+    ON CONFLICT(id, conversation_id) DO UPDATE SET
+        text = excluded.text,
+        issuer = excluded.issuer,
+        system = excluded.system,
+        tool = excluded.tool,
+        incomplete = excluded.incomplete,
+        error = excluded.error,
+        refusal = excluded.refusal,
+        token_count = excluded.token_count,
+        created_at = excluded.created_at
+"#;
+
+// The lookups by conversation below are served by the indexes of the
+// migrations, the plans are checked by the tests
+const SELECT_MESSAGES: &str = "SELECT id, conversation_id, text, issuer, system, token_count, created_at, tool, incomplete, error, refusal FROM messages WHERE conversation_id = ?";
+
+const SELECT_CONTEXTS: &str = "SELECT id, conversation_id, last_message_id, content, token_count, created_at FROM contexts WHERE conversation_id = ?";
+
+const DELETE_MESSAGES: &str = "DELETE FROM messages WHERE conversation_id = ?";
+
+const DELETE_CONTEXTS: &str = "DELETE FROM contexts WHERE conversation_id = ?";
+
+const UPSERT_CONTEXT: &str = r#"
+    INSERT INTO contexts (id, conversation_id, last_message_id, content, token_count, created_at)
+    VALUES (:id, :conversation_id, :last_message_id, :content, :token_count, :created_at)
+    ON CONFLICT(id, conversation_id, last_message_id) DO UPDATE SET
+        content = excluded.content,
+        token_count = excluded.token_count,
+        created_at = excluded.created_at
+"#;
+
 impl Sqlite {
     pub async fn new(path: Option<&str>) -> Result<Self> {
+        Self::open(&SqliteStorage {
+            path: path.map(String::from),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Open the database with the settings of the config. An in-memory
+    /// database is used if the path is not set.
+    pub async fn open(config: &SqliteStorage) -> Result<Self> {
+        let path = config.path.as_deref().filter(|path| !path.is_empty());
         let conn = match path {
             Some(path) => Connection::open_with_flags(
                 resolve_path(path).wrap_err("resolving path")?,
@@ -36,16 +97,66 @@ impl Sqlite {
                 .wrap_err("opening in-memory database")?,
         };
 
-        let ret = Self { conn };
+        let ret = Self {
+            conn,
+            track_edits: config.track_edits.unwrap_or_default(),
+        };
+        ret.configure(config, path.is_some())
+            .await
+            .wrap_err("configuring database")?;
         ret.run_migration().await.wrap_err("running migration")?;
         Ok(ret)
     }
 
+    /// Set the busy timeout and the journal mode of a database on disk. The
+    /// WAL journal is kept in the `-wal` and `-shm` files next to the
+    /// database. The journal mode persists in the database, so it is set back
+    /// to the default one if WAL is disabled.
+    async fn configure(&self, config: &SqliteStorage, on_disk: bool) -> Result<()> {
+        let busy_timeout = config.busy_timeout();
+        let journal_mode = if config.wal() { "WAL" } else { "DELETE" };
+        self.conn
+            .call(move |conn| {
+                conn.busy_timeout(busy_timeout)?;
+                if !on_disk {
+                    return Ok(());
+                }
+                let mode: String =
+                    conn.pragma_update_and_check(None, "journal_mode", journal_mode, |row| {
+                        row.get(0)
+                    })?;
+                if !mode.eq_ignore_ascii_case(journal_mode) {
+                    log::warn!(
+                        "The database journal mode is {} instead of {}",
+                        mode,
+                        journal_mode
+                    );
+                }
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
     async fn run_migration(&self) -> Result<()> {
         self.conn
             .call(|conn| Ok(conn.execute_batch(MIGRATION)?))
             .await
             .wrap_err("executing migration")?;
+
+        self.conn
+            .call(|conn| {
+                let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+                for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+                    let tx = conn.transaction()?;
+                    tx.execute_batch(migration)?;
+                    tx.pragma_update(None, "user_version", i + 1)?;
+                    tx.commit()?;
+                }
+                Ok(())
+            })
+            .await
+            .wrap_err("executing incremental migrations")?;
         Ok(())
     }
 }
@@ -68,38 +179,60 @@ impl Storage for Sqlite {
 
     async fn get_messages(&self, conversation_id: &str) -> Result<Vec<Message>> {
         let conversation_id = conversation_id.to_string();
-        let messages = self.conn.call(move |conn| {
-        let mut stmt = conn.prepare(
-            "SELECT id, conversation_id, text, issuer, system, token_count, created_at FROM messages WHERE conversation_id = ?",
-        )?;
-
-        let mut rows = stmt.query(params![conversation_id])?;
-        let mut messages = vec![];
-        while let Some(row) = rows.next()? {
-            let id: String = row.get(0)?;
-            let text: String = row.get(2)?;
-            let issuer: String = row.get(3)?;
-            let system: i32 = row.get(4)?;
-            let token_count: usize = row.get(5)?;
-            let created_at: i64 = row.get(6)?;
-
-            let issuer = if system == 1 {
-                Issuer::System(issuer)
-            } else {
-                Issuer::User(issuer)
-            };
-
-            let created_at = chrono::DateTime::from_timestamp_millis(created_at).ok_or(tokio_rusqlite::Error::Other(eyre::eyre!("invalid timestamp").into()))?;
-
-            messages.push(Message::new(issuer, text).with_id(id).with_created_at(created_at).with_token_count(token_count));
-        }
-        messages.sort_by(|a, b| {
-            a.created_at()
-                .timestamp_millis()
-                .cmp(&b.created_at().timestamp_millis())
-        });
-        Ok(messages)
-        }).await?;
+        let messages = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_MESSAGES)?;
+
+                let mut rows = stmt.query(params![conversation_id])?;
+                let mut messages = vec![];
+                while let Some(row) = rows.next()? {
+                    let id: String = row.get(0)?;
+                    let text: String = row.get(2)?;
+                    let issuer: String = row.get(3)?;
+                    let system: i32 = row.get(4)?;
+                    let token_count: usize = row.get(5)?;
+                    let created_at: i64 = row.get(6)?;
+                    let tool: i32 = row.get(7)?;
+                    let incomplete: i32 = row.get(8)?;
+                    let error: i32 = row.get(9)?;
+                    let refusal: i32 = row.get(10)?;
+                    let severity = if error == 1 {
+                        Severity::Error
+                    } else {
+                        Severity::Info
+                    };
+
+                    let issuer = if tool == 1 {
+                        Issuer::Tool(issuer)
+                    } else if system == 1 {
+                        Issuer::System(issuer)
+                    } else {
+                        Issuer::User(issuer)
+                    };
+
+                    let created_at = chrono::DateTime::from_timestamp_millis(created_at).ok_or(
+                        tokio_rusqlite::Error::Other(eyre::eyre!("invalid timestamp").into()),
+                    )?;
+
+                    messages.push(
+                        Message::new(issuer, text)
+                            .with_id(id)
+                            .with_created_at(created_at)
+                            .with_token_count(token_count)
+                            .with_incomplete(incomplete == 1)
+                            .with_refusal(refusal == 1)
+                            .with_severity(severity),
+                    );
+                }
+                messages.sort_by(|a, b| {
+                    a.created_at()
+                        .timestamp_millis()
+                        .cmp(&b.created_at().timestamp_millis())
+                });
+                Ok(messages)
+            })
+            .await?;
         Ok(messages)
     }
 
@@ -131,10 +264,28 @@ impl Storage for Sqlite {
                         tokio_rusqlite::Error::Other(eyre::eyre!("invalid updated_at").into()),
                     )?;
 
+                    let system_prompt: Option<String> = row.get(4)?;
+                    let budget: Option<String> = row.get(5)?;
+                    let budget = budget.and_then(|budget| {
+                        serde_json::from_str::<Budget>(&budget)
+                            .inspect_err(|err| log::warn!("Invalid budget of {}: {}", id, err))
+                            .ok()
+                    });
+                    let spending = Spending {
+                        tokens: row.get(6)?,
+                        cost: row.get(7)?,
+                    };
+
                     let mut con = Conversation::default()
                         .with_id(&id)
                         .with_title(title)
-                        .with_created_at(created_at);
+                        .with_created_at(created_at)
+                        .with_budget(budget)
+                        .with_spending(spending);
+
+                    if let Some(system_prompt) = system_prompt {
+                        con = con.with_system_prompt(system_prompt);
+                    }
 
                     if updated_at.timestamp_millis() > 0 {
                         con = con.with_updated_at(updated_at);
@@ -156,75 +307,220 @@ impl Storage for Sqlite {
         Ok(conversations)
     }
 
-    async fn upsert_conversation(&self, conversation: Conversation) -> Result<()> {
+    async fn upsert_conversation(&self, conversation: Conversation) -> Result<bool> {
         if conversation.id().is_empty() {
             bail!("conversation id is empty");
         }
 
+        let budget = conversation
+            .budget()
+            .map(|budget| serde_json::to_string(&budget))
+            .transpose()
+            .wrap_err("serializing budget")?;
+        let inserted = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let exists: bool = tx.query_row(
+                    "SELECT EXISTS (SELECT 1 FROM conversations WHERE id = ?)",
+                    params![conversation.id()],
+                    |row| row.get(0),
+                )?;
+                execute_upsert_conversation(
+                    &mut tx.prepare(UPSERT_CONVERSATION)?,
+                    &conversation,
+                    budget.as_deref(),
+                )?;
+                tx.commit()?;
+                Ok(!exists)
+            })
+            .await?;
+        Ok(inserted)
+    }
+
+    async fn delete_conversation(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        let deleted_at = chrono::Utc::now().timestamp_millis();
         self.conn
             .call(move |conn| {
                 let tx = conn.transaction()?;
                 tx.execute(
-                    r#"INSERT INTO conversations (id, title, created_at, updated_at)
-                VALUES (:id, :title, :created_at, :updated_at)
-                ON CONFLICT(id) DO UPDATE SET
-                    title = excluded.title,
-                    created_at = excluded.created_at,
-                    updated_at = excluded.updated_at
-                "#,
-                    named_params! {
-                        ":id": conversation.id(),
-                        ":title": conversation.title(),
-                        ":created_at": conversation.created_at().timestamp_millis(),
-                        ":updated_at": conversation.updated_at().timestamp_millis(),
-                    },
+                    "UPDATE conversations SET deleted_at = ? WHERE id = ?",
+                    params![deleted_at, id],
                 )?;
-                tx.commit()?;
-                Ok(())
+                Ok(tx.commit()?)
             })
             .await?;
         Ok(())
     }
 
-    async fn delete_conversation(&self, id: &str) -> Result<()> {
+    async fn restore_conversation(&self, id: &str) -> Result<()> {
         let id = id.to_string();
         self.conn
             .call(move |conn| {
                 let tx = conn.transaction()?;
-                tx.execute("DELETE FROM conversations WHERE id = ?", params![id])?;
+                tx.execute(
+                    "UPDATE conversations SET deleted_at = NULL WHERE id = ?",
+                    params![id],
+                )?;
                 Ok(tx.commit()?)
             })
             .await?;
         Ok(())
     }
 
+    async fn purge_deleted_conversations(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize> {
+        let before = before.timestamp_millis();
+        let purged = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let deleted = "SELECT id FROM conversations WHERE deleted_at IS NOT NULL AND deleted_at < :before";
+                tx.execute(
+                    &format!("DELETE FROM contexts WHERE conversation_id IN ({})", deleted),
+                    named_params! {":before": before},
+                )?;
+                tx.execute(
+                    &format!("DELETE FROM embeddings WHERE conversation_id IN ({})", deleted),
+                    named_params! {":before": before},
+                )?;
+                tx.execute(
+                    &format!("DELETE FROM message_edits WHERE conversation_id IN ({})", deleted),
+                    named_params! {":before": before},
+                )?;
+                tx.execute(
+                    &format!("DELETE FROM messages WHERE conversation_id IN ({})", deleted),
+                    named_params! {":before": before},
+                )?;
+                let purged = tx.execute(
+                    "DELETE FROM conversations WHERE deleted_at IS NOT NULL AND deleted_at < :before",
+                    named_params! {":before": before},
+                )?;
+                tx.commit()?;
+                Ok(purged)
+            })
+            .await?;
+        Ok(purged)
+    }
+
+    async fn prune_conversations(
+        &self,
+        max_conversations: Option<usize>,
+        updated_before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<usize> {
+        if max_conversations.is_none() && updated_before.is_none() {
+            return Ok(0);
+        }
+
+        // A negative limit means no limit
+        let max = max_conversations.map_or(-1, |max| max as i64);
+        let before = updated_before.map_or(i64::MIN, |before| before.timestamp_millis());
+        let pruned = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let pruned = r#"
+                    SELECT id FROM conversations
+                    WHERE deleted_at IS NULL AND (
+                        updated_at < :before OR id NOT IN (
+                            SELECT id FROM conversations WHERE deleted_at IS NULL
+                            ORDER BY updated_at DESC LIMIT :max
+                        )
+                    )
+                "#;
+                let params = named_params! {":before": before, ":max": max};
+                tx.execute(
+                    &format!("DELETE FROM contexts WHERE conversation_id IN ({})", pruned),
+                    params,
+                )?;
+                tx.execute(
+                    &format!(
+                        "DELETE FROM embeddings WHERE conversation_id IN ({})",
+                        pruned
+                    ),
+                    params,
+                )?;
+                tx.execute(
+                    &format!(
+                        "DELETE FROM message_edits WHERE conversation_id IN ({})",
+                        pruned
+                    ),
+                    params,
+                )?;
+                tx.execute(
+                    &format!("DELETE FROM messages WHERE conversation_id IN ({})", pruned),
+                    params,
+                )?;
+                let pruned = tx.execute(
+                    &format!("DELETE FROM conversations WHERE id IN ({})", pruned),
+                    params,
+                )?;
+                tx.commit()?;
+                Ok(pruned)
+            })
+            .await?;
+        Ok(pruned)
+    }
+
     async fn add_messages(&self, conversation_id: &str, messages: &[Message]) -> Result<()> {
         let conversation_id = conversation_id.to_string();
         let messages = messages.to_vec();
         self.conn
             .call(move |conn| {
                 let tx = conn.transaction()?;
-                for message in messages {
-                    tx.execute(
-                    r#"INSERT INTO messages (id, conversation_id, text, issuer, system, token_count, created_at)
-            VALUES (:id, :conversation_id, :text, :issuer, :system, :token_count, :created_at)
-            ON CONFLICT(id, conversation_id) DO UPDATE SET
-                text = excluded.text,
-                issuer = excluded.issuer,
-                system = excluded.system,
-                token_count = excluded.token_count,
-                created_at = excluded.created_at
-            "#,
-                    named_params! {
-                        ":id": message.id(),
-                        ":conversation_id": conversation_id,
-                        ":text": message.text(),
-                        ":issuer": message.issuer_str(),
-                        ":system": message.is_system() as i32,
-                        ":token_count": message.token_count() as i32,
-                        ":created_at": message.created_at().timestamp_millis()
-                    },
-                )?;
+                {
+                    let mut stmt = tx.prepare_cached(UPSERT_MESSAGE)?;
+                    for message in &messages {
+                        execute_upsert_message(&mut stmt, &conversation_id, message)?;
+                    }
+                }
+                Ok(tx.commit()?)
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn bulk_upsert(&self, conversations: &[Conversation]) -> Result<()> {
+        let mut rows = Vec::with_capacity(conversations.len());
+        for conversation in conversations {
+            if conversation.id().is_empty() {
+                bail!("conversation id is empty");
+            }
+            let budget = conversation
+                .budget()
+                .map(|budget| serde_json::to_string(&budget))
+                .transpose()
+                .wrap_err("serializing budget")?;
+            rows.push((conversation.clone(), budget));
+        }
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut convo_stmt = tx.prepare_cached(UPSERT_CONVERSATION)?;
+                    let mut restore_stmt = tx.prepare_cached(
+                        "UPDATE conversations SET deleted_at = NULL WHERE id = ?",
+                    )?;
+                    let mut message_stmt = tx.prepare_cached(UPSERT_MESSAGE)?;
+                    let mut ctx_stmt = tx.prepare_cached(UPSERT_CONTEXT)?;
+                    for (conversation, budget) in &rows {
+                        execute_upsert_conversation(
+                            &mut convo_stmt,
+                            conversation,
+                            budget.as_deref(),
+                        )?;
+                        restore_stmt.execute(params![conversation.id()])?;
+                        for message in conversation.messages() {
+                            execute_upsert_message(&mut message_stmt, conversation.id(), message)?;
+                        }
+                        for ctx in conversation.contexts() {
+                            execute_upsert_context(&mut ctx_stmt, conversation.id(), ctx)?;
+                        }
+                    }
                 }
                 Ok(tx.commit()?)
             })
@@ -238,31 +534,51 @@ impl Storage for Sqlite {
         let text = message.text().to_string();
         let issuer = message.issuer_str().to_string();
         let system = message.is_system() as i32;
+        let tool = message.is_tool() as i32;
+        let incomplete = message.is_incomplete() as i32;
+        let error = message.is_error() as i32;
+        let refusal = message.is_refusal() as i32;
         let token_count = message.token_count() as i32;
         let timestamp = message.created_at().timestamp_millis();
+        let track_edits = self.track_edits;
         let affected_rows = self
             .conn
             .call(move |conn| {
-                Ok(conn.execute(
-                    r#"INSERT INTO messages (id, conversation_id, text, issuer, system, token_count, created_at)
-            VALUES (:id, :conversation_id, :text, :issuer, :system, :token_count, :created_at)
-            ON CONFLICT(id, conversation_id) DO UPDATE SET
-                text = excluded.text,
-                issuer = excluded.issuer,
-                system = excluded.system,
-                token_count = excluded.token_count,
-                created_at = excluded.created_at
-            "#,
+                let tx = conn.transaction()?;
+                if track_edits {
+                    // A partial response is not an edit, it is completed
+                    let previous = tx
+                        .query_row(
+                            "SELECT text FROM messages WHERE id = ? AND conversation_id = ? AND incomplete = 0",
+                            params![id, conversation_id],
+                            |row| row.get::<_, String>(0),
+                        )
+                        .optional()?;
+                    if previous.as_ref().is_some_and(|previous| *previous != text) {
+                        tx.execute(
+                            "INSERT INTO message_edits (message_id, conversation_id, text, edited_at) VALUES (?, ?, ?, ?)",
+                            params![id, conversation_id, previous, chrono::Utc::now().timestamp_millis()],
+                        )?;
+                    }
+                }
+                let affected_rows = tx.execute(
+                    UPSERT_MESSAGE,
                     named_params! {
                         ":id": id,
                         ":conversation_id": conversation_id,
                         ":text": text,
                         ":issuer": issuer,
                         ":system": system,
+                        ":tool": tool,
+                        ":incomplete": incomplete,
+                        ":error": error,
+                        ":refusal": refusal,
                         ":token_count":token_count,
                         ":created_at": timestamp
                     },
-                )?)
+                )?;
+                tx.commit()?;
+                Ok(affected_rows)
             })
             .await?;
 
@@ -272,11 +588,47 @@ impl Storage for Sqlite {
         Ok(())
     }
 
+    async fn get_message_edits(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> Result<Vec<MessageEdit>> {
+        let conversation_id = conversation_id.to_string();
+        let message_id = message_id.to_string();
+        let edits = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT text, edited_at FROM message_edits WHERE conversation_id = ? AND message_id = ? ORDER BY edited_at, id",
+                )?;
+                let mut rows = stmt.query(params![conversation_id, message_id])?;
+                let mut edits = vec![];
+                while let Some(row) = rows.next()? {
+                    let text: String = row.get(0)?;
+                    let edited_at: i64 = row.get(1)?;
+                    let edited_at = chrono::DateTime::from_timestamp_millis(edited_at).ok_or(
+                        tokio_rusqlite::Error::Other(eyre::eyre!("invalid edited_at").into()),
+                    )?;
+                    edits.push(MessageEdit { text, edited_at });
+                }
+                Ok(edits)
+            })
+            .await
+            .wrap_err("getting message edits")?;
+        Ok(edits)
+    }
+
     async fn delete_messsage(&self, id: &str) -> Result<()> {
         let id = id.to_string();
         self.conn
             .call(move |conn| {
                 let tx = conn.transaction()?;
+                // The edits of the deleted messages only, the message IDs are
+                // unique per conversation
+                tx.execute(
+                    "DELETE FROM message_edits WHERE (message_id, conversation_id) IN (SELECT id, conversation_id FROM messages WHERE id = ?)",
+                    params![id],
+                )?;
                 tx.execute("DELETE FROM messages WHERE id = ?", params![id])?;
                 Ok(tx.commit()?)
             })
@@ -284,6 +636,27 @@ impl Storage for Sqlite {
         Ok(())
     }
 
+    async fn clear_messages(&self, conversation_id: &str) -> Result<()> {
+        let conversation_id = conversation_id.to_string();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "DELETE FROM message_edits WHERE conversation_id = ?",
+                    params![conversation_id],
+                )?;
+                tx.execute(DELETE_MESSAGES, params![conversation_id])?;
+                tx.execute(DELETE_CONTEXTS, params![conversation_id])?;
+                tx.execute(
+                    "DELETE FROM embeddings WHERE conversation_id = ?",
+                    params![conversation_id],
+                )?;
+                Ok(tx.commit()?)
+            })
+            .await?;
+        Ok(())
+    }
+
     async fn upsert_context(&self, conversation_id: &str, ctx: ConvoContext) -> Result<()> {
         let conversation_id = conversation_id.to_string();
         let ctx_id = ctx.id().to_string();
@@ -291,13 +664,7 @@ impl Storage for Sqlite {
             .conn
             .call(move |conn| {
                 Ok(conn.execute(
-                    r#"INSERT INTO contexts (id, conversation_id, last_message_id, content, token_count, created_at)
-            VALUES (:id, :conversation_id, :last_message_id, :content, :token_count, :created_at)
-            ON CONFLICT(id, conversation_id, last_message_id) DO UPDATE SET
-                content = excluded.content,
-                token_count = excluded.token_count,
-                created_at = excluded.created_at
-            "#,
+                    UPSERT_CONTEXT,
                     named_params! {
                         ":id": ctx.id(),
                         ":conversation_id": conversation_id,
@@ -315,52 +682,300 @@ impl Storage for Sqlite {
         }
         Ok(())
     }
+
+    async fn get_app_state(&self, key: &str) -> Result<Option<String>> {
+        let key = key.to_string();
+        let value = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare("SELECT value FROM app_state WHERE key = ?")?;
+                let mut rows = stmt.query(params![key])?;
+                match rows.next()? {
+                    Some(row) => Ok(Some(row.get::<_, String>(0)?)),
+                    None => Ok(None),
+                }
+            })
+            .await?;
+        Ok(value)
+    }
+
+    async fn set_app_state(&self, key: &str, value: &str) -> Result<()> {
+        let key = key.to_string();
+        let value = value.to_string();
+        self.conn
+            .call(move |conn| {
+                Ok(conn.execute(
+                    r#"INSERT INTO app_state (key, value) VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value"#,
+                    params![key, value],
+                )?)
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<Stats> {
+        let stats = self
+            .conn
+            .call(|conn| {
+                // The notices of the app are stored as system messages
+                // issued by "system", they are not counted
+                const MESSAGES: &str = r#"
+                    SELECT messages.* FROM messages
+                    JOIN conversations ON conversations.id = messages.conversation_id
+                    WHERE conversations.deleted_at IS NULL
+                        AND NOT (messages.system = 1 AND messages.issuer = 'system')
+                "#;
+
+                let mut stats = Stats {
+                    conversations: conn.query_row(
+                        "SELECT COUNT(*) FROM conversations WHERE deleted_at IS NULL",
+                        [],
+                        |row| row.get(0),
+                    )?,
+                    messages: conn.query_row(
+                        &format!("SELECT COUNT(*) FROM ({})", MESSAGES),
+                        [],
+                        |row| row.get(0),
+                    )?,
+                    ..Default::default()
+                };
+
+                let mut stmt = conn.prepare(&format!(
+                    r#"SELECT CASE WHEN tool = 1 THEN 'tool' WHEN system = 1 THEN 'assistant' ELSE 'user' END AS role,
+                        SUM(token_count) FROM ({}) GROUP BY role ORDER BY role DESC"#,
+                    MESSAGES
+                ))?;
+                stats.tokens_by_role = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let busiest_day = conn
+                    .prepare(&format!(
+                        r#"SELECT date(created_at / 1000, 'unixepoch', 'localtime') AS day, COUNT(*) AS count
+                        FROM ({}) GROUP BY day ORDER BY count DESC, day DESC LIMIT 1"#,
+                        MESSAGES
+                    ))?
+                    .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+                    .next()
+                    .transpose()?;
+                stats.busiest_day = busiest_day.and_then(|(day, count)| {
+                    Some((chrono::NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?, count))
+                });
+
+                // The responses are issued by the model
+                stats.top_model = conn
+                    .prepare(&format!(
+                        r#"SELECT issuer, COUNT(*) AS count FROM ({})
+                        WHERE system = 1 AND tool = 0 GROUP BY issuer ORDER BY count DESC, issuer LIMIT 1"#,
+                        MESSAGES
+                    ))?
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .next()
+                    .transpose()?;
+                Ok(stats)
+            })
+            .await
+            .wrap_err("getting stats")?;
+        Ok(stats)
+    }
+
+    async fn get_embeddings(&self, model: &str) -> Result<HashMap<String, Embedding>> {
+        let model = model.to_string();
+        let embeddings = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"SELECT embeddings.conversation_id, embeddings.vector, embeddings.updated_at
+                    FROM embeddings JOIN conversations ON conversations.id = embeddings.conversation_id
+                    WHERE conversations.deleted_at IS NULL AND embeddings.model = ?"#,
+                )?;
+                let mut rows = stmt.query(params![model])?;
+                let mut embeddings = HashMap::new();
+                while let Some(row) = rows.next()? {
+                    let id: String = row.get(0)?;
+                    let vector: Vec<u8> = row.get(1)?;
+                    let updated_at: i64 = row.get(2)?;
+                    let updated_at = chrono::DateTime::from_timestamp_millis(updated_at).ok_or(
+                        tokio_rusqlite::Error::Other(eyre::eyre!("invalid updated_at").into()),
+                    )?;
+                    embeddings.insert(
+                        id,
+                        Embedding {
+                            model: model.clone(),
+                            vector: decode_vector(&vector),
+                            updated_at,
+                        },
+                    );
+                }
+                Ok(embeddings)
+            })
+            .await
+            .wrap_err("getting embeddings")?;
+        Ok(embeddings)
+    }
+
+    async fn upsert_embedding(&self, conversation_id: &str, embedding: Embedding) -> Result<()> {
+        let conversation_id = conversation_id.to_string();
+        self.conn
+            .call(move |conn| {
+                Ok(conn.execute(
+                    r#"INSERT INTO embeddings (conversation_id, model, vector, updated_at)
+                    VALUES (:conversation_id, :model, :vector, :updated_at)
+                    ON CONFLICT(conversation_id) DO UPDATE SET
+                        model = excluded.model,
+                        vector = excluded.vector,
+                        updated_at = excluded.updated_at"#,
+                    named_params! {
+                        ":conversation_id": conversation_id,
+                        ":model": embedding.model,
+                        ":vector": encode_vector(&embedding.vector),
+                        ":updated_at": embedding.updated_at.timestamp_millis(),
+                    },
+                )?)
+            })
+            .await
+            .wrap_err("upserting embedding")?;
+        Ok(())
+    }
+
+    async fn get_outdated_embeddings(&self, model: &str) -> Result<Vec<String>> {
+        let model = model.to_string();
+        let ids = self
+            .conn
+            .call(move |conn| {
+                // A conversation which was never updated has an updated_at of 0
+                let mut stmt = conn.prepare(
+                    r#"SELECT conversations.id FROM conversations
+                    LEFT JOIN embeddings ON embeddings.conversation_id = conversations.id
+                        AND embeddings.model = ?
+                    WHERE conversations.deleted_at IS NULL
+                        AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id)
+                        AND (embeddings.updated_at IS NULL OR embeddings.updated_at <
+                            COALESCE(NULLIF(conversations.updated_at, 0), conversations.created_at))
+                    ORDER BY conversations.id"#,
+                )?;
+                let ids = stmt
+                    .query_map(params![model], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(ids)
+            })
+            .await
+            .wrap_err("getting outdated embeddings")?;
+        Ok(ids)
+    }
 }
 
 impl Sqlite {
     async fn get_contexts(&self, conversation_id: &str) -> Result<Vec<ConvoContext>> {
         let conversation_id = conversation_id.to_string();
-        let contexts = self.conn.call(move |conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, conversation_id, last_message_id, content, token_count, created_at FROM contexts WHERE conversation_id = ?",
-            )?;
-
-            let mut rows = stmt.query(params![conversation_id])?;
-            let mut contexts = vec![];
-            while let Some(row) = rows.next()? {
-                let id: String = row.get(0)?;
-                let last_message_id: String = row.get(2)?;
-                let content: String = row.get(3)?;
-                let token_count: usize = row.get(4)?;
-                let created_at: i64 = row.get(5)?;
-                let created_at =
-                    chrono::DateTime::from_timestamp_millis(created_at).ok_or(tokio_rusqlite::Error::Other(eyre::eyre!("invalid timestamp").into()))?;
-
-                contexts.push(
-                    ConvoContext::new(&last_message_id)
-                        .with_id(id)
-                        .with_content(content)
-                        .with_token_count(token_count)
-                        .with_created_at(created_at),
-                );
-            }
+        let contexts = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(SELECT_CONTEXTS)?;
+
+                let mut rows = stmt.query(params![conversation_id])?;
+                let mut contexts = vec![];
+                while let Some(row) = rows.next()? {
+                    let id: String = row.get(0)?;
+                    let last_message_id: String = row.get(2)?;
+                    let content: String = row.get(3)?;
+                    let token_count: usize = row.get(4)?;
+                    let created_at: i64 = row.get(5)?;
+                    let created_at = chrono::DateTime::from_timestamp_millis(created_at).ok_or(
+                        tokio_rusqlite::Error::Other(eyre::eyre!("invalid timestamp").into()),
+                    )?;
 
-            contexts.sort_by(|a, b| {
-                a.created_at()
-                    .timestamp_millis()
-                    .cmp(&b.created_at().timestamp_millis())
-            });
-            Ok(contexts)
-        }).await?;
+                    contexts.push(
+                        ConvoContext::new(&last_message_id)
+                            .with_id(id)
+                            .with_content(content)
+                            .with_token_count(token_count)
+                            .with_created_at(created_at),
+                    );
+                }
+
+                contexts.sort_by(|a, b| {
+                    a.created_at()
+                        .timestamp_millis()
+                        .cmp(&b.created_at().timestamp_millis())
+                });
+                Ok(contexts)
+            })
+            .await?;
         Ok(contexts)
     }
 }
 
 type Param = (&'static str, Box<dyn ToSql>);
 
+fn execute_upsert_conversation(
+    stmt: &mut tokio_rusqlite::Statement,
+    conversation: &Conversation,
+    budget: Option<&str>,
+) -> tokio_rusqlite::Result<usize> {
+    Ok(stmt.execute(named_params! {
+        ":id": conversation.id(),
+        ":title": conversation.title(),
+        ":system_prompt": conversation.system_prompt(),
+        ":budget": budget,
+        ":spent_tokens": conversation.spending().tokens,
+        ":spent_cost": conversation.spending().cost,
+        ":created_at": conversation.created_at().timestamp_millis(),
+        ":updated_at": conversation.updated_at().timestamp_millis(),
+    })?)
+}
+
+fn execute_upsert_message(
+    stmt: &mut tokio_rusqlite::Statement,
+    conversation_id: &str,
+    message: &Message,
+) -> tokio_rusqlite::Result<usize> {
+    Ok(stmt.execute(named_params! {
+        ":id": message.id(),
+        ":conversation_id": conversation_id,
+        ":text": message.text(),
+        ":issuer": message.issuer_str(),
+        ":system": message.is_system() as i32,
+        ":tool": message.is_tool() as i32,
+        ":incomplete": message.is_incomplete() as i32,
+        ":error": message.is_error() as i32,
+        ":refusal": message.is_refusal() as i32,
+        ":token_count": message.token_count() as i32,
+        ":created_at": message.created_at().timestamp_millis()
+    })?)
+}
+
+fn execute_upsert_context(
+    stmt: &mut tokio_rusqlite::Statement,
+    conversation_id: &str,
+    ctx: &ConvoContext,
+) -> tokio_rusqlite::Result<usize> {
+    Ok(stmt.execute(named_params! {
+        ":id": ctx.id(),
+        ":conversation_id": conversation_id,
+        ":last_message_id": ctx.last_message_id(),
+        ":content": ctx.content(),
+        ":token_count": ctx.token_count() as i32,
+        ":created_at": ctx.created_at().timestamp_millis(),
+    })?)
+}
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
 fn filter_to_query(filter: &FilterConversation) -> (String, Vec<Param>) {
-    let mut query =
-        String::from("SELECT id, title, created_at, updated_at FROM conversations WHERE 1=1");
+    let mut query = String::from(
+        "SELECT id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost FROM conversations WHERE deleted_at IS NULL",
+    );
     let mut params: Vec<(&str, Box<dyn ToSql>)> = vec![];
 
     if let Some(id) = filter.id() {
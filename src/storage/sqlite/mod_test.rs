@@ -1,4 +1,6 @@
 use super::*;
+use crate::models::ToolCall;
+use crate::storage::LAST_CONVERSATION_STATE;
 
 #[test]
 fn test_filter_to_query() {
@@ -7,7 +9,7 @@ fn test_filter_to_query() {
     let (query, params) = filter_to_query(&filter);
     assert_eq!(
         query,
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE 1=1 AND id = :id"
+        "SELECT id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost FROM conversations WHERE deleted_at IS NULL AND id = :id"
     );
 
     assert_eq!(params.len(), 1);
@@ -17,7 +19,7 @@ fn test_filter_to_query() {
     let (query, params) = filter_to_query(&filter);
     assert_eq!(
         query,
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE 1=1 AND id = :id AND title LIKE :title"
+        "SELECT id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost FROM conversations WHERE deleted_at IS NULL AND id = :id AND title LIKE :title"
     );
     assert_eq!(params.len(), 2);
     assert_eq!(params[0].0, ":id");
@@ -27,7 +29,7 @@ fn test_filter_to_query() {
     let (query, params) = filter_to_query(&filter);
     assert_eq!(
         query,
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE 1=1 AND id = :id AND title LIKE :title AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND text LIKE :message_contains)"
+        "SELECT id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost FROM conversations WHERE deleted_at IS NULL AND id = :id AND title LIKE :title AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND text LIKE :message_contains)"
     );
 
     assert_eq!(params.len(), 3);
@@ -39,7 +41,7 @@ fn test_filter_to_query() {
     let (query, params) = filter_to_query(&filter);
     assert_eq!(
         query,
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE 1=1 AND id = :id AND title LIKE :title AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND text LIKE :message_contains) AND created_at >= :created_at_from"
+        "SELECT id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost FROM conversations WHERE deleted_at IS NULL AND id = :id AND title LIKE :title AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND text LIKE :message_contains) AND created_at >= :created_at_from"
     );
     assert_eq!(params.len(), 4);
     assert_eq!(params[0].0, ":id");
@@ -51,7 +53,7 @@ fn test_filter_to_query() {
     let (query, params) = filter_to_query(&filter);
     assert_eq!(
         query,
-        "SELECT id, title, created_at, updated_at FROM conversations WHERE 1=1 AND id = :id AND title LIKE :title AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND text LIKE :message_contains) AND updated_at <= :updated_at_to AND created_at >= :created_at_from"
+        "SELECT id, title, created_at, updated_at, system_prompt, budget, spent_tokens, spent_cost FROM conversations WHERE deleted_at IS NULL AND id = :id AND title LIKE :title AND EXISTS (SELECT 1 FROM messages WHERE conversation_id = conversations.id AND text LIKE :message_contains) AND updated_at <= :updated_at_to AND created_at >= :created_at_from"
     );
     assert_eq!(params.len(), 5);
     assert_eq!(params[0].0, ":id");
@@ -71,7 +73,9 @@ async fn test_upsert_conversation() {
         .with_title("Test Conversation")
         .with_created_at(chrono::Utc::now());
 
-    db.upsert_conversation(expected.clone()).await.unwrap();
+    assert!(db.upsert_conversation(expected.clone()).await.unwrap());
+    // Updated the second time
+    assert!(!db.upsert_conversation(expected.clone()).await.unwrap());
 
     let actual = db.get_conversation("test_id").await.unwrap();
     assert!(actual.is_some());
@@ -86,6 +90,54 @@ async fn test_upsert_conversation() {
     assert_eq!(actual.messages().len(), 0);
 }
 
+#[tokio::test]
+async fn test_upsert_conversation_system_prompt() {
+    let db = Sqlite::new(None).await.unwrap();
+
+    let mut convo = Conversation::default()
+        .with_id("test_id")
+        .with_system_prompt("Be concise");
+    db.upsert_conversation(convo.clone()).await.unwrap();
+
+    let actual = db.get_conversation("test_id").await.unwrap().unwrap();
+    assert_eq!(actual.system_prompt(), Some("Be concise"));
+
+    convo.set_system_prompt("");
+    db.upsert_conversation(convo).await.unwrap();
+    let actual = db.get_conversation("test_id").await.unwrap().unwrap();
+    assert_eq!(actual.system_prompt(), None);
+}
+
+#[tokio::test]
+async fn test_upsert_conversation_budget() {
+    let db = Sqlite::new(None).await.unwrap();
+
+    let budget = Budget {
+        tokens: None,
+        cost: Some(1.5),
+    };
+    let mut convo = Conversation::default()
+        .with_id("test_id")
+        .with_budget(Some(budget));
+    convo.add_spending(1200, 0.25);
+    db.upsert_conversation(convo.clone()).await.unwrap();
+
+    let actual = db.get_conversation("test_id").await.unwrap().unwrap();
+    assert_eq!(actual.budget(), Some(budget));
+    assert_eq!(
+        actual.spending(),
+        Spending {
+            tokens: 1200,
+            cost: 0.25
+        }
+    );
+
+    convo.set_budget(None);
+    db.upsert_conversation(convo).await.unwrap();
+    let actual = db.get_conversation("test_id").await.unwrap().unwrap();
+    assert_eq!(actual.budget(), None);
+}
+
 #[tokio::test]
 async fn test_insert_conversation_with_messages() {
     let db = Sqlite::new(None).await.unwrap();
@@ -215,6 +267,52 @@ async fn test_delete_conversation() {
     assert!(actual.is_empty());
 }
 
+#[tokio::test]
+async fn test_restore_and_purge_conversation() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    for id in ["convo1", "convo2"] {
+        db.upsert_conversation(Conversation::default().with_id(id))
+            .await
+            .unwrap();
+        db.add_messages(id, &[Message::new_user("user", "Hello").with_id("msg1")])
+            .await
+            .unwrap();
+    }
+
+    db.delete_conversation("convo1").await.unwrap();
+    db.delete_conversation("convo2").await.unwrap();
+    let conversations = db
+        .get_conversations(FilterConversation::default())
+        .await
+        .unwrap();
+    assert!(conversations.is_empty());
+
+    db.restore_conversation("convo1").await.unwrap();
+    let actual = db.get_conversation("convo1").await.unwrap();
+    assert!(actual.is_some());
+    assert_eq!(actual.unwrap().messages().len(), 1);
+
+    // Only the conversations deleted before the given time are purged
+    let purged = db
+        .purge_deleted_conversations(chrono::Utc::now() - chrono::Duration::days(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 0);
+
+    let purged = db
+        .purge_deleted_conversations(chrono::Utc::now() + chrono::Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+    assert!(db.get_messages("convo2").await.unwrap().is_empty());
+    assert_eq!(db.get_messages("convo1").await.unwrap().len(), 1);
+
+    db.restore_conversation("convo2").await.unwrap();
+    assert!(db.get_conversation("convo2").await.unwrap().is_none());
+}
+
 #[tokio::test]
 async fn test_get_conversation_not_exist() {
     let db = Sqlite::new(None).await.unwrap();
@@ -359,6 +457,192 @@ async fn test_upsert_message() {
     assert_eq!(actual[1].id(), "msg2");
 }
 
+#[tokio::test]
+async fn test_upsert_tool_message() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let conversation = Conversation::default()
+        .with_id("test_id")
+        .with_title("Test Conversation")
+        .with_created_at(chrono::Utc::now());
+    db.upsert_conversation(conversation).await.unwrap();
+
+    let call = ToolCall {
+        tool: "read_file".to_string(),
+        provider: "fs".to_string(),
+        args: None,
+        result: "content".to_string(),
+        is_error: false,
+    };
+    db.upsert_message("test_id", Message::new_tool(&call).with_id("tool1"))
+        .await
+        .unwrap();
+
+    let actual = db.get_messages("test_id").await.unwrap();
+    assert_eq!(actual.len(), 1);
+    assert!(actual[0].is_tool());
+    assert_eq!(actual[0].issuer_str(), "read_file");
+    assert_eq!(actual[0].tool_call(), Some(call));
+}
+
+#[tokio::test]
+async fn test_upsert_incomplete_message() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let conversation = Conversation::default()
+        .with_id("test_id")
+        .with_title("Test Conversation")
+        .with_created_at(chrono::Utc::now());
+    db.upsert_conversation(conversation).await.unwrap();
+
+    // The partial response is saved while streaming
+    let message = Message::new_system("gpt-4o", "Hello")
+        .with_id("msg1")
+        .with_incomplete(true);
+    db.upsert_message("test_id", message.clone()).await.unwrap();
+
+    let actual = db.get_messages("test_id").await.unwrap();
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].text(), "Hello");
+    assert!(actual[0].is_incomplete());
+
+    // The complete response replaces it
+    let message = message.with_text("Hello there!").with_incomplete(false);
+    db.upsert_message("test_id", message).await.unwrap();
+
+    let actual = db.get_messages("test_id").await.unwrap();
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].text(), "Hello there!");
+    assert!(!actual[0].is_incomplete());
+}
+
+#[tokio::test]
+async fn test_upsert_error_message() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let conversation = Conversation::default()
+        .with_id("test_id")
+        .with_title("Test Conversation")
+        .with_created_at(chrono::Utc::now());
+    db.upsert_conversation(conversation).await.unwrap();
+
+    let created_at = chrono::Utc::now();
+    let messages = vec![
+        Message::new_system("system", "Aborted!")
+            .with_id("msg1")
+            .with_created_at(created_at),
+        Message::new_system("system", "Error: timeout")
+            .with_id("msg2")
+            .with_created_at(created_at + chrono::Duration::seconds(1))
+            .with_severity(Severity::Error),
+    ];
+    db.add_messages("test_id", &messages).await.unwrap();
+
+    let actual = db.get_messages("test_id").await.unwrap();
+    let severities = actual.iter().map(Message::severity).collect::<Vec<_>>();
+    assert_eq!(severities, vec![Severity::Info, Severity::Error]);
+}
+
+#[tokio::test]
+async fn test_upsert_refusal_message() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+    db.upsert_conversation(Conversation::default().with_id("test_id"))
+        .await
+        .unwrap();
+
+    let message = Message::new_system("gpt-4o", "I can't help with that.")
+        .with_id("msg1")
+        .with_refusal(true);
+    db.upsert_message("test_id", message).await.unwrap();
+
+    let actual = db.get_messages("test_id").await.unwrap();
+    assert!(actual[0].is_refusal());
+}
+
+#[tokio::test]
+async fn test_upsert_message_tracks_edits() {
+    let db = Sqlite::open(&SqliteStorage {
+        track_edits: Some(true),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    db.run_migration().await.unwrap();
+
+    let conversation = Conversation::default()
+        .with_id("test_id")
+        .with_title("Test Conversation")
+        .with_created_at(chrono::Utc::now());
+    db.upsert_conversation(conversation).await.unwrap();
+
+    // Completing a streamed response is not an edit
+    let message = Message::new_system("gpt-4o", "Hel")
+        .with_id("msg1")
+        .with_incomplete(true);
+    db.upsert_message("test_id", message.clone()).await.unwrap();
+    let message = message.with_text("Hello").with_incomplete(false);
+    db.upsert_message("test_id", message.clone()).await.unwrap();
+    // Neither is saving the same text again
+    db.upsert_message("test_id", message.clone()).await.unwrap();
+    assert!(
+        db.get_message_edits("test_id", "msg1")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    db.upsert_message("test_id", message.clone().with_text("Hi"))
+        .await
+        .unwrap();
+    db.upsert_message("test_id", message.clone().with_text("Hey"))
+        .await
+        .unwrap();
+    let edits = db.get_message_edits("test_id", "msg1").await.unwrap();
+    let texts = edits.iter().map(|e| e.text.as_str()).collect::<Vec<_>>();
+    assert_eq!(texts, vec!["Hello", "Hi"]);
+    assert_eq!(db.get_messages("test_id").await.unwrap()[0].text(), "Hey");
+
+    // The edits are scoped to the conversation of the message
+    db.upsert_conversation(Conversation::default().with_id("other_id"))
+        .await
+        .unwrap();
+    assert!(
+        db.get_message_edits("other_id", "msg1")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    db.delete_messsage("msg1").await.unwrap();
+    assert!(
+        db.get_message_edits("test_id", "msg1")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // Nothing is kept unless enabled
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+    db.upsert_conversation(Conversation::default().with_id("test_id"))
+        .await
+        .unwrap();
+    db.upsert_message("test_id", message.clone()).await.unwrap();
+    db.upsert_message("test_id", message.with_text("Hi"))
+        .await
+        .unwrap();
+    assert!(
+        db.get_message_edits("test_id", "msg1")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
 #[tokio::test]
 async fn test_delete_message() {
     let db = Sqlite::new(None).await.unwrap();
@@ -392,6 +676,84 @@ async fn test_delete_message() {
     assert_eq!(actual.len(), 0);
 }
 
+#[tokio::test]
+async fn test_clear_messages() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let convo = fake_converstations();
+    for convo in &convo[..2] {
+        db.upsert_conversation(convo.clone()).await.unwrap();
+        db.add_messages(convo.id(), convo.messages()).await.unwrap();
+        for ctx in convo.contexts() {
+            db.upsert_context(convo.id(), ctx.clone()).await.unwrap();
+        }
+    }
+
+    db.clear_messages(convo[0].id()).await.unwrap();
+    let cleared = db
+        .get_conversation(convo[0].id())
+        .await
+        .unwrap()
+        .expect("the conversation is kept");
+    assert_eq!(cleared.title(), convo[0].title());
+    assert!(cleared.messages().is_empty());
+    assert!(cleared.contexts().is_empty());
+
+    // The other conversations are left untouched
+    let other = db.get_conversation(convo[1].id()).await.unwrap().unwrap();
+    assert_eq!(other.messages().len(), convo[1].messages().len());
+    assert_eq!(other.contexts().len(), convo[1].contexts().len());
+}
+
+#[tokio::test]
+async fn test_get_outdated_embeddings() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let now = chrono::Utc::now();
+    for id in ["convo1", "convo2", "convo3"] {
+        let convo = Conversation::default().with_id(id).with_updated_at(now);
+        db.upsert_conversation(convo).await.unwrap();
+        db.add_messages(id, &[Message::new_user("user", "Hello").with_id("msg1")])
+            .await
+            .unwrap();
+    }
+    // Without messages there is nothing to embed
+    db.upsert_conversation(Conversation::default().with_id("convo4"))
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_outdated_embeddings("model").await.unwrap(),
+        vec!["convo1", "convo2", "convo3"]
+    );
+
+    let embedding = |updated_at| Embedding {
+        model: "model".to_string(),
+        vector: vec![1.0],
+        updated_at,
+    };
+    db.upsert_embedding("convo1", embedding(now)).await.unwrap();
+    db.upsert_embedding("convo2", embedding(now - chrono::Duration::minutes(1)))
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_outdated_embeddings("model").await.unwrap(),
+        vec!["convo2", "convo3"]
+    );
+    // Computed by another model
+    assert_eq!(
+        db.get_outdated_embeddings("other").await.unwrap(),
+        vec!["convo1", "convo2", "convo3"]
+    );
+
+    db.delete_conversation("convo3").await.unwrap();
+    assert_eq!(
+        db.get_outdated_embeddings("model").await.unwrap(),
+        vec!["convo2"]
+    );
+}
+
 #[tokio::test]
 async fn test_conversation_context() {
     let db = Sqlite::new(None).await.unwrap();
@@ -426,3 +788,247 @@ async fn test_conversation_context() {
         convo[0].contexts()[0].created_at().timestamp_millis()
     );
 }
+
+#[tokio::test]
+async fn test_bulk_upsert() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let convo = fake_converstations();
+    db.upsert_conversation(convo[1].clone()).await.unwrap();
+    db.delete_conversation(convo[1].id()).await.unwrap();
+
+    db.bulk_upsert(&convo).await.unwrap();
+    for expected in &convo {
+        let actual = db.get_conversation(expected.id()).await.unwrap().unwrap();
+        assert_eq!(actual.title(), expected.title());
+        assert_eq!(actual.messages().len(), expected.messages().len());
+        assert_eq!(actual.contexts().len(), expected.contexts().len());
+    }
+
+    // Nothing is written if one of the conversations is invalid
+    let invalid = vec![
+        Conversation::default().with_id("convo_new"),
+        Conversation::default(),
+    ];
+    assert!(db.bulk_upsert(&invalid).await.is_err());
+    assert!(db.get_conversation("convo_new").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_bulk_upsert_rollback() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+    db.upsert_conversation(Conversation::default().with_id("kept").with_title("Kept"))
+        .await
+        .unwrap();
+    // The failure is only reached in the middle of the transaction
+    db.conn
+        .call(|conn| {
+            Ok(conn.execute_batch(
+                r#"CREATE TRIGGER fail_message BEFORE INSERT ON messages
+                WHEN NEW.id = 'bad' BEGIN SELECT RAISE(ABORT, 'bad message'); END;"#,
+            )?)
+        })
+        .await
+        .unwrap();
+
+    let conversations = vec![
+        Conversation::default()
+            .with_id("kept")
+            .with_title("Renamed")
+            .with_messages(vec![Message::new_user("user", "Hello").with_id("msg1")]),
+        Conversation::default()
+            .with_id("convo_new")
+            .with_messages(vec![
+                Message::new_user("user", "Hello").with_id("msg1"),
+                Message::new_user("user", "Hello").with_id("bad"),
+            ]),
+    ];
+    let err = db.bulk_upsert(&conversations).await.unwrap_err();
+    assert!(format!("{:?}", err).contains("bad message"));
+
+    // The rows written before the failure are rolled back with it
+    let kept = db.get_conversation("kept").await.unwrap().unwrap();
+    assert_eq!(kept.title(), "Kept");
+    assert!(kept.messages().is_empty());
+    assert!(db.get_conversation("convo_new").await.unwrap().is_none());
+    assert!(db.get_messages("convo_new").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_lookup_by_conversation_uses_index() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let (filter, _) = filter_to_query(&FilterConversation::default().with_message_contains("x"));
+    let queries = [
+        (SELECT_MESSAGES.to_string(), "idx_messages_conversation_id"),
+        (DELETE_MESSAGES.to_string(), "idx_messages_conversation_id"),
+        (SELECT_CONTEXTS.to_string(), "idx_contexts_conversation_id"),
+        (DELETE_CONTEXTS.to_string(), "idx_contexts_conversation_id"),
+        (filter, "idx_messages_conversation_id"),
+    ];
+    for (query, index) in queries {
+        let plan = db
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", query))?;
+                // The values don't change the plan
+                let params = tokio_rusqlite::params_from_iter(std::iter::repeat_n(
+                    "id",
+                    stmt.parameter_count(),
+                ));
+                let details = stmt
+                    .query_map(params, |row| row.get::<_, String>(3))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(details.join("\n"))
+            })
+            .await
+            .unwrap();
+        assert!(plan.contains(index), "{}", plan);
+        assert!(!plan.contains("SCAN messages"), "{}", plan);
+        assert!(!plan.contains("SCAN contexts"), "{}", plan);
+    }
+}
+
+#[tokio::test]
+async fn test_app_state() {
+    let db = Sqlite::new(None).await.unwrap();
+
+    assert_eq!(
+        db.get_app_state(LAST_CONVERSATION_STATE).await.unwrap(),
+        None
+    );
+
+    db.set_app_state(LAST_CONVERSATION_STATE, "convo1")
+        .await
+        .unwrap();
+    db.set_app_state(LAST_CONVERSATION_STATE, "convo2")
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_app_state(LAST_CONVERSATION_STATE).await.unwrap(),
+        Some("convo2".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_prune_conversations() {
+    let db = Sqlite::new(None).await.unwrap();
+    db.run_migration().await.unwrap();
+
+    let now = chrono::Utc::now();
+    for (i, days) in [10, 5, 3, 1].iter().enumerate() {
+        let id = format!("convo{}", i);
+        let updated_at = now - chrono::Duration::days(*days);
+        db.upsert_conversation(
+            Conversation::default()
+                .with_id(&id)
+                .with_created_at(updated_at)
+                .with_updated_at(updated_at),
+        )
+        .await
+        .unwrap();
+        db.add_messages(&id, &[Message::new_user("user", "Hello").with_id("msg1")])
+            .await
+            .unwrap();
+    }
+    // The deleted conversations are left to the purge
+    db.delete_conversation("convo3").await.unwrap();
+
+    assert_eq!(db.prune_conversations(None, None).await.unwrap(), 0);
+
+    // Keep the 2 most recently updated conversations
+    assert_eq!(db.prune_conversations(Some(2), None).await.unwrap(), 1);
+    assert!(db.get_conversation("convo0").await.unwrap().is_none());
+    assert!(db.get_messages("convo0").await.unwrap().is_empty());
+
+    let before = now - chrono::Duration::days(4);
+    assert_eq!(db.prune_conversations(None, Some(before)).await.unwrap(), 1);
+    assert!(db.get_conversation("convo1").await.unwrap().is_none());
+    assert!(db.get_conversation("convo2").await.unwrap().is_some());
+
+    db.restore_conversation("convo3").await.unwrap();
+    assert!(db.get_conversation("convo3").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_stats() {
+    let db = Sqlite::new(None).await.unwrap();
+    assert_eq!(db.stats().await.unwrap(), Stats::default());
+
+    let day = chrono::Local::now().date_naive();
+    for (id, model) in [("a", "gpt-4o"), ("b", "gpt-4o"), ("c", "o3")] {
+        db.upsert_conversation(Conversation::default().with_id(id))
+            .await
+            .unwrap();
+        db.add_messages(
+            id,
+            &[
+                Message::new_system("system", "Hello"),
+                Message::new_user("user", "Hi").with_token_count(10),
+                Message::new_system(model, "Hello!").with_token_count(20),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+    db.delete_conversation("c").await.unwrap();
+
+    let stats = db.stats().await.unwrap();
+    assert_eq!(
+        stats,
+        Stats {
+            conversations: 2,
+            messages: 4,
+            tokens_by_role: vec![("user".to_string(), 20), ("assistant".to_string(), 40)],
+            busiest_day: Some((day, 4)),
+            top_model: Some(("gpt-4o".to_string(), 2)),
+        }
+    );
+    assert_eq!(stats.avg_messages_per_conversation(), 2.0);
+}
+
+#[tokio::test]
+async fn test_open_wal() {
+    let dir = std::env::temp_dir().join(format!("chatty-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("chat.db").to_string_lossy().to_string();
+
+    let config = SqliteStorage {
+        path: Some(path.clone()),
+        busy_timeout_ms: Some(1500),
+        ..Default::default()
+    };
+    let db = Sqlite::open(&config).await.unwrap();
+    let (mode, timeout) = db
+        .conn
+        .call(|conn| {
+            let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+            let timeout: u64 = conn.query_row("PRAGMA busy_timeout", [], |row| row.get(0))?;
+            Ok((mode, timeout))
+        })
+        .await
+        .unwrap();
+    assert_eq!(mode, "wal");
+    assert_eq!(timeout, 1500);
+    drop(db);
+
+    // The journal mode persists in the database, it is switched back once
+    // disabled
+    let config = SqliteStorage {
+        path: Some(path),
+        wal: Some(false),
+        ..Default::default()
+    };
+    let db = Sqlite::open(&config).await.unwrap();
+    let mode = db
+        .conn
+        .call(|conn| Ok(conn.query_row("PRAGMA journal_mode", [], |row| row.get::<_, String>(0))?))
+        .await
+        .unwrap();
+    assert_eq!(mode, "delete");
+    drop(db);
+    std::fs::remove_dir_all(dir).unwrap();
+}